@@ -0,0 +1,444 @@
+use std::fmt;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Json {
+  Null,
+  Bool(bool),
+  Number(f64),
+  String(String),
+  Array(Vec<Json>),
+  Object(Vec<(String, Json)>),
+}
+
+impl Json {
+  pub fn object(fields: Vec<(&str, Json)>) -> Self {
+    Json::Object(
+      fields
+        .into_iter()
+        .map(|(key, value)| (key.to_string(), value))
+        .collect(),
+    )
+  }
+
+  pub fn get(&self, key: &str) -> Option<&Json> {
+    match self {
+      Json::Object(fields) => fields
+        .iter()
+        .find(|(k, _)| k == key)
+        .map(|(_, value)| value),
+      _ => None,
+    }
+  }
+
+  pub fn pointer(&self, keys: &[&str]) -> Option<&Json> {
+    keys.iter().try_fold(self, |value, key| value.get(key))
+  }
+
+  pub fn as_str(&self) -> Option<&str> {
+    match self {
+      Json::String(string) => Some(string),
+      _ => None,
+    }
+  }
+
+  pub fn as_usize(&self) -> Option<usize> {
+    match self {
+      Json::Number(number) if *number >= 0.0 && number.fract() == 0.0 => {
+        Some(*number as usize)
+      }
+      _ => None,
+    }
+  }
+
+  pub fn as_array(&self) -> Option<&[Json]> {
+    match self {
+      Json::Array(items) => Some(items),
+      _ => None,
+    }
+  }
+}
+
+impl From<bool> for Json {
+  fn from(bool: bool) -> Self {
+    Json::Bool(bool)
+  }
+}
+
+impl From<usize> for Json {
+  fn from(number: usize) -> Self {
+    Json::Number(number as f64)
+  }
+}
+
+impl From<&str> for Json {
+  fn from(string: &str) -> Self {
+    Json::String(string.to_string())
+  }
+}
+
+impl From<String> for Json {
+  fn from(string: String) -> Self {
+    Json::String(string)
+  }
+}
+
+impl From<Vec<Json>> for Json {
+  fn from(items: Vec<Json>) -> Self {
+    Json::Array(items)
+  }
+}
+
+impl fmt::Display for Json {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      Json::Null => write!(f, "null"),
+      Json::Bool(bool) => write!(f, "{}", bool),
+      Json::Number(number) if number.is_finite() => write!(f, "{}", number),
+      Json::Number(_) => write!(f, "null"),
+      Json::String(string) => write_string(f, string),
+      Json::Array(items) => {
+        write!(f, "[")?;
+        for (i, item) in items.iter().enumerate() {
+          if i > 0 {
+            write!(f, ",")?;
+          }
+          write!(f, "{}", item)?;
+        }
+        write!(f, "]")
+      }
+      Json::Object(fields) => {
+        write!(f, "{{")?;
+        for (i, (key, value)) in fields.iter().enumerate() {
+          if i > 0 {
+            write!(f, ",")?;
+          }
+          write_string(f, key)?;
+          write!(f, ":{}", value)?;
+        }
+        write!(f, "}}")
+      }
+    }
+  }
+}
+
+fn write_string(f: &mut fmt::Formatter<'_>, string: &str) -> fmt::Result {
+  write!(f, "\"")?;
+  for c in string.chars() {
+    match c {
+      '"' => write!(f, "\\\"")?,
+      '\\' => write!(f, "\\\\")?,
+      '\n' => write!(f, "\\n")?,
+      '\r' => write!(f, "\\r")?,
+      '\t' => write!(f, "\\t")?,
+      c if c.is_control() => write!(f, "\\u{:04x}", c as u32)?,
+      c => write!(f, "{}", c)?,
+    }
+  }
+  write!(f, "\"")
+}
+
+/// Arrays and objects nested deeper than this are refused rather than
+/// overflowing the stack.
+const MAX_DEPTH: usize = 128;
+
+/// Reader of JSON, which when `comments` is set also takes the comments and
+/// trailing commas VS Code allows in its theme files.
+struct Parser<'a> {
+  chars: std::iter::Peekable<std::str::Chars<'a>>,
+  comments: bool,
+  depth: usize,
+}
+
+impl<'a> Parser<'a> {
+  fn skip_whitespace(&mut self) {
+    loop {
+      match self.chars.peek() {
+        Some(' ' | '\t' | '\n' | '\r') => {
+          self.chars.next();
+        }
+        Some(c) if self.comments && c.is_whitespace() => {
+          self.chars.next();
+        }
+        Some('/') if self.comments => {
+          // a `/` starting no comment is left for the caller to refuse
+          let mut ahead = self.chars.clone();
+          ahead.next();
+          match ahead.next() {
+            Some('/') => {
+              self.chars = ahead;
+              self.chars.find(|&c| c == '\n');
+            }
+            Some('*') => {
+              self.chars = ahead;
+              let mut last = ' ';
+              for c in &mut self.chars {
+                if last == '*' && c == '/' {
+                  break;
+                }
+                last = c;
+              }
+            }
+            _ => return,
+          }
+        }
+        _ => return,
+      }
+    }
+  }
+
+  fn expect(&mut self, expected: char) -> Result<(), anyhow::Error> {
+    self.skip_whitespace();
+    match self.chars.next() {
+      Some(c) if c == expected => Ok(()),
+      c => anyhow::bail!("expected `{}`, found {:?}", expected, c),
+    }
+  }
+
+  fn value(&mut self) -> Result<Json, anyhow::Error> {
+    self.skip_whitespace();
+    match self.chars.peek() {
+      Some('{' | '[') if self.depth == MAX_DEPTH => {
+        anyhow::bail!("nested deeper than {} levels", MAX_DEPTH)
+      }
+      Some('{') => {
+        self.chars.next();
+        self.depth += 1;
+        let mut fields = vec![];
+        loop {
+          self.skip_whitespace();
+          if self.chars.peek() == Some(&'}') {
+            self.chars.next();
+            self.depth -= 1;
+            break Ok(Json::Object(fields));
+          }
+          self.expect('"')?;
+          let key = self.string()?;
+          self.expect(':')?;
+          fields.push((key, self.value()?));
+          self.separator('}')?;
+        }
+      }
+      Some('[') => {
+        self.chars.next();
+        self.depth += 1;
+        let mut items = vec![];
+        loop {
+          self.skip_whitespace();
+          if self.chars.peek() == Some(&']') {
+            self.chars.next();
+            self.depth -= 1;
+            break Ok(Json::Array(items));
+          }
+          items.push(self.value()?);
+          self.separator(']')?;
+        }
+      }
+      Some('"') => {
+        self.chars.next();
+        Ok(Json::String(self.string()?))
+      }
+      Some(_) => {
+        let mut word = String::new();
+        while let Some(&c) = self.chars.peek() {
+          if !(c.is_alphanumeric() || "+-.".contains(c)) {
+            break;
+          }
+          word.push(c);
+          self.chars.next();
+        }
+        match word.as_str() {
+          "null" => Ok(Json::Null),
+          "true" => Ok(Json::Bool(true)),
+          "false" => Ok(Json::Bool(false)),
+          _ if is_number(&word) => Ok(Json::Number(word.parse()?)),
+          _ => anyhow::bail!("unexpected `{}`", word),
+        }
+      }
+      None => anyhow::bail!("unexpected end of input"),
+    }
+  }
+
+  fn separator(&mut self, end: char) -> Result<(), anyhow::Error> {
+    self.skip_whitespace();
+    match self.chars.peek() {
+      Some(',') => {
+        self.chars.next();
+        self.skip_whitespace();
+        match self.chars.peek() {
+          Some(&c) if c == end && !self.comments => {
+            anyhow::bail!("trailing comma before `{}`", end)
+          }
+          _ => Ok(()),
+        }
+      }
+      Some(&c) if c == end => Ok(()),
+      c => anyhow::bail!("expected `,` or `{}`, found {:?}", end, c),
+    }
+  }
+
+  fn code_unit(&mut self) -> Option<u32> {
+    let hex = (&mut self.chars).take(4).collect::<String>();
+    u32::from_str_radix(&hex, 16).ok()
+  }
+
+  fn string(&mut self) -> Result<String, anyhow::Error> {
+    let mut string = String::new();
+    loop {
+      match self.chars.next() {
+        Some('"') => break Ok(string),
+        Some('\\') => match self.chars.next() {
+          Some('n') => string.push('\n'),
+          Some('t') => string.push('\t'),
+          Some('r') => string.push('\r'),
+          Some('b') => string.push('\u{8}'),
+          Some('f') => string.push('\u{c}'),
+          Some('u') => {
+            let mut code = self.code_unit();
+            // characters outside the basic plane are written as a pair of
+            // UTF-16 surrogates
+            if let Some(high @ 0xd800..=0xdbff) = code {
+              let mut low = self.chars.clone();
+              if low.next() == Some('\\') && low.next() == Some('u') {
+                self.chars.nth(1);
+                code = self.code_unit().map(|low| {
+                  0x10000 + ((high - 0xd800) << 10) + (low.wrapping_sub(0xdc00))
+                });
+              }
+            }
+            let c = code
+              .and_then(std::char::from_u32)
+              .unwrap_or(std::char::REPLACEMENT_CHARACTER);
+            string.push(c);
+          }
+          Some(c) => string.push(c),
+          None => anyhow::bail!("unterminated string"),
+        },
+        Some(c) => string.push(c),
+        None => anyhow::bail!("unterminated string"),
+      }
+    }
+  }
+}
+
+fn is_number(word: &str) -> bool {
+  let digits = |s: &str| {
+    s.len() - s.trim_start_matches(|c: char| c.is_ascii_digit()).len()
+  };
+  let rest = word.strip_prefix('-').unwrap_or(word);
+  let int = digits(rest);
+  if int == 0 || (int > 1 && rest.starts_with('0')) {
+    return false;
+  }
+  let mut rest = &rest[int..];
+  if let Some(fraction) = rest.strip_prefix('.') {
+    if digits(fraction) == 0 {
+      return false;
+    }
+    rest = &fraction[digits(fraction)..];
+  }
+  if let Some(exponent) = rest.strip_prefix(|c| c == 'e' || c == 'E') {
+    let exponent = exponent.trim_start_matches(|c| c == '+' || c == '-');
+    if digits(exponent) == 0 {
+      return false;
+    }
+    rest = &exponent[digits(exponent)..];
+  }
+  rest.is_empty()
+}
+
+pub fn parse(source: &str) -> Result<Json, anyhow::Error> {
+  read(source, false)
+}
+
+pub fn parse_with_comments(source: &str) -> Result<Json, anyhow::Error> {
+  read(source, true)
+}
+
+fn read(source: &str, comments: bool) -> Result<Json, anyhow::Error> {
+  let mut parser = Parser {
+    chars: source.chars().peekable(),
+    comments,
+    depth: 0,
+  };
+  let value = parser.value()?;
+  parser.skip_whitespace();
+  if parser.chars.next().is_some() {
+    anyhow::bail!("trailing characters after the value");
+  }
+  Ok(value)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn parses_json_with_comments() {
+    let json = parse_with_comments(
+      r#"{
+        // a comment
+        "a": [1, -2.5e1, "x\"A", null, true,],
+        /* another */ "b": {},
+      }"#,
+    )
+    .unwrap();
+    assert_eq!(
+      json.get("a"),
+      Some(&Json::Array(vec![
+        Json::Number(1.0),
+        Json::Number(-25.0),
+        Json::String(String::from("x\"A")),
+        Json::Null,
+        Json::Bool(true),
+      ]))
+    );
+    assert_eq!(json.get("b"), Some(&Json::Object(vec![])));
+    assert!(parse("{\"a\": 1} x").is_err());
+  }
+
+  #[test]
+  fn refuses_a_slash_outside_comments() {
+    assert!(parse_with_comments("[1 /2]").is_err());
+    assert!(parse_with_comments("[1, /]").is_err());
+    assert!(parse_with_comments("[1 /**/]").is_ok());
+  }
+
+  #[test]
+  fn refuses_what_json_does_not_allow() {
+    assert!(parse("[1, 2]").is_ok());
+    assert!(parse("// a comment\n[]").is_err());
+    assert!(parse("[1,]").is_err());
+    assert!(parse(r#"{"a": 1,}"#).is_err());
+    for number in &["NaN", "inf", "+1", ".5", "1.", "01", "0x1", "1e"] {
+      assert!(parse(number).is_err(), "{}", number);
+    }
+    assert_eq!(parse("-0.5E+2").unwrap(), Json::Number(-50.0));
+    assert!(parse(&"[".repeat(MAX_DEPTH)).is_err());
+    let deep = format!("{}{}", "[".repeat(MAX_DEPTH), "]".repeat(MAX_DEPTH));
+    assert!(parse(&deep).is_ok());
+    assert!(parse(&format!("[{}]", deep)).is_err());
+    assert!(parse(&"[".repeat(100_000)).is_err());
+  }
+
+  #[test]
+  fn writes_what_it_reads() {
+    let json = Json::object(vec![
+      ("id", 1.into()),
+      ("text", "a\"b\\c\n\u{1}é😀".into()),
+      (
+        "items",
+        vec![Json::Null, true.into(), Json::Number(0.5)].into(),
+      ),
+    ]);
+    let written = json.to_string();
+    assert_eq!(
+      written,
+      r#"{"id":1,"text":"a\"b\\c\n\u0001é😀","items":[null,true,0.5]}"#
+    );
+    assert_eq!(parse(&written).unwrap(), json);
+    assert_eq!(
+      parse(r#""\ud83d\ude00""#).unwrap(),
+      Json::String("😀".to_string())
+    );
+  }
+}
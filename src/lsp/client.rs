@@ -0,0 +1,977 @@
+use super::completion::{self, CompletionItem};
+use super::config::ServerConfig;
+use super::hover::{self, SignatureHelp};
+use super::workspace_edit::{self, CodeAction, WorkspaceEdit};
+use super::{char_range, path_to_uri, position, range, Diagnostic, TextEdit};
+use crate::json::{self, Json};
+use std::collections::HashMap;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Stdio};
+use std::sync::{mpsc, Arc};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+/// Called from the thread reading the messages of a server whenever one
+/// arrives, so that they get polled.
+pub type Waker = Arc<dyn Fn() + Send + Sync>;
+
+/// How long a server is given to answer `shutdown`, and then to exit, before
+/// it is killed.
+const EXIT_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Something a server sent which the editor acts on.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Event {
+  Diagnostics {
+    path: PathBuf,
+    diagnostics: Vec<Diagnostic>,
+  },
+  Completion {
+    path: PathBuf,
+    id: usize,
+    items: Vec<CompletionItem>,
+  },
+  Hover {
+    path: PathBuf,
+    id: usize,
+    contents: Option<String>,
+  },
+  SignatureHelp {
+    path: PathBuf,
+    id: usize,
+    help: Option<SignatureHelp>,
+  },
+  Edit {
+    path: PathBuf,
+    id: usize,
+    edit: WorkspaceEdit,
+  },
+  CodeActions {
+    path: PathBuf,
+    id: usize,
+    actions: Vec<CodeAction>,
+  },
+  ApplyEdit {
+    server: String,
+    id: Json,
+    edit: WorkspaceEdit,
+  },
+}
+
+/// How a server wants to be told about changes to the text.
+#[derive(Copy, Clone, Debug, PartialEq)]
+enum SyncKind {
+  None,
+  Full,
+  Incremental,
+}
+
+struct Document {
+  path: PathBuf,
+  language_id: String,
+  version: usize,
+  text: ropey::Rope,
+  opened: bool,
+  diagnostics: Vec<Json>,
+}
+
+struct Pending {
+  method: String,
+  document: Option<(String, usize)>,
+}
+
+pub(super) fn write_message(
+  writer: &mut impl Write,
+  message: &Json,
+) -> io::Result<()> {
+  let body = message.to_string();
+  write!(writer, "Content-Length: {}\r\n\r\n{}", body.len(), body)?;
+  writer.flush()
+}
+
+pub(super) fn read_message(
+  reader: &mut impl BufRead,
+) -> io::Result<Option<Json>> {
+  let mut length = None;
+  loop {
+    let mut line = String::new();
+    if reader.read_line(&mut line)? == 0 {
+      return Ok(None);
+    }
+    match line.trim_end() {
+      "" if length.is_some() => break,
+      line => {
+        if let Some(value) = line.strip_prefix("Content-Length:") {
+          length = value.trim().parse::<usize>().ok();
+        }
+      }
+    }
+  }
+  let mut body = vec![0; length.unwrap_or_default()];
+  reader.read_exact(&mut body)?;
+  json::parse(&String::from_utf8_lossy(&body))
+    .map(Some)
+    .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err.to_string()))
+}
+
+fn client_capabilities() -> Json {
+  Json::object(vec![
+    (
+      "textDocument",
+      Json::object(vec![
+        (
+          "synchronization",
+          Json::object(vec![("dynamicRegistration", false.into())]),
+        ),
+        ("publishDiagnostics", Json::object(vec![])),
+        (
+          "completion",
+          Json::object(vec![(
+            "completionItem",
+            Json::object(vec![
+              ("snippetSupport", true.into()),
+              (
+                "documentationFormat",
+                vec!["plaintext".into(), "markdown".into()].into(),
+              ),
+            ]),
+          )]),
+        ),
+        (
+          "hover",
+          Json::object(vec![(
+            "contentFormat",
+            vec!["markdown".into(), "plaintext".into()].into(),
+          )]),
+        ),
+        (
+          "signatureHelp",
+          Json::object(vec![(
+            "signatureInformation",
+            Json::object(vec![
+              (
+                "documentationFormat",
+                vec!["markdown".into(), "plaintext".into()].into(),
+              ),
+              (
+                "parameterInformation",
+                Json::object(vec![("labelOffsetSupport", true.into())]),
+              ),
+            ]),
+          )]),
+        ),
+        ("rename", Json::object(vec![])),
+        (
+          "codeAction",
+          Json::object(vec![(
+            "codeActionLiteralSupport",
+            Json::object(vec![(
+              "codeActionKind",
+              Json::object(vec![(
+                "valueSet",
+                vec![
+                  "quickfix".into(),
+                  "refactor".into(),
+                  "refactor.extract".into(),
+                  "refactor.inline".into(),
+                  "refactor.rewrite".into(),
+                  "source".into(),
+                  "source.organizeImports".into(),
+                ]
+                .into(),
+              )]),
+            )]),
+          )]),
+        ),
+        ("formatting", Json::object(vec![])),
+        ("rangeFormatting", Json::object(vec![])),
+      ]),
+    ),
+    (
+      "workspace",
+      Json::object(vec![
+        ("applyEdit", true.into()),
+        (
+          "workspaceEdit",
+          Json::object(vec![("documentChanges", true.into())]),
+        ),
+      ]),
+    ),
+    (
+      "general",
+      Json::object(vec![("positionEncodings", vec!["utf-16".into()].into())]),
+    ),
+  ])
+}
+
+/// Connection to a language server speaking JSON-RPC, usually over the stdio
+/// of its process.
+pub struct Client {
+  child: Option<Child>,
+  writer: Option<Box<dyn Write + Send>>,
+  incoming: mpsc::Receiver<Json>,
+  next_id: usize,
+  pending: HashMap<usize, Pending>,
+  initialized: bool,
+  sync: SyncKind,
+  pub capabilities: Json,
+  documents: HashMap<String, Document>,
+}
+
+impl Client {
+  pub fn spawn(
+    config: &ServerConfig,
+    root: &Path,
+    waker: Waker,
+  ) -> io::Result<Self> {
+    let mut child = Command::new(&config.command)
+      .args(&config.args)
+      .current_dir(root)
+      .stdin(Stdio::piped())
+      .stdout(Stdio::piped())
+      .stderr(Stdio::null())
+      .spawn()?;
+    let pipes = child.stdout.take().zip(child.stdin.take());
+    let (stdout, stdin) =
+      pipes.ok_or_else(|| io::Error::new(io::ErrorKind::Other, "no stdio"))?;
+    let mut client = Self::connect(stdout, stdin, root, waker);
+    client.child = Some(child);
+    Ok(client)
+  }
+
+  pub fn connect(
+    reader: impl Read + Send + 'static,
+    writer: impl Write + Send + 'static,
+    root: &Path,
+    waker: Waker,
+  ) -> Self {
+    let (sender, incoming) = mpsc::channel();
+    std::thread::spawn(move || {
+      let mut reader = BufReader::new(reader);
+      loop {
+        let message = match read_message(&mut reader) {
+          Ok(Some(message)) => message,
+          Err(err) if err.kind() == io::ErrorKind::InvalidData => {
+            log::warn!("unreadable message from the language server: {}", err);
+            continue;
+          }
+          Ok(None) | Err(_) => return,
+        };
+        if sender.send(message).is_err() {
+          return;
+        }
+        waker();
+      }
+    });
+
+    let mut client = Self {
+      child: None,
+      writer: Some(Box::new(writer)),
+      incoming,
+      next_id: 1,
+      pending: HashMap::new(),
+      initialized: false,
+      sync: SyncKind::None,
+      capabilities: Json::Null,
+      documents: HashMap::new(),
+    };
+    let root_uri = path_to_uri(root);
+    let name = root
+      .file_name()
+      .map_or(String::new(), |name| name.to_string_lossy().into_owned());
+    client.request(
+      "initialize",
+      Json::object(vec![
+        ("processId", (std::process::id() as usize).into()),
+        ("rootUri", root_uri.clone().into()),
+        (
+          "workspaceFolders",
+          vec![Json::object(vec![
+            ("uri", root_uri.into()),
+            ("name", name.into()),
+          ])]
+          .into(),
+        ),
+        ("capabilities", client_capabilities()),
+        (
+          "clientInfo",
+          Json::object(vec![("name", env!("CARGO_PKG_NAME").into())]),
+        ),
+      ]),
+    );
+    client
+  }
+
+  fn send(&mut self, message: Json) {
+    if let Some(writer) = &mut self.writer {
+      if let Err(err) = write_message(writer, &message) {
+        log::error!("failed to write to the language server: {}", err);
+        self.writer = None;
+      }
+    }
+  }
+
+  fn message(method: &str, params: Json) -> Vec<(&str, Json)> {
+    let mut fields = vec![("jsonrpc", "2.0".into()), ("method", method.into())];
+    if params != Json::Null {
+      fields.push(("params", params));
+    }
+    fields
+  }
+
+  pub fn request(&mut self, method: &str, params: Json) -> usize {
+    self.send_request(method, params, None)
+  }
+
+  fn send_request(
+    &mut self,
+    method: &str,
+    params: Json,
+    document: Option<(String, usize)>,
+  ) -> usize {
+    let id = self.next_id;
+    self.next_id += 1;
+    let pending = Pending {
+      method: method.to_string(),
+      document,
+    };
+    self.pending.insert(id, pending);
+    let mut fields = Self::message(method, params);
+    fields.insert(1, ("id", id.into()));
+    self.send(Json::object(fields));
+    id
+  }
+
+  pub fn notify(&mut self, method: &str, params: Json) {
+    self.send(Json::object(Self::message(method, params)));
+  }
+
+  pub fn cancel(&mut self, id: usize) {
+    if self.pending.remove(&id).is_some() {
+      let params = Json::object(vec![("id", id.into())]);
+      self.notify("$/cancelRequest", params);
+    }
+  }
+
+  pub fn open(&mut self, path: &Path, language_id: &str, text: &ropey::Rope) {
+    let uri = path_to_uri(path);
+    self.documents.insert(
+      uri.clone(),
+      Document {
+        path: path.to_path_buf(),
+        language_id: language_id.to_string(),
+        version: 0,
+        text: text.clone(),
+        opened: false,
+        diagnostics: vec![],
+      },
+    );
+    if self.initialized {
+      self.send_open(&uri);
+    }
+  }
+
+  fn send_open(&mut self, uri: &str) {
+    let document = match self.documents.get_mut(uri) {
+      Some(document) => document,
+      None => return,
+    };
+    document.opened = true;
+    let params = Json::object(vec![(
+      "textDocument",
+      Json::object(vec![
+        ("uri", uri.into()),
+        ("languageId", document.language_id.as_str().into()),
+        ("version", document.version.into()),
+        ("text", document.text.to_string().into()),
+      ]),
+    )]);
+    self.notify("textDocument/didOpen", params);
+  }
+
+  pub fn has(&self, path: &Path) -> bool {
+    self.documents.contains_key(&path_to_uri(path))
+  }
+
+  pub fn version(&self, path: &Path) -> Option<usize> {
+    Some(self.documents.get(&path_to_uri(path))?.version)
+  }
+
+  fn document_request(
+    &mut self,
+    method: &str,
+    capability: &str,
+    path: &Path,
+    params: impl FnOnce(&Document) -> Vec<(&'static str, Json)>,
+  ) -> Option<usize> {
+    match self.capabilities.get(capability)? {
+      Json::Bool(false) | Json::Null => return None,
+      _ => {}
+    }
+    let uri = path_to_uri(path);
+    let document = self.documents.get(&uri).filter(|d| d.opened)?;
+    let version = document.version;
+    let mut params = params(document);
+    params.insert(
+      0,
+      (
+        "textDocument",
+        Json::object(vec![("uri", uri.as_str().into())]),
+      ),
+    );
+    let document = Some((uri, version));
+    Some(self.send_request(method, Json::object(params), document))
+  }
+
+  fn position_request(
+    &mut self,
+    method: &str,
+    capability: &str,
+    path: &Path,
+    char_idx: usize,
+  ) -> Option<usize> {
+    self.document_request(method, capability, path, |document| {
+      vec![("position", position(&document.text, char_idx))]
+    })
+  }
+
+  pub fn completion(&mut self, path: &Path, char_idx: usize) -> Option<usize> {
+    let method = "textDocument/completion";
+    self.position_request(method, "completionProvider", path, char_idx)
+  }
+
+  pub fn hover(&mut self, path: &Path, char_idx: usize) -> Option<usize> {
+    let method = "textDocument/hover";
+    self.position_request(method, "hoverProvider", path, char_idx)
+  }
+
+  pub fn signature_help(
+    &mut self,
+    path: &Path,
+    char_idx: usize,
+  ) -> Option<usize> {
+    let method = "textDocument/signatureHelp";
+    self.position_request(method, "signatureHelpProvider", path, char_idx)
+  }
+
+  pub fn rename(
+    &mut self,
+    path: &Path,
+    char_idx: usize,
+    new_name: &str,
+  ) -> Option<usize> {
+    let method = "textDocument/rename";
+    self.document_request(method, "renameProvider", path, |document| {
+      vec![
+        ("position", position(&document.text, char_idx)),
+        ("newName", new_name.into()),
+      ]
+    })
+  }
+
+  pub fn code_actions(
+    &mut self,
+    path: &Path,
+    chars: Range<usize>,
+  ) -> Option<usize> {
+    let method = "textDocument/codeAction";
+    self.document_request(method, "codeActionProvider", path, |document| {
+      let diagnostics = document
+        .diagnostics
+        .iter()
+        .filter(|diagnostic| {
+          let range = diagnostic.get("range");
+          let range = range.and_then(|json| char_range(&document.text, json));
+          range.map_or(false, |range| {
+            range.start <= chars.end && chars.start <= range.end
+          })
+        })
+        .cloned()
+        .collect::<Vec<_>>();
+      vec![
+        ("range", range(&document.text, chars)),
+        (
+          "context",
+          Json::object(vec![("diagnostics", diagnostics.into())]),
+        ),
+      ]
+    })
+  }
+
+  pub fn execute_command(&mut self, command: &Json) {
+    let name = command.get("command").cloned().unwrap_or(Json::Null);
+    let arguments = command.get("arguments").cloned();
+    let mut params = vec![("command", name)];
+    params.extend(arguments.map(|arguments| ("arguments", arguments)));
+    self.request("workspace/executeCommand", Json::object(params));
+  }
+
+  pub fn formatting(
+    &mut self,
+    path: &Path,
+    char_range: Option<Range<usize>>,
+    indent_unit: &str,
+  ) -> Option<usize> {
+    let options = Json::object(vec![
+      ("tabSize", indent_unit.len().max(1).into()),
+      ("insertSpaces", (!indent_unit.starts_with('\t')).into()),
+    ]);
+    match char_range {
+      Some(char_range) => {
+        let method = "textDocument/rangeFormatting";
+        let capability = "documentRangeFormattingProvider";
+        self.document_request(method, capability, path, |document| {
+          vec![
+            ("range", range(&document.text, char_range)),
+            ("options", options),
+          ]
+        })
+      }
+      None => {
+        let method = "textDocument/formatting";
+        let capability = "documentFormattingProvider";
+        self.document_request(method, capability, path, |_| {
+          vec![("options", options)]
+        })
+      }
+    }
+  }
+
+  fn characters(&self, capability: &str, keys: &[&str]) -> Vec<String> {
+    keys
+      .iter()
+      .filter_map(|key| self.capabilities.pointer(&[capability, key]))
+      .filter_map(Json::as_array)
+      .flatten()
+      .filter_map(|c| Some(c.as_str()?.to_string()))
+      .collect()
+  }
+
+  pub fn trigger_characters(&self) -> Vec<String> {
+    self.characters("completionProvider", &["triggerCharacters"])
+  }
+
+  pub fn signature_trigger_characters(&self) -> Vec<String> {
+    let keys = ["triggerCharacters", "retriggerCharacters"];
+    self.characters("signatureHelpProvider", &keys)
+  }
+
+  pub fn change(&mut self, path: &Path, edits: &[TextEdit]) {
+    let uri = path_to_uri(path);
+    let sync = self.sync;
+    let document = match self.documents.get_mut(&uri) {
+      Some(document) if !edits.is_empty() => document,
+      _ => return,
+    };
+    // files not opened yet are sent whole once they are
+    let sync = if document.opened {
+      sync
+    } else {
+      SyncKind::None
+    };
+    let mut changes = vec![];
+    for edit in edits {
+      if sync == SyncKind::Incremental {
+        changes.push(Json::object(vec![
+          ("range", range(&document.text, edit.range.clone())),
+          ("text", edit.new_text.as_str().into()),
+        ]));
+      }
+      document.text.remove(edit.range.clone());
+      document.text.insert(edit.range.start, &edit.new_text);
+    }
+    if sync == SyncKind::Full {
+      changes.push(Json::object(vec![(
+        "text",
+        document.text.to_string().into(),
+      )]));
+    }
+    document.version += 1;
+    if !changes.is_empty() {
+      let params = Json::object(vec![
+        (
+          "textDocument",
+          Json::object(vec![
+            ("uri", uri.into()),
+            ("version", document.version.into()),
+          ]),
+        ),
+        ("contentChanges", changes.into()),
+      ]);
+      self.notify("textDocument/didChange", params);
+    }
+  }
+
+  pub fn close(&mut self, path: &Path) {
+    let uri = path_to_uri(path);
+    if let Some(document) = self.documents.remove(&uri) {
+      if document.opened {
+        let params = Json::object(vec![(
+          "textDocument",
+          Json::object(vec![("uri", uri.into())]),
+        )]);
+        self.notify("textDocument/didClose", params);
+      }
+    }
+  }
+
+  pub fn applied(&mut self, id: Json, result: Result<(), String>) {
+    let mut applied = vec![("applied", result.is_ok().into())];
+    if let Err(reason) = result {
+      applied.push(("failureReason", reason.into()));
+    }
+    self.send(Json::object(vec![
+      ("jsonrpc", "2.0".into()),
+      ("id", id),
+      ("result", Json::object(applied)),
+    ]));
+  }
+
+  pub fn poll(&mut self) -> Vec<Event> {
+    let mut events = vec![];
+    while let Ok(message) = self.incoming.try_recv() {
+      events.extend(self.handle(message));
+    }
+    events
+  }
+
+  fn handle(&mut self, message: Json) -> Option<Event> {
+    let method = message.get("method").and_then(Json::as_str);
+    match (method, message.get("id")) {
+      // edits the server makes are answered once they are made, other
+      // requests get an empty answer
+      (Some("workspace/applyEdit"), Some(id)) => {
+        let edit = message.pointer(&["params", "edit"]);
+        let edit = WorkspaceEdit::from_json(edit.unwrap_or(&Json::Null));
+        Some(Event::ApplyEdit {
+          server: String::new(),
+          id: id.clone(),
+          edit: self.versioned(edit, None),
+        })
+      }
+      (Some(method), Some(id)) => {
+        let result = match method {
+          "workspace/configuration" => {
+            let items = message
+              .pointer(&["params", "items"])
+              .and_then(Json::as_array)
+              .map_or(0, |items| items.len());
+            vec![Json::Null; items].into()
+          }
+          _ => Json::Null,
+        };
+        let response = Json::object(vec![
+          ("jsonrpc", "2.0".into()),
+          ("id", id.clone()),
+          ("result", result),
+        ]);
+        self.send(response);
+        None
+      }
+      (Some("textDocument/publishDiagnostics"), None) => {
+        let params = message.get("params")?;
+        let uri = params.get("uri")?.as_str()?;
+        let document = self.documents.get_mut(uri)?;
+        document.diagnostics = params.get("diagnostics")?.as_array()?.to_vec();
+        let diagnostics = document
+          .diagnostics
+          .iter()
+          .filter_map(|json| Diagnostic::from_json(&document.text, json))
+          .collect();
+        Some(Event::Diagnostics {
+          path: document.path.clone(),
+          diagnostics,
+        })
+      }
+      (Some(_), None) => None,
+      (None, Some(id)) => {
+        let id = id.as_usize()?;
+        let pending = self.pending.remove(&id)?;
+        if let Some(error) = message.get("error") {
+          let error = error.get("message").and_then(Json::as_str);
+          log::warn!(
+            "{} failed: {}",
+            pending.method,
+            error.unwrap_or_default()
+          );
+        }
+        let result = message.get("result").unwrap_or(&Json::Null);
+        let requested = pending.document.as_ref();
+        let document = requested.and_then(|(uri, _)| self.documents.get(uri));
+        match (pending.method.as_str(), document) {
+          ("initialize", _) => {
+            self.initialized(result);
+            None
+          }
+          // the positions of the items are read in the text as it is now,
+          // which they aren't in if it changed since the request
+          ("textDocument/completion", Some(document))
+            if requested.map(|(_, version)| *version)
+              == Some(document.version) =>
+          {
+            Some(Event::Completion {
+              path: document.path.clone(),
+              id,
+              items: completion::items(&document.text, result),
+            })
+          }
+          ("textDocument/hover", Some(document)) => Some(Event::Hover {
+            path: document.path.clone(),
+            id,
+            contents: hover::contents(result),
+          }),
+          ("textDocument/signatureHelp", Some(document)) => {
+            Some(Event::SignatureHelp {
+              path: document.path.clone(),
+              id,
+              help: SignatureHelp::from_json(result),
+            })
+          }
+          ("textDocument/rename", Some(document)) => Some(Event::Edit {
+            path: document.path.clone(),
+            id,
+            edit: self.versioned(WorkspaceEdit::from_json(result), requested),
+          }),
+          ("textDocument/formatting", Some(document))
+          | ("textDocument/rangeFormatting", Some(document)) => {
+            let version = requested.map(|(_, version)| *version);
+            Some(Event::Edit {
+              path: document.path.clone(),
+              id,
+              edit: WorkspaceEdit::of_file(&document.path, version, result),
+            })
+          }
+          ("textDocument/codeAction", Some(document)) => {
+            let mut actions = workspace_edit::code_actions(result);
+            for action in &mut actions {
+              action.edit = action
+                .edit
+                .take()
+                .map(|edit| self.versioned(edit, requested));
+            }
+            Some(Event::CodeActions {
+              path: document.path.clone(),
+              id,
+              actions,
+            })
+          }
+          _ => None,
+        }
+      }
+      (None, None) => None,
+    }
+  }
+
+  fn versioned(
+    &self,
+    mut edit: WorkspaceEdit,
+    requested: Option<&(String, usize)>,
+  ) -> WorkspaceEdit {
+    for file in edit.files.iter_mut().filter(|file| file.version.is_none()) {
+      let uri = path_to_uri(&file.path);
+      file.version = match requested {
+        Some((requested, version)) if *requested == uri => Some(*version),
+        _ => self.documents.get(&uri).map(|document| document.version),
+      };
+    }
+    edit
+  }
+
+  fn initialized(&mut self, result: &Json) {
+    self.capabilities =
+      result.get("capabilities").cloned().unwrap_or(Json::Null);
+    let sync = self.capabilities.get("textDocumentSync");
+    let kind = sync
+      .and_then(Json::as_usize)
+      .or_else(|| sync?.get("change")?.as_usize());
+    self.sync = match kind {
+      Some(1) => SyncKind::Full,
+      Some(2) => SyncKind::Incremental,
+      _ => SyncKind::None,
+    };
+    self.initialized = true;
+    self.notify("initialized", Json::object(vec![]));
+    let uris = self.documents.keys().cloned().collect::<Vec<_>>();
+    for uri in uris {
+      self.send_open(&uri);
+    }
+  }
+
+  pub fn shut_down(&mut self) -> Option<JoinHandle<()>> {
+    if self.writer.is_none() && self.child.is_none() {
+      return None;
+    }
+    let shutdown = match self.initialized {
+      true => Some(self.request("shutdown", Json::Null)),
+      false => None,
+    };
+    let writer = self.writer.take();
+    let incoming = std::mem::replace(&mut self.incoming, mpsc::channel().1);
+    let child = self.child.take();
+    Some(std::thread::spawn(move || {
+      exit(shutdown, writer, incoming, child)
+    }))
+  }
+}
+
+fn exit(
+  shutdown: Option<usize>,
+  writer: Option<Box<dyn Write + Send>>,
+  incoming: mpsc::Receiver<Json>,
+  child: Option<Child>,
+) {
+  if let (Some(id), Some(mut writer)) = (shutdown, writer) {
+    let deadline = Instant::now() + EXIT_TIMEOUT;
+    while let Some(left) = deadline.checked_duration_since(Instant::now()) {
+      match incoming.recv_timeout(left) {
+        Ok(message) if message.get("method").is_none() => {
+          if message.get("id").and_then(Json::as_usize) == Some(id) {
+            break;
+          }
+        }
+        Ok(_) => {}
+        Err(_) => break,
+      }
+    }
+    let exit = Json::object(Client::message("exit", Json::Null));
+    let _ = write_message(&mut writer, &exit);
+  }
+  let mut child = match child {
+    Some(child) => child,
+    None => return,
+  };
+  let deadline = Instant::now() + EXIT_TIMEOUT;
+  while Instant::now() < deadline {
+    if let Ok(Some(_)) = child.try_wait() {
+      return;
+    }
+    std::thread::sleep(Duration::from_millis(10));
+  }
+  let _ = child.kill();
+  let _ = child.wait();
+}
+
+impl Drop for Client {
+  fn drop(&mut self) {
+    self.shut_down();
+  }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+  use super::*;
+  use crate::lsp::Severity;
+  use std::sync::atomic::{AtomicUsize, Ordering};
+
+  fn frame(message: &str) -> String {
+    format!("Content-Length: {}\r\n\r\n{}", message.len(), message)
+  }
+
+  #[test]
+  fn syncs_documents_with_a_scripted_server() {
+    let dir = std::env::temp_dir()
+      .join(format!("devcode-lsp-test-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("a.rs");
+    let replies = dir.join("replies");
+    let log = dir.join("log");
+    std::fs::write(
+      &replies,
+      frame(
+        r#"{"jsonrpc":"2.0","id":1,"result":{"capabilities":
+          {"textDocumentSync":{"openClose":true,"change":2}}}}"#,
+      ) + &frame(&format!(
+        r#"{{"jsonrpc":"2.0","method":"textDocument/publishDiagnostics",
+          "params":{{"uri":"{}","diagnostics":[{{"range":{{"start":
+          {{"line":0,"character":3}},"end":{{"line":0,"character":7}}}},
+          "severity":2,"source":"rustc","message":"unused"}}]}}}}"#,
+        path_to_uri(&path)
+      )),
+    )
+    .unwrap();
+
+    // the server plays back its replies, and logs what it is sent
+    let config = ServerConfig {
+      command: "sh".to_string(),
+      args: vec![
+        "-c".to_string(),
+        format!("cat '{}'; cat > '{}'", replies.display(), log.display()),
+      ],
+      language_id: "rust".to_string(),
+      format_on_save: false,
+    };
+    let woken = Arc::new(AtomicUsize::new(0));
+    let counter = Arc::clone(&woken);
+    let waker: Waker = Arc::new(move || {
+      counter.fetch_add(1, Ordering::SeqCst);
+    });
+    let mut client = Client::spawn(&config, &dir, waker).unwrap();
+    client.open(&path, "rust", &ropey::Rope::from_str("fn main() {}\n"));
+
+    let deadline = Instant::now() + Duration::from_secs(5);
+    let mut events = vec![];
+    while events.is_empty() && Instant::now() < deadline {
+      events.extend(client.poll());
+      std::thread::sleep(Duration::from_millis(10));
+    }
+    assert_eq!(
+      events,
+      vec![Event::Diagnostics {
+        path: path.clone(),
+        diagnostics: vec![Diagnostic {
+          range: 3..7,
+          severity: Severity::Warning,
+          message: "unused".to_string(),
+          source: Some("rustc".to_string()),
+        }],
+      }]
+    );
+    // the reader wakes the editor after passing on each message, so the
+    // second wake may come after its message was polled
+    while woken.load(Ordering::SeqCst) < 2 && Instant::now() < deadline {
+      std::thread::sleep(Duration::from_millis(10));
+    }
+    assert_eq!(woken.load(Ordering::SeqCst), 2);
+
+    let edit = TextEdit {
+      range: 7..7,
+      new_text: "s".to_string(),
+    };
+    client.change(&path, &[edit]);
+    client.shut_down().unwrap().join().unwrap();
+    assert!(client.shut_down().is_none());
+    drop(client);
+
+    let log = std::fs::read(&log).unwrap();
+    let mut reader = io::Cursor::new(log);
+    let mut sent = vec![];
+    while let Some(message) = read_message(&mut reader).unwrap() {
+      sent.push(message);
+    }
+    let methods = sent
+      .iter()
+      .filter_map(|message| message.get("method")?.as_str())
+      .collect::<Vec<_>>();
+    assert_eq!(
+      methods,
+      [
+        "initialize",
+        "initialized",
+        "textDocument/didOpen",
+        "textDocument/didChange",
+        "shutdown",
+        "exit"
+      ]
+    );
+    assert_eq!(
+      sent[3].get("params").unwrap().to_string(),
+      format!(
+        r#"{{"textDocument":{{"uri":"{}","version":1}},"contentChanges":[{{"range":{{"start":{{"line":0,"character":7}},"end":{{"line":0,"character":7}}}},"text":"s"}}]}}"#,
+        path_to_uri(&path)
+      )
+    );
+    std::fs::remove_dir_all(&dir).unwrap();
+  }
+}
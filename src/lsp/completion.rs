@@ -0,0 +1,336 @@
+use super::{char_range, TextEdit};
+use crate::json::Json;
+use std::collections::HashMap;
+use std::ops::Range;
+
+/// Entry of the completions a server offers at a position.
+#[derive(Clone, Debug, PartialEq)]
+pub struct CompletionItem {
+  pub label: String,
+  pub kind: Option<usize>,
+  pub detail: Option<String>,
+  pub documentation: Option<String>,
+  pub filter_text: String,
+  pub sort_text: String,
+  pub range: Option<Range<usize>>,
+  pub insert_text: String,
+  pub snippet: bool,
+  pub additional_edits: Vec<TextEdit>,
+}
+
+impl CompletionItem {
+  fn from_json(text: &ropey::Rope, json: &Json) -> Option<Self> {
+    let label = json.get("label")?.as_str()?.to_string();
+    let string =
+      |key: &str| json.get(key).and_then(Json::as_str).map(str::to_string);
+    // documentation is either a string or markup with its kind
+    let documentation = json.get("documentation").and_then(|documentation| {
+      documentation
+        .as_str()
+        .or_else(|| documentation.get("value")?.as_str())
+        .map(str::to_string)
+    });
+    // edits replacing a range take the one of inserting, not of replacing
+    let text_edit = json.get("textEdit");
+    let range = text_edit.and_then(|edit| {
+      char_range(text, edit.get("range").or_else(|| edit.get("insert"))?)
+    });
+    let insert_text = text_edit
+      .and_then(|edit| edit.get("newText")?.as_str())
+      .map(str::to_string)
+      .or_else(|| string("insertText"))
+      .unwrap_or_else(|| label.clone());
+    let additional_edits = json
+      .get("additionalTextEdits")
+      .and_then(Json::as_array)
+      .map_or_else(Vec::new, |edits| {
+        edits
+          .iter()
+          .filter_map(|edit| TextEdit::from_json(text, edit))
+          .collect()
+      });
+    Some(Self {
+      kind: json.get("kind").and_then(Json::as_usize),
+      detail: string("detail"),
+      documentation,
+      filter_text: string("filterText").unwrap_or_else(|| label.clone()),
+      sort_text: string("sortText").unwrap_or_else(|| label.clone()),
+      range,
+      insert_text,
+      snippet: json.get("insertTextFormat").and_then(Json::as_usize) == Some(2),
+      additional_edits,
+      label,
+    })
+  }
+}
+
+pub fn items(text: &ropey::Rope, result: &Json) -> Vec<CompletionItem> {
+  result
+    .as_array()
+    .or_else(|| result.get("items")?.as_array())
+    .map_or_else(Vec::new, |items| {
+      items
+        .iter()
+        .filter_map(|item| CompletionItem::from_json(text, item))
+        .collect()
+    })
+}
+
+/// Number the stops of variables get until they are put after the others.
+const VARIABLE: usize = usize::MAX;
+
+type Placeholders = HashMap<usize, String>;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Snippet {
+  pub text: String,
+  pub stops: Vec<(usize, Range<usize>)>,
+}
+
+impl Snippet {
+  pub fn parse(source: &str) -> Self {
+    // a stop repeats the text of its placeholder, which may come after it,
+    // so the placeholders are read first
+    let first = Self::parse_with(source, &Placeholders::new());
+    let mut placeholders = Placeholders::new();
+    for (number, range) in first.stops.iter().rev() {
+      if !range.is_empty() {
+        let text = first.text.chars().skip(range.start).take(range.len());
+        placeholders.insert(*number, text.collect());
+      }
+    }
+    let mut snippet = Self::parse_with(source, &placeholders);
+
+    // unknown variables are placeholders following the numbered ones
+    let mut next = snippet
+      .stops
+      .iter()
+      .map(|(number, _)| *number)
+      .filter(|number| *number != VARIABLE)
+      .max()
+      .unwrap_or(0);
+    for (number, _) in &mut snippet.stops {
+      if *number == VARIABLE {
+        next += 1;
+        *number = next;
+      }
+    }
+    snippet
+  }
+
+  fn parse_with(source: &str, placeholders: &Placeholders) -> Self {
+    let mut snippet = Self {
+      text: String::new(),
+      stops: vec![],
+    };
+    let mut chars = source.chars().peekable();
+    snippet.parse_until(&mut chars, None, placeholders);
+    snippet
+  }
+
+  fn push_stop(&mut self, number: usize, text: &str) {
+    let start = self.len();
+    self.text.push_str(text);
+    self.stops.push((number, start..self.len()));
+  }
+
+  fn len(&self) -> usize {
+    self.text.chars().count()
+  }
+
+  fn parse_until(
+    &mut self,
+    chars: &mut std::iter::Peekable<std::str::Chars>,
+    end: Option<char>,
+    placeholders: &Placeholders,
+  ) {
+    while let Some(c) = chars.next() {
+      match c {
+        _ if Some(c) == end => return,
+        '\\' => match chars.peek() {
+          Some(&escaped) if "$}\\,|".contains(escaped) => {
+            self.text.push(escaped);
+            chars.next();
+          }
+          _ => self.text.push(c),
+        },
+        '$' => match chars.peek() {
+          Some(digit) if digit.is_ascii_digit() => match Self::number(chars) {
+            Ok(number) => {
+              let text = placeholders.get(&number).map_or("", String::as_str);
+              self.push_stop(number, text);
+            }
+            Err(digits) => {
+              self.text.push('$');
+              self.text.push_str(&digits);
+            }
+          },
+          Some('{') => {
+            chars.next();
+            self.parse_braced(chars, placeholders);
+          }
+          Some(c) if c.is_alphabetic() || *c == '_' => {
+            let name = Self::name(chars);
+            self.push_stop(VARIABLE, &name);
+          }
+          _ => self.text.push(c),
+        },
+        c => self.text.push(c),
+      }
+    }
+  }
+
+  fn parse_braced(
+    &mut self,
+    chars: &mut std::iter::Peekable<std::str::Chars>,
+    placeholders: &Placeholders,
+  ) {
+    let number = match chars.peek() {
+      Some(c) if c.is_ascii_digit() => match Self::number(chars) {
+        Ok(number) => Ok(number),
+        Err(digits) => {
+          self.text.push_str("${");
+          self.text.push_str(&digits);
+          return;
+        }
+      },
+      _ => Err(Self::name(chars)),
+    };
+    let start = self.len();
+    match (chars.next(), number) {
+      // the default of a variable, none of which are known
+      (Some(':'), Err(_)) => self.parse_until(chars, Some('}'), placeholders),
+      (Some(':'), Ok(number)) => {
+        self.parse_until(chars, Some('}'), placeholders);
+        self.stops.push((number, start..self.len()));
+      }
+      (Some('|'), number) => {
+        let choices = chars.take_while(|&c| c != '|').collect::<String>();
+        let first = choices.split(',').next().unwrap_or_default();
+        self.text.push_str(first);
+        chars.find(|&c| c == '}');
+        if let Ok(number) = number {
+          self.stops.push((number, start..self.len()));
+        }
+      }
+      (_, Ok(number)) => {
+        let text = placeholders.get(&number).map_or("", String::as_str);
+        self.push_stop(number, text);
+      }
+      (_, Err(name)) if !name.is_empty() => self.push_stop(VARIABLE, &name),
+      _ => {}
+    }
+  }
+
+  fn number(
+    chars: &mut std::iter::Peekable<std::str::Chars>,
+  ) -> Result<usize, String> {
+    let mut digits = String::new();
+    let mut number = Some(0usize);
+    while let Some(digit) = chars.peek().and_then(|c| c.to_digit(10)) {
+      number = number
+        .and_then(|number| number.checked_mul(10))
+        .and_then(|number| number.checked_add(digit as usize));
+      digits.extend(chars.next());
+    }
+    number.ok_or(digits)
+  }
+
+  fn name(chars: &mut std::iter::Peekable<std::str::Chars>) -> String {
+    let mut name = String::new();
+    while let Some(c) = chars.next_if(|c| c.is_alphanumeric() || *c == '_') {
+      name.push(c);
+    }
+    name
+  }
+
+  pub fn tab_order(&self) -> Vec<Range<usize>> {
+    let mut stops = self
+      .stops
+      .iter()
+      .filter(|(number, _)| *number > 0)
+      .collect::<Vec<_>>();
+    stops.sort_by_key(|(number, _)| *number);
+    stops.dedup_by_key(|(number, _)| *number);
+    let last = match self.stops.iter().find(|(number, _)| *number == 0) {
+      Some((_, range)) => range.clone(),
+      None => self.len()..self.len(),
+    };
+    stops
+      .into_iter()
+      .map(|(_, range)| range.clone())
+      .chain(std::iter::once(last))
+      .collect()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::json;
+
+  #[test]
+  fn reads_completion_items() {
+    let text = ropey::Rope::from_str("use a;\nfoo.ba");
+    let result = json::parse(
+      r#"{"isIncomplete":false,"items":[
+        {"label":"bar","kind":2,"detail":"fn bar()",
+         "documentation":{"kind":"markdown","value":"Does *bar*."},
+         "textEdit":{"newText":"bar()","range":{"start":{"line":1,"character":4},
+           "end":{"line":1,"character":6}}},
+         "additionalTextEdits":[{"newText":"use b;\n","range":{"start":
+           {"line":1,"character":0},"end":{"line":1,"character":0}}}]},
+        {"label":"baz","insertText":"baz($1)","insertTextFormat":2,
+         "sortText":"0"}
+      ]}"#,
+    )
+    .unwrap();
+    let items = items(&text, &result);
+    assert_eq!(items.len(), 2);
+    assert_eq!(items[0].kind, Some(2));
+    assert_eq!(items[0].documentation.as_deref(), Some("Does *bar*."));
+    assert_eq!(items[0].range, Some(11..13));
+    assert_eq!(items[0].insert_text, "bar()");
+    assert_eq!(
+      items[0].additional_edits,
+      vec![TextEdit {
+        range: 7..7,
+        new_text: "use b;\n".to_string(),
+      }]
+    );
+    assert!(!items[0].snippet);
+    assert_eq!((items[1].range.clone(), items[1].snippet), (None, true));
+    assert_eq!(items[1].filter_text, "baz");
+    assert_eq!(items[1].sort_text, "0");
+  }
+
+  #[test]
+  fn expands_snippets() {
+    let snippet = Snippet::parse("for ${1:item} in ${2:items} {\n\t$0\n}");
+    assert_eq!(snippet.text, "for item in items {\n\t\n}");
+    assert_eq!(snippet.tab_order(), vec![4..8, 12..17, 21..21]);
+
+    let snippet =
+      Snippet::parse(r"${2:b} ${1:a ${3:c}} ${4|x,y|} \$5 $TM_FILENAME");
+    assert_eq!(snippet.text, "b a c x $5 TM_FILENAME");
+    assert_eq!(
+      snippet.tab_order(),
+      vec![2..5, 0..1, 4..5, 6..7, 11..22, 22..22]
+    );
+    assert_eq!(Snippet::parse("plain").tab_order(), vec![5..5]);
+
+    let snippet = Snippet::parse("f($2, ${1:a}, $1)$0;");
+    assert_eq!(snippet.text, "f(, a, a);");
+    assert_eq!(snippet.tab_order(), vec![4..5, 2..2, 9..9]);
+    let snippet = Snippet::parse("$1 = ${1:x} + ${VAR:y}");
+    assert_eq!(snippet.text, "x = x + y");
+
+    let snippet =
+      Snippet::parse("$99999999999999999999 ${99999999999999999999:x}");
+    assert_eq!(
+      snippet.text,
+      "$99999999999999999999 ${99999999999999999999:x}"
+    );
+    assert!(snippet.stops.is_empty());
+  }
+}
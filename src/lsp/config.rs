@@ -0,0 +1,142 @@
+use std::collections::HashMap;
+
+/// File with the user's language servers, see `parse`.
+pub const SERVERS_FILE: &str = "~/.config/devcode/servers.toml";
+
+/// How to start the language server of a language.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ServerConfig {
+  pub command: String,
+  pub args: Vec<String>,
+  pub language_id: String,
+  pub format_on_save: bool,
+}
+
+impl ServerConfig {
+  fn new(command: &str, args: &[&str], language_id: &str) -> Self {
+    Self {
+      command: command.to_string(),
+      args: args.iter().map(|arg| arg.to_string()).collect(),
+      language_id: language_id.to_string(),
+      format_on_save: false,
+    }
+  }
+
+  pub fn key(&self) -> String {
+    std::iter::once(&self.command)
+      .chain(&self.args)
+      .cloned()
+      .collect::<Vec<_>>()
+      .join(" ")
+  }
+}
+
+pub fn builtin() -> HashMap<String, ServerConfig> {
+  let typescript = |language_id| {
+    ServerConfig::new("typescript-language-server", &["--stdio"], language_id)
+  };
+  vec![
+    ("rust", ServerConfig::new("rust-analyzer", &[], "rust")),
+    ("javascript", typescript("javascript")),
+    ("jsx", typescript("javascriptreact")),
+    ("typescript", typescript("typescript")),
+    ("tsx", typescript("typescriptreact")),
+    (
+      "python",
+      ServerConfig::new("pyright-langserver", &["--stdio"], "python"),
+    ),
+  ]
+  .into_iter()
+  .map(|(name, config)| (name.to_string(), config))
+  .collect()
+}
+
+pub fn parse(
+  source: &str,
+  configs: &mut HashMap<String, ServerConfig>,
+) -> Result<(), anyhow::Error> {
+  let value: toml::Value = source.parse()?;
+  let table = value
+    .as_table()
+    .ok_or_else(|| anyhow::anyhow!("expected a table"))?;
+  for (language, server) in table {
+    let command = server
+      .get("command")
+      .and_then(|command| command.as_str())
+      .ok_or_else(|| anyhow::anyhow!("`{}` has no command", language))?;
+    if command.is_empty() {
+      configs.remove(language);
+      continue;
+    }
+    let args = server
+      .get("args")
+      .and_then(|args| args.as_array())
+      .map(|args| {
+        args
+          .iter()
+          .filter_map(|arg| Some(arg.as_str()?.to_string()))
+          .collect()
+      })
+      .unwrap_or_default();
+    let language_id = server
+      .get("language_id")
+      .and_then(|id| id.as_str())
+      .unwrap_or(language);
+    let format_on_save = server
+      .get("format_on_save")
+      .and_then(|format| format.as_bool())
+      .unwrap_or_default();
+    configs.insert(
+      language.clone(),
+      ServerConfig {
+        command: command.to_string(),
+        args,
+        language_id: language_id.to_string(),
+        format_on_save,
+      },
+    );
+  }
+  Ok(())
+}
+
+pub fn load() -> HashMap<String, ServerConfig> {
+  let mut configs = builtin();
+  let path = shellexpand::tilde(SERVERS_FILE);
+  if let Ok(source) = std::fs::read_to_string(path.as_ref()) {
+    if let Err(err) = parse(&source, &mut configs) {
+      log::warn!("failed to read {}: {}", SERVERS_FILE, err);
+    }
+  }
+  configs
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn applies_user_servers() {
+    let mut configs = builtin();
+    parse(
+      r#"
+        [go]
+        command = "gopls"
+
+        [python]
+        command = "pylsp"
+        args = ["-v"]
+        format_on_save = true
+
+        [rust]
+        command = ""
+      "#,
+      &mut configs,
+    )
+    .unwrap();
+    assert_eq!(configs["go"], ServerConfig::new("gopls", &[], "go"));
+    assert_eq!(configs["python"].key(), "pylsp -v");
+    assert!(configs["python"].format_on_save);
+    assert!(!configs.contains_key("rust"));
+    assert_eq!(configs["tsx"].key(), configs["javascript"].key());
+  }
+}
@@ -0,0 +1,155 @@
+use crate::json::Json;
+use std::ops::Range;
+
+pub fn contents(result: &Json) -> Option<String> {
+  fn part(json: &Json) -> Option<String> {
+    if let Some(markdown) = json.as_str() {
+      return Some(markdown.to_string());
+    }
+    let value = json.get("value")?.as_str()?;
+    match json.get("language").and_then(Json::as_str) {
+      Some(language) => Some(format!("```{}\n{}\n```", language, value)),
+      None => Some(value.to_string()),
+    }
+  }
+
+  let contents = result.get("contents")?;
+  let markdown = match contents.as_array() {
+    Some(parts) => parts
+      .iter()
+      .filter_map(part)
+      .collect::<Vec<_>>()
+      .join("\n\n"),
+    None => part(contents)?,
+  };
+  Some(markdown).filter(|markdown| !markdown.trim().is_empty())
+}
+
+/// Signature of the call the cursor is in.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SignatureHelp {
+  pub label: String,
+  pub parameter: Option<Range<usize>>,
+  pub documentation: Option<String>,
+}
+
+fn documentation(json: Option<&Json>) -> Option<String> {
+  let json = json?;
+  json
+    .as_str()
+    .or_else(|| json.get("value")?.as_str())
+    .map(str::to_string)
+}
+
+fn utf16_to_char(text: &str, offset: usize) -> usize {
+  let mut units = 0;
+  text
+    .chars()
+    .take_while(|c| {
+      units += c.len_utf16();
+      units <= offset
+    })
+    .count()
+}
+
+impl SignatureHelp {
+  pub fn from_json(result: &Json) -> Option<Self> {
+    let signatures = result.get("signatures")?.as_array()?;
+    let active = result
+      .get("activeSignature")
+      .and_then(Json::as_usize)
+      .unwrap_or_default();
+    let signature = signatures.get(active).or_else(|| signatures.first())?;
+    let label = signature.get("label")?.as_str()?.to_string();
+
+    let active = signature
+      .get("activeParameter")
+      .or_else(|| result.get("activeParameter"))
+      .and_then(Json::as_usize)
+      .unwrap_or_default();
+    let parameter = signature
+      .get("parameters")
+      .and_then(Json::as_array)
+      .and_then(|parameters| parameters.get(active));
+    // parameters are labeled with their text, which is looked for after
+    // the name of the signature, or with their UTF-16 offsets
+    let range = parameter.and_then(|parameter| {
+      let name = parameter.get("label")?;
+      match name.as_array() {
+        Some(offsets) => {
+          let start = offsets.get(0)?.as_usize()?;
+          let end = offsets.get(1)?.as_usize()?;
+          Some(utf16_to_char(&label, start)..utf16_to_char(&label, end))
+        }
+        None => {
+          let name = name.as_str()?;
+          let from = label.find('(').unwrap_or_default();
+          let at = from + label[from..].find(name)?;
+          let start = label[..at].chars().count();
+          Some(start..start + name.chars().count())
+        }
+      }
+    });
+
+    let parts = [
+      documentation(parameter.and_then(|p| p.get("documentation"))),
+      documentation(signature.get("documentation")),
+    ];
+    let documentation = parts.iter().flatten().cloned().collect::<Vec<_>>();
+    Some(Self {
+      label,
+      parameter: range,
+      documentation: Some(documentation.join("\n\n"))
+        .filter(|documentation| !documentation.is_empty()),
+    })
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::json;
+
+  #[test]
+  fn reads_hover_contents() {
+    let markup = json::parse(
+      r#"{"contents":{"kind":"markdown","value":"```rust\nfn a()\n```"}}"#,
+    )
+    .unwrap();
+    assert_eq!(contents(&markup).as_deref(), Some("```rust\nfn a()\n```"));
+    let marked = json::parse(
+      r#"{"contents":[{"language":"python","value":"def a()"},"Does *a*."]}"#,
+    )
+    .unwrap();
+    assert_eq!(
+      contents(&marked).as_deref(),
+      Some("```python\ndef a()\n```\n\nDoes *a*.")
+    );
+    assert_eq!(contents(&json::parse(r#"{"contents":""}"#).unwrap()), None);
+    assert_eq!(contents(&Json::Null), None);
+  }
+
+  #[test]
+  fn reads_the_active_parameter() {
+    let result = json::parse(
+      r#"{"signatures":[{"label":"fn add(a: i32, b: i32)",
+        "documentation":"Adds.","parameters":[{"label":"a: i32"},
+        {"label":"b: i32","documentation":"The second."}]}],
+        "activeParameter":1}"#,
+    )
+    .unwrap();
+    let help = SignatureHelp::from_json(&result).unwrap();
+    assert_eq!(help.parameter, Some(15..21));
+    assert_eq!(help.documentation.as_deref(), Some("The second.\n\nAdds."));
+
+    // offsets count UTF-16 code units
+    let result = json::parse(
+      r#"{"signatures":[{"label":"f(é, 😀: u8)","activeParameter":1,
+        "parameters":[{"label":[2,3]},{"label":[5,11]}]}]}"#,
+    )
+    .unwrap();
+    let help = SignatureHelp::from_json(&result).unwrap();
+    assert_eq!(help.parameter, Some(5..10));
+    assert_eq!(SignatureHelp::from_json(&Json::Null), None);
+  }
+}
@@ -0,0 +1,711 @@
+//! Language server completing the words of the files and telling how often
+//! they are used, renaming them, and trimming trailing whitespace as
+//! formatting, run with `devcode --mock-language-server` to try the editor
+//! without a real one. It is only built with the `mock-language-server`
+//! feature, and for the tests.
+
+use super::client::{read_message, write_message};
+use super::{apply_edits, char_index, char_range, range, TextEdit};
+use crate::json::Json;
+use std::collections::{BTreeMap, HashMap};
+use std::io::{self, BufRead, Write};
+use std::ops::Range;
+
+fn is_word(c: char) -> bool {
+  c.is_alphanumeric() || c == '_'
+}
+
+fn words(text: &ropey::Rope) -> BTreeMap<String, usize> {
+  let mut words = BTreeMap::new();
+  let mut word = String::new();
+  for c in text.chars().chain(std::iter::once(' ')) {
+    if is_word(c) {
+      word.push(c);
+    } else if word.chars().count() > 1 {
+      *words.entry(std::mem::take(&mut word)).or_insert(0) += 1;
+    } else {
+      word.clear();
+    }
+  }
+  words
+}
+
+fn completions(text: &ropey::Rope, char_idx: usize) -> Json {
+  let mut chars = text.chars_at(char_idx);
+  let mut start = char_idx;
+  while chars.prev().map_or(false, is_word) {
+    start -= 1;
+  }
+  let typed = text.slice(start..char_idx).to_string();
+  let replaced = range(text, start..char_idx);
+
+  let mut items = words(text)
+    .into_iter()
+    .filter(|(word, _)| *word != typed)
+    .map(|(word, count)| {
+      Json::object(vec![
+        ("label", word.as_str().into()),
+        ("kind", 1.into()),
+        (
+          "documentation",
+          format!("Word of the file, used {} times.", count).into(),
+        ),
+        (
+          "textEdit",
+          Json::object(vec![
+            ("range", replaced.clone()),
+            ("newText", word.into()),
+          ]),
+        ),
+      ])
+    })
+    .collect::<Vec<_>>();
+  items.push(Json::object(vec![
+    ("label", "fn".into()),
+    ("kind", 15.into()),
+    ("detail", "function".into()),
+    ("insertText", "fn ${1:name}(${2}) {\n\t$0\n}".into()),
+    ("insertTextFormat", 2.into()),
+  ]));
+  let import = TextEdit {
+    range: 0..0,
+    new_text: "use std::collections::HashMap;\n".to_string(),
+  };
+  items.push(Json::object(vec![
+    ("label", "HashMap".into()),
+    ("kind", 22.into()),
+    ("detail", "std::collections::HashMap".into()),
+    (
+      "additionalTextEdits",
+      vec![Json::object(vec![
+        ("range", range(text, import.range)),
+        ("newText", import.new_text.into()),
+      ])]
+      .into(),
+    ),
+  ]));
+  Json::object(vec![
+    ("isIncomplete", false.into()),
+    ("items", items.into()),
+  ])
+}
+
+fn word_at(text: &ropey::Rope, char_idx: usize) -> Option<Range<usize>> {
+  let mut start = char_idx;
+  let mut chars = text.chars_at(char_idx);
+  while chars.prev().map_or(false, is_word) {
+    start -= 1;
+  }
+  let end =
+    char_idx + text.chars_at(char_idx).take_while(|c| is_word(*c)).count();
+  Some(start..end).filter(|word| !word.is_empty())
+}
+
+fn hover(text: &ropey::Rope, char_idx: usize) -> Json {
+  let word = match word_at(text, char_idx) {
+    Some(word) => text.slice(word).to_string(),
+    None => return Json::Null,
+  };
+  let count = words(text).get(&word).copied().unwrap_or_default();
+  let value = format!(
+    "```rust\n{}\n```\nWord of the file, used **{}** times.",
+    word, count
+  );
+  Json::object(vec![(
+    "contents",
+    Json::object(vec![("kind", "markdown".into()), ("value", value.into())]),
+  )])
+}
+
+fn signature_help(text: &ropey::Rope, char_idx: usize) -> Json {
+  let mut chars = text.chars_at(char_idx);
+  let mut at = char_idx;
+  let mut depth = 0;
+  let mut commas = 0;
+  loop {
+    match chars.prev() {
+      Some('(') if depth == 0 => break,
+      Some('(') => depth -= 1,
+      Some(')') => depth += 1,
+      Some(',') if depth == 0 => commas += 1,
+      Some(';') | Some('{') | Some('}') | None => return Json::Null,
+      _ => {}
+    }
+    at -= 1;
+  }
+  let name = match word_at(text, at - 1) {
+    Some(word) => text.slice(word).to_string(),
+    None => return Json::Null,
+  };
+  let parameter = |label: &str| Json::object(vec![("label", label.into())]);
+  let signature = Json::object(vec![
+    (
+      "label",
+      format!("fn {}(first: i32, second: i32)", name).into(),
+    ),
+    ("documentation", format!("Calls `{}`.", name).into()),
+    (
+      "parameters",
+      vec![parameter("first: i32"), parameter("second: i32")].into(),
+    ),
+  ]);
+  Json::object(vec![
+    ("signatures", vec![signature].into()),
+    ("activeSignature", 0.into()),
+    ("activeParameter", commas.min(1).into()),
+  ])
+}
+
+fn text_edit(text: &ropey::Rope, chars: Range<usize>, new_text: &str) -> Json {
+  Json::object(vec![
+    ("range", range(text, chars)),
+    ("newText", new_text.into()),
+  ])
+}
+
+fn workspace_edit(changes: Vec<(String, Vec<Json>)>) -> Json {
+  let changes = changes
+    .into_iter()
+    .map(|(uri, edits)| (uri, edits.into()))
+    .collect();
+  Json::object(vec![("changes", Json::Object(changes))])
+}
+
+fn rename(
+  documents: &HashMap<String, ropey::Rope>,
+  text: &ropey::Rope,
+  char_idx: usize,
+  new_name: &str,
+) -> Json {
+  let word = match word_at(text, char_idx) {
+    Some(word) => text.slice(word).to_string(),
+    None => return Json::Null,
+  };
+  let mut uris = documents.keys().collect::<Vec<_>>();
+  uris.sort();
+  let changes = uris
+    .into_iter()
+    .map(|uri| {
+      let text = &documents[uri];
+      let mut edits = vec![];
+      let mut start = 0;
+      for (i, c) in text.chars().chain(std::iter::once(' ')).enumerate() {
+        if is_word(c) {
+          continue;
+        }
+        if text.slice(start..i) == word.as_str() {
+          edits.push(text_edit(text, start..i, new_name));
+        }
+        start = i + 1;
+      }
+      (uri.clone(), edits)
+    })
+    .filter(|(_, edits)| !edits.is_empty())
+    .collect();
+  workspace_edit(changes)
+}
+
+fn format(text: &ropey::Rope, chars: Range<usize>) -> Json {
+  let rows = text.char_to_line(chars.start)..=text.char_to_line(chars.end);
+  let edits = rows
+    .filter_map(|row| {
+      let line = text.line(row).to_string();
+      let content = line.trim_end_matches(|c| c == '\n' || c == '\r');
+      let trimmed = content.trim_end_matches(|c| c == ' ' || c == '\t');
+      let start = text.line_to_char(row) + trimmed.chars().count();
+      let end = text.line_to_char(row) + content.chars().count();
+      Some(text_edit(text, start..end, "")).filter(|_| start < end)
+    })
+    .collect::<Vec<_>>();
+  edits.into()
+}
+
+fn code_actions(text: &ropey::Rope, uri: &str, chars: Range<usize>) -> Json {
+  let mut actions = vec![];
+  if let Some(word) = word_at(text, chars.start) {
+    let upper = text.slice(word.clone()).to_string().to_uppercase();
+    actions.push(Json::object(vec![
+      ("title", format!("Write `{}`", upper).into()),
+      ("kind", "refactor.rewrite".into()),
+      (
+        "edit",
+        workspace_edit(vec![(
+          uri.to_string(),
+          vec![text_edit(text, word, &upper)],
+        )]),
+      ),
+    ]));
+  }
+  let row = text.char_to_line(chars.start);
+  actions.push(Json::object(vec![
+    ("title", "Add a comment above".into()),
+    ("command", COMMENT_COMMAND.into()),
+    ("arguments", vec![uri.into(), row.into()].into()),
+  ]));
+  actions.into()
+}
+
+/// Command adding a comment above a line, which the server makes the
+/// client apply.
+const COMMENT_COMMAND: &str = "mock.comment";
+
+fn document<'a>(
+  documents: &'a mut HashMap<String, ropey::Rope>,
+  params: &Json,
+) -> Option<&'a mut ropey::Rope> {
+  let uri = params.pointer(&["textDocument", "uri"])?.as_str()?;
+  documents.get_mut(uri)
+}
+
+pub fn serve(
+  mut reader: impl BufRead,
+  mut writer: impl Write,
+) -> io::Result<()> {
+  let mut documents = HashMap::new();
+  while let Some(message) = read_message(&mut reader)? {
+    let params = message.get("params").cloned().unwrap_or(Json::Null);
+    let method = message.get("method").and_then(Json::as_str);
+    let result = match method.unwrap_or_default() {
+      "initialize" => Json::object(vec![
+        (
+          "capabilities",
+          Json::object(vec![
+            ("textDocumentSync", 2.into()),
+            (
+              "completionProvider",
+              Json::object(vec![(
+                "triggerCharacters",
+                vec![".".into()].into(),
+              )]),
+            ),
+            ("hoverProvider", true.into()),
+            (
+              "signatureHelpProvider",
+              Json::object(vec![(
+                "triggerCharacters",
+                vec!["(".into(), ",".into()].into(),
+              )]),
+            ),
+            ("renameProvider", true.into()),
+            ("codeActionProvider", true.into()),
+            (
+              "executeCommandProvider",
+              Json::object(vec![(
+                "commands",
+                vec![COMMENT_COMMAND.into()].into(),
+              )]),
+            ),
+            ("documentFormattingProvider", true.into()),
+            ("documentRangeFormattingProvider", true.into()),
+          ]),
+        ),
+        (
+          "serverInfo",
+          Json::object(vec![("name", "mock-language-server".into())]),
+        ),
+      ]),
+      "textDocument/didOpen" => {
+        let document = params.get("textDocument");
+        let uri = document.and_then(|d| d.get("uri")?.as_str());
+        let text = document.and_then(|d| d.get("text")?.as_str());
+        if let Some((uri, text)) = uri.zip(text) {
+          documents.insert(uri.to_string(), ropey::Rope::from_str(text));
+        }
+        Json::Null
+      }
+      "textDocument/didChange" => {
+        let changes = params.get("contentChanges").and_then(Json::as_array);
+        if let Some(text) = document(&mut documents, &params) {
+          for change in changes.unwrap_or_default() {
+            let new_text = change.get("text").and_then(Json::as_str);
+            let new_text = new_text.unwrap_or_default().to_string();
+            let range = change
+              .get("range")
+              .map_or(Some(0..text.len_chars()), |range| {
+                char_range(text, range)
+              });
+            if let Some(range) = range {
+              apply_edits(text, &[TextEdit { range, new_text }]);
+            }
+          }
+        }
+        Json::Null
+      }
+      "textDocument/didClose" => {
+        let uri = params.pointer(&["textDocument", "uri"]);
+        if let Some(uri) = uri.and_then(Json::as_str) {
+          documents.remove(uri);
+        }
+        Json::Null
+      }
+      "textDocument/completion" => {
+        let position = params.get("position");
+        match (document(&mut documents, &params), position) {
+          (Some(text), Some(position)) => match char_index(text, position) {
+            Some(char_idx) => completions(text, char_idx),
+            None => Json::Null,
+          },
+          _ => Json::Null,
+        }
+      }
+      "textDocument/hover" | "textDocument/signatureHelp" => {
+        let position = params.get("position");
+        let answer = match method {
+          Some("textDocument/hover") => hover,
+          _ => signature_help,
+        };
+        match (document(&mut documents, &params), position) {
+          (Some(text), Some(position)) => match char_index(text, position) {
+            Some(char_idx) => answer(text, char_idx),
+            None => Json::Null,
+          },
+          _ => Json::Null,
+        }
+      }
+      "textDocument/rename" => {
+        let position = params.get("position");
+        let new_name = params.get("newName").and_then(Json::as_str);
+        let uri = params.pointer(&["textDocument", "uri"]);
+        let text = uri.and_then(|uri| documents.get(uri.as_str()?));
+        let char_idx = text.zip(position).and_then(|(text, position)| {
+          Some((text, char_index(text, position)?))
+        });
+        match (char_idx, new_name) {
+          (Some((text, char_idx)), Some(new_name)) => {
+            rename(&documents, text, char_idx, new_name)
+          }
+          _ => Json::Null,
+        }
+      }
+      "textDocument/formatting" | "textDocument/rangeFormatting" => {
+        match document(&mut documents, &params) {
+          Some(text) => {
+            let chars = params
+              .get("range")
+              .map_or(Some(0..text.len_chars()), |range| {
+                char_range(text, range)
+              });
+            chars.map_or(Json::Null, |chars| format(text, chars))
+          }
+          None => Json::Null,
+        }
+      }
+      "textDocument/codeAction" => {
+        let uri = params.pointer(&["textDocument", "uri"]);
+        let uri = uri.and_then(Json::as_str).unwrap_or_default().to_string();
+        let chars = params.get("range");
+        match (document(&mut documents, &params), chars) {
+          (Some(text), Some(chars)) => match char_range(text, chars) {
+            Some(chars) => code_actions(text, &uri, chars),
+            None => Json::Null,
+          },
+          _ => Json::Null,
+        }
+      }
+      "workspace/executeCommand" => {
+        let arguments = params.get("arguments").and_then(Json::as_array);
+        let uri = arguments.and_then(|arguments| arguments.get(0)?.as_str());
+        let row = arguments.and_then(|arguments| arguments.get(1)?.as_usize());
+        let text = uri.and_then(|uri| documents.get(uri));
+        if let Some(((uri, text), row)) = uri.zip(text).zip(row) {
+          let start = text.line_to_char(row.min(text.len_lines() - 1));
+          let comment = text_edit(text, start..start, "// comment\n");
+          let edit = workspace_edit(vec![(uri.to_string(), vec![comment])]);
+          let request = Json::object(vec![
+            ("jsonrpc", "2.0".into()),
+            ("id", "mock-apply-edit".into()),
+            ("method", "workspace/applyEdit".into()),
+            ("params", Json::object(vec![("edit", edit)])),
+          ]);
+          write_message(&mut writer, &request)?;
+        }
+        Json::Null
+      }
+      "exit" => return Ok(()),
+      _ => Json::Null,
+    };
+    // notifications have no id and get no answer, nor do the answers of
+    // the client
+    if let (Some(id), Some(_)) = (message.get("id"), method) {
+      let response = Json::object(vec![
+        ("jsonrpc", "2.0".into()),
+        ("id", id.clone()),
+        ("result", result),
+      ]);
+      write_message(&mut writer, &response)?;
+    }
+  }
+  Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::lsp::completion::CompletionItem;
+  use crate::lsp::workspace_edit::text_edits;
+  use crate::lsp::{Client, Event, Waker};
+  use std::io::{BufReader, Read};
+  use std::path::Path;
+  use std::sync::{mpsc, Arc};
+  use std::time::{Duration, Instant};
+
+  /// Writing end of an in-memory pipe.
+  struct PipeWriter(mpsc::Sender<Vec<u8>>);
+
+  impl Write for PipeWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+      self
+        .0
+        .send(buf.to_vec())
+        .map_err(|_| io::Error::from(io::ErrorKind::BrokenPipe))?;
+      Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+      Ok(())
+    }
+  }
+
+  /// Reading end of an in-memory pipe, at its end once the writer is gone.
+  struct PipeReader {
+    receiver: mpsc::Receiver<Vec<u8>>,
+    buffer: io::Cursor<Vec<u8>>,
+  }
+
+  impl Read for PipeReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+      while self.buffer.position() as usize == self.buffer.get_ref().len() {
+        match self.receiver.recv() {
+          Ok(bytes) => self.buffer = io::Cursor::new(bytes),
+          Err(_) => return Ok(0),
+        }
+      }
+      self.buffer.read(buf)
+    }
+  }
+
+  fn pipe() -> (PipeWriter, PipeReader) {
+    let (sender, receiver) = mpsc::channel();
+    let reader = PipeReader {
+      receiver,
+      buffer: io::Cursor::new(vec![]),
+    };
+    (PipeWriter(sender), reader)
+  }
+
+  fn edit(client: &mut Client, path: &Path, range: Range<usize>, text: &str) {
+    let edit = TextEdit {
+      range,
+      new_text: text.to_string(),
+    };
+    client.change(path, &[edit]);
+  }
+
+  fn answer_id(event: &Event) -> Option<usize> {
+    match event {
+      Event::Completion { id, .. }
+      | Event::Hover { id, .. }
+      | Event::SignatureHelp { id, .. }
+      | Event::Edit { id, .. }
+      | Event::CodeActions { id, .. } => Some(*id),
+      Event::Diagnostics { .. } | Event::ApplyEdit { .. } => None,
+    }
+  }
+
+  fn answered_until(client: &mut Client, last: usize) -> Vec<usize> {
+    let deadline = Instant::now() + Duration::from_secs(5);
+    let mut ids = vec![];
+    while !ids.contains(&last) && Instant::now() < deadline {
+      ids.extend(client.poll().iter().filter_map(answer_id));
+      std::thread::sleep(Duration::from_millis(10));
+    }
+    ids
+  }
+
+  fn ask(
+    client: &mut Client,
+    request: impl Fn(&mut Client) -> Option<usize>,
+  ) -> Event {
+    let deadline = Instant::now() + Duration::from_secs(5);
+    let mut asked = None;
+    while Instant::now() < deadline {
+      for event in client.poll() {
+        let id = answer_id(&event);
+        if id.is_some() && id == asked {
+          return event;
+        }
+      }
+      if asked.is_none() {
+        asked = request(client);
+      }
+      std::thread::sleep(Duration::from_millis(10));
+    }
+    panic!("no answer");
+  }
+
+  fn complete(
+    client: &mut Client,
+    path: &Path,
+    char_idx: usize,
+  ) -> Vec<CompletionItem> {
+    let request = |client: &mut Client| client.completion(path, char_idx);
+    match ask(client, request) {
+      Event::Completion { items, .. } => items,
+      _ => unreachable!(),
+    }
+  }
+
+  fn connect() -> (Client, std::thread::JoinHandle<()>) {
+    let (to_server, server_input) = pipe();
+    let (server_output, from_server) = pipe();
+    let server = std::thread::spawn(move || {
+      serve(BufReader::new(server_input), server_output).unwrap();
+    });
+    let waker: Waker = Arc::new(|| {});
+    let client = Client::connect(from_server, to_server, Path::new("/"), waker);
+    (client, server)
+  }
+
+  #[test]
+  fn completes_the_words_of_the_file() {
+    let (mut client, server) = connect();
+    let path = Path::new("/a.rs");
+    client.open(path, "rust", &ropey::Rope::from_str(""));
+
+    let text = "let alpha = 1;\nlet beta = al";
+    edit(&mut client, path, 0..0, text);
+    let items = complete(&mut client, path, text.len());
+    let labels = items
+      .iter()
+      .map(|item| item.label.as_str())
+      .collect::<Vec<_>>();
+    assert_eq!(labels, ["alpha", "beta", "let", "fn", "HashMap"]);
+    assert_eq!(items[0].range, Some(26..28));
+    assert!(items[3].snippet);
+    assert_eq!(items[4].additional_edits[0].range, 0..0);
+
+    // the server follows the edits sent as changed parts
+    edit(&mut client, path, 19..23, "gamma");
+    let items = complete(&mut client, path, text.len() + 1);
+    assert_eq!(items[1].label, "gamma");
+
+    // completions asked for before the text changed are dropped
+    let stale = client.completion(path, text.len() + 1).unwrap();
+    edit(&mut client, path, 0..0, " ");
+    let fresh = client.completion(path, text.len() + 2).unwrap();
+    let ids = answered_until(&mut client, fresh);
+    assert!(ids.contains(&fresh) && !ids.contains(&stale));
+
+    drop(client);
+    server.join().unwrap();
+  }
+
+  #[test]
+  fn documents_words_and_calls() {
+    let (mut client, server) = connect();
+    let path = Path::new("/a.rs");
+    client.open(path, "rust", &ropey::Rope::from_str(""));
+
+    let text = "let alpha = alpha(1, (2), ";
+    edit(&mut client, path, 0..0, text);
+    let hover = |client: &mut Client| client.hover(path, 6);
+    match ask(&mut client, hover) {
+      Event::Hover { contents, .. } => assert_eq!(
+        contents.as_deref(),
+        Some("```rust\nalpha\n```\nWord of the file, used **2** times.")
+      ),
+      _ => unreachable!(),
+    }
+
+    // the answer to a cancelled request is dropped
+    let cancelled = client.hover(path, 6).unwrap();
+    client.cancel(cancelled);
+    let hover = client.hover(path, 6).unwrap();
+    let ids = answered_until(&mut client, hover);
+    assert!(ids.contains(&hover) && !ids.contains(&cancelled));
+
+    let signature =
+      |client: &mut Client| client.signature_help(path, text.len());
+    match ask(&mut client, signature) {
+      Event::SignatureHelp {
+        help: Some(help), ..
+      } => {
+        assert_eq!(help.label, "fn alpha(first: i32, second: i32)");
+        assert_eq!(help.parameter, Some(21..32));
+      }
+      _ => panic!("no signature"),
+    }
+
+    drop(client);
+    server.join().unwrap();
+  }
+
+  #[test]
+  fn renames_formats_and_acts_on_words() {
+    let (mut client, server) = connect();
+    let (a, b) = (Path::new("/a.rs"), Path::new("/b.rs"));
+    client.open(a, "rust", &ropey::Rope::from_str(""));
+    client.open(b, "rust", &ropey::Rope::from_str("use alpha;\n"));
+
+    let text = "let alpha = 1;  \nalpha();\n";
+    edit(&mut client, a, 0..0, text);
+    let rename = |client: &mut Client| client.rename(a, 4, "beta");
+    match ask(&mut client, rename) {
+      Event::Edit { edit, .. } => {
+        let files = edit
+          .files
+          .iter()
+          .map(|file| (file.path.as_path(), file.edits.len()));
+        assert_eq!(files.collect::<Vec<_>>(), [(a, 2), (b, 1)]);
+        // the edits are of the text the rename was asked about
+        assert!(edit.files[0].version.is_some());
+        assert_eq!(edit.files[0].version, client.version(a));
+      }
+      _ => unreachable!(),
+    }
+
+    let format = |client: &mut Client| client.formatting(a, None, "    ");
+    match ask(&mut client, format) {
+      Event::Edit { edit, .. } => {
+        let edits =
+          text_edits(&ropey::Rope::from_str(text), &edit.files[0].edits);
+        assert_eq!(
+          edits,
+          [TextEdit {
+            range: 14..16,
+            new_text: String::new()
+          }]
+        );
+      }
+      _ => unreachable!(),
+    }
+
+    let actions = |client: &mut Client| client.code_actions(a, 4..4);
+    let actions = match ask(&mut client, actions) {
+      Event::CodeActions { actions, .. } => actions,
+      _ => unreachable!(),
+    };
+    let titles = actions.iter().map(|action| action.title.as_str());
+    assert_eq!(
+      titles.collect::<Vec<_>>(),
+      ["Write `ALPHA`", "Add a comment above"]
+    );
+
+    // the command is run by the server, which has the client make its edit
+    client.execute_command(actions[1].command.as_ref().unwrap());
+    let deadline = Instant::now() + Duration::from_secs(5);
+    let mut applied = None;
+    while applied.is_none() && Instant::now() < deadline {
+      applied = client.poll().into_iter().find_map(|event| match event {
+        Event::ApplyEdit { edit, .. } => Some(edit),
+        _ => None,
+      });
+      std::thread::sleep(Duration::from_millis(10));
+    }
+    let edit = applied.expect("no edit");
+    assert_eq!(edit.files[0].path, a);
+
+    drop(client);
+    server.join().unwrap();
+  }
+}
@@ -0,0 +1,470 @@
+use crate::json::Json;
+use std::collections::{HashMap, HashSet};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+
+mod client;
+pub mod completion;
+pub mod config;
+pub mod hover;
+#[cfg(any(test, feature = "mock-language-server"))]
+pub mod mock;
+pub mod workspace_edit;
+
+pub use client::{Client, Event, Waker};
+use config::ServerConfig;
+
+/// How bad a diagnostic is, the worst first.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+  Error,
+  Warning,
+  Information,
+  Hint,
+}
+
+/// Problem a language server found in a file.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Diagnostic {
+  pub range: Range<usize>,
+  pub severity: Severity,
+  pub message: String,
+  pub source: Option<String>,
+}
+
+impl Diagnostic {
+  fn from_json(text: &ropey::Rope, json: &Json) -> Option<Self> {
+    let severity = match json.get("severity").and_then(Json::as_usize) {
+      Some(2) => Severity::Warning,
+      Some(3) => Severity::Information,
+      Some(4) => Severity::Hint,
+      _ => Severity::Error,
+    };
+    Some(Self {
+      range: char_range(text, json.get("range")?)?,
+      severity,
+      message: json.get("message")?.as_str()?.to_string(),
+      source: json
+        .get("source")
+        .and_then(Json::as_str)
+        .map(str::to_string),
+    })
+  }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct TextEdit {
+  pub range: Range<usize>,
+  pub new_text: String,
+}
+
+impl TextEdit {
+  fn from_json(text: &ropey::Rope, json: &Json) -> Option<Self> {
+    Some(Self {
+      range: char_range(text, json.get("range")?)?,
+      new_text: json.get("newText")?.as_str()?.to_string(),
+    })
+  }
+}
+
+pub fn apply_edits(text: &mut ropey::Rope, edits: &[TextEdit]) -> Vec<usize> {
+  let mut order = (0..edits.len()).collect::<Vec<_>>();
+  order.sort_by_key(|&i| edits[i].range.start);
+  // from the last to the first, so that the ranges before still hold
+  for &i in order.iter().rev() {
+    let end = edits[i].range.end.min(text.len_chars());
+    let start = edits[i].range.start.min(end);
+    text.remove(start..end);
+    text.insert(start, &edits[i].new_text);
+  }
+
+  let mut starts = vec![0; edits.len()];
+  let mut shift = 0isize;
+  for &i in &order {
+    let edit = &edits[i];
+    starts[i] = (edit.range.start as isize + shift) as usize;
+    shift += edit.new_text.chars().count() as isize - edit.range.len() as isize;
+  }
+  starts
+}
+
+pub fn index_after_edits(edits: &[TextEdit], char_idx: usize) -> usize {
+  let mut moved = char_idx as isize;
+  for edit in edits {
+    let new_len = edit.new_text.chars().count() as isize;
+    if edit.range.end <= char_idx {
+      moved += new_len - edit.range.len() as isize;
+    } else if edit.range.start < char_idx {
+      moved += edit.range.start as isize + new_len - char_idx as isize;
+    }
+  }
+  moved.max(0) as usize
+}
+
+fn is_unreserved(byte: u8) -> bool {
+  byte.is_ascii_alphanumeric() || b"-._~/".contains(&byte)
+}
+
+pub fn path_to_uri(path: &Path) -> String {
+  let path = path.to_string_lossy().replace('\\', "/");
+  let mut uri = String::from("file://");
+  if !path.starts_with('/') {
+    uri.push('/');
+  }
+  for byte in path.bytes() {
+    if is_unreserved(byte) || byte == b':' {
+      uri.push(byte as char);
+    } else {
+      uri += &format!("%{:02X}", byte);
+    }
+  }
+  uri
+}
+
+pub fn uri_to_path(uri: &str) -> Option<PathBuf> {
+  let mut rest = uri.strip_prefix("file://")?.as_bytes();
+  let mut bytes = vec![];
+  while let Some((&byte, tail)) = rest.split_first() {
+    let escaped = tail
+      .get(..2)
+      .and_then(|hex| std::str::from_utf8(hex).ok())
+      .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+    match escaped {
+      Some(escaped) if byte == b'%' => {
+        bytes.push(escaped);
+        rest = &tail[2..];
+      }
+      _ => {
+        bytes.push(byte);
+        rest = tail;
+      }
+    }
+  }
+  let path = String::from_utf8(bytes).ok()?;
+  // drive letters come after the slash starting the path
+  match path.as_bytes() {
+    [b'/', _, b':', ..] => Some(PathBuf::from(&path[1..])),
+    _ => Some(PathBuf::from(path)),
+  }
+}
+
+pub fn position(text: &ropey::Rope, char_idx: usize) -> Json {
+  let char_idx = char_idx.min(text.len_chars());
+  let line = text.char_to_line(char_idx);
+  let line_start = text.line_to_char(line);
+  let character =
+    text.char_to_utf16_cu(char_idx) - text.char_to_utf16_cu(line_start);
+  Json::object(vec![("line", line.into()), ("character", character.into())])
+}
+
+pub fn char_index(text: &ropey::Rope, position: &Json) -> Option<usize> {
+  let line = position.get("line")?.as_usize()?;
+  let character = position.get("character")?.as_usize()?;
+  if line >= text.len_lines() {
+    return Some(text.len_chars());
+  }
+  let line_start = text.line_to_char(line);
+  let slice = text.line(line);
+  let mut chars = slice.chars_at(slice.len_chars());
+  let line_break = match (chars.prev(), chars.prev()) {
+    (Some('\n'), Some('\r')) => 2,
+    (Some('\n' | '\r' | '\u{b}' | '\u{c}'), _) => 1,
+    (Some('\u{85}' | '\u{2028}' | '\u{2029}'), _) => 1,
+    _ => 0,
+  };
+  let line_end = line_start + slice.len_chars() - line_break;
+  let code_unit = text.char_to_utf16_cu(line_start) + character;
+  let char_idx = text.utf16_cu_to_char(code_unit.min(text.len_utf16_cu()));
+  Some(char_idx.min(line_end))
+}
+
+pub fn range(text: &ropey::Rope, range: Range<usize>) -> Json {
+  Json::object(vec![
+    ("start", position(text, range.start)),
+    ("end", position(text, range.end)),
+  ])
+}
+
+pub fn char_range(text: &ropey::Rope, range: &Json) -> Option<Range<usize>> {
+  let start = char_index(text, range.get("start")?)?;
+  let end = char_index(text, range.get("end")?)?;
+  Some(start..end.max(start))
+}
+
+pub fn text_change(
+  before: &ropey::Rope,
+  after: &ropey::Rope,
+) -> Option<(Range<usize>, String)> {
+  if before == after {
+    return None;
+  }
+  let prefix = before
+    .chars()
+    .zip(after.chars())
+    .take_while(|(a, b)| a == b)
+    .count();
+  let most = before.len_chars().min(after.len_chars()) - prefix;
+  let mut before_chars = before.chars_at(before.len_chars());
+  let mut after_chars = after.chars_at(after.len_chars());
+  let mut suffix = 0;
+  while suffix < most {
+    match (before_chars.prev(), after_chars.prev()) {
+      (Some(a), Some(b)) if a == b => suffix += 1,
+      _ => break,
+    }
+  }
+  Some((
+    prefix..before.len_chars() - suffix,
+    after.slice(prefix..after.len_chars() - suffix).to_string(),
+  ))
+}
+
+/// Language servers of the workspace, started for the languages of the
+/// files opened and kept in sync with their text.
+pub struct LanguageServers {
+  root: PathBuf,
+  configs: HashMap<String, ServerConfig>,
+  clients: HashMap<String, Client>,
+  failed: HashSet<String>,
+  waker: Waker,
+}
+
+impl LanguageServers {
+  pub fn new(root: PathBuf, waker: Waker) -> Self {
+    Self {
+      root,
+      configs: config::load(),
+      clients: HashMap::new(),
+      failed: HashSet::new(),
+      waker,
+    }
+  }
+
+  pub fn open(&mut self, path: &Path, language: &str, text: &ropey::Rope) {
+    self.close(path);
+    let config = match self.configs.get(language) {
+      Some(config) => config,
+      None => return,
+    };
+    let key = config.key();
+    if self.failed.contains(&key) {
+      return;
+    }
+    if !self.clients.contains_key(&key) {
+      match Client::spawn(config, &self.root, self.waker.clone()) {
+        Ok(client) => {
+          self.clients.insert(key.clone(), client);
+        }
+        Err(err) => {
+          log::warn!("failed to start {}: {}", config.command, err);
+          self.failed.insert(key);
+          return;
+        }
+      }
+    }
+    let language_id = config.language_id.clone();
+    if let Some(client) = self.clients.get_mut(&key) {
+      client.open(path, &language_id, text);
+    }
+  }
+
+  fn client_of(&mut self, path: &Path) -> Option<&mut Client> {
+    self.clients.values_mut().find(|client| client.has(path))
+  }
+
+  pub fn completion(&mut self, path: &Path, char_idx: usize) -> Option<usize> {
+    self.client_of(path)?.completion(path, char_idx)
+  }
+
+  pub fn trigger_characters(&mut self, path: &Path) -> Vec<String> {
+    self
+      .client_of(path)
+      .map_or_else(Vec::new, |client| client.trigger_characters())
+  }
+
+  pub fn hover(&mut self, path: &Path, char_idx: usize) -> Option<usize> {
+    self.client_of(path)?.hover(path, char_idx)
+  }
+
+  pub fn cancel(&mut self, path: &Path, id: usize) {
+    if let Some(client) = self.client_of(path) {
+      client.cancel(id);
+    }
+  }
+
+  pub fn signature_help(
+    &mut self,
+    path: &Path,
+    char_idx: usize,
+  ) -> Option<usize> {
+    self.client_of(path)?.signature_help(path, char_idx)
+  }
+
+  pub fn signature_trigger_characters(&mut self, path: &Path) -> Vec<String> {
+    self
+      .client_of(path)
+      .map_or_else(Vec::new, |client| client.signature_trigger_characters())
+  }
+
+  pub fn rename(
+    &mut self,
+    path: &Path,
+    char_idx: usize,
+    new_name: &str,
+  ) -> Option<usize> {
+    self.client_of(path)?.rename(path, char_idx, new_name)
+  }
+
+  pub fn code_actions(
+    &mut self,
+    path: &Path,
+    range: Range<usize>,
+  ) -> Option<usize> {
+    self.client_of(path)?.code_actions(path, range)
+  }
+
+  pub fn execute_command(&mut self, path: &Path, command: &Json) {
+    if let Some(client) = self.client_of(path) {
+      client.execute_command(command);
+    }
+  }
+
+  pub fn formatting(
+    &mut self,
+    path: &Path,
+    range: Option<Range<usize>>,
+    indent_unit: &str,
+  ) -> Option<usize> {
+    self.client_of(path)?.formatting(path, range, indent_unit)
+  }
+
+  pub fn version(&self, path: &Path) -> Option<usize> {
+    self
+      .clients
+      .values()
+      .find_map(|client| client.version(path))
+  }
+
+  pub fn format_on_save(&self, language: &str) -> bool {
+    self
+      .configs
+      .get(language)
+      .map_or(false, |config| config.format_on_save)
+  }
+
+  pub fn change(&mut self, path: &Path, edits: &[TextEdit]) {
+    for client in self.clients.values_mut() {
+      client.change(path, edits);
+    }
+  }
+
+  pub fn close(&mut self, path: &Path) {
+    for client in self.clients.values_mut() {
+      client.close(path);
+    }
+  }
+
+  pub fn poll(&mut self) -> Vec<Event> {
+    let mut events = vec![];
+    for (key, client) in &mut self.clients {
+      events.extend(client.poll().into_iter().map(|event| match event {
+        Event::ApplyEdit { id, edit, .. } => Event::ApplyEdit {
+          server: key.clone(),
+          id,
+          edit,
+        },
+        event => event,
+      }));
+    }
+    events
+  }
+
+  pub fn applied(
+    &mut self,
+    server: &str,
+    id: Json,
+    result: Result<(), String>,
+  ) {
+    if let Some(client) = self.clients.get_mut(server) {
+      client.applied(id, result);
+    }
+  }
+}
+
+impl Drop for LanguageServers {
+  fn drop(&mut self) {
+    let threads = self
+      .clients
+      .values_mut()
+      .filter_map(Client::shut_down)
+      .collect::<Vec<_>>();
+    for thread in threads {
+      let _ = thread.join();
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn converts_positions_and_uris() {
+    let text = ropey::Rope::from_str("a😀b\ncd");
+    assert_eq!(
+      position(&text, 2).to_string(),
+      r#"{"line":0,"character":3}"#
+    );
+    let at = |line: usize, character: usize| {
+      Json::object(vec![("line", line.into()), ("character", character.into())])
+    };
+    assert_eq!(char_index(&text, &at(0, 3)), Some(2));
+    assert_eq!(char_index(&text, &at(0, 9)), Some(3));
+    assert_eq!(char_index(&text, &at(1, 9)), Some(6));
+    assert_eq!(char_index(&text, &at(5, 0)), Some(6));
+    let text = ropey::Rope::from_str("ab\r\ncd");
+    assert_eq!(char_index(&text, &at(0, 3)), Some(2));
+
+    let uri = path_to_uri(Path::new("/tmp/a b/ü.rs"));
+    assert_eq!(uri, "file:///tmp/a%20b/%C3%BC.rs");
+    assert_eq!(uri_to_path(&uri), Some(PathBuf::from("/tmp/a b/ü.rs")));
+    assert_eq!(uri_to_path("file:///C:/a%2"), Some(PathBuf::from("C:/a%2")));
+    assert_eq!(uri_to_path("untitled:a"), None);
+  }
+
+  #[test]
+  fn applies_edits_from_the_last() {
+    let mut text = ropey::Rope::from_str("one two three");
+    let edit = |range: Range<usize>, new_text: &str| TextEdit {
+      range,
+      new_text: new_text.to_string(),
+    };
+    let starts = apply_edits(
+      &mut text,
+      &[edit(8..13, "3"), edit(0..3, "uno"), edit(4..4, "and ")],
+    );
+    assert_eq!(text.to_string(), "uno and two 3");
+    assert_eq!(starts, vec![12, 0, 4]);
+
+    // indices move along, or past the edit they were in
+    let edits = [edit(0..3, ""), edit(4..7, "2")];
+    assert_eq!(index_after_edits(&edits, 3), 0);
+    assert_eq!(index_after_edits(&edits, 5), 2);
+    assert_eq!(index_after_edits(&edits, 9), 4);
+  }
+
+  #[test]
+  fn finds_the_changed_part() {
+    let change = |before: &str, after: &str| {
+      text_change(
+        &ropey::Rope::from_str(before),
+        &ropey::Rope::from_str(after),
+      )
+    };
+    assert_eq!(change("abc", "abc"), None);
+    assert_eq!(change("abc", "abxc"), Some((2..2, "x".to_string())));
+    assert_eq!(change("aaa", "aa"), Some((2..3, String::new())));
+    assert_eq!(change("ab\ncd", "a\nd"), Some((1..4, "\n".to_string())));
+    assert_eq!(change("", "x"), Some((0..0, "x".to_string())));
+  }
+}
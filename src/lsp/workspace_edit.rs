@@ -0,0 +1,237 @@
+use super::{apply_edits, uri_to_path, TextEdit};
+use crate::json::Json;
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+/// Edits of a file, as the server sent them, their char ranges depending on
+/// the text they are read against.
+#[derive(Clone, Debug, PartialEq)]
+pub struct FileEdits {
+  pub path: PathBuf,
+  pub version: Option<usize>,
+  pub edits: Vec<Json>,
+}
+
+/// Edits of files of the workspace, which servers answer renames, code
+/// actions and formatting with.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct WorkspaceEdit {
+  pub files: Vec<FileEdits>,
+  pub resource_operations: bool,
+}
+
+impl WorkspaceEdit {
+  pub fn from_json(json: &Json) -> Self {
+    let mut edit = Self::default();
+    if let Some(changes) = json.get("documentChanges").and_then(Json::as_array)
+    {
+      for change in changes {
+        let uri = change.pointer(&["textDocument", "uri"]);
+        let version = change.pointer(&["textDocument", "version"]);
+        let edits = change.get("edits").and_then(Json::as_array);
+        if let Some((uri, edits)) = uri.and_then(Json::as_str).zip(edits) {
+          edit.push(uri, version.and_then(Json::as_usize), edits);
+        } else if change.get("kind").is_some() {
+          edit.resource_operations = true;
+        }
+      }
+    } else if let Some(Json::Object(changes)) = json.get("changes") {
+      for (uri, edits) in changes {
+        edit.push(uri, None, edits.as_array().unwrap_or_default());
+      }
+    }
+    edit
+  }
+
+  pub fn of_file(path: &Path, version: Option<usize>, edits: &Json) -> Self {
+    let edits = edits.as_array().unwrap_or_default().to_vec();
+    Self {
+      files: vec![FileEdits {
+        path: path.to_path_buf(),
+        version,
+        edits,
+      }],
+      resource_operations: false,
+    }
+  }
+
+  fn push(&mut self, uri: &str, version: Option<usize>, edits: &[Json]) {
+    if let Some(path) = uri_to_path(uri) {
+      self.files.push(FileEdits {
+        path,
+        version,
+        edits: edits.to_vec(),
+      });
+    }
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.files.iter().all(|file| file.edits.is_empty())
+  }
+}
+
+pub fn text_edits(text: &ropey::Rope, edits: &[Json]) -> Vec<TextEdit> {
+  edits
+    .iter()
+    .filter_map(|edit| TextEdit::from_json(text, edit))
+    .collect()
+}
+
+pub fn apply_on_disk(path: &Path, edits: &[Json]) -> io::Result<()> {
+  let mut text = ropey::Rope::from_reader(fs::File::open(path)?)?;
+  let edits = text_edits(&text, edits);
+  apply_edits(&mut text, &edits);
+  write_file(path, &text)
+}
+
+/// Writes the text to a temporary file next to the path and renames it over
+/// the path, so a failed write leaves the file as it was.
+pub fn write_file(path: &Path, text: &ropey::Rope) -> io::Result<()> {
+  let name = path.file_name().unwrap_or_default().to_string_lossy();
+  let temp = path.with_file_name(format!(".{}.{}~", name, std::process::id()));
+  let written = fs::File::create(&temp)
+    .and_then(|file| {
+      let mut writer = io::BufWriter::new(file);
+      text.write_to(&mut writer)?;
+      writer.flush()
+    })
+    .and_then(|_| match fs::metadata(path) {
+      Ok(metadata) => fs::set_permissions(&temp, metadata.permissions()),
+      Err(_) => Ok(()),
+    })
+    .and_then(|_| fs::rename(&temp, path));
+  if written.is_err() {
+    let _ = fs::remove_file(&temp);
+  }
+  written
+}
+
+/// Fix or refactoring a server offers for part of a file.
+#[derive(Clone, Debug, PartialEq)]
+pub struct CodeAction {
+  pub title: String,
+  pub kind: Option<String>,
+  pub edit: Option<WorkspaceEdit>,
+  pub command: Option<Json>,
+}
+
+impl CodeAction {
+  fn from_json(json: &Json) -> Option<Self> {
+    if json.get("disabled").is_some() {
+      return None;
+    }
+    let title = json.get("title")?.as_str()?.to_string();
+    // the command of a bare command is the name of what it runs
+    let command = match json.get("command") {
+      Some(Json::String(_)) => Some(json.clone()),
+      command => command.cloned(),
+    };
+    Some(Self {
+      title,
+      kind: json.get("kind").and_then(Json::as_str).map(str::to_string),
+      edit: json.get("edit").map(WorkspaceEdit::from_json),
+      command,
+    })
+  }
+}
+
+pub fn code_actions(result: &Json) -> Vec<CodeAction> {
+  result
+    .as_array()
+    .unwrap_or_default()
+    .iter()
+    .filter_map(CodeAction::from_json)
+    .collect()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::json;
+
+  #[test]
+  fn reads_workspace_edits() {
+    let edit = |line: usize, new_text: &str| {
+      format!(
+        r#"{{"range":{{"start":{{"line":{0},"character":0}},
+          "end":{{"line":{0},"character":1}}}},"newText":"{1}"}}"#,
+        line, new_text
+      )
+    };
+    let changes = json::parse(&format!(
+      r#"{{"changes":{{"file:///a.rs":[{}],"file:///b%20c.rs":[{},{}]}}}}"#,
+      edit(0, "x"),
+      edit(1, "y"),
+      edit(0, "z")
+    ))
+    .unwrap();
+    let changes = WorkspaceEdit::from_json(&changes);
+    let paths = changes.files.iter().map(|file| file.path.as_path());
+    assert_eq!(
+      paths.collect::<Vec<_>>(),
+      [Path::new("/a.rs"), Path::new("/b c.rs")]
+    );
+
+    let text = ropey::Rope::from_str("ab\ncd\n");
+    let edits = text_edits(&text, &changes.files[1].edits);
+    assert_eq!(edits[0].range, 3..4);
+    assert_eq!(edits[1].new_text, "z");
+
+    let document_changes = json::parse(&format!(
+      r#"{{"documentChanges":[{{"textDocument":{{"uri":"file:///a.rs",
+        "version":3}},"edits":[{}]}},{{"kind":"create","uri":"file:///d.rs"}}],
+        "changes":{{"file:///ignored.rs":[]}}}}"#,
+      edit(0, "x")
+    ))
+    .unwrap();
+    let document_changes = WorkspaceEdit::from_json(&document_changes);
+    assert_eq!(document_changes.files.len(), 1);
+    assert_eq!(document_changes.files[0].version, Some(3));
+    assert!(document_changes.resource_operations);
+    assert!(!document_changes.is_empty());
+    assert!(WorkspaceEdit::from_json(&Json::Null).is_empty());
+  }
+
+  #[test]
+  fn edits_files_on_disk() {
+    let dir = std::env::temp_dir()
+      .join(format!("devcode-edit-test-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("a.rs");
+    fs::write(&path, "ab\ncd\n").unwrap();
+    let edits = json::parse(
+      r#"[{"range":{"start":{"line":1,"character":0},
+        "end":{"line":1,"character":1}},"newText":"x"}]"#,
+    )
+    .unwrap();
+    apply_on_disk(&path, edits.as_array().unwrap()).unwrap();
+    assert_eq!(fs::read_to_string(&path).unwrap(), "ab\nxd\n");
+    // nothing is left next to the file
+    assert_eq!(fs::read_dir(&dir).unwrap().count(), 1);
+
+    assert!(apply_on_disk(&dir.join("b.rs"), &[]).is_err());
+    fs::remove_dir_all(&dir).unwrap();
+  }
+
+  #[test]
+  fn reads_code_actions_and_commands() {
+    let result = json::parse(
+      r#"[{"title":"Fix","kind":"quickfix","edit":{"changes":{}},
+        "command":{"title":"Fix","command":"fix","arguments":[1]}},
+        {"title":"Run","command":"run"},
+        {"title":"Off","disabled":{"reason":"no"}}]"#,
+    )
+    .unwrap();
+    let actions = code_actions(&result);
+    assert_eq!(actions.len(), 2);
+    assert_eq!(actions[0].kind.as_deref(), Some("quickfix"));
+    assert!(actions[0].edit.is_some());
+    let command = actions[0].command.as_ref().unwrap();
+    assert_eq!(command.get("command").and_then(Json::as_str), Some("fix"));
+    assert_eq!(
+      (actions[1].edit.clone(), actions[1].command.is_some()),
+      (None, true)
+    );
+  }
+}
@@ -1,17 +1,33 @@
 #![deny(warnings)]
 
+mod json;
+mod lsp;
 mod renderer;
 
 use crate::renderer::input::TextInput;
 use std::collections::HashMap;
 use std::path::PathBuf;
 use winit::dpi::PhysicalPosition;
-use winit::event::{ElementState, MouseScrollDelta, WindowEvent};
+use winit::event::{
+  ElementState, ModifiersState, MouseScrollDelta, StartCause, VirtualKeyCode,
+  WindowEvent,
+};
+use winit::event_loop::ControlFlow;
+
+/// Most scope headers pinned at the top of the text, unless set with
+/// `DEVCODE_STICKY_SCROLL`.
+const STICKY_SCROLL_DEPTH: usize = 5;
 
 fn main() -> Result<(), anyhow::Error> {
   env_logger::init();
 
   let args: Vec<String> = std::env::args().collect();
+  #[cfg(feature = "mock-language-server")]
+  if args.get(1).map(String::as_str) == Some("--mock-language-server") {
+    let stdin = std::io::stdin();
+    lsp::mock::serve(stdin.lock(), std::io::stdout())?;
+    return Ok(());
+  }
 
   let file = args
     .get(1)
@@ -24,16 +40,30 @@ fn main() -> Result<(), anyhow::Error> {
     anyhow::bail!("path isn't a file");
   }
 
-  let font = get_font(std::env::var("DEVCODE_FONT").ok().as_ref())?;
+  let font = get_font_family(std::env::var("DEVCODE_FONT").ok().as_ref())?;
+  let theme = std::env::var("DEVCODE_THEME").ok();
+  let sticky_scroll_depth = std::env::var("DEVCODE_STICKY_SCROLL")
+    .ok()
+    .and_then(|depth| depth.parse().ok())
+    .unwrap_or(STICKY_SCROLL_DEPTH);
 
   let event_loop = winit::event_loop::EventLoop::new();
   let mut ren = futures::executor::block_on(async {
-    renderer::Renderer::new(&event_loop, font, filepath).await
+    renderer::Renderer::new(
+      &event_loop,
+      font,
+      theme.as_deref(),
+      sticky_scroll_depth,
+      filepath,
+    )
+    .await
   })?;
 
   ren.window.request_redraw();
 
   let mut mouse_pos = PhysicalPosition::new(0.0f64, 0.0f64);
+  let mut modifiers = ModifiersState::empty();
+  let mut mouse_pressed = false;
 
   event_loop.run(move |event, _, control_flow| match event {
     winit::event::Event::WindowEvent { event, .. } => match event {
@@ -42,7 +72,7 @@ fn main() -> Result<(), anyhow::Error> {
         ren.window.request_redraw();
       }
       WindowEvent::CloseRequested => {
-        *control_flow = winit::event_loop::ControlFlow::Exit;
+        *control_flow = ControlFlow::Exit;
       }
       WindowEvent::MouseWheel { delta, .. } => {
         match delta {
@@ -68,24 +98,54 @@ fn main() -> Result<(), anyhow::Error> {
       }
       WindowEvent::KeyboardInput { input, .. } => {
         if input.state == ElementState::Pressed {
-          if let Some(keycode) = input.virtual_keycode {
-            ren.code_views.input_special(ren.size.cast(), keycode);
+          match input.virtual_keycode {
+            Some(VirtualKeyCode::T)
+              if modifiers.ctrl() && modifiers.shift() && !modifiers.alt() =>
+            {
+              ren.cycle_theme();
+            }
+            Some(keycode) => {
+              ren
+                .code_views
+                .input_special(ren.size.cast(), keycode, modifiers);
+            }
+            None => {}
           }
           ren.window.request_redraw();
         }
       }
+      WindowEvent::Focused(true) => renderer::input::clipboard::refresh(),
       WindowEvent::ReceivedCharacter(ch) => {
         ren.code_views.input_char(ren.size.cast(), ch);
       }
-      WindowEvent::CursorMoved { position, .. } => mouse_pos = position,
+      WindowEvent::ModifiersChanged(state) => modifiers = state,
+      WindowEvent::CursorMoved { position, .. } => {
+        mouse_pos = position;
+        if mouse_pressed {
+          ren.drag(mouse_pos, modifiers);
+        } else {
+          ren.hover(mouse_pos);
+        }
+      }
       WindowEvent::MouseInput { state, .. } => {
-        ren.click(mouse_pos, state);
+        mouse_pressed = state == ElementState::Pressed;
+        ren.click(mouse_pos, state, modifiers);
         ren.window.request_redraw();
       }
       _ => {}
     },
+    winit::event::Event::NewEvents(StartCause::Init) => {
+      *control_flow = ControlFlow::Wait;
+    }
+    winit::event::Event::UserEvent(()) => {
+      ren.reload_theme();
+      ren.code_views.poll_language_servers(ren.size.cast());
+      ren.code_views.poll_search(ren.size.cast());
+      ren.code_views.poll_hover();
+      ren.window.request_redraw();
+    }
     winit::event::Event::RedrawRequested(_) => ren.redraw().unwrap(),
-    _ => *control_flow = winit::event_loop::ControlFlow::Wait,
+    _ => {}
   });
 }
 
@@ -159,6 +219,44 @@ fn get_font(
   Ok(wgpu_glyph::ab_glyph::FontArc::try_from_vec(font)?)
 }
 
+fn get_font_family(
+  name: Option<&String>,
+) -> Result<renderer::font::FontFamily, anyhow::Error> {
+  let regular = get_font(name)?;
+  let fonts = get_font_map();
+  let base = name.map_or("JetBrainsMono", |name| {
+    name.strip_suffix("-Regular").unwrap_or(name)
+  });
+  let face = |styles: &[&str]| {
+    let font = styles.iter().find_map(|style| {
+      let path = fonts.get(&format!("{}-{}", base, style))?;
+      let font = std::fs::read(path).ok()?;
+      wgpu_glyph::ab_glyph::FontArc::try_from_vec(font).ok()
+    });
+    if font.is_none() {
+      log::info!("no {} face of {}, drawing it from another", styles[0], base);
+    }
+    font
+  };
+  let bold = face(&["Bold"]);
+  let italic = face(&["Italic", "Oblique"]);
+  let bold_italic = face(&["BoldItalic", "BoldOblique"]);
+
+  let synthesize = renderer::font::synthesize;
+  let bold_italic = match (bold_italic, &bold, &italic) {
+    (Some(font), _, _) => font,
+    (None, Some(bold), _) => synthesize(bold, false, true),
+    (None, None, Some(italic)) => synthesize(italic, true, false),
+    (None, None, None) => synthesize(&regular, true, true),
+  };
+  Ok(renderer::font::FontFamily {
+    bold: bold.unwrap_or_else(|| synthesize(&regular, true, false)),
+    italic: italic.unwrap_or_else(|| synthesize(&regular, false, true)),
+    bold_italic,
+    regular,
+  })
+}
+
 #[cfg(test)]
 mod tests {
   use crate::*;
@@ -176,4 +274,10 @@ mod tests {
   fn get_default_font() {
     assert!(get_font(None).is_ok());
   }
+
+  #[test]
+  fn get_font_families() {
+    assert!(get_font_family(Some(&String::from("Montserrat-Regular"))).is_ok());
+    assert!(get_font_family(None).is_ok());
+  }
 }
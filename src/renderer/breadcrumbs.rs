@@ -0,0 +1,414 @@
+use crate::renderer::code_view::tags::{self, Symbol};
+use crate::renderer::input::{line_length, max_line_length};
+use crate::renderer::rectangle::Rectangle;
+use crate::renderer::theme;
+use crate::renderer::Dimensions;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use wgpu::util::StagingBelt;
+use wgpu::{CommandEncoder, Device, TextureView};
+use wgpu_glyph::ab_glyph::FontArc;
+use wgpu_glyph::{GlyphBrush, Section, Text};
+use winit::dpi::{PhysicalPosition, PhysicalSize};
+use winit::event::ModifiersState;
+
+const BREADCRUMBS_PADDING: f32 = 5.0;
+const SEPARATOR: &str = " › ";
+/// Entries the dropdown shows at once, the others are scrolled to.
+const DROPDOWN_ROWS: usize = 12;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Target {
+  File(PathBuf),
+  Directory(PathBuf),
+  Symbol(usize),
+}
+
+enum CrumbKind {
+  Path(PathBuf),
+  Symbol(usize),
+}
+
+struct Crumb {
+  label: String,
+  kind: CrumbKind,
+  end: f32,
+}
+
+struct Dropdown {
+  rect: Rectangle,
+  entries: Vec<(String, Target)>,
+  scroll_offset_y: f32,
+  crumb: usize,
+}
+
+/// Bar showing the path of the file from the workspace root followed by the
+/// symbols the cursor is in.
+pub struct Breadcrumbs {
+  device: Rc<wgpu::Device>,
+  font: FontArc,
+  font_height: f32,
+  rect: Rectangle,
+  pub dimensions: Dimensions,
+  root: PathBuf,
+  path: PathBuf,
+  path_crumbs: Vec<(String, PathBuf)>,
+  symbols: Vec<Symbol>,
+  crumbs: Vec<Crumb>,
+  dropdown: Option<Dropdown>,
+  picked: Option<Target>,
+}
+
+impl Breadcrumbs {
+  pub fn new(
+    device: &Rc<wgpu::Device>,
+    screen_size: PhysicalSize<f32>,
+    font: FontArc,
+    font_height: f32,
+    dimensions: Dimensions,
+    root: PathBuf,
+  ) -> Self {
+    let rect = Rectangle::new(
+      device,
+      screen_size,
+      dimensions,
+      theme::rgb(theme::current().ui.breadcrumbs_background),
+      None,
+    );
+
+    Self {
+      device: Rc::clone(device),
+      font,
+      font_height,
+      rect,
+      dimensions,
+      root,
+      path: PathBuf::new(),
+      path_crumbs: vec![],
+      symbols: vec![],
+      crumbs: vec![],
+      dropdown: None,
+      picked: None,
+    }
+  }
+
+  pub fn height(font_height: f32) -> f32 {
+    font_height + 2.0 * BREADCRUMBS_PADDING
+  }
+
+  pub fn update(&mut self, path: &Path, symbols: &[Symbol], char_idx: usize) {
+    if path != self.path {
+      self.path = path.to_path_buf();
+      let (mut full, relative) = match path.strip_prefix(&self.root) {
+        Ok(relative) => (self.root.clone(), relative),
+        Err(_) => (PathBuf::new(), path),
+      };
+      self.path_crumbs = relative
+        .components()
+        .map(|component| {
+          full.push(component);
+          let label = component.as_os_str().to_string_lossy().into_owned();
+          (label, full.clone())
+        })
+        .collect();
+    }
+    let mut crumbs = self
+      .path_crumbs
+      .iter()
+      .map(|(label, full)| (label.clone(), CrumbKind::Path(full.clone())))
+      .collect::<Vec<_>>();
+    for i in tags::enclosing_chain(symbols, char_idx) {
+      crumbs.push((symbols[i].label(), CrumbKind::Symbol(i)));
+    }
+
+    let mut text = String::new();
+    self.crumbs = crumbs
+      .into_iter()
+      .map(|(label, kind)| {
+        if !text.is_empty() {
+          text += SEPARATOR;
+        }
+        text += &label;
+        Crumb {
+          end: BREADCRUMBS_PADDING
+            + line_length(&text, self.font.clone(), self.font_height),
+          label,
+          kind,
+        }
+      })
+      .collect();
+    self.symbols = symbols.to_vec();
+    if let Some(dropdown) = &self.dropdown {
+      if dropdown.crumb >= self.crumbs.len() {
+        self.dropdown = None;
+      }
+    }
+  }
+
+  pub fn take_picked(&mut self) -> Option<Target> {
+    self.picked.take()
+  }
+
+  fn open_dropdown(
+    &mut self,
+    screen_size: PhysicalSize<f32>,
+    crumb: usize,
+    entries: Vec<(String, Target)>,
+  ) {
+    let x = match crumb {
+      0 => BREADCRUMBS_PADDING,
+      _ => self.crumbs[crumb - 1].end,
+    };
+    let width = max_line_length(
+      entries.iter().map(|(label, _)| label.clone()),
+      self.font.clone(),
+      self.font_height,
+    ) + 2.0 * BREADCRUMBS_PADDING;
+    let rows = entries.len().min(DROPDOWN_ROWS).max(1);
+    let rect = Rectangle::new(
+      &self.device,
+      screen_size,
+      Dimensions {
+        x: self.dimensions.x + x,
+        y: self.dimensions.y + self.dimensions.height,
+        width,
+        height: rows as f32 * self.font_height,
+      },
+      theme::rgb(theme::current().ui.breadcrumbs_background),
+      None,
+    );
+    self.dropdown = Some(Dropdown {
+      rect,
+      entries,
+      scroll_offset_y: 0.0,
+      crumb,
+    });
+  }
+
+  fn directory_entries(dir: &Path) -> Vec<(String, Target)> {
+    let mut entries = match dir.read_dir() {
+      Ok(entries) => entries
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .collect::<Vec<_>>(),
+      Err(err) => {
+        log::warn!("failed to list {:?}: {}", dir, err);
+        return vec![];
+      }
+    };
+    entries.sort_by(|a, b| b.is_dir().cmp(&a.is_dir()).then(a.cmp(b)));
+    entries
+      .into_iter()
+      .map(|path| {
+        let name = path
+          .file_name()
+          .map(|name| name.to_string_lossy().into_owned())
+          .unwrap_or_default();
+        if path.is_dir() {
+          (format!("{}/", name), Target::Directory(path))
+        } else {
+          (name, Target::File(path))
+        }
+      })
+      .collect()
+  }
+
+  fn siblings(&self, crumb: usize) -> Vec<(String, Target)> {
+    match &self.crumbs[crumb].kind {
+      CrumbKind::Path(path) => path
+        .parent()
+        .map(Self::directory_entries)
+        .unwrap_or_default(),
+      CrumbKind::Symbol(i) => tags::siblings(&self.symbols, *i)
+        .into_iter()
+        .map(|j| {
+          let symbol = &self.symbols[j];
+          (symbol.label(), Target::Symbol(symbol.name_start))
+        })
+        .collect(),
+    }
+  }
+
+  pub fn redraw_overlay(
+    &mut self,
+    glyph_brush: &mut GlyphBrush<()>,
+    device: &Device,
+    staging_belt: &mut StagingBelt,
+    encoder: &mut CommandEncoder,
+    target: &TextureView,
+    size: PhysicalSize<u32>,
+  ) {
+    let dropdown = match &self.dropdown {
+      Some(dropdown) => dropdown,
+      None => return,
+    };
+    let color = theme::current().ui.breadcrumbs_foreground;
+    let dimensions = dropdown.rect.dimensions;
+    for (i, (label, _)) in dropdown.entries.iter().enumerate() {
+      glyph_brush.queue(Section {
+        screen_position: (
+          dimensions.x + BREADCRUMBS_PADDING,
+          dimensions.y + dropdown.scroll_offset_y + i as f32 * self.font_height,
+        ),
+        text: vec![Text::new(label)
+          .with_color(color)
+          .with_scale(self.font_height)],
+        ..Section::default()
+      });
+    }
+
+    glyph_brush
+      .draw_queued_with_transform_and_scissoring(
+        device,
+        staging_belt,
+        encoder,
+        target,
+        wgpu_glyph::orthographic_projection(size.width, size.height),
+        dimensions.into(),
+      )
+      .unwrap();
+  }
+
+  pub fn get_overlay_rects(&self) -> Vec<&Rectangle> {
+    self
+      .dropdown
+      .iter()
+      .map(|dropdown| &dropdown.rect)
+      .collect()
+  }
+}
+
+impl super::RenderElement for Breadcrumbs {
+  fn resize(&mut self, screen_size: PhysicalSize<f32>) {
+    self.dimensions.width = screen_size.width - self.dimensions.x;
+    self.rect.resize(screen_size, self.dimensions);
+    self.dropdown = None;
+  }
+
+  fn theme_changed(&mut self, _screen_size: PhysicalSize<f32>) {
+    let color = theme::rgb(theme::current().ui.breadcrumbs_background);
+    self.rect.set_color(color);
+    if let Some(dropdown) = &mut self.dropdown {
+      dropdown.rect.set_color(color);
+    }
+  }
+
+  fn scroll(
+    &mut self,
+    offset: PhysicalPosition<f64>,
+    _screen_size: PhysicalSize<f32>,
+  ) {
+    if let Some(dropdown) = &mut self.dropdown {
+      let hidden = dropdown.entries.len().saturating_sub(DROPDOWN_ROWS);
+      dropdown.scroll_offset_y = (dropdown.scroll_offset_y + offset.y as f32)
+        .min(0.0)
+        .max(-(hidden as f32 * self.font_height));
+    }
+  }
+
+  fn click(
+    &mut self,
+    position: PhysicalPosition<f64>,
+    screen_size: PhysicalSize<f32>,
+    _modifiers: ModifiersState,
+  ) {
+    let absolute = PhysicalPosition {
+      x: position.x as f32 + self.dimensions.x,
+      y: position.y as f32 + self.dimensions.y,
+    };
+    if let Some(dropdown) = self.dropdown.take() {
+      if let Some(pos) = dropdown.rect.dimensions.contains(absolute) {
+        let row = ((pos.y - dropdown.scroll_offset_y) / self.font_height)
+          .floor() as usize;
+        match dropdown.entries.get(row) {
+          Some((_, Target::Directory(dir))) => {
+            let entries = Self::directory_entries(dir);
+            self.open_dropdown(screen_size, dropdown.crumb, entries);
+          }
+          Some((_, target)) => self.picked = Some(target.clone()),
+          None => self.dropdown = Some(dropdown),
+        }
+        return;
+      }
+      if position.y as f32 > self.dimensions.height {
+        return;
+      }
+      let crumb = self.crumbs.iter().position(|c| position.x as f32 <= c.end);
+      if crumb == Some(dropdown.crumb) {
+        return;
+      }
+    }
+
+    if position.y as f32 <= self.dimensions.height {
+      if let Some(crumb) =
+        self.crumbs.iter().position(|c| position.x as f32 <= c.end)
+      {
+        let entries = self.siblings(crumb);
+        self.open_dropdown(screen_size, crumb, entries);
+      }
+    }
+  }
+
+  fn redraw(
+    &mut self,
+    glyph_brush: &mut GlyphBrush<()>,
+    device: &Device,
+    staging_belt: &mut StagingBelt,
+    encoder: &mut CommandEncoder,
+    target: &TextureView,
+    size: PhysicalSize<u32>,
+  ) {
+    let color = theme::current().ui.breadcrumbs_foreground;
+    let mut text = vec![];
+    for (i, crumb) in self.crumbs.iter().enumerate() {
+      if i > 0 {
+        text.push(
+          Text::new(SEPARATOR)
+            .with_color(color)
+            .with_scale(self.font_height),
+        );
+      }
+      text.push(
+        Text::new(&crumb.label)
+          .with_color(color)
+          .with_scale(self.font_height),
+      );
+    }
+    glyph_brush.queue(Section {
+      screen_position: (
+        self.dimensions.x + BREADCRUMBS_PADDING,
+        self.dimensions.y + BREADCRUMBS_PADDING,
+      ),
+      text,
+      ..Section::default()
+    });
+
+    glyph_brush
+      .draw_queued_with_transform_and_scissoring(
+        device,
+        staging_belt,
+        encoder,
+        target,
+        wgpu_glyph::orthographic_projection(size.width, size.height),
+        self.dimensions.into(),
+      )
+      .unwrap();
+  }
+
+  fn get_rects(&self) -> Vec<&Rectangle> {
+    vec![&self.rect]
+  }
+
+  fn get_elements(&mut self) -> Vec<&mut dyn super::RenderElement> {
+    vec![]
+  }
+
+  fn get_dimensions(&self) -> Dimensions {
+    match &self.dropdown {
+      Some(dropdown) => Dimensions {
+        height: self.dimensions.height + dropdown.rect.dimensions.height,
+        ..self.dimensions
+      },
+      None => self.dimensions,
+    }
+  }
+}
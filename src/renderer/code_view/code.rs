@@ -1,77 +1,1262 @@
-use super::super::input::{max_line_length, Cursor};
+use super::super::input::edit::{Edit, Recording};
+use super::super::input::history::{History, Snapshot};
+use super::super::input::{
+  brackets, clipboard, column_at_x, cursor_x_position, dedup_lines,
+  expand_selection_key, for_each_cursor, insert_text, is_box_selection_key,
+  is_line_command, lines, max_line_length, move_cursor, next_occurrence,
+  position, word_range, Cursor,
+};
 use super::super::rectangle::Rectangle;
+use super::fold;
+use super::language::Language;
+use super::sticky;
+use super::syntax_selection::Expansions;
+use crate::lsp::{self, Diagnostic, TextEdit};
+use crate::renderer::font::font_id;
+use crate::renderer::theme;
 use crate::renderer::Dimensions;
+use crate::renderer::RenderElement;
 use std::cell::RefCell;
+use std::ops::Range;
 use std::rc::Rc;
-use wgpu_glyph::ab_glyph::FontArc;
+use wgpu_glyph::ab_glyph::{Font, FontArc, ScaleFont};
 use wgpu_glyph::{GlyphPositioner, Layout, Section, SectionGeometry, Text};
 use winit::dpi::{PhysicalPosition, PhysicalSize};
-use winit::event::VirtualKeyCode;
+use winit::event::{ModifiersState, VirtualKeyCode};
+
+/// Drawn after the first row of a folded region.
+const FOLD_PLACEHOLDER: &str = " … ";
+/// Width of each segment of the squiggles under diagnostics.
+const SQUIGGLE_STEP: f32 = 2.0;
+/// Heights of the segments of a squiggle above its lowest point, repeated
+/// along it.
+const SQUIGGLE_WAVE: [f32; 4] = [0.0, 1.0, 2.0, 1.0];
+
+/// Column selection, as the row and the x position within the lines of the
+/// corner it was started from and of the opposite corner.
+#[derive(Copy, Clone)]
+struct BoxSelection {
+  anchor: (usize, f32),
+  head: (usize, f32),
+}
 
 pub struct Code {
+  device: Rc<wgpu::Device>,
   font: FontArc,
   font_height: f32,
   text: Rc<RefCell<ropey::Rope>>,
   scroll_offset: PhysicalPosition<f64>,
-  cursor: Cursor,
+  cursors: Vec<Cursor>,
+  box_selection: Option<BoxSelection>,
+  selection_rects: Vec<Rectangle>,
+  decoration_rects: Vec<Rectangle>,
+  matching_bracket_rects: Vec<Rectangle>,
+  query_matches: Vec<Range<usize>>,
+  query_match_rects: Vec<Rectangle>,
+  diagnostics: Rc<RefCell<Vec<Diagnostic>>>,
+  diagnostic_rects: Vec<Rectangle>,
+  history: History,
+  changes: Vec<TextEdit>,
+  snippet: Option<(Vec<Range<usize>>, usize)>,
+  typed_at: Vec<usize>,
   max_line_length: f32,
   pub dimensions: Dimensions,
   highlight_config: Option<super::highlight::Config>,
+  folds: Rc<RefCell<fold::Folds>>,
+  sticky: Rc<RefCell<sticky::Sticky>>,
+  sticky_rect: Rectangle,
+  expansions: Expansions,
 }
 
 impl Code {
-  fn generate_glyph_text<'r>(
+  fn glyph_text_range<'r>(
     &self,
     text: &'r ropey::Rope,
-    start_line: usize,
-    end_line: usize,
+    range: Range<usize>,
+    theme: &theme::Theme,
   ) -> Vec<Text<'r>> {
-    let start_char = text.line_to_char(start_line);
-    let end_char = text.line_to_char(end_line);
-
     if let Some(config) = &self.highlight_config {
       config
         .highlights
         .iter()
-        .skip_while(|(_, end, _)| end <= &start_char)
-        .take_while(|(_, end, _)| end <= &end_char)
-        .flat_map(|(start, end, name)| {
+        .skip_while(|span| span.end <= range.start)
+        .take_while(|span| span.start < range.end)
+        .flat_map(|span| {
+          let (color, style) = span
+            .highlight()
+            .map_or((theme.ui.foreground, Default::default()), |h| {
+              theme.highlight(h)
+            });
           text
-            .slice(start.max(&start_char)..end.min(&end_char))
+            .slice(span.start.max(range.start)..span.end.min(range.end))
             .chunks()
             .map(move |c| {
               Text::new(c)
-                .with_color(
-                  name.map(|n| n.color()).unwrap_or([0.9, 0.9, 0.9, 1.0]),
-                )
+                .with_color(color)
                 .with_scale(self.font_height)
+                .with_font_id(font_id(style.bold, style.italic))
             })
         })
         .collect()
     } else {
       text
-        .lines_at(start_line)
-        .take(start_line - end_line)
-        .flat_map(|line| {
-          line.chunks().map(|text| {
-            Text::new(text)
-              .with_color([0.9, 0.9, 0.9, 1.0])
-              .with_scale(self.font_height)
-          })
+        .slice(range)
+        .chunks()
+        .map(|c| {
+          Text::new(c)
+            .with_color(theme.ui.foreground)
+            .with_scale(self.font_height)
         })
         .collect()
     }
   }
 
+  fn generate_glyph_text<'r>(
+    &self,
+    text: &'r ropey::Rope,
+    rows: &[usize],
+  ) -> Vec<Text<'r>> {
+    let theme = theme::current();
+    let folds = self.folds.borrow();
+    let mut glyphs = vec![];
+    // consecutive rows are drawn together, up to a folded region
+    let mut run_start = rows.first().copied().unwrap_or_default();
+    for (i, &row) in rows.iter().enumerate() {
+      let start = text.line_to_char(run_start);
+      if folds.folded_at(row).is_some() {
+        let end = text.line_to_char(row) + lines::line_len(text, row);
+        glyphs.extend(self.glyph_text_range(text, start..end, &theme));
+        glyphs.push(
+          Text::new(FOLD_PLACEHOLDER)
+            .with_color(theme.ui.matching_bracket)
+            .with_scale(self.font_height),
+        );
+        glyphs.push(Text::new("\n").with_scale(self.font_height));
+      } else if rows.get(i + 1) != Some(&(row + 1)) {
+        let end = text.line_to_char(row + 1);
+        glyphs.extend(self.glyph_text_range(text, start..end, &theme));
+      } else {
+        continue;
+      }
+      if let Some(&next) = rows.get(i + 1) {
+        run_start = next;
+      }
+    }
+    glyphs
+  }
+
+  fn shown_rows(&self) -> Vec<usize> {
+    let upper_bound =
+      ((-self.scroll_offset.y) / self.font_height as f64).floor() as usize;
+    let count = (self.dimensions.height / self.font_height).ceil() as usize + 1;
+    self.folds.borrow().shown_rows(
+      upper_bound,
+      count,
+      self.text.borrow().len_lines(),
+    )
+  }
+
+  fn update_folds(&mut self) {
+    let text = self.text.borrow();
+    let (foldable, scopes) =
+      match self.highlight_config.as_ref().and_then(|config| {
+        Some((config.language.folds.as_ref()?, config.tree.as_ref()?))
+      }) {
+        Some((query, tree)) => (
+          fold::foldable_regions(query, tree, &text),
+          fold::captured_rows(query, tree, &text, &["scope"]),
+        ),
+        None => (vec![], vec![]),
+      };
+    self.folds.borrow_mut().foldable = foldable;
+    self.sticky.borrow_mut().scopes = scopes;
+  }
+
+  fn change_folds(
+    &mut self,
+    screen_size: PhysicalSize<f32>,
+    f: impl FnOnce(&mut fold::Folds, &[usize]),
+  ) {
+    let rows = self.cursors.iter().map(|c| c.row).collect::<Vec<_>>();
+    let mut folds = self.folds.borrow_mut();
+    f(&mut folds, &rows);
+
+    let text = self.text.borrow();
+    for cursor in &mut self.cursors {
+      if folds.is_hidden(cursor.row) {
+        cursor.row = folds.shown_row(cursor.row);
+        cursor.column = cursor.column.min(lines::line_len(&text, cursor.row));
+        cursor.selection = None;
+      }
+    }
+    drop(text);
+    drop(folds);
+    self.box_selection = None;
+    self.scroll(PhysicalPosition { x: 0.0, y: 0.0 }, screen_size);
+    self.place_cursors(screen_size);
+    self.update_rects(screen_size);
+  }
+
+  pub fn toggle_fold(&mut self, screen_size: PhysicalSize<f32>, row: usize) {
+    self.change_folds(screen_size, |folds, _| {
+      if !folds.unfold(row) {
+        let region = folds.foldable.iter().find(|f| f.start == row).copied();
+        if let Some(region) = region {
+          folds.fold(region.start, region.end);
+        }
+      }
+    });
+  }
+
+  pub fn row_at(&self, y: f64) -> usize {
+    let visual_row =
+      ((y - self.scroll_offset.y) / self.font_height as f64).floor() as usize;
+    self.folds.borrow().row_at(visual_row)
+  }
+
+  pub fn tree_id(&self) -> usize {
+    self
+      .highlight_config
+      .as_ref()
+      .map_or(0, |config| config.tree_id)
+  }
+
+  pub fn tree(&self) -> Option<&tree_sitter::Tree> {
+    self
+      .highlight_config
+      .as_ref()
+      .and_then(|config| config.tree.as_ref())
+  }
+
+  pub fn highlights(&self) -> &[super::highlight::Span] {
+    self
+      .highlight_config
+      .as_ref()
+      .map_or(&[], |config| &config.highlights)
+  }
+
+  pub fn cursor_index(&self) -> usize {
+    self.cursors[0].index(&self.text.borrow())
+  }
+
+  pub fn selection_range(&self) -> Option<Range<usize>> {
+    self.cursors[0].selection_range(&self.text.borrow())
+  }
+
+  pub fn cursor_dimensions(&self) -> Dimensions {
+    self.cursors[0].rect.dimensions
+  }
+
+  pub fn char_dimensions(&self, char_idx: usize) -> Dimensions {
+    let text = self.text.borrow();
+    let (row, column) = position(&text, char_idx.min(text.len_chars()));
+    let origin = PhysicalPosition {
+      x: self.dimensions.x + self.scroll_offset.x as f32,
+      y: 0.0,
+    };
+    let x = cursor_x_position(
+      row,
+      column,
+      &text,
+      self.font.clone(),
+      self.font_height,
+      origin,
+    );
+    let visual_row = self.folds.borrow().visual_row(row);
+    Dimensions {
+      x: x.unwrap_or(origin.x),
+      y: self.dimensions.y
+        + self.scroll_offset.y as f32
+        + visual_row as f32 * self.font_height,
+      width: 0.0,
+      height: self.font_height,
+    }
+  }
+
+  pub fn char_at(&self, position: PhysicalPosition<f64>) -> Option<usize> {
+    if position.y < self.sticky_height() as f64 {
+      return None;
+    }
+    let text = self.text.borrow();
+    let row = self.row_at(position.y);
+    if row >= text.len_lines() {
+      return None;
+    }
+    let x = (position.x - self.scroll_offset.x) as f32;
+    let (font, font_height) = (self.font.clone(), self.font_height);
+    let column = column_at_x(&text, row, x, font.clone(), font_height);
+    let origin = PhysicalPosition { x: 0.0, y: 0.0 };
+    let column_x =
+      cursor_x_position(row, column, &text, font, font_height, origin);
+    // the nearest boundary between chars is after the char when it is
+    // right of the position
+    let column = match column_x {
+      Some(column_x) if column_x > x && column > 0 => column - 1,
+      _ => column,
+    };
+    if column >= lines::line_len(&text, row) {
+      return None;
+    }
+    Some(text.line_to_char(row) + column)
+  }
+
+  pub fn go_to(&mut self, screen_size: PhysicalSize<f32>, char_idx: usize) {
+    let (row, column) = position(&self.text.borrow(), char_idx);
+    let mut folds = self.folds.borrow_mut();
+    while folds.is_hidden(row) {
+      let start = folds.shown_row(row);
+      folds.unfold(start);
+    }
+    drop(folds);
+
+    self.box_selection = None;
+    self.cursors.truncate(1);
+    let cursor = &mut self.cursors[0];
+    cursor.row = row;
+    cursor.column = column;
+    cursor.selection = None;
+    self.place_cursors(screen_size);
+    self.update_rects(screen_size);
+  }
+
+  pub fn cursor_scroll_offset(&self) -> f64 {
+    let row = self.folds.borrow().visual_row(self.cursors[0].row);
+    let y = (row as f32 * self.font_height) as f64 + self.scroll_offset.y;
+    let top = self.sticky_height() as f64;
+    if y < top || y + self.font_height as f64 > self.dimensions.height as f64 {
+      (self.dimensions.height / 3.0) as f64 - y
+    } else {
+      0.0
+    }
+  }
+
+  pub fn row_scroll_offset(&self, row: usize) -> f64 {
+    let row = self.folds.borrow().visual_row(row);
+    -((row as f32 * self.font_height) as f64) - self.scroll_offset.y
+  }
+
+  fn sticky_height(&self) -> f32 {
+    self.sticky.borrow().rows.len() as f32 * self.font_height
+  }
+
+  pub fn sticky_row_at(&self, y: f64) -> Option<usize> {
+    let i = (y / self.font_height as f64).floor() as usize;
+    self.sticky.borrow().rows.get(i).copied()
+  }
+
+  fn update_sticky(&mut self, screen_size: PhysicalSize<f32>) {
+    let mut rows = vec![];
+    {
+      let folds = self.folds.borrow();
+      let sticky = self.sticky.borrow();
+      let top =
+        ((-self.scroll_offset.y) / self.font_height as f64).floor() as usize;
+      loop {
+        let row = folds.row_at(top + rows.len());
+        let headers = sticky::scope_headers(&sticky.scopes, row, sticky.depth);
+        let done = headers.len() <= rows.len();
+        rows = headers;
+        if done {
+          break;
+        }
+      }
+    }
+
+    self.sticky_rect.resize(
+      screen_size,
+      Dimensions {
+        height: rows.len() as f32 * self.font_height,
+        ..self.dimensions
+      },
+    );
+    self.sticky_rect.region = Some(self.dimensions.into());
+    self.sticky.borrow_mut().rows = rows;
+  }
+
+  fn snapshot(&self) -> Snapshot {
+    Snapshot {
+      text: self.text.borrow().clone(),
+      cursors: self.cursor_positions(),
+    }
+  }
+
+  fn cursor_positions(&self) -> Vec<(usize, usize)> {
+    self
+      .cursors
+      .iter()
+      .map(|cursor| (cursor.row, cursor.column))
+      .collect()
+  }
+
+  fn undo(&mut self, screen_size: PhysicalSize<f32>, redo: bool) {
+    let current = self.snapshot();
+    let before = current.text.clone();
+    let snapshot = if redo {
+      self.history.redo(current)
+    } else {
+      self.history.undo(current)
+    };
+
+    if let Some(snapshot) = snapshot {
+      self.snippet = None;
+      let mut text = self.text.borrow_mut();
+      *text = snapshot.text;
+      if let Some((range, new_text)) = lsp::text_change(&before, &text) {
+        let edit = TextEdit { range, new_text };
+        self
+          .folds
+          .borrow_mut()
+          .update(&before, std::slice::from_ref(&edit));
+        self.changes.push(edit);
+      }
+      self.cursors.truncate(snapshot.cursors.len().max(1));
+      while self.cursors.len() < snapshot.cursors.len() {
+        let cursor = self.new_cursor(screen_size);
+        self.cursors.push(cursor);
+      }
+      for (cursor, &(row, column)) in
+        self.cursors.iter_mut().zip(&snapshot.cursors)
+      {
+        cursor.selection = None;
+        cursor.row = row;
+        cursor.column = column;
+      }
+      self.max_line_length = max_line_length(
+        text.lines().map(|l| l.to_string()),
+        self.font.clone(),
+        self.font_height,
+      );
+      if let Some(config) = &mut self.highlight_config {
+        config.generate(&text);
+      }
+      drop(text);
+      self.update_folds();
+      self.place_cursors(screen_size);
+      self.update_rects(screen_size);
+    }
+  }
+
+  pub fn language(&self) -> Option<&Rc<Language>> {
+    self
+      .highlight_config
+      .as_ref()
+      .map(|config| &config.language)
+  }
+
+  pub fn set_highlight_config(
+    &mut self,
+    screen_size: PhysicalSize<f32>,
+    config: Option<super::highlight::Config>,
+  ) {
+    self.highlight_config = config;
+    if let Some(config) = &mut self.highlight_config {
+      config.generate(&self.text.borrow());
+    }
+    self.update_folds();
+    self.update_rects(screen_size);
+  }
+
+  fn new_cursor(&self, screen_size: PhysicalSize<f32>) -> Cursor {
+    Cursor::new(
+      &self.device,
+      screen_size,
+      Dimensions {
+        width: 4.0,
+        height: self.font_height,
+        ..self.dimensions
+      },
+      theme::rgb(theme::current().ui.cursor),
+      Some(self.dimensions.into()),
+    )
+  }
+
+  fn place_cursors(&mut self, screen_size: PhysicalSize<f32>) {
+    let text = self.text.borrow();
+    let folds = self.folds.borrow();
+    for cursor in &mut self.cursors {
+      let (row, column) = (cursor.row, cursor.column);
+      move_cursor(
+        screen_size,
+        &text,
+        cursor,
+        row,
+        column,
+        self.font.clone(),
+        self.font_height,
+        PhysicalPosition {
+          x: self.dimensions.x,
+          y: self.dimensions.y,
+        },
+        self.scroll_offset.cast(),
+      );
+      let y = self.dimensions.y
+        + self.scroll_offset.y as f32
+        + (folds.visual_row(row) as f32 * self.font_height);
+      cursor.rect.resize(
+        screen_size,
+        Dimensions {
+          y,
+          ..cursor.rect.dimensions
+        },
+      );
+    }
+  }
+
+  fn add_cursor_vertically(
+    &mut self,
+    screen_size: PhysicalSize<f32>,
+    up: bool,
+  ) {
+    let text = self.text.borrow();
+    let folds = self.folds.borrow();
+    let rows = self.cursors.iter().map(|cursor| cursor.row);
+    let (row, column) = if up {
+      let row = rows.min().unwrap_or_default();
+      if row == 0 {
+        return;
+      }
+      (
+        folds.shown_row(row - 1),
+        self.cursors.iter().find(|c| c.row == row).unwrap().column,
+      )
+    } else {
+      let row = rows.max().unwrap_or_default();
+      let below = folds.next_shown_row(row + 1);
+      if below >= text.len_lines() {
+        return;
+      }
+      (
+        below,
+        self.cursors.iter().find(|c| c.row == row).unwrap().column,
+      )
+    };
+    let column = column.min(lines::line_len(&text, row));
+    drop(text);
+    drop(folds);
+
+    let mut cursor = self.new_cursor(screen_size);
+    cursor.row = row;
+    cursor.column = column;
+    self.cursors.push(cursor);
+  }
+
+  fn expand_selection(&mut self, screen_size: PhysicalSize<f32>, grow: bool) {
+    let text = self.text.borrow();
+    let ranges = self
+      .cursors
+      .iter()
+      .map(|cursor| {
+        cursor.selection_range(&text).unwrap_or_else(|| {
+          let index = cursor.index(&text);
+          index..index
+        })
+      })
+      .collect::<Vec<_>>();
+    let ranges = match (grow, &self.highlight_config) {
+      (
+        true,
+        Some(super::highlight::Config {
+          tree: Some(tree), ..
+        }),
+      ) => self.expansions.expand(tree, &text, ranges),
+      (true, _) => None,
+      (false, _) => self.expansions.shrink(ranges),
+    };
+
+    if let Some(ranges) = ranges {
+      for (cursor, range) in self.cursors.iter_mut().zip(ranges) {
+        cursor.selection = if range.is_empty() {
+          None
+        } else {
+          Some(position(&text, range.start))
+        };
+        let (row, column) = position(&text, range.end);
+        cursor.row = row;
+        cursor.column = column;
+      }
+    }
+    drop(text);
+    self.place_cursors(screen_size);
+    self.update_rects(screen_size);
+  }
+
+  fn add_next_occurrence(&mut self, screen_size: PhysicalSize<f32>) {
+    let text = self.text.borrow();
+    let last = self.cursors.last_mut().unwrap();
+    let range = match last.selection_range(&text) {
+      Some(range) => range,
+      None => {
+        if let Some(range) = word_range(&text, last.index(&text)) {
+          last.selection = Some(position(&text, range.start));
+          let (row, column) = position(&text, range.end);
+          last.row = row;
+          last.column = column;
+        }
+        return;
+      }
+    };
+
+    let needle = text.slice(range.clone()).to_string();
+    let next = match next_occurrence(&text, &needle, range.end) {
+      Some(next) => next,
+      None => return,
+    };
+    let taken = self
+      .cursors
+      .iter()
+      .any(|cursor| cursor.selection_range(&text) == Some(next.clone()));
+    if taken {
+      return;
+    }
+
+    let mut cursor = self.new_cursor(screen_size);
+    cursor.selection = Some(position(&text, next.start));
+    let (row, column) = position(&text, next.end);
+    cursor.row = row;
+    cursor.column = column;
+    drop(text);
+    self.cursors.push(cursor);
+  }
+
+  fn update_box_selection(&mut self, screen_size: PhysicalSize<f32>) {
+    let selection = match self.box_selection {
+      Some(selection) => selection,
+      None => return,
+    };
+    let (anchor_row, head_row) = (selection.anchor.0, selection.head.0);
+    // the row the box was started from holds the primary cursor
+    let rows = if anchor_row <= head_row {
+      (anchor_row..=head_row).collect::<Vec<_>>()
+    } else {
+      (head_row..=anchor_row).rev().collect()
+    };
+
+    let text = self.text.borrow();
+    let folds = self.folds.borrow();
+    let column = |row: usize, x: f32| {
+      column_at_x(&text, row, x, self.font.clone(), self.font_height)
+    };
+    let cursors = rows
+      .into_iter()
+      .filter(|row| !folds.is_hidden(*row))
+      .map(|row| {
+        let mut cursor = self.new_cursor(screen_size);
+        cursor.row = row;
+        cursor.column = column(row, selection.head.1);
+        cursor.selection = Some((row, column(row, selection.anchor.1)));
+        cursor
+      })
+      .collect();
+    drop(text);
+    drop(folds);
+
+    self.cursors = cursors;
+    self.place_cursors(screen_size);
+    self.update_rects(screen_size);
+  }
+
+  fn extend_box_selection(
+    &mut self,
+    screen_size: PhysicalSize<f32>,
+    key: VirtualKeyCode,
+  ) {
+    let text = self.text.borrow();
+    let mut selection = self.box_selection.unwrap_or_else(|| {
+      let cursor = &self.cursors[0];
+      let x = cursor_x_position(
+        cursor.row,
+        cursor.column,
+        &text,
+        self.font.clone(),
+        self.font_height,
+        PhysicalPosition { x: 0.0, y: 0.0 },
+      )
+      .unwrap_or_default();
+      BoxSelection {
+        anchor: (cursor.row, x),
+        head: (cursor.row, x),
+      }
+    });
+    let space = self
+      .font
+      .as_scaled(self.font_height)
+      .h_advance(self.font.glyph_id(' '));
+
+    let folds = self.folds.borrow();
+    let (row, x) = &mut selection.head;
+    match key {
+      VirtualKeyCode::Up => *row = folds.shown_row(row.saturating_sub(1)),
+      VirtualKeyCode::Down => {
+        let below = folds.next_shown_row(*row + 1);
+        if below < text.len_lines() {
+          *row = below;
+        }
+      }
+      VirtualKeyCode::Left => *x = (*x - space).max(0.0),
+      _ => *x += space,
+    }
+    drop(text);
+    drop(folds);
+
+    self.box_selection = Some(selection);
+    self.update_box_selection(screen_size);
+  }
+
+  fn copy(&mut self, screen_size: PhysicalSize<f32>, cut: bool) {
+    let text = self.text.borrow();
+    let mut ranges = self
+      .cursors
+      .iter()
+      .filter_map(|cursor| cursor.selection_range(&text))
+      .collect::<Vec<_>>();
+    if ranges.is_empty() {
+      return;
+    }
+    ranges.sort_by_key(|range| range.start);
+    clipboard::set(
+      ranges
+        .into_iter()
+        .map(|range| text.slice(range).to_string())
+        .collect(),
+    );
+    drop(text);
+
+    if cut {
+      self.edit_cursors(screen_size, |text, cursor| {
+        insert_text(text, cursor, "")
+      });
+    }
+  }
+
+  fn paste(&mut self, screen_size: PhysicalSize<f32>) {
+    let text = self.text.borrow();
+    let mut indices = self
+      .cursors
+      .iter()
+      .map(|cursor| {
+        cursor
+          .selection_range(&text)
+          .map_or(cursor.index(&text), |range| range.start)
+      })
+      .collect::<Vec<_>>();
+    indices.sort_unstable();
+    drop(text);
+
+    // cursors are edited from the last one in the text backwards, so each
+    // cursor's own start is still where it was when it is reached
+    let pieces = indices
+      .into_iter()
+      .zip(clipboard::pieces_for(self.cursors.len()))
+      .collect::<std::collections::HashMap<_, _>>();
+    self.edit_cursors(screen_size, |text, cursor| {
+      let start = cursor
+        .selection_range(text)
+        .map_or(cursor.index(text), |range| range.start);
+      if let Some(piece) = pieces.get(&start) {
+        insert_text(text, cursor, piece);
+      }
+    });
+  }
+
+  fn edit_cursors(
+    &mut self,
+    screen_size: PhysicalSize<f32>,
+    f: impl FnMut(&mut Recording, &mut Cursor),
+  ) {
+    let before = self.snapshot();
+    let mut text = self.text.borrow_mut();
+    let mut recording = Recording::new(&mut text);
+    for_each_cursor(&mut recording, &mut self.cursors, f);
+    let edits = recording.edits;
+    drop(text);
+    self.record(before, text_edits(edits), false);
+    self.text_changed(screen_size);
+  }
+
+  pub fn apply_edits(
+    &mut self,
+    screen_size: PhysicalSize<f32>,
+    edits: &[TextEdit],
+    selection: Option<(usize, Range<usize>)>,
+  ) {
+    let before = self.snapshot();
+    let cursor = self.cursor_index();
+    let mut text = self.text.borrow_mut();
+    // in the order they are made, from the last to the first
+    let len = text.len_chars();
+    let mut made = edits.to_vec();
+    made.sort_by_key(|edit| edit.range.start);
+    made.reverse();
+    for edit in &mut made {
+      edit.range.end = edit.range.end.min(len);
+      edit.range.start = edit.range.start.min(edit.range.end);
+    }
+    let starts = lsp::apply_edits(&mut text, edits);
+    let (anchor, head) = match selection {
+      Some((i, offsets)) => {
+        let start = starts.get(i).copied().unwrap_or_default();
+        (start + offsets.start, start + offsets.end)
+      }
+      None => {
+        let moved = lsp::index_after_edits(edits, cursor);
+        (moved, moved)
+      }
+    };
+    let anchor = position(&text, anchor.min(text.len_chars()));
+    let head = position(&text, head.min(text.len_chars()));
+    drop(text);
+
+    self.box_selection = None;
+    self.cursors.truncate(1);
+    let cursor = &mut self.cursors[0];
+    cursor.row = head.0;
+    cursor.column = head.1;
+    cursor.selection = Some(anchor).filter(|anchor| *anchor != head);
+    self.record(before, made, false);
+    self.text_changed(screen_size);
+  }
+
+  fn record(
+    &mut self,
+    before: Snapshot,
+    edits: Vec<TextEdit>,
+    mergeable: bool,
+  ) {
+    if !edits.is_empty() {
+      self.folds.borrow_mut().update(&before.text, &edits);
+      let cursors = self.cursor_positions();
+      self.history.record(before, cursors, mergeable);
+      if let Some((stops, _)) = &mut self.snippet {
+        for stop in stops.iter_mut() {
+          *stop = edits.iter().fold(stop.clone(), range_after_edit);
+        }
+      }
+      self.changes.extend(edits);
+    }
+  }
+
+  pub fn start_snippet(&mut self, stops: Vec<Range<usize>>) {
+    self.snippet = Some((stops, 0)).filter(|(stops, _)| stops.len() > 1);
+  }
+
+  pub fn step_snippet(
+    &mut self,
+    screen_size: PhysicalSize<f32>,
+    forward: bool,
+  ) -> bool {
+    let cursor = self.cursor_index();
+    let (stops, current) = match self.snippet.take() {
+      Some((stops, current))
+        if self.cursors.len() == 1
+          && stops[current].start <= cursor
+          && cursor <= stops[current].end =>
+      {
+        (stops, current)
+      }
+      _ => return false,
+    };
+    let next = if forward {
+      current + 1
+    } else {
+      current.saturating_sub(1)
+    };
+    let stop = stops[next].clone();
+    if next + 1 < stops.len() {
+      self.snippet = Some((stops, next));
+    }
+    let text = self.text.borrow();
+    let anchor = position(&text, stop.start.min(text.len_chars()));
+    let head = position(&text, stop.end.min(text.len_chars()));
+    drop(text);
+    self.box_selection = None;
+    let cursor = &mut self.cursors[0];
+    cursor.row = head.0;
+    cursor.column = head.1;
+    cursor.selection = Some(anchor).filter(|anchor| *anchor != head);
+    self.place_cursors(screen_size);
+    self.update_rects(screen_size);
+    true
+  }
+
+  pub fn take_changes(&mut self) -> Vec<TextEdit> {
+    std::mem::take(&mut self.changes)
+  }
+
+  fn text_changed(&mut self, screen_size: PhysicalSize<f32>) {
+    let text = self.text.borrow();
+    self.max_line_length = max_line_length(
+      text.lines().map(|l| l.to_string()),
+      self.font.clone(),
+      self.font_height,
+    );
+    if let Some(config) = &mut self.highlight_config {
+      config.generate(&text);
+    }
+    drop(text);
+    self.update_folds();
+    self.place_cursors(screen_size);
+    self.update_rects(screen_size);
+  }
+
+  fn update_rects(&mut self, screen_size: PhysicalSize<f32>) {
+    self.update_sticky(screen_size);
+    self.update_selection_rects(screen_size);
+    self.update_decoration_rects(screen_size);
+    self.update_matching_bracket_rects(screen_size);
+    self.update_query_match_rects(screen_size);
+    self.update_diagnostic_rects(screen_size);
+  }
+
+  fn shown_chars(&self, text: &ropey::Rope) -> (usize, usize) {
+    let rows = self.shown_rows();
+    match (rows.first(), rows.last()) {
+      (Some(first), Some(last)) => {
+        let end = (last + 1).min(text.len_lines());
+        (text.line_to_char(*first), text.line_to_char(end))
+      }
+      _ => (0, 0),
+    }
+  }
+
+  pub fn set_query_matches(
+    &mut self,
+    screen_size: PhysicalSize<f32>,
+    matches: Vec<Range<usize>>,
+  ) {
+    if matches != self.query_matches {
+      self.query_matches = matches;
+      self.update_query_match_rects(screen_size);
+    }
+  }
+
+  fn update_query_match_rects(&mut self, screen_size: PhysicalSize<f32>) {
+    let text = self.text.borrow();
+    let color = theme::rgb(theme::current().ui.query_match);
+    let (first, last) = self.shown_chars(&text);
+    let mut rects = vec![];
+    for range in self
+      .query_matches
+      .iter()
+      .filter(|range| range.end >= first && range.start <= last)
+    {
+      for (row, x_start, x_end, _) in
+        self.visible_row_extents(&text, range.clone())
+      {
+        rects.push(self.row_rect(
+          screen_size,
+          row,
+          x_start,
+          x_end - x_start,
+          0.0,
+          self.font_height,
+          color,
+        ));
+      }
+    }
+    self.query_match_rects = rects;
+  }
+
+  pub fn update_diagnostics(&mut self, screen_size: PhysicalSize<f32>) {
+    self.update_diagnostic_rects(screen_size);
+  }
+
+  fn update_diagnostic_rects(&mut self, screen_size: PhysicalSize<f32>) {
+    let text = self.text.borrow();
+    let ui = theme::current().ui;
+    let (first, last) = self.shown_chars(&text);
+    let mut diagnostics = self
+      .diagnostics
+      .borrow()
+      .iter()
+      .filter(|d| d.range.end >= first && d.range.start <= last)
+      .cloned()
+      .collect::<Vec<_>>();
+    diagnostics.sort_by(|a, b| b.severity.cmp(&a.severity));
+
+    let mut rects = vec![];
+    for diagnostic in diagnostics {
+      let color = theme::rgb(ui.diagnostic(diagnostic.severity));
+      // the ranges are clamped as the text may have changed since the
+      // server sent them
+      let len = text.len_chars();
+      let start = diagnostic.range.start.min(len);
+      let mut end = diagnostic.range.end.min(len);
+      if start == end && end < len && text.char(end) != '\n' {
+        end += 1;
+      }
+      for (row, x_start, x_end, _) in
+        self.visible_row_extents(&text, start..end)
+      {
+        let width = (x_end - x_start).max(self.font_height / 2.0);
+        let steps = (width / SQUIGGLE_STEP).ceil() as usize;
+        for step in 0..steps {
+          let height = SQUIGGLE_WAVE[step % SQUIGGLE_WAVE.len()];
+          rects.push(self.row_rect(
+            screen_size,
+            row,
+            x_start + step as f32 * SQUIGGLE_STEP,
+            SQUIGGLE_STEP,
+            self.font_height - 1.0 - height,
+            1.0,
+            color,
+          ));
+        }
+      }
+    }
+    self.diagnostic_rects = rects;
+  }
+
+  fn update_matching_bracket_rects(&mut self, screen_size: PhysicalSize<f32>) {
+    let text = self.text.borrow();
+    let tree = self
+      .highlight_config
+      .as_ref()
+      .and_then(|config| config.tree.as_ref());
+    let brackets =
+      brackets::matching_bracket(&text, self.cursors[0].index(&text), tree);
+    let folds = self.folds.borrow();
+
+    let mut rects = vec![];
+    for index in brackets.iter().flat_map(|(a, b)| [*a, *b]) {
+      let (row, column) = position(&text, index);
+      if folds.is_hidden(row) {
+        continue;
+      }
+      let x_position = |column: usize| {
+        cursor_x_position(
+          row,
+          column,
+          &text,
+          self.font.clone(),
+          self.font_height,
+          PhysicalPosition { x: 0.0, y: 0.0 },
+        )
+        .unwrap_or_default()
+      };
+      let x =
+        self.dimensions.x + self.scroll_offset.x as f32 + x_position(column);
+      let y = self.dimensions.y
+        + self.scroll_offset.y as f32
+        + (folds.visual_row(row) as f32 * self.font_height);
+      let width = x_position(column + 1) - x_position(column);
+      let height = self.font_height;
+
+      for dimensions in [
+        Dimensions {
+          x,
+          y,
+          width,
+          height: 1.0,
+        },
+        Dimensions {
+          x,
+          y: y + height - 1.0,
+          width,
+          height: 1.0,
+        },
+        Dimensions {
+          x,
+          y,
+          width: 1.0,
+          height,
+        },
+        Dimensions {
+          x: x + width - 1.0,
+          y,
+          width: 1.0,
+          height,
+        },
+      ] {
+        rects.push(Rectangle::new(
+          &self.device,
+          screen_size,
+          dimensions,
+          theme::rgb(theme::current().ui.matching_bracket),
+          Some(self.dimensions.into()),
+        ));
+      }
+    }
+    self.matching_bracket_rects = rects;
+  }
+
+  fn visible_row_extents(
+    &self,
+    text: &ropey::Rope,
+    range: Range<usize>,
+  ) -> Vec<(usize, f32, f32, bool)> {
+    let shown_rows = self.shown_rows();
+    let folds = self.folds.borrow();
+    let (upper_bound, lower_bound) =
+      match (shown_rows.first(), shown_rows.last()) {
+        (Some(first), Some(last)) => (*first, *last),
+        _ => return vec![],
+      };
+    let x_position = |row: usize, column: usize| {
+      cursor_x_position(
+        row,
+        column,
+        text,
+        self.font.clone(),
+        self.font_height,
+        PhysicalPosition { x: 0.0, y: 0.0 },
+      )
+      .unwrap_or_default()
+    };
+
+    let start_row = text.char_to_line(range.start);
+    let end_row = text.char_to_line(range.end);
+    (start_row.max(upper_bound)..=end_row.min(lower_bound))
+      .filter(|row| !folds.is_hidden(*row))
+      .map(|row| {
+        let line_start = text.line_to_char(row);
+        let start = if row == start_row {
+          range.start - line_start
+        } else {
+          0
+        };
+        let (end, continues) = if row == end_row {
+          (range.end - line_start, false)
+        } else {
+          (lines::line_len(text, row), true)
+        };
+        (row, x_position(row, start), x_position(row, end), continues)
+      })
+      .collect()
+  }
+
+  #[allow(clippy::too_many_arguments)]
+  fn row_rect(
+    &self,
+    screen_size: PhysicalSize<f32>,
+    row: usize,
+    x: f32,
+    width: f32,
+    y: f32,
+    height: f32,
+    color: [f32; 3],
+  ) -> Rectangle {
+    Rectangle::new(
+      &self.device,
+      screen_size,
+      Dimensions {
+        x: self.dimensions.x + self.scroll_offset.x as f32 + x,
+        y: self.dimensions.y
+          + self.scroll_offset.y as f32
+          + (self.folds.borrow().visual_row(row) as f32 * self.font_height)
+          + y,
+        width,
+        height,
+      },
+      color,
+      Some(self.dimensions.into()),
+    )
+  }
+
+  fn update_selection_rects(&mut self, screen_size: PhysicalSize<f32>) {
+    let text = self.text.borrow();
+    let color = theme::rgb(theme::current().ui.selection);
+    let mut rects = vec![];
+    for range in self
+      .cursors
+      .iter()
+      .filter_map(|cursor| cursor.selection_range(&text))
+    {
+      for (row, x_start, x_end, continues) in
+        self.visible_row_extents(&text, range)
+      {
+        // selected line breaks are shown as a bit of space after the line
+        let trailing = if continues {
+          self.font_height / 2.0
+        } else {
+          0.0
+        };
+        rects.push(self.row_rect(
+          screen_size,
+          row,
+          x_start,
+          x_end + trailing - x_start,
+          0.0,
+          self.font_height,
+          color,
+        ));
+      }
+    }
+    self.selection_rects = rects;
+  }
+
+  fn update_decoration_rects(&mut self, screen_size: PhysicalSize<f32>) {
+    let config = match &self.highlight_config {
+      Some(config) => config,
+      None => {
+        self.decoration_rects.clear();
+        return;
+      }
+    };
+    let text = self.text.borrow();
+    let theme = theme::current();
+    let shown_rows = self.shown_rows();
+    let (start_char, end_char) = match (shown_rows.first(), shown_rows.last()) {
+      (Some(first), Some(last)) => {
+        (text.line_to_char(*first), text.line_to_char(last + 1))
+      }
+      _ => (0, 0),
+    };
+
+    let mut rects = vec![];
+    for span in config
+      .highlights
+      .iter()
+      .skip_while(|span| span.end <= start_char)
+      .take_while(|span| span.start < end_char)
+    {
+      let (color, style) = match span.highlight() {
+        Some(highlight) => theme.highlight(highlight),
+        None => continue,
+      };
+      let decorations = [
+        (style.underline, self.font_height - 1.0),
+        (style.strikethrough, self.font_height / 2.0),
+      ];
+      if !decorations.iter().any(|(on, _)| *on) {
+        continue;
+      }
+
+      for (row, x_start, x_end, _) in
+        self.visible_row_extents(&text, span.start..span.end)
+      {
+        for (_, y) in decorations.iter().filter(|(on, _)| *on) {
+          rects.push(self.row_rect(
+            screen_size,
+            row,
+            x_start,
+            x_end - x_start,
+            *y,
+            1.0,
+            theme::rgb(color),
+          ));
+        }
+      }
+    }
+    self.decoration_rects = rects;
+  }
+
+  #[allow(clippy::too_many_arguments)]
   pub fn new(
-    device: &wgpu::Device,
+    device: &Rc<wgpu::Device>,
     screen_size: PhysicalSize<f32>,
     font: FontArc,
     font_height: f32,
     dimensions: Dimensions,
     text: Rc<RefCell<ropey::Rope>>,
-    highlight_config: Option<super::highlight::Config>,
+    mut highlight_config: Option<super::highlight::Config>,
+    folds: Rc<RefCell<fold::Folds>>,
+    sticky: Rc<RefCell<sticky::Sticky>>,
+    diagnostics: Rc<RefCell<Vec<Diagnostic>>>,
   ) -> Self {
+    if let Some(config) = &mut highlight_config {
+      config.generate(&text.borrow());
+    }
+
     let cursor = Cursor::new(
       device,
       screen_size,
@@ -80,7 +1265,7 @@ impl Code {
         height: font_height,
         ..dimensions
       },
-      [0.68, 0.28, 0.26],
+      theme::rgb(theme::current().ui.cursor),
       Some(dimensions.into()),
     );
 
@@ -89,17 +1274,47 @@ impl Code {
       font.clone(),
       font_height,
     );
+    let sticky_rect = Rectangle::new(
+      device,
+      screen_size,
+      Dimensions {
+        height: 0.0,
+        ..dimensions
+      },
+      theme::rgb(theme::current().ui.sticky_scroll_background),
+      Some(dimensions.into()),
+    );
 
-    Self {
+    let mut code = Self {
+      device: Rc::clone(device),
       font,
       font_height,
       text,
       scroll_offset: PhysicalPosition { x: 0.0, y: 0.0 },
-      cursor,
+      cursors: vec![cursor],
+      box_selection: None,
+      selection_rects: vec![],
+      decoration_rects: vec![],
+      matching_bracket_rects: vec![],
+      query_matches: vec![],
+      query_match_rects: vec![],
+      diagnostics,
+      diagnostic_rects: vec![],
+      history: History::default(),
+      changes: vec![],
+      snippet: None,
+      typed_at: vec![],
       max_line_length,
       dimensions,
       highlight_config,
-    }
+      folds,
+      sticky,
+      sticky_rect,
+      expansions: Expansions::default(),
+    };
+    code.update_folds();
+    code.update_rects(screen_size);
+    code
   }
 }
 
@@ -108,61 +1323,245 @@ impl super::super::input::TextInput for Code {
     &mut self,
     screen_size: PhysicalSize<f32>,
     key: VirtualKeyCode,
+    modifiers: ModifiersState,
   ) {
-    super::super::input::input_special(
-      screen_size,
-      key,
-      &mut self.text.borrow_mut(),
-      &mut self.cursor,
-      self.font.clone(),
-      self.font_height,
-      PhysicalPosition {
-        x: self.dimensions.x,
-        y: 0.0,
-      },
-      self.scroll_offset.cast(),
-    );
+    if modifiers.ctrl() && matches!(key, VirtualKeyCode::Z | VirtualKeyCode::Y)
+    {
+      let redo = key == VirtualKeyCode::Y || modifiers.shift();
+      self.undo(screen_size, redo);
+      return;
+    }
+
+    if let Some(grow) = expand_selection_key(key, modifiers) {
+      self.box_selection = None;
+      self.expand_selection(screen_size, grow);
+      return;
+    }
+
+    let alt_shift = modifiers.alt() && modifiers.shift() && !modifiers.ctrl();
+    if is_box_selection_key(key, modifiers) {
+      self.extend_box_selection(screen_size, key);
+      return;
+    }
+    self.box_selection = None;
+
+    match key {
+      VirtualKeyCode::C | VirtualKeyCode::X
+        if modifiers.ctrl() && !modifiers.alt() =>
+      {
+        self.copy(screen_size, key == VirtualKeyCode::X);
+        return;
+      }
+      VirtualKeyCode::V if modifiers.ctrl() => {
+        self.paste(screen_size);
+        return;
+      }
+      VirtualKeyCode::Escape => {
+        self.cursors.truncate(1);
+        self.update_rects(screen_size);
+        return;
+      }
+      VirtualKeyCode::Up | VirtualKeyCode::Down
+        if modifiers.ctrl() && modifiers.alt() =>
+      {
+        self.add_cursor_vertically(screen_size, key == VirtualKeyCode::Up);
+        self.place_cursors(screen_size);
+        self.update_rects(screen_size);
+        return;
+      }
+      VirtualKeyCode::D if modifiers.ctrl() && !modifiers.shift() => {
+        self.add_next_occurrence(screen_size);
+        self.place_cursors(screen_size);
+        self.update_rects(screen_size);
+        return;
+      }
+      VirtualKeyCode::LBracket | VirtualKeyCode::RBracket
+        if modifiers.ctrl() && modifiers.shift() =>
+      {
+        let fold = key == VirtualKeyCode::LBracket;
+        self.change_folds(screen_size, |folds, rows| {
+          for &row in rows {
+            if fold {
+              if let Some(region) = folds.foldable_around(row) {
+                folds.fold(region.start, region.end);
+              }
+            } else {
+              folds.unfold(row);
+            }
+          }
+        });
+        return;
+      }
+      VirtualKeyCode::LBracket if alt_shift => {
+        self.change_folds(screen_size, |folds, _| folds.fold_level(1));
+        return;
+      }
+      VirtualKeyCode::RBracket if alt_shift => {
+        self.change_folds(screen_size, |folds, _| folds.unfold_all());
+        return;
+      }
+      VirtualKeyCode::Key1
+      | VirtualKeyCode::Key2
+      | VirtualKeyCode::Key3
+      | VirtualKeyCode::Key4
+      | VirtualKeyCode::Key5
+      | VirtualKeyCode::Key6
+      | VirtualKeyCode::Key7
+      | VirtualKeyCode::Key8
+      | VirtualKeyCode::Key9
+        if alt_shift =>
+      {
+        let level = key as usize - VirtualKeyCode::Key1 as usize + 1;
+        self.change_folds(screen_size, |folds, _| folds.fold_level(level));
+        return;
+      }
+      _ => {}
+    }
+    if is_line_command(key, modifiers) {
+      dedup_lines(&self.text.borrow(), &mut self.cursors);
+    }
+
+    let before = self.snapshot();
+    let font = self.font.clone();
+    let font_height = self.font_height;
+    let offset = PhysicalPosition {
+      x: self.dimensions.x,
+      y: self.dimensions.y,
+    };
+    let scroll_offset = self.scroll_offset.cast();
+    let syntax = self.highlight_config.as_ref();
+    let folds = self.folds.borrow();
+    let mut text = self.text.borrow_mut();
+    let mut recording = Recording::new(&mut text);
+    for_each_cursor(&mut recording, &mut self.cursors, |text, cursor| {
+      super::super::input::input_special(
+        screen_size,
+        key,
+        modifiers,
+        text,
+        cursor,
+        font.clone(),
+        font_height,
+        offset,
+        scroll_offset,
+        syntax,
+        &folds,
+      );
+    });
+    drop(folds);
+    let edits = recording.edits;
+    drop(text);
+    self.record(before, text_edits(edits), false);
 
     // TODO: remove, shouldnt generate highglights when moving cursor around
     if let Some(config) = &mut self.highlight_config {
       config.generate(&self.text.borrow());
     }
+    self.update_folds();
+    self.place_cursors(screen_size);
+    self.update_rects(screen_size);
   }
 
   fn input_char(&mut self, screen_size: PhysicalSize<f32>, ch: char) {
-    self.max_line_length = super::super::input::input_char(
-      screen_size,
-      ch,
-      &mut self.text.borrow_mut(),
-      &mut self.cursor,
-      self.font.clone(),
-      self.font_height,
-      PhysicalPosition {
-        x: self.dimensions.x,
-        y: 0.0,
-      },
-      self.scroll_offset.cast(),
-    );
+    // control characters sent alongside shortcuts keep the box selection
+    if !ch.is_control() || matches!(ch, '\r' | '\t' | '\u{7f}' | '\u{1b}') {
+      self.box_selection = None;
+    }
+    let before = self.snapshot();
+    let mergeable = (ch.is_alphanumeric() || ch == '_')
+      && self.cursors.iter().all(|cursor| cursor.selection.is_none());
+    let font = self.font.clone();
+    let font_height = self.font_height;
+    let offset = PhysicalPosition {
+      x: self.dimensions.x,
+      y: self.dimensions.y,
+    };
+    let scroll_offset = self.scroll_offset.cast();
+    let syntax = self.highlight_config.as_ref();
+    let mut max_line_length = self.max_line_length;
+    let folds = self.folds.borrow();
+    let mut text = self.text.borrow_mut();
+    let at = self.cursors.iter().map(|cursor| cursor.index(&text));
+    if !at.eq(self.typed_at.iter().copied()) {
+      self
+        .cursors
+        .iter_mut()
+        .for_each(|cursor| cursor.closers.clear());
+    }
+    let mut recording = Recording::new(&mut text);
+    for_each_cursor(&mut recording, &mut self.cursors, |text, cursor| {
+      max_line_length = super::super::input::input_char(
+        screen_size,
+        ch,
+        text,
+        cursor,
+        font.clone(),
+        font_height,
+        offset,
+        scroll_offset,
+        syntax,
+        &folds,
+      );
+    });
+    drop(folds);
+    let edits = recording.edits;
+    self.typed_at = self
+      .cursors
+      .iter()
+      .map(|cursor| cursor.index(&text))
+      .collect();
+    drop(text);
+    self.record(before, text_edits(edits), mergeable);
+    self.max_line_length = max_line_length;
 
     if let Some(config) = &mut self.highlight_config {
       config.generate(&self.text.borrow());
     }
+    self.update_folds();
+    self.place_cursors(screen_size);
+    self.update_rects(screen_size);
   }
 }
 
-impl super::super::RenderElement for Code {
-  fn resize(&mut self, screen_size: PhysicalSize<f32>) {
-    self.dimensions.width = screen_size.width - self.dimensions.x;
+fn range_after_edit(range: Range<usize>, edit: &TextEdit) -> Range<usize> {
+  let new_len = edit.new_text.chars().count();
+  if edit.range.start > range.end {
+    range
+  } else if edit.range.end < range.start {
+    let start = range.start - edit.range.len() + new_len;
+    start..start + range.len()
+  } else {
+    let start = range.start.min(edit.range.start);
+    start..range.end.max(edit.range.end) + new_len - edit.range.len()
+  }
+}
 
-    self.cursor.rect.resize(
-      screen_size.cast(),
-      Dimensions {
-        y: self.font_height - (self.cursor.row as f32 * self.font_height),
-        ..self.cursor.rect.dimensions
-      },
-    );
+fn text_edits(edits: Vec<Edit>) -> Vec<TextEdit> {
+  let edits = edits.into_iter().map(|edit| TextEdit {
+    range: edit.range,
+    new_text: edit.text,
+  });
+  edits.collect()
+}
 
-    self.cursor.rect.region = Some(self.dimensions.into());
+impl super::super::RenderElement for Code {
+  fn theme_changed(&mut self, screen_size: PhysicalSize<f32>) {
+    let ui = theme::current().ui;
+    for cursor in &mut self.cursors {
+      cursor.rect.set_color(theme::rgb(ui.cursor));
+    }
+    self
+      .sticky_rect
+      .set_color(theme::rgb(ui.sticky_scroll_background));
+    self.update_rects(screen_size);
+  }
+
+  fn resize(&mut self, screen_size: PhysicalSize<f32>) {
+    self.place_cursors(screen_size);
+    for cursor in &mut self.cursors {
+      cursor.rect.region = Some(self.dimensions.into());
+    }
+    self.update_rects(screen_size);
   }
 
   fn scroll(
@@ -175,36 +1574,41 @@ impl super::super::RenderElement for Code {
         .max((screen_size.width - self.max_line_length) as f64) // TODO
         .min(0.0);
     } else {
-      self.scroll_offset.y = (self.scroll_offset.y + offset.y).min(0.0).max(
-        -((self.text.borrow().len_lines() - 3) as f32 * self.font_height)
-          as f64,
-      );
+      let folds = self.folds.borrow();
+      let visual_rows =
+        folds.visual_row(self.text.borrow().len_lines() - 1) + 1;
+      self.scroll_offset.y = (self.scroll_offset.y + offset.y)
+        .min(0.0)
+        .max(-(visual_rows.saturating_sub(3) as f32 * self.font_height) as f64);
     }
 
-    self.cursor.rect.resize(
-      screen_size,
-      Dimensions {
-        x: self.dimensions.x
-          + self.scroll_offset.x as f32
-          + self.cursor.x_offset,
-        y: self.dimensions.y
-          + self.scroll_offset.y as f32
-          + (self.cursor.row as f32 * self.font_height),
-        ..self.cursor.rect.dimensions
-      },
-    );
+    let folds = self.folds.borrow();
+    for cursor in &mut self.cursors {
+      cursor.rect.resize(
+        screen_size,
+        Dimensions {
+          x: self.dimensions.x + self.scroll_offset.x as f32 + cursor.x_offset,
+          y: self.dimensions.y
+            + self.scroll_offset.y as f32
+            + (folds.visual_row(cursor.row) as f32 * self.font_height),
+          ..cursor.rect.dimensions
+        },
+      );
+    }
+    drop(folds);
+    self.update_rects(screen_size);
   }
 
   fn click(
     &mut self,
     position: PhysicalPosition<f64>,
-    _screen_size: PhysicalSize<f32>,
+    screen_size: PhysicalSize<f32>,
+    modifiers: ModifiersState,
   ) {
-    let line = ((position.y - self.scroll_offset.y) / self.font_height as f64)
-      .floor() as usize;
     let layout = Layout::default_wrap();
 
     let text = self.text.borrow();
+    let line = self.row_at(position.y).min(text.len_lines() - 1);
     let text_line = text.line(line);
     let string = text_line.to_string();
     let section_glyphs = &layout.calculate_glyphs(
@@ -218,15 +1622,59 @@ impl super::super::RenderElement for Code {
     let mut c = 0;
     for section_glyph in section_glyphs {
       c += 1;
-      self.cursor.x_offset = section_glyph.glyph.position.x;
       if (position.x as f32) < section_glyph.glyph.position.x {
         c -= 1;
         break;
       }
     }
+    drop(text);
 
-    self.cursor.row = line;
-    self.cursor.column = c;
+    // alt+shift+click starts a box selection, alt+click adds a cursor and a
+    // plain click collapses to a single one
+    self.box_selection = None;
+    if modifiers.alt() && modifiers.shift() {
+      let x = (position.x - self.scroll_offset.x) as f32;
+      self.box_selection = Some(BoxSelection {
+        anchor: (line, x),
+        head: (line, x),
+      });
+      self.update_box_selection(screen_size);
+      return;
+    } else if modifiers.alt() {
+      let exists = self
+        .cursors
+        .iter()
+        .any(|cursor| cursor.row == line && cursor.column == c);
+      if exists {
+        return;
+      }
+      let cursor = self.new_cursor(screen_size);
+      self.cursors.push(cursor);
+    } else {
+      self.cursors.truncate(1);
+    }
+    let cursor = self.cursors.last_mut().unwrap();
+    cursor.row = line;
+    cursor.column = c;
+    cursor.selection = None;
+    self.place_cursors(screen_size);
+    self.update_rects(screen_size);
+  }
+
+  fn drag(
+    &mut self,
+    position: PhysicalPosition<f64>,
+    screen_size: PhysicalSize<f32>,
+    _modifiers: ModifiersState,
+  ) {
+    let row = self
+      .row_at(position.y)
+      .min(self.text.borrow().len_lines() - 1);
+    let x = (position.x - self.scroll_offset.x) as f32;
+    if let Some(selection) = &mut self.box_selection {
+      selection.head = (row, x);
+      self.update_box_selection(screen_size);
+    }
   }
 
   fn redraw(
@@ -238,23 +1686,21 @@ impl super::super::RenderElement for Code {
     target: &wgpu::TextureView,
     size: PhysicalSize<u32>,
   ) {
-    let upper_bound =
-      ((-self.scroll_offset.y) / self.font_height as f64).floor() as usize;
-    let lower_bound = (upper_bound
-      + (self.dimensions.height / self.font_height).ceil() as usize)
-      .min(self.text.borrow().len_lines());
-
+    let rows = self.shown_rows();
     let text = self.text.borrow();
+    let x = self.dimensions.x + self.scroll_offset.x as f32;
     glyph_brush.queue(Section {
       screen_position: (
-        self.dimensions.x + self.scroll_offset.x as f32,
+        x,
         -(((-self.scroll_offset.y as f32) % self.font_height)
           - self.dimensions.y),
       ),
-      text: self.generate_glyph_text(&text, upper_bound, lower_bound),
+      text: self.generate_glyph_text(&text, &rows),
       ..Section::default()
     });
 
+    // the text behind the pinned headers is left out
+    let sticky_height = self.sticky_height();
     glyph_brush
       .draw_queued_with_transform_and_scissoring(
         device,
@@ -262,13 +1708,61 @@ impl super::super::RenderElement for Code {
         encoder,
         target,
         wgpu_glyph::orthographic_projection(size.width, size.height),
-        self.dimensions.into(),
+        Dimensions {
+          y: self.dimensions.y + sticky_height,
+          height: self.dimensions.height - sticky_height,
+          ..self.dimensions
+        }
+        .into(),
       )
       .unwrap();
+
+    if sticky_height > 0.0 {
+      let theme = theme::current();
+      let glyphs = self
+        .sticky
+        .borrow()
+        .rows
+        .iter()
+        .flat_map(|row| {
+          let range = text.line_to_char(*row)..text.line_to_char(row + 1);
+          self.glyph_text_range(&text, range, &theme)
+        })
+        .collect();
+      glyph_brush.queue(Section {
+        screen_position: (x, self.dimensions.y),
+        text: glyphs,
+        ..Section::default()
+      });
+
+      glyph_brush
+        .draw_queued_with_transform_and_scissoring(
+          device,
+          staging_belt,
+          encoder,
+          target,
+          wgpu_glyph::orthographic_projection(size.width, size.height),
+          Dimensions {
+            height: sticky_height,
+            ..self.dimensions
+          }
+          .into(),
+        )
+        .unwrap();
+    }
   }
 
   fn get_rects(&self) -> Vec<&Rectangle> {
-    vec![&self.cursor.rect]
+    let mut vec = self.query_match_rects.iter().collect::<Vec<_>>();
+    vec.extend(self.selection_rects.iter());
+    vec.extend(self.decoration_rects.iter());
+    vec.extend(self.diagnostic_rects.iter());
+    vec.extend(self.matching_bracket_rects.iter());
+    vec.extend(self.cursors.iter().map(|cursor| &cursor.rect));
+    if !self.sticky.borrow().rows.is_empty() {
+      vec.push(&self.sticky_rect);
+    }
+    vec
   }
 
   fn get_elements(&mut self) -> Vec<&mut dyn super::super::RenderElement> {
@@ -0,0 +1,343 @@
+use super::highlight::HighlightNames;
+use crate::lsp::completion::CompletionItem;
+use crate::renderer::font::font_id;
+use crate::renderer::input::max_line_length;
+use crate::renderer::rectangle::Rectangle;
+use crate::renderer::symbol_index::fuzzy_match;
+use crate::renderer::theme;
+use crate::renderer::Dimensions;
+use std::rc::Rc;
+use wgpu::util::StagingBelt;
+use wgpu::{CommandEncoder, Device, TextureView};
+use wgpu_glyph::ab_glyph::FontArc;
+use wgpu_glyph::{GlyphBrush, Layout, Section, Text};
+use winit::dpi::PhysicalSize;
+
+const COMPLETION_PADDING: f32 = 5.0;
+/// Items the popup shows at once, the others are scrolled to.
+pub const COMPLETION_ROWS: usize = 10;
+/// Width of the documentation of the picked item, in font heights.
+const DOCS_WIDTH: f32 = 20.0;
+
+fn icon(kind: Option<usize>) -> (&'static str, HighlightNames) {
+  match kind {
+    Some(1) => ("τ", HighlightNames::String),
+    Some(2) | Some(3) => ("ƒ", HighlightNames::Function),
+    Some(4) => ("ƒ", HighlightNames::Constructor),
+    Some(5) | Some(10) => ("◇", HighlightNames::Property),
+    Some(6) => ("▪", HighlightNames::Variable),
+    Some(7) | Some(8) | Some(13) | Some(22) => ("■", HighlightNames::Type),
+    Some(9) => ("§", HighlightNames::Type),
+    Some(11) | Some(12) | Some(16) | Some(20) | Some(21) => {
+      ("◆", HighlightNames::Constant)
+    }
+    Some(14) => ("⌘", HighlightNames::Keyword),
+    Some(15) => ("¶", HighlightNames::Escape),
+    Some(17) | Some(18) | Some(19) => ("□", HighlightNames::String),
+    Some(23) => ("▲", HighlightNames::Attribute),
+    Some(24) => ("⊃", HighlightNames::Operator),
+    Some(25) => ("▽", HighlightNames::Type),
+    _ => ("▫", HighlightNames::Comment),
+  }
+}
+
+/// Popup under the cursor listing the completions of the word being typed,
+/// with the documentation of the picked one beside it.
+pub struct Completion {
+  font: FontArc,
+  font_height: f32,
+  items: Vec<CompletionItem>,
+  shown: Vec<usize>,
+  typed: String,
+  selected: usize,
+  first: usize,
+  pub start: usize,
+  pub text: ropey::Rope,
+  anchor: Dimensions,
+  rect: Rectangle,
+  selection_rect: Rectangle,
+  docs_rect: Rectangle,
+}
+
+impl Completion {
+  #[allow(clippy::too_many_arguments)]
+  pub fn new(
+    device: &Rc<wgpu::Device>,
+    screen_size: PhysicalSize<f32>,
+    font: FontArc,
+    font_height: f32,
+    items: Vec<CompletionItem>,
+    start: usize,
+    text: ropey::Rope,
+    anchor: Dimensions,
+  ) -> Self {
+    let ui = theme::current().ui;
+    let rect = |color| {
+      Rectangle::new(
+        device,
+        screen_size,
+        Dimensions::default(),
+        theme::rgb(color),
+        None,
+      )
+    };
+    Self {
+      font,
+      font_height,
+      items,
+      shown: vec![],
+      typed: String::new(),
+      selected: 0,
+      first: 0,
+      start,
+      text,
+      anchor,
+      rect: rect(ui.completion_background),
+      selection_rect: rect(ui.completion_selection),
+      docs_rect: rect(ui.completion_background),
+    }
+  }
+
+  pub fn filter(
+    &mut self,
+    screen_size: PhysicalSize<f32>,
+    typed: &str,
+    anchor: Dimensions,
+  ) -> bool {
+    let mut matches = self
+      .items
+      .iter()
+      .enumerate()
+      .filter_map(|(i, item)| {
+        let (score, _) = fuzzy_match(typed, &item.filter_text)?;
+        Some((score, i))
+      })
+      .collect::<Vec<_>>();
+    let items = &self.items;
+    matches.sort_by(|(a_score, a), (b_score, b)| {
+      b_score
+        .cmp(a_score)
+        .then_with(|| items[*a].sort_text.cmp(&items[*b].sort_text))
+    });
+    self.shown = matches.into_iter().map(|(_, i)| i).collect();
+    self.typed = typed.to_string();
+    self.anchor = anchor;
+    self.selected = 0;
+    self.first = 0;
+    self.update_rects(screen_size);
+    !self.shown.is_empty()
+  }
+
+  pub fn select(&mut self, screen_size: PhysicalSize<f32>, delta: isize) {
+    let len = self.shown.len() as isize;
+    if len == 0 {
+      return;
+    }
+    let selected = self.selected as isize + delta;
+    self.selected = if selected < 0 && self.selected == 0 {
+      len - 1
+    } else if selected >= len && self.selected as isize == len - 1 {
+      0
+    } else {
+      selected.max(0).min(len - 1)
+    } as usize;
+    if self.selected < self.first {
+      self.first = self.selected;
+    } else if self.selected >= self.first + COMPLETION_ROWS {
+      self.first = self.selected + 1 - COMPLETION_ROWS;
+    }
+    self.update_rects(screen_size);
+  }
+
+  pub fn selected(&self) -> Option<&CompletionItem> {
+    let i = self.shown.get(self.selected)?;
+    Some(&self.items[*i])
+  }
+
+  fn row_text(item: &CompletionItem) -> String {
+    let (icon, _) = icon(item.kind);
+    format!(
+      "{} {}  {}",
+      icon,
+      item.label,
+      item.detail.as_deref().unwrap_or("")
+    )
+  }
+
+  fn update_rects(&mut self, screen_size: PhysicalSize<f32>) {
+    let rows = self.shown.len().min(COMPLETION_ROWS);
+    let width = max_line_length(
+      self.shown.iter().map(|i| Self::row_text(&self.items[*i])),
+      self.font.clone(),
+      self.font_height,
+    ) + 2.0 * COMPLETION_PADDING;
+    let width = width.min(screen_size.width / 2.0);
+    let height = rows as f32 * self.font_height;
+    let below = self.anchor.y + self.anchor.height;
+    let y = if below + height > screen_size.height {
+      (self.anchor.y - height).max(0.0)
+    } else {
+      below
+    };
+    let x = self.anchor.x.min(screen_size.width - width).max(0.0);
+    let list = Dimensions {
+      x,
+      y,
+      width,
+      height,
+    };
+    self.rect.resize(screen_size, list);
+
+    let row = (self.selected - self.first) as f32;
+    self.selection_rect.resize(
+      screen_size,
+      Dimensions {
+        y: y + row * self.font_height,
+        height: self.font_height,
+        ..list
+      },
+    );
+
+    let docs_width = DOCS_WIDTH * self.font_height;
+    let docs_x = if x + width + docs_width > screen_size.width {
+      x - docs_width
+    } else {
+      x + width
+    };
+    let has_docs = self.selected().map_or(false, |item| {
+      item.detail.is_some() || item.documentation.is_some()
+    });
+    self.docs_rect.resize(
+      screen_size,
+      Dimensions {
+        x: docs_x,
+        width: if has_docs { docs_width } else { 0.0 },
+        ..list
+      },
+    );
+  }
+
+  pub fn redraw_overlay(
+    &mut self,
+    glyph_brush: &mut GlyphBrush<()>,
+    device: &Device,
+    staging_belt: &mut StagingBelt,
+    encoder: &mut CommandEncoder,
+    target: &TextureView,
+    size: PhysicalSize<u32>,
+  ) {
+    let theme = theme::current();
+    let (comment, _) = theme.highlight(HighlightNames::Comment);
+    let list = self.rect.dimensions;
+    for (row, i) in self
+      .shown
+      .iter()
+      .skip(self.first)
+      .take(COMPLETION_ROWS)
+      .enumerate()
+    {
+      let item = &self.items[*i];
+      let (icon, highlight) = icon(item.kind);
+      let (color, _) = theme.highlight(highlight);
+      let mut text = vec![
+        Text::new(icon)
+          .with_color(color)
+          .with_scale(self.font_height),
+        Text::new(" ").with_scale(self.font_height),
+      ];
+      // the chars of the label matching what was typed stand out
+      let matched = fuzzy_match(&self.typed, &item.label)
+        .map_or_else(Vec::new, |(_, chars)| chars);
+      let mut runs: Vec<(String, bool)> = vec![];
+      for (j, c) in item.label.chars().enumerate() {
+        let bold = matched.contains(&j);
+        match runs.last_mut() {
+          Some((run, run_bold)) if *run_bold == bold => run.push(c),
+          _ => runs.push((c.to_string(), bold)),
+        }
+      }
+      let (keyword, _) = theme.highlight(HighlightNames::Keyword);
+      for (run, bold) in &runs {
+        text.push(
+          Text::new(run)
+            .with_color(if *bold { keyword } else { theme.ui.foreground })
+            .with_font_id(font_id(*bold, false))
+            .with_scale(self.font_height),
+        );
+      }
+      if let Some(detail) = &item.detail {
+        text.push(Text::new("  ").with_scale(self.font_height));
+        text.push(
+          Text::new(detail)
+            .with_color(comment)
+            .with_scale(self.font_height),
+        );
+      }
+      glyph_brush.queue(Section {
+        screen_position: (
+          list.x + COMPLETION_PADDING,
+          list.y + row as f32 * self.font_height,
+        ),
+        text,
+        ..Section::default()
+      });
+    }
+    glyph_brush
+      .draw_queued_with_transform_and_scissoring(
+        device,
+        staging_belt,
+        encoder,
+        target,
+        wgpu_glyph::orthographic_projection(size.width, size.height),
+        list.into(),
+      )
+      .unwrap();
+
+    let docs = self.docs_rect.dimensions;
+    let item = match self.selected() {
+      Some(item) if docs.width > 0.0 => item,
+      _ => return,
+    };
+    let mut text = vec![];
+    if let Some(detail) = &item.detail {
+      text.push(
+        Text::new(detail)
+          .with_color(comment)
+          .with_scale(self.font_height),
+      );
+      text.push(Text::new("\n\n").with_scale(self.font_height));
+    }
+    if let Some(documentation) = &item.documentation {
+      text.push(
+        Text::new(documentation)
+          .with_color(theme.ui.foreground)
+          .with_scale(self.font_height),
+      );
+    }
+    glyph_brush.queue(Section {
+      screen_position: (docs.x + COMPLETION_PADDING, docs.y),
+      bounds: (docs.width - 2.0 * COMPLETION_PADDING, docs.height),
+      text,
+      layout: Layout::default_wrap(),
+    });
+    glyph_brush
+      .draw_queued_with_transform_and_scissoring(
+        device,
+        staging_belt,
+        encoder,
+        target,
+        wgpu_glyph::orthographic_projection(size.width, size.height),
+        docs.into(),
+      )
+      .unwrap();
+  }
+
+  pub fn get_overlay_rects(&self) -> Vec<&Rectangle> {
+    let mut vec = vec![&self.rect];
+    if self.docs_rect.dimensions.width > 0.0 {
+      vec.push(&self.docs_rect);
+    }
+    vec.push(&self.selection_rect);
+    vec
+  }
+}
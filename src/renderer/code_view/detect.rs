@@ -0,0 +1,124 @@
+pub fn glob_matches(pattern: &str, name: &str) -> bool {
+  fn matches(pattern: &[char], name: &[char]) -> bool {
+    match pattern.split_first() {
+      None => name.is_empty(),
+      Some(('*', rest)) => {
+        (0..=name.len()).any(|skip| matches(rest, &name[skip..]))
+      }
+      Some((c, rest)) => match name.split_first() {
+        Some((n, name)) => (*c == '?' || c == n) && matches(rest, name),
+        None => false,
+      },
+    }
+  }
+
+  let pattern = pattern.chars().collect::<Vec<_>>();
+  let name = name.chars().collect::<Vec<_>>();
+  matches(&pattern, &name)
+}
+
+pub fn shebang_interpreter(first_line: &str) -> Option<String> {
+  let command = first_line.strip_prefix("#!")?;
+  let mut words = command.split_whitespace();
+  let program = words.next()?;
+  let program = program.rsplit('/').next().unwrap_or(program);
+  let program = if program == "env" {
+    words.find(|word| !word.starts_with('-') && !word.contains('='))?
+  } else {
+    program
+  };
+  Some(program.to_string())
+}
+
+pub fn strip_version(interpreter: &str) -> &str {
+  interpreter.trim_end_matches(|c: char| c.is_ascii_digit() || c == '.')
+}
+
+pub fn modeline_language(
+  lines: impl Iterator<Item = impl AsRef<str>>,
+) -> Option<String> {
+  for line in lines {
+    let line = line.as_ref();
+    if let Some(start) = line.find("-*-") {
+      let rest = &line[start + 3..];
+      if let Some(end) = rest.find("-*-") {
+        let inner = rest[..end].trim();
+        let mode = if inner.contains(':') {
+          inner.split(';').find_map(|var| {
+            let (key, value) = var.split_once(':')?;
+            (key.trim().eq_ignore_ascii_case("mode")).then(|| value.trim())
+          })
+        } else {
+          Some(inner)
+        };
+        if let Some(mode) = mode.filter(|mode| !mode.is_empty()) {
+          return Some(mode.to_lowercase());
+        }
+      }
+    }
+
+    for marker in ["vim:", "vi:", "ex:"] {
+      // the marker starts the line or follows whitespace
+      let found = line.match_indices(marker).find(|(start, _)| {
+        line[..*start]
+          .chars()
+          .last()
+          .map_or(true, char::is_whitespace)
+      });
+      if let Some((start, _)) = found {
+        let rest = &line[start + marker.len()..];
+        let language = rest
+          .split(|c: char| c.is_whitespace() || c == ':')
+          .find_map(|option| {
+            let (key, value) = option.split_once('=')?;
+            matches!(key, "ft" | "filetype" | "syntax").then(|| value)
+          });
+        if let Some(language) = language.filter(|l| !l.is_empty()) {
+          return Some(language.to_lowercase());
+        }
+      }
+    }
+  }
+  None
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn matches_globs() {
+    assert!(glob_matches("Dockerfile.*", "Dockerfile.dev"));
+    assert!(glob_matches("*.d.ts", "index.d.ts"));
+    assert!(glob_matches(".bash?c", ".bashrc"));
+    assert!(!glob_matches("*.ts", "main.rs"));
+  }
+
+  #[test]
+  fn reads_shebangs() {
+    let interpreter = shebang_interpreter("#!/usr/bin/env -S python3 -u");
+    assert_eq!(interpreter.as_deref(), Some("python3"));
+    assert_eq!(strip_version("python3.9"), "python");
+    let interpreter = shebang_interpreter("#!/bin/bash -e");
+    assert_eq!(interpreter.as_deref(), Some("bash"));
+    assert_eq!(shebang_interpreter("# comment"), None);
+  }
+
+  #[test]
+  fn reads_modelines() {
+    let language = modeline_language(
+      ["# -*- mode: Python; coding: utf-8 -*-"].iter().copied(),
+    );
+    assert_eq!(language.as_deref(), Some("python"));
+    let language = modeline_language(["/* -*- c++ -*- */"].iter().copied());
+    assert_eq!(language.as_deref(), Some("c++"));
+    let language =
+      modeline_language(["# vim: set ts=2 ft=sh:"].iter().copied());
+    assert_eq!(language.as_deref(), Some("sh"));
+    assert_eq!(modeline_language(["let x = 1;"].iter().copied()), None);
+    let language = modeline_language(["complex: ft=sh"].iter().copied());
+    assert_eq!(language, None);
+    let language = modeline_language(["vi:ft=sh"].iter().copied());
+    assert_eq!(language.as_deref(), Some("sh"));
+  }
+}
@@ -0,0 +1,272 @@
+use crate::lsp::TextEdit;
+use std::ops::Range;
+
+/// Region which can be folded, from the row it starts on, which stays
+/// visible, to its last row.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Foldable {
+  pub start: usize,
+  pub end: usize,
+  pub level: usize,
+}
+
+pub fn captured_rows(
+  query: &tree_sitter::Query,
+  tree: &tree_sitter::Tree,
+  text: &ropey::Rope,
+  names: &[&str],
+) -> Vec<Range<usize>> {
+  let capture_names = query.capture_names();
+  let mut cursor = tree_sitter::QueryCursor::new();
+  let mut ranges = vec![];
+  for m in cursor.matches(query, tree.root_node(), |node| {
+    let chars =
+      text.byte_to_char(node.start_byte())..text.byte_to_char(node.end_byte());
+    text.slice(chars).to_string()
+  }) {
+    for capture in m.captures {
+      if !names.contains(&capture_names[capture.index as usize].as_str()) {
+        continue;
+      }
+      let node = capture.node;
+      let start = node.start_position().row;
+      let end = match node.end_position() {
+        position if position.column == 0 => position.row.saturating_sub(1),
+        position => position.row,
+      };
+      if end > start {
+        ranges.push(start..end);
+      }
+    }
+  }
+  ranges
+}
+
+pub fn foldable_regions(
+  query: &tree_sitter::Query,
+  tree: &tree_sitter::Tree,
+  text: &ropey::Rope,
+) -> Vec<Foldable> {
+  let mut ranges = captured_rows(query, tree, text, &["fold", "scope"]);
+  ranges.sort_by_key(|range| (range.start, std::cmp::Reverse(range.end)));
+  ranges.dedup_by_key(|range| range.start);
+
+  let mut outer: Vec<Range<usize>> = vec![];
+  ranges
+    .into_iter()
+    .map(|range| {
+      while outer.last().map_or(false, |last| last.end < range.end) {
+        outer.pop();
+      }
+      outer.push(range.clone());
+      Foldable {
+        start: range.start,
+        end: range.end,
+        level: outer.len(),
+      }
+    })
+    .collect()
+}
+
+/// Regions of the text which can be folded and those which are, hiding all
+/// their rows but the first.
+#[derive(Clone, Debug, Default)]
+pub struct Folds {
+  pub foldable: Vec<Foldable>,
+  folded: Vec<(usize, usize)>,
+}
+
+impl Folds {
+  pub fn folded_at(&self, row: usize) -> Option<(usize, usize)> {
+    self.folded.iter().find(|(start, _)| *start == row).copied()
+  }
+
+  fn hiding(&self, row: usize) -> Option<(usize, usize)> {
+    self
+      .folded
+      .iter()
+      .find(|(start, end)| *start < row && row <= *end)
+      .copied()
+  }
+
+  pub fn is_hidden(&self, row: usize) -> bool {
+    self.hiding(row).is_some()
+  }
+
+  pub fn shown_row(&self, row: usize) -> usize {
+    self.hiding(row).map_or(row, |(start, _)| start)
+  }
+
+  pub fn next_shown_row(&self, row: usize) -> usize {
+    self.hiding(row).map_or(row, |(_, end)| end + 1)
+  }
+
+  pub fn visual_row(&self, row: usize) -> usize {
+    let row = self.shown_row(row);
+    let hidden = self
+      .folded
+      .iter()
+      .take_while(|(start, _)| *start < row)
+      .map(|(start, end)| end - start)
+      .sum::<usize>();
+    row - hidden
+  }
+
+  pub fn row_at(&self, visual_row: usize) -> usize {
+    let mut row = visual_row;
+    for (start, end) in &self.folded {
+      if *start >= row {
+        break;
+      }
+      row += end - start;
+    }
+    row
+  }
+
+  pub fn shown_rows(
+    &self,
+    visual_row: usize,
+    count: usize,
+    len_lines: usize,
+  ) -> Vec<usize> {
+    let mut rows = vec![];
+    let mut row = self.row_at(visual_row);
+    while rows.len() < count && row < len_lines {
+      rows.push(row);
+      row = self.next_shown_row(row + 1);
+    }
+    rows
+  }
+
+  pub fn fold(&mut self, start: usize, end: usize) {
+    if self.is_hidden(start) || self.folded_at(start).is_some() {
+      return;
+    }
+    self.folded.retain(|(s, e)| !(start <= *s && *e <= end));
+    let i = self.folded.partition_point(|(s, _)| *s < start);
+    self.folded.insert(i, (start, end));
+  }
+
+  pub fn unfold(&mut self, row: usize) -> bool {
+    let len = self.folded.len();
+    self.folded.retain(|(start, _)| *start != row);
+    self.folded.len() != len
+  }
+
+  pub fn foldable_around(&self, row: usize) -> Option<Foldable> {
+    self
+      .foldable
+      .iter()
+      .filter(|f| f.start <= row && row <= f.end)
+      .max_by_key(|f| f.level)
+      .copied()
+  }
+
+  pub fn fold_level(&mut self, level: usize) {
+    self.folded.clear();
+    let regions = self
+      .foldable
+      .iter()
+      .filter(|f| f.level == level)
+      .map(|f| (f.start, f.end))
+      .collect::<Vec<_>>();
+    for (start, end) in regions {
+      self.fold(start, end);
+    }
+  }
+
+  pub fn unfold_all(&mut self) {
+    self.folded.clear();
+  }
+
+  pub fn update(&mut self, text: &ropey::Rope, edits: &[TextEdit]) {
+    if self.folded.is_empty() {
+      return;
+    }
+    let mut text = text.clone();
+    for edit in edits {
+      let len = text.len_chars();
+      let (start, end) = (edit.range.start.min(len), edit.range.end.min(len));
+      let (first, last) = (text.char_to_line(start), text.char_to_line(end));
+      let added = edit.new_text.matches('\n').count();
+      let splits = added > 0 && end != text.line_to_char(last);
+      self.folded.retain(|&(s, e)| {
+        e < first || s > last || (s == last && (first < s || !splits))
+      });
+      for (s, e) in self.folded.iter_mut().filter(|(s, _)| *s >= last) {
+        *s = *s + added - (last - first);
+        *e = *e + added - (last - first);
+      }
+      text.remove(start..end);
+      text.insert(start, &edit.new_text);
+    }
+    let len_lines = text.len_lines();
+    self.folded.retain(|(_, end)| *end < len_lines);
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::renderer::code_view::language::parse_builtin;
+
+  #[test]
+  fn finds_foldable_regions() {
+    let source = "impl A {\n  fn a() {\n    1\n  }\n}\n/* a\n */\n";
+    let (language, tree) = parse_builtin("rust", source);
+    let query = language.folds.as_ref().unwrap();
+    let text = ropey::Rope::from_str(source);
+
+    let regions = foldable_regions(query, &tree, &text)
+      .into_iter()
+      .map(|f| (f.start, f.end, f.level))
+      .collect::<Vec<_>>();
+    assert_eq!(regions, vec![(0, 4, 1), (1, 3, 2), (5, 6, 1)]);
+  }
+
+  #[test]
+  fn maps_rows_around_folds() {
+    let mut folds = Folds::default();
+    folds.fold(2, 4);
+    folds.fold(7, 8);
+    assert!(folds.is_hidden(3) && !folds.is_hidden(2));
+    assert_eq!(folds.visual_row(5), 3);
+    assert_eq!(folds.visual_row(4), 2);
+    assert_eq!(folds.row_at(3), 5);
+    assert_eq!(folds.row_at(5), 7);
+    assert_eq!(folds.row_at(6), 9);
+    assert_eq!(folds.shown_rows(1, 4, 10), vec![1, 2, 5, 6]);
+    assert_eq!(folds.next_shown_row(3), 5);
+    assert_eq!(folds.shown_row(8), 7);
+
+    folds.fold(1, 5);
+    assert_eq!(folds.folded, vec![(1, 5), (7, 8)]);
+  }
+
+  #[test]
+  fn moves_folds_with_the_text() {
+    let text = ropey::Rope::from_str("a\nb {\nc\n}\nd {\ne\n}\n");
+    let edit = |range: Range<usize>, new_text: &str| TextEdit {
+      range,
+      new_text: new_text.to_string(),
+    };
+    let mut folds = Folds::default();
+    folds.fold(1, 3);
+    folds.fold(4, 6);
+
+    // "x\na\nb {\nc\n}\nd {\ne\n}\n", then a change to the first row
+    folds.update(&text, &[edit(0..0, "x\n"), edit(4..4, "b")]);
+    assert_eq!(folds.folded, vec![(2, 4), (5, 7)]);
+
+    let text = ropey::Rope::from_str("x\na\nbb {\nc\n}\nd {\ne\n}\n");
+    folds.update(&text, &[edit(19..19, "y\n")]);
+    assert_eq!(folds.folded, vec![(2, 4)]);
+
+    // a row added before the first row, then one splitting it
+    folds.update(&text, &[edit(4..4, "\n")]);
+    assert_eq!(folds.folded, vec![(3, 5)]);
+    let text = ropey::Rope::from_str("x\na\n\nbb {\nc\n}\nd {\ne\ny\n}\n");
+    folds.update(&text, &[edit(9..9, "\n")]);
+    assert!(folds.folded.is_empty());
+  }
+}
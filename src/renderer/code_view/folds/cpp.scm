@@ -0,0 +1,11 @@
+[
+  (function_definition)
+  (class_specifier)
+  (namespace_definition)
+  (struct_specifier)
+] @scope
+
+[
+  (initializer_list)
+  (comment)
+] @fold
@@ -0,0 +1,11 @@
+[
+  (constructor_declaration)
+  (method_declaration)
+  (class_declaration)
+  (enum_declaration)
+  (interface_declaration)
+] @scope
+
+[
+  (comment)
+] @fold
@@ -0,0 +1,13 @@
+[
+  (function_declaration)
+  (generator_function_declaration)
+  (method_definition)
+  (class_declaration)
+] @scope
+
+[
+  (arrow_function)
+  (function)
+  (object)
+  (comment)
+] @fold
@@ -0,0 +1,13 @@
+[
+  (constructor_declaration)
+  (method_definition)
+  (value_definition)
+  (class_definition)
+  (module_definition)
+] @scope
+
+[
+  (function_expression)
+  (object_type)
+  (comment)
+] @fold
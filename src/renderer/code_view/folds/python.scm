@@ -0,0 +1,9 @@
+[
+  (function_definition)
+  (class_definition)
+] @scope
+
+[
+  (dictionary)
+  (comment)
+] @fold
@@ -0,0 +1,15 @@
+[
+  (function_item)
+  (enum_item)
+  (impl_item)
+  (mod_item)
+  (struct_item)
+  (trait_item)
+] @scope
+
+[
+  (macro_definition)
+  (union_item)
+  (struct_expression)
+  (block_comment)
+] @fold
@@ -0,0 +1,8 @@
+[
+  (enum_declaration)
+  (interface_declaration)
+] @scope
+
+[
+  (object_type)
+] @fold
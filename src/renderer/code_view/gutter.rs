@@ -1,7 +1,12 @@
-use crate::renderer::input::max_line_length;
+use super::fold::Folds;
+use super::sticky::Sticky;
+use crate::lsp::{Diagnostic, Severity};
+use crate::renderer::input::{line_length, max_line_length};
 use crate::renderer::rectangle::Rectangle;
+use crate::renderer::theme;
 use crate::renderer::Dimensions;
 use std::cell::RefCell;
+use std::collections::HashMap;
 use std::rc::Rc;
 use wgpu::util::StagingBelt;
 use wgpu::{CommandEncoder, Device, TextureView};
@@ -11,16 +16,26 @@ use winit::dpi::{PhysicalPosition, PhysicalSize};
 
 const GUTTER_MARGIN: f32 = 10.0;
 const GUTTER_PADDING: f32 = 10.0;
+const FOLDED_MARKER: &str = "▸";
+const UNFOLDED_MARKER: &str = "▾";
+/// Drawn before the number of the rows with diagnostics, in the color of
+/// the worst.
+const DIAGNOSTIC_ICON: &str = "●";
 
 pub struct Gutter {
   text: Rc<RefCell<ropey::Rope>>,
+  folds: Rc<RefCell<Folds>>,
+  sticky: Rc<RefCell<Sticky>>,
+  diagnostics: Rc<RefCell<Vec<Diagnostic>>>,
   rect: Rectangle,
+  marker_width: f32,
   pub dimensions: Dimensions,
   scroll_offset_y: f64,
   font_height: f32,
 }
 
 impl Gutter {
+  #[allow(clippy::too_many_arguments)]
   pub fn new(
     device: &wgpu::Device,
     font: FontArc,
@@ -28,12 +43,22 @@ impl Gutter {
     screen_size: PhysicalSize<f32>,
     dimensions: Dimensions,
     text: Rc<RefCell<ropey::Rope>>,
+    folds: Rc<RefCell<Folds>>,
+    sticky: Rc<RefCell<Sticky>>,
+    diagnostics: Rc<RefCell<Vec<Diagnostic>>>,
   ) -> Self {
     let line_numbers =
       (0..(text.borrow().len_lines() - 1)).map(|i| i.to_string());
-    let line_numbers_width = max_line_length(line_numbers, font, font_height);
+    let line_numbers_width =
+      max_line_length(line_numbers, font.clone(), font_height);
+    let icon_width = line_length(DIAGNOSTIC_ICON, font.clone(), font_height);
+    let marker_width = line_length(FOLDED_MARKER, font, font_height);
 
-    let rect_size = line_numbers_width + GUTTER_PADDING;
+    let rect_size = GUTTER_PADDING
+      + icon_width
+      + line_numbers_width
+      + GUTTER_PADDING
+      + marker_width;
 
     let rect = Rectangle::new(
       device,
@@ -42,12 +67,16 @@ impl Gutter {
         width: rect_size,
         ..dimensions
       },
-      [0.68, 0.28, 0.26],
+      theme::rgb(theme::current().ui.gutter_background),
       None,
     );
 
     Self {
       text,
+      folds,
+      sticky,
+      diagnostics,
+      marker_width,
       dimensions: Dimensions {
         width: rect_size + GUTTER_MARGIN,
         ..dimensions
@@ -70,14 +99,24 @@ impl super::super::RenderElement for Gutter {
     );
   }
 
+  fn theme_changed(&mut self, _screen_size: PhysicalSize<f32>) {
+    let color = theme::current().ui.gutter_background;
+    self.rect.set_color(theme::rgb(color));
+  }
+
   fn scroll(
     &mut self,
     offset: PhysicalPosition<f64>,
     _screen_size: PhysicalSize<f32>,
   ) {
-    self.scroll_offset_y = (self.scroll_offset_y + offset.y).min(0.0).max(
-      -((self.text.borrow().len_lines() - 3) as f32 * self.font_height) as f64,
-    );
+    let visual_rows = self
+      .folds
+      .borrow()
+      .visual_row(self.text.borrow().len_lines() - 1)
+      + 1;
+    self.scroll_offset_y = (self.scroll_offset_y + offset.y)
+      .min(0.0)
+      .max(-(visual_rows.saturating_sub(3) as f32 * self.font_height) as f64);
   }
 
   fn redraw(
@@ -91,37 +130,67 @@ impl super::super::RenderElement for Gutter {
   ) {
     let upper_bound =
       ((-self.scroll_offset_y) / self.font_height as f64).floor() as usize;
-    let lower_bound = (upper_bound
-      + (self.dimensions.height / self.font_height).ceil() as usize)
-      .min(self.text.borrow().len_lines());
+    let count = (self.dimensions.height / self.font_height).ceil() as usize;
+    let folds = self.folds.borrow();
+    let text = self.text.borrow();
+    let rows = folds.shown_rows(upper_bound, count, text.len_lines());
 
-    let mut line_count = upper_bound;
+    let mut worst = HashMap::<usize, Severity>::new();
+    for diagnostic in self.diagnostics.borrow().iter() {
+      let row = text.char_to_line(diagnostic.range.start.min(text.len_chars()));
+      let severity = worst.entry(row).or_insert(diagnostic.severity);
+      *severity = diagnostic.severity.min(*severity);
+    }
+    let ui = theme::current().ui;
+    let mut icons = vec![];
     let mut line_numbers = String::new();
-    for _ in self
-      .text
-      .borrow()
-      .lines_at(upper_bound)
-      .take(lower_bound - upper_bound)
-    {
-      // TODO
-      line_count += 1;
-      line_numbers += &format!("{}\n", line_count);
+    let mut markers = String::new();
+    for row in rows {
+      icons.push(match worst.get(&row) {
+        Some(severity) => Text::new(DIAGNOSTIC_ICON)
+          .with_color(ui.diagnostic(*severity))
+          .with_scale(self.font_height),
+        None => Text::new("").with_scale(self.font_height),
+      });
+      icons.push(Text::new("\n").with_scale(self.font_height));
+      line_numbers += &format!("{}\n", row + 1);
+      if folds.folded_at(row).is_some() {
+        markers += FOLDED_MARKER;
+      } else if folds.foldable.iter().any(|f| f.start == row) {
+        markers += UNFOLDED_MARKER;
+      }
+      markers.push('\n');
     }
 
+    let numbers_end = self.dimensions.x + self.dimensions.width
+      - (GUTTER_PADDING + GUTTER_MARGIN + self.marker_width);
+    let y = -(((-self.scroll_offset_y as f32) % self.font_height)
+      - self.dimensions.y);
+    let color = ui.gutter_foreground;
     glyph_brush.queue(Section {
-      screen_position: (
-        (self.dimensions.x
-          + (self.dimensions.width - (GUTTER_PADDING + GUTTER_MARGIN))),
-        -(((-self.scroll_offset_y as f32) % self.font_height)
-          - self.dimensions.y),
-      ),
+      screen_position: (numbers_end, y),
       text: vec![Text::new(&line_numbers)
-        .with_color([0.9, 0.9, 0.9, 1.0])
+        .with_color(color)
         .with_scale(self.font_height)],
       layout: Layout::default_wrap().h_align(HorizontalAlign::Right),
       ..Section::default()
     });
+    glyph_brush.queue(Section {
+      screen_position: (self.dimensions.x + GUTTER_PADDING / 2.0, y),
+      text: icons,
+      ..Section::default()
+    });
+    glyph_brush.queue(Section {
+      screen_position: (numbers_end + GUTTER_PADDING / 2.0, y),
+      text: vec![Text::new(&markers)
+        .with_color(color)
+        .with_scale(self.font_height)],
+      ..Section::default()
+    });
 
+    // the numbers behind the pinned headers are left out
+    let sticky = self.sticky.borrow();
+    let sticky_height = sticky.rows.len() as f32 * self.font_height;
     glyph_brush
       .draw_queued_with_transform_and_scissoring(
         device,
@@ -129,9 +198,45 @@ impl super::super::RenderElement for Gutter {
         encoder,
         target,
         wgpu_glyph::orthographic_projection(size.width, size.height),
-        self.dimensions.into(),
+        Dimensions {
+          y: self.dimensions.y + sticky_height,
+          height: self.dimensions.height - sticky_height,
+          ..self.dimensions
+        }
+        .into(),
       )
       .unwrap();
+
+    if sticky_height > 0.0 {
+      let line_numbers = sticky
+        .rows
+        .iter()
+        .map(|row| format!("{}\n", row + 1))
+        .collect::<String>();
+      glyph_brush.queue(Section {
+        screen_position: (numbers_end, self.dimensions.y),
+        text: vec![Text::new(&line_numbers)
+          .with_color(color)
+          .with_scale(self.font_height)],
+        layout: Layout::default_wrap().h_align(HorizontalAlign::Right),
+        ..Section::default()
+      });
+
+      glyph_brush
+        .draw_queued_with_transform_and_scissoring(
+          device,
+          staging_belt,
+          encoder,
+          target,
+          wgpu_glyph::orthographic_projection(size.width, size.height),
+          Dimensions {
+            height: sticky_height,
+            ..self.dimensions
+          }
+          .into(),
+        )
+        .unwrap();
+    }
   }
 
   fn get_rects(&self) -> Vec<&Rectangle> {
@@ -1,9 +1,10 @@
+use super::language::{Language, Registry};
 use std::convert::TryFrom;
-use tree_sitter_highlight::{
-  HighlightConfiguration, HighlightEvent, Highlighter,
-};
+use std::rc::Rc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use tree_sitter_highlight::{HighlightEvent, Highlighter};
 
-#[derive(Copy, Clone, Debug, num_enum::TryFromPrimitive)]
+#[derive(Copy, Clone, Debug, PartialEq, num_enum::TryFromPrimitive)]
 #[repr(u8)]
 pub enum HighlightNames {
   Constant,
@@ -40,8 +41,17 @@ pub enum HighlightNames {
   LocalReference,
 }
 
+/// How the text of a highlight is drawn besides its color.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct Style {
+  pub bold: bool,
+  pub italic: bool,
+  pub underline: bool,
+  pub strikethrough: bool,
+}
+
 impl HighlightNames {
-  const VARIANTS: [HighlightNames; 32] = [
+  pub const VARIANTS: [HighlightNames; 32] = [
     HighlightNames::Constant,
     HighlightNames::ConstantBuiltin,
     HighlightNames::Tag,
@@ -75,67 +85,6 @@ impl HighlightNames {
     HighlightNames::LocalDefinition,
     HighlightNames::LocalReference,
   ];
-
-  pub fn color(&self) -> [f32; 4] {
-    #[allow(clippy::excessive_precision)]
-    match self {
-      HighlightNames::Constant => [0.59607843, 0.4627451, 0.66666667, 1.0],
-      HighlightNames::ConstantBuiltin => {
-        [0.65882353, 0.33333333, 0.44705882, 1.0]
-      }
-      HighlightNames::Tag => [0.94117647, 0.77647059, 0.45490196, 1.0], // TODO
-      HighlightNames::Type => [0.94117647, 0.77647059, 0.45490196, 1.0], //
-      HighlightNames::TypeBuiltin => [0.8, 0.47058824, 0.19607843, 1.0], //
-      HighlightNames::Constructor => [0.91372549, 0.74509804, 0.40784314, 1.0], // TODO
-      HighlightNames::Function => [0.9, 0.9, 0.9, 1.0], // TODO: function usage and definition
-      HighlightNames::FunctionBuiltin => [0.9, 0.9, 0.9, 1.0], // TODO: function usage and definition
-      HighlightNames::FunctionMethod => {
-        [0.91372549, 0.74509804, 0.40784314, 1.0] // TODO: methods
-      }
-      HighlightNames::FunctionMacro => {
-        [0.30588235, 0.67843137, 0.89803922, 1.0] //
-      }
-      HighlightNames::Property => [0.59607843, 0.46666667, 0.66666667, 1.0], //
-      HighlightNames::Comment => [0.47843137, 0.34509804, 0.5254902, 1.0],   //
-      HighlightNames::PunctuationBracket => {
-        [0.9, 0.9, 0.9, 1.0] // TODO
-      }
-      HighlightNames::PunctuationDelimiter => {
-        [0.278431371, 0.60784314, 0.49411765, 1.0] // TODO
-      }
-      HighlightNames::PunctuationSpecial => {
-        [0.278431371, 0.60784314, 0.49411765, 1.0] // TODO
-      }
-      HighlightNames::Variable => [0.8, 0.47058824, 0.19607843, 1.0], // TODO
-      HighlightNames::VariableParameter => [0.8, 0.4, 0.4, 1.0],      //
-      HighlightNames::VariableBuiltin => [0.8, 0.47058824, 0.19607843, 1.0],
-      HighlightNames::Label => [0.1254902, 0.6, 0.61568627, 1.0], //
-      HighlightNames::Keyword => [0.8, 0.47058824, 0.19607843, 1.0], //
-      HighlightNames::String => [0.50588235, 0.72941176, 0.34901961, 1.0], //
-      HighlightNames::StringSpecial => {
-        [0.50588235, 0.72941176, 0.34901961, 1.0]
-      } // TODO
-      HighlightNames::Escape => [0.52941176, 0.74117647, 0.77647059, 1.0], //
-      HighlightNames::Attribute => [0.83111111, 0.70980392, 0.16078431, 1.0],
-      HighlightNames::Operator => [0.278431371, 0.60784314, 0.49411765, 1.0], //
-      HighlightNames::Embedded => [0.278431371, 0.60784314, 0.49411765, 1.0], // TODO
-      HighlightNames::Number => [0.278431371, 0.60784314, 0.49411765, 1.0], // TODO
-
-      HighlightNames::InjectionLanguage => {
-        [0.278431371, 0.60784314, 0.49411765, 1.0]
-      } // TODO
-      HighlightNames::InjectionContent => {
-        [0.278431371, 0.60784314, 0.49411765, 1.0]
-      } // TODO
-      HighlightNames::LocalScope => [0.278431371, 0.60784314, 0.49411765, 1.0], // TODO
-      HighlightNames::LocalDefinition => {
-        [0.278431371, 0.60784314, 0.49411765, 1.0]
-      } // TODO
-      HighlightNames::LocalReference => {
-        [0.278431371, 0.60784314, 0.49411765, 1.0]
-      } // TODO
-    }
-  }
 }
 
 impl std::fmt::Display for HighlightNames {
@@ -177,156 +126,127 @@ impl std::fmt::Display for HighlightNames {
   }
 }
 
+#[derive(Clone, Debug, PartialEq)]
+pub struct Span {
+  pub start: usize,
+  pub end: usize,
+  pub layers: Vec<HighlightNames>,
+}
+
+impl Span {
+  pub fn highlight(&self) -> Option<HighlightNames> {
+    self.layers.last().copied()
+  }
+}
+
 pub struct Config {
-  config: HighlightConfiguration,
-  /// Vec of tuples of char_start, chard_end and HighlightName
-  pub highlights: Vec<(usize, usize, Option<HighlightNames>)>,
+  parser: tree_sitter::Parser,
+  pub tree: Option<tree_sitter::Tree>,
+  pub tree_id: usize,
+  pub highlights: Vec<Span>,
+  pub language: Rc<Language>,
+  registry: Rc<Registry>,
 }
 
+/// Number of trees parsed, giving them their ids.
+static TREES: AtomicUsize = AtomicUsize::new(0);
+
 impl Config {
+  pub fn new(language: Rc<Language>, registry: Rc<Registry>) -> Self {
+    let mut parser = tree_sitter::Parser::new();
+    parser.set_language(language.highlight.language).unwrap();
+
+    Self {
+      language,
+      registry,
+      parser,
+      tree: None,
+      tree_id: 0,
+      highlights: vec![],
+    }
+  }
+
+  fn layers_at(&self, char_idx: usize) -> &[HighlightNames] {
+    let i = self.highlights.partition_point(|span| span.end <= char_idx);
+    match self.highlights.get(i) {
+      Some(span) if span.start <= char_idx => &span.layers,
+      _ => &[],
+    }
+  }
+
+  pub fn in_string_or_comment(&self, char_idx: usize) -> bool {
+    if char_idx == 0 {
+      return false;
+    }
+    let has = |char_idx: usize, names: &[HighlightNames]| {
+      self
+        .layers_at(char_idx)
+        .iter()
+        .any(|layer| names.contains(layer))
+    };
+    let strings = [HighlightNames::String, HighlightNames::StringSpecial];
+    (has(char_idx - 1, &strings) && has(char_idx, &strings))
+      || has(char_idx - 1, &[HighlightNames::Comment])
+  }
+
   pub fn generate(&mut self, rope: &ropey::Rope) {
     let mut highlighter = Highlighter::new();
     let source = rope.bytes().collect::<Vec<u8>>();
+    self.tree = self.parser.parse(&source, None);
+    self.tree_id = TREES.fetch_add(1, Ordering::Relaxed) + 1;
+    let registry = &self.registry;
     let highlights = highlighter
-      .highlight(&self.config, &source, None, |_| None)
+      .highlight(&self.language.highlight, &source, None, |name| {
+        registry.get(name).map(|language| &language.highlight)
+      })
       .unwrap();
 
     self.highlights.clear();
-    let mut current_range = (0, 0);
-    let mut current_highlight = None;
+    // highlights which are started but not yet ended, with None for those
+    // which have no name the editor knows of
+    let mut stack = vec![];
     for event in highlights {
       match event.unwrap() {
         HighlightEvent::Source { start, end } => {
-          let start = rope.byte_to_char(start);
-          let end = rope.byte_to_char(end);
-          if current_highlight.is_none() {
-            self.highlights.push((start, end, None));
-          } else {
-            current_range = (start, end);
-          }
+          self.highlights.push(Span {
+            start: rope.byte_to_char(start),
+            end: rope.byte_to_char(end),
+            layers: stack.iter().flatten().copied().collect(),
+          });
         }
         HighlightEvent::HighlightStart(s) => {
-          current_highlight = HighlightNames::try_from(s.0 as u8).ok();
+          stack.push(HighlightNames::try_from(s.0 as u8).ok());
         }
         HighlightEvent::HighlightEnd => {
-          self.highlights.push((
-            current_range.0,
-            current_range.1,
-            current_highlight,
-          ));
-          current_highlight = None;
+          stack.pop();
         }
       }
     }
   }
 }
 
-pub fn config_from_extension(ext: Option<&std::ffi::OsStr>) -> Option<Config> {
-  let mut config = match ext?.to_string_lossy().as_ref() {
-    "cpp" | "cxx" | "cc" => HighlightConfiguration::new(
-      tree_sitter_cpp::language(),
-      tree_sitter_cpp::HIGHLIGHT_QUERY,
-      "",
-      "",
-    ),
-    "java" => HighlightConfiguration::new(
-      tree_sitter_java::language(),
-      tree_sitter_java::HIGHLIGHT_QUERY,
-      "",
-      "",
-    ),
-    "js" | "cjs" | "mjs" => HighlightConfiguration::new(
-      tree_sitter_javascript::language(),
-      tree_sitter_javascript::HIGHLIGHT_QUERY,
-      tree_sitter_javascript::INJECTION_QUERY,
-      tree_sitter_javascript::LOCALS_QUERY,
-    ),
-    "jsx" => HighlightConfiguration::new(
-      tree_sitter_javascript::language(),
-      tree_sitter_javascript::JSX_HIGHLIGHT_QUERY,
-      tree_sitter_javascript::INJECTION_QUERY,
-      tree_sitter_javascript::LOCALS_QUERY,
-    ),
-    "ml" => HighlightConfiguration::new(
-      tree_sitter_ocaml::language_ocaml(),
-      tree_sitter_ocaml::HIGHLIGHT_QUERY,
-      "",
-      tree_sitter_ocaml::LOCALS_QUERY,
-    ),
-    "mli" => HighlightConfiguration::new(
-      tree_sitter_ocaml::language_ocaml_interface(),
-      tree_sitter_ocaml::HIGHLIGHT_QUERY,
-      "",
-      tree_sitter_ocaml::LOCALS_QUERY,
-    ),
-    "py" => HighlightConfiguration::new(
-      tree_sitter_python::language(),
-      tree_sitter_python::HIGHLIGHT_QUERY,
-      "",
-      "",
-    ),
-    "rs" => tree_sitter_highlight::HighlightConfiguration::new(
-      tree_sitter_rust::language(),
-      tree_sitter_rust::HIGHLIGHT_QUERY,
-      "",
-      "",
-    ),
-    "ts" => tree_sitter_highlight::HighlightConfiguration::new(
-      tree_sitter_typescript::language_typescript(),
-      tree_sitter_typescript::HIGHLIGHT_QUERY,
-      "",
-      tree_sitter_typescript::LOCALS_QUERY,
-    ),
-    "tsx" => tree_sitter_highlight::HighlightConfiguration::new(
-      tree_sitter_typescript::language_tsx(),
-      tree_sitter_typescript::HIGHLIGHT_QUERY,
-      "",
-      tree_sitter_typescript::LOCALS_QUERY,
-    ),
-    _ => return None,
-  }
-  .unwrap();
-
-  config.configure(
-    &HighlightNames::VARIANTS
-      .iter()
-      .map(|v| v.to_string())
-      .collect::<Vec<String>>(),
-  );
-
-  Some(Config {
-    config,
-    highlights: vec![],
-  })
-}
-
 #[cfg(test)]
-#[test]
-fn names_contains_all_language_names() {
-  let names = HighlightNames::VARIANTS
-    .iter()
-    .map(|v| v.to_string())
-    .collect::<Vec<String>>();
+mod tests {
+  use super::*;
 
-  for lang in [
-    "cpp", "java", "js", "jsx", "ml", "mli", "py", "rs", "ts", "tsx",
-  ] {
-    let config =
-      config_from_extension(Some(std::ffi::OsStr::new(lang))).unwrap();
-    assert!(
-      config
-        .config
-        .names()
-        .iter()
-        .all(|name| names.contains(name)),
-      "Language '{}' doesnt have all names in Names struct. Missing are: {:?}",
-      lang,
-      config
-        .config
-        .names()
-        .iter()
-        .filter(|name| !names.contains(name))
-        .collect::<Vec<_>>()
+  #[test]
+  fn layers_injected_highlights() {
+    let registry = Rc::new(Registry::builtin());
+    let language = registry.get("javascript").unwrap().clone();
+    let mut config = Config::new(language, Rc::clone(&registry));
+    let text = ropey::Rope::from_str("js`let x = \"a\"`;");
+    config.generate(&text);
+
+    let keyword = config
+      .highlights
+      .iter()
+      .find(|span| text.slice(span.start..span.end) == "let")
+      .unwrap();
+    assert_eq!(
+      keyword.layers,
+      vec![HighlightNames::String, HighlightNames::Keyword]
     );
+    assert!(config.in_string_or_comment(4));
+    assert!(!config.in_string_or_comment(1));
   }
 }
@@ -0,0 +1,528 @@
+use super::highlight::{HighlightNames, Span};
+use crate::renderer::rectangle::Rectangle;
+use crate::renderer::search;
+use crate::renderer::theme;
+use crate::renderer::Dimensions;
+use std::ops::Range;
+use wgpu::util::StagingBelt;
+use wgpu::{CommandEncoder, Device, TextureView};
+use wgpu_glyph::{GlyphBrush, Section, Text};
+use winit::dpi::{PhysicalPosition, PhysicalSize};
+use winit::event::VirtualKeyCode;
+
+const INSPECTOR_PADDING: f32 = 10.0;
+/// Rows above the tree, holding the query and what running it gave.
+const HEADER_ROWS: usize = 2;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Row {
+  pub depth: usize,
+  pub field: Option<&'static str>,
+  pub kind: String,
+  pub start: tree_sitter::Point,
+  pub end: tree_sitter::Point,
+  pub range: Range<usize>,
+  pub highlight: Option<HighlightNames>,
+}
+
+pub fn rows(
+  tree: &tree_sitter::Tree,
+  text: &ropey::Rope,
+  highlights: &[Span],
+) -> Vec<Row> {
+  let mut rows = vec![];
+  let mut cursor = tree.walk();
+  let mut depth = 0;
+  loop {
+    let node = cursor.node();
+    let range =
+      text.byte_to_char(node.start_byte())..text.byte_to_char(node.end_byte());
+    rows.push(Row {
+      depth,
+      field: cursor.field_name(),
+      kind: if node.is_named() {
+        node.kind().to_string()
+      } else {
+        format!("{:?}", node.kind())
+      },
+      start: node.start_position(),
+      end: node.end_position(),
+      highlight: covering_highlight(highlights, range.clone()),
+      range,
+    });
+
+    if cursor.goto_first_child() {
+      depth += 1;
+      continue;
+    }
+    while !cursor.goto_next_sibling() {
+      if !cursor.goto_parent() {
+        return rows;
+      }
+      depth -= 1;
+    }
+  }
+}
+
+fn covering_highlight(
+  highlights: &[Span],
+  range: Range<usize>,
+) -> Option<HighlightNames> {
+  let i = highlights.partition_point(|span| span.end <= range.start);
+  let mut spans = highlights[i..]
+    .iter()
+    .take_while(|span| span.start < range.end.max(range.start + 1));
+  let mut layers = spans.next()?.layers.clone();
+  for span in spans {
+    let common = layers
+      .iter()
+      .zip(&span.layers)
+      .take_while(|(a, b)| a == b)
+      .count();
+    layers.truncate(common);
+  }
+  layers.last().copied()
+}
+
+pub fn query_matches(
+  query: &tree_sitter::Query,
+  tree: &tree_sitter::Tree,
+  text: &ropey::Rope,
+) -> Vec<Range<usize>> {
+  let source = text.bytes().collect::<Vec<u8>>();
+  let mut cursor = tree_sitter::QueryCursor::new();
+  let mut ranges = cursor
+    .matches(query, tree.root_node(), |node| &source[node.byte_range()])
+    .flat_map(|m| m.captures.iter().map(|capture| capture.node.byte_range()))
+    .map(|bytes| text.byte_to_char(bytes.start)..text.byte_to_char(bytes.end))
+    .collect::<Vec<_>>();
+  ranges.sort_by_key(|range| (range.start, range.end));
+  ranges.dedup();
+  ranges
+}
+
+/// Panel listing the syntax tree of the text, with the node under the
+/// primary cursor marked, and a query to run against the tree.
+pub struct Inspector {
+  rect: Rectangle,
+  header_rect: Rectangle,
+  current_rect: Rectangle,
+  font_height: f32,
+  pub dimensions: Dimensions,
+  scroll_offset_y: f32,
+  rows: Vec<Row>,
+  listed: Option<usize>,
+  current: Option<usize>,
+  query: String,
+  compiled: Option<(tree_sitter::Language, Result<tree_sitter::Query, String>)>,
+  matched: Option<usize>,
+  pub focused: bool,
+  status: String,
+  matches: Vec<Range<usize>>,
+}
+
+impl Inspector {
+  pub fn new(
+    device: &wgpu::Device,
+    screen_size: PhysicalSize<f32>,
+    font_height: f32,
+    dimensions: Dimensions,
+  ) -> Self {
+    let ui = theme::current().ui;
+    let rect = Rectangle::new(
+      device,
+      screen_size,
+      dimensions,
+      theme::rgb(ui.tree_background),
+      None,
+    );
+    let header_rect = Rectangle::new(
+      device,
+      screen_size,
+      dimensions,
+      theme::rgb(ui.breadcrumbs_background),
+      None,
+    );
+    let current_rect = Rectangle::new(
+      device,
+      screen_size,
+      dimensions,
+      theme::rgb(ui.selection),
+      None,
+    );
+
+    let mut inspector = Self {
+      rect,
+      header_rect,
+      current_rect,
+      font_height,
+      dimensions,
+      scroll_offset_y: 0.0,
+      rows: vec![],
+      listed: None,
+      current: None,
+      query: String::new(),
+      compiled: None,
+      matched: None,
+      focused: false,
+      status: String::new(),
+      matches: vec![],
+    };
+    inspector.place_rects(screen_size);
+    inspector
+  }
+
+  fn tree_dimensions(&self) -> Dimensions {
+    let header_height = HEADER_ROWS as f32 * self.font_height;
+    Dimensions {
+      y: self.dimensions.y + header_height,
+      height: (self.dimensions.height - header_height).max(0.0),
+      ..self.dimensions
+    }
+  }
+
+  pub fn update(
+    &mut self,
+    screen_size: PhysicalSize<f32>,
+    tree_id: usize,
+    tree: Option<&tree_sitter::Tree>,
+    text: &ropey::Rope,
+    highlights: &[Span],
+    char_idx: usize,
+  ) {
+    if self.listed != Some(tree_id) {
+      self.listed = Some(tree_id);
+      self.rows =
+        tree.map_or_else(Vec::new, |tree| rows(tree, text, highlights));
+    }
+    if self.matched != Some(tree_id) {
+      self.matched = Some(tree_id);
+      self.run_query(tree, text);
+    }
+    self.current = self
+      .rows
+      .iter()
+      .rposition(|row| row.range.contains(&char_idx))
+      .or_else(|| (!self.rows.is_empty()).then(|| 0));
+
+    let height = self.tree_dimensions().height;
+    if let Some(current) = self.current {
+      let y = current as f32 * self.font_height;
+      if y + self.scroll_offset_y < 0.0 {
+        self.scroll_offset_y = -y;
+      } else if y + self.scroll_offset_y + self.font_height > height {
+        self.scroll_offset_y = height - self.font_height - y;
+      }
+    }
+    self.clamp_scroll(screen_size);
+  }
+
+  fn clamp_scroll(&mut self, screen_size: PhysicalSize<f32>) {
+    let max_offset =
+      self.rows.len().saturating_sub(1) as f32 * self.font_height;
+    self.scroll_offset_y = self.scroll_offset_y.min(0.0).max(-max_offset);
+    self.place_rects(screen_size);
+  }
+
+  fn run_query(
+    &mut self,
+    tree: Option<&tree_sitter::Tree>,
+    text: &ropey::Rope,
+  ) {
+    self.matches.clear();
+    self.status.clear();
+    let tree = match tree {
+      Some(tree) if !self.query.trim().is_empty() => tree,
+      _ => return,
+    };
+    let language = tree.language();
+    if !matches!(&self.compiled, Some((compiled, _)) if *compiled == language) {
+      let query = search::compile_query(language, &self.query);
+      self.compiled = Some((language, query));
+    }
+    match &self.compiled {
+      Some((_, Ok(query))) => {
+        self.matches = query_matches(query, tree, text);
+        self.status = match self.matches.len() {
+          1 => "1 match".to_string(),
+          n => format!("{} matches", n),
+        };
+      }
+      Some((_, Err(error))) => self.status = error.clone(),
+      None => {}
+    }
+  }
+
+  pub fn matches(&self) -> &[Range<usize>] {
+    &self.matches
+  }
+
+  pub fn input_char(&mut self, ch: char) -> bool {
+    let changed = match ch {
+      '\u{7f}' | '\u{8}' => self.query.pop().is_some(),
+      '\u{1b}' => {
+        self.focused = false;
+        false
+      }
+      c if c.is_control() => false,
+      c => {
+        self.query.push(c);
+        true
+      }
+    };
+    if changed {
+      self.compiled = None;
+      self.matched = None;
+    }
+    changed
+  }
+
+  pub fn input_special(&mut self, key: VirtualKeyCode) {
+    if key == VirtualKeyCode::Escape {
+      self.focused = false;
+    }
+  }
+
+  pub fn is_query_at(&self, y: f64) -> bool {
+    y < self.font_height as f64
+  }
+
+  pub fn row_at(&self, y: f64) -> Option<&Row> {
+    let header_height = HEADER_ROWS as f32 * self.font_height;
+    let y = y as f32 - header_height;
+    if y < 0.0 {
+      return None;
+    }
+    let row = (y - self.scroll_offset_y) / self.font_height;
+    self.rows.get(row.floor() as usize)
+  }
+
+  fn place_rects(&mut self, screen_size: PhysicalSize<f32>) {
+    self.rect.resize(screen_size, self.dimensions);
+    self.header_rect.resize(
+      screen_size,
+      Dimensions {
+        height: HEADER_ROWS as f32 * self.font_height,
+        ..self.dimensions
+      },
+    );
+    let tree_dimensions = self.tree_dimensions();
+    let row = self.current.unwrap_or_default() as f32;
+    self.current_rect.resize(
+      screen_size,
+      Dimensions {
+        y: tree_dimensions.y + self.scroll_offset_y + row * self.font_height,
+        height: self.font_height,
+        ..tree_dimensions
+      },
+    );
+    self.current_rect.region = Some(tree_dimensions.into());
+  }
+}
+
+impl super::super::RenderElement for Inspector {
+  fn resize(&mut self, screen_size: PhysicalSize<f32>) {
+    self.place_rects(screen_size);
+  }
+
+  fn scroll(
+    &mut self,
+    offset: PhysicalPosition<f64>,
+    screen_size: PhysicalSize<f32>,
+  ) {
+    self.scroll_offset_y += offset.y as f32;
+    self.clamp_scroll(screen_size);
+  }
+
+  fn theme_changed(&mut self, _screen_size: PhysicalSize<f32>) {
+    let ui = theme::current().ui;
+    self.rect.set_color(theme::rgb(ui.tree_background));
+    self
+      .header_rect
+      .set_color(theme::rgb(ui.breadcrumbs_background));
+    self.current_rect.set_color(theme::rgb(ui.selection));
+  }
+
+  fn redraw(
+    &mut self,
+    glyph_brush: &mut GlyphBrush<()>,
+    device: &Device,
+    staging_belt: &mut StagingBelt,
+    encoder: &mut CommandEncoder,
+    target: &TextureView,
+    size: PhysicalSize<u32>,
+  ) {
+    let theme = theme::current();
+    let ui = theme.ui;
+    let x = self.dimensions.x + INSPECTOR_PADDING;
+
+    let caret = if self.focused { "|" } else { "" };
+    glyph_brush.queue(Section {
+      screen_position: (x, self.dimensions.y),
+      text: vec![
+        Text::new("query › ")
+          .with_scale(self.font_height)
+          .with_color(ui.breadcrumbs_foreground),
+        Text::new(&self.query)
+          .with_scale(self.font_height)
+          .with_color(ui.foreground),
+        Text::new(caret)
+          .with_scale(self.font_height)
+          .with_color(ui.cursor),
+      ],
+      ..Section::default()
+    });
+    glyph_brush.queue(Section {
+      screen_position: (x, self.dimensions.y + self.font_height),
+      text: vec![Text::new(&self.status)
+        .with_scale(self.font_height)
+        .with_color(ui.breadcrumbs_foreground)],
+      ..Section::default()
+    });
+    glyph_brush
+      .draw_queued_with_transform_and_scissoring(
+        device,
+        staging_belt,
+        encoder,
+        target,
+        wgpu_glyph::orthographic_projection(size.width, size.height),
+        self.dimensions.into(),
+      )
+      .unwrap();
+
+    let tree_dimensions = self.tree_dimensions();
+    if tree_dimensions.height <= 0.0 {
+      return;
+    }
+    let first = (-self.scroll_offset_y / self.font_height).floor() as usize;
+    let count = (tree_dimensions.height / self.font_height).ceil() as usize + 1;
+    let labels = self
+      .rows
+      .iter()
+      .skip(first)
+      .take(count)
+      .map(|row| {
+        let field = row.field.map(|f| format!("{}: ", f)).unwrap_or_default();
+        let range = format!(
+          " [{}:{} - {}:{}]",
+          row.start.row + 1,
+          row.start.column + 1,
+          row.end.row + 1,
+          row.end.column + 1
+        );
+        let highlight = row
+          .highlight
+          .map(|h| (format!(" @{}", h), theme.highlight(h).0));
+        (field, range, highlight)
+      })
+      .collect::<Vec<_>>();
+    for (i, (row, (field, range, highlight))) in
+      self.rows.iter().skip(first).zip(&labels).enumerate()
+    {
+      let mut text = vec![
+        Text::new(field)
+          .with_scale(self.font_height)
+          .with_color(ui.gutter_foreground),
+        Text::new(&row.kind)
+          .with_scale(self.font_height)
+          .with_color(ui.tree_foreground),
+        Text::new(range)
+          .with_scale(self.font_height)
+          .with_color(ui.gutter_foreground),
+      ];
+      if let Some((name, color)) = highlight {
+        text.push(
+          Text::new(name)
+            .with_scale(self.font_height)
+            .with_color(*color),
+        );
+      }
+      glyph_brush.queue(Section {
+        screen_position: (
+          x + row.depth as f32 * self.font_height,
+          tree_dimensions.y
+            + self.scroll_offset_y
+            + (first + i) as f32 * self.font_height,
+        ),
+        text,
+        ..Section::default()
+      });
+    }
+
+    glyph_brush
+      .draw_queued_with_transform_and_scissoring(
+        device,
+        staging_belt,
+        encoder,
+        target,
+        wgpu_glyph::orthographic_projection(size.width, size.height),
+        tree_dimensions.into(),
+      )
+      .unwrap();
+  }
+
+  fn get_rects(&self) -> Vec<&Rectangle> {
+    let mut vec = vec![&self.rect, &self.header_rect];
+    if self.current.is_some() {
+      vec.push(&self.current_rect);
+    }
+    vec
+  }
+
+  fn get_elements(&mut self) -> Vec<&mut dyn super::super::RenderElement> {
+    vec![]
+  }
+
+  fn get_dimensions(&self) -> Dimensions {
+    self.dimensions
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::renderer::code_view::highlight::Config;
+  use crate::renderer::code_view::language::Registry;
+  use std::rc::Rc;
+
+  #[test]
+  fn lists_nodes_and_matches() {
+    let registry = Rc::new(Registry::builtin());
+    let language = registry.get("rust").unwrap().clone();
+    let mut config = Config::new(language, Rc::clone(&registry));
+    let text = ropey::Rope::from_str("fn a() {}\n");
+    config.generate(&text);
+    let tree = config.tree.as_ref().unwrap();
+
+    let rows = rows(tree, &text, &config.highlights);
+    let listed = rows
+      .iter()
+      .map(|row| (row.depth, row.field, row.kind.as_str()))
+      .collect::<Vec<_>>();
+    assert_eq!(
+      listed,
+      vec![
+        (0, None, "source_file"),
+        (1, None, "function_item"),
+        (2, None, "\"fn\""),
+        (2, Some("name"), "identifier"),
+        (2, Some("parameters"), "parameters"),
+        (3, None, "\"(\""),
+        (3, None, "\")\""),
+        (2, Some("body"), "block"),
+        (3, None, "\"{\""),
+        (3, None, "\"}\""),
+      ]
+    );
+    assert_eq!(rows[2].highlight, Some(HighlightNames::Keyword));
+    assert_eq!(rows[3].highlight, Some(HighlightNames::Function));
+    assert_eq!(rows[3].range, 3..4);
+
+    let query = tree_sitter::Query::new(
+      tree.language(),
+      "(function_item name: (identifier) @name)",
+    )
+    .unwrap();
+    assert_eq!(query_matches(&query, tree, &text), vec![3..4]);
+  }
+}
@@ -0,0 +1,712 @@
+use super::detect;
+use super::highlight::HighlightNames;
+use crate::renderer::input::comment::CommentTokens;
+use crate::renderer::input::pairs;
+use std::path::Path;
+use std::rc::Rc;
+use tree_sitter_highlight::HighlightConfiguration;
+
+/// Directory holding the grammars loaded at runtime, one directory per
+/// language.
+const GRAMMARS_DIR: &str = "~/.config/devcode/grammars";
+/// File with user rules for detecting languages, see `Overrides`.
+const LANGUAGES_FILE: &str = "~/.config/devcode/languages.toml";
+
+fn strings(values: &[&str]) -> Vec<String> {
+  values.iter().map(|value| value.to_string()).collect()
+}
+
+pub struct Language {
+  pub name: String,
+  pub aliases: Vec<String>,
+  pub extensions: Vec<String>,
+  pub filenames: Vec<String>,
+  pub globs: Vec<String>,
+  pub interpreters: Vec<String>,
+  pub highlight: HighlightConfiguration,
+  pub tags: Option<tree_sitter::Query>,
+  pub locals: Option<tree_sitter::Query>,
+  pub folds: Option<tree_sitter::Query>,
+  pub auto_pairs: Vec<(char, char)>,
+  pub comment_tokens: CommentTokens,
+  library: Option<libloading::Library>,
+}
+
+impl Language {
+  fn new(
+    name: &str,
+    extensions: &[&str],
+    language: tree_sitter::Language,
+    highlights: &str,
+    injections: &str,
+    locals: &str,
+  ) -> Result<Self, anyhow::Error> {
+    let mut highlight =
+      HighlightConfiguration::new(language, highlights, injections, locals)
+        .map_err(|err| anyhow::anyhow!("invalid query: {:?}", err))?;
+    highlight.configure(
+      &HighlightNames::VARIANTS
+        .iter()
+        .map(|v| v.to_string())
+        .collect::<Vec<String>>(),
+    );
+    let locals = match locals {
+      "" => None,
+      locals => Some(
+        tree_sitter::Query::new(language, locals)
+          .map_err(|err| anyhow::anyhow!("invalid locals query: {:?}", err))?,
+      ),
+    };
+
+    Ok(Self {
+      name: name.to_string(),
+      aliases: vec![],
+      extensions: strings(extensions),
+      filenames: vec![],
+      globs: vec![],
+      interpreters: vec![],
+      highlight,
+      tags: None,
+      locals,
+      folds: None,
+      auto_pairs: pairs::DEFAULT_PAIRS.to_vec(),
+      comment_tokens: CommentTokens::c_like(),
+      library: None,
+    })
+  }
+
+  fn is_named(&self, name: &str) -> bool {
+    std::iter::once(&self.name)
+      .chain(&self.aliases)
+      .any(|n| n.eq_ignore_ascii_case(name))
+  }
+
+  fn with_tags(mut self, tags: &str) -> Result<Self, anyhow::Error> {
+    let query = tree_sitter::Query::new(self.highlight.language, tags)
+      .map_err(|err| anyhow::anyhow!("invalid tags query: {:?}", err))?;
+    self.tags = Some(query);
+    Ok(self)
+  }
+
+  fn with_locals(mut self, locals: &str) -> Result<Self, anyhow::Error> {
+    let query = tree_sitter::Query::new(self.highlight.language, locals)
+      .map_err(|err| anyhow::anyhow!("invalid locals query: {:?}", err))?;
+    self.locals = Some(query);
+    Ok(self)
+  }
+
+  fn with_folds(mut self, folds: &str) -> Result<Self, anyhow::Error> {
+    let query = tree_sitter::Query::new(self.highlight.language, folds)
+      .map_err(|err| anyhow::anyhow!("invalid folds query: {:?}", err))?;
+    self.folds = Some(query);
+    Ok(self)
+  }
+
+  fn load(dir: &Path) -> Result<Self, anyhow::Error> {
+    let name = dir
+      .file_name()
+      .ok_or_else(|| anyhow::anyhow!("invalid grammar directory"))?
+      .to_string_lossy()
+      .to_string();
+    let manifest: toml::Value =
+      std::fs::read_to_string(dir.join("grammar.toml"))?.parse()?;
+    let string = |key: &str| manifest.get(key).and_then(|v| v.as_str());
+
+    let library_path = match string("library") {
+      Some(library) => dir.join(library),
+      None => std::fs::read_dir(dir)?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .find(|path| {
+          path.extension().and_then(|ext| ext.to_str())
+            == Some(std::env::consts::DLL_EXTENSION)
+        })
+        .ok_or_else(|| anyhow::anyhow!("no grammar library in {:?}", dir))?,
+    };
+    let symbol = string("symbol")
+      .map(|symbol| symbol.to_string())
+      .unwrap_or_else(|| format!("tree_sitter_{}", name.replace('-', "_")));
+
+    // SAFETY: the library is expected to be a tree-sitter grammar exporting
+    // the language function under `symbol`. The language points into the
+    // library, which is kept in the `Language` so that it is unloaded only
+    // after the queries of the language were dropped. The parsers and trees
+    // made with the language are kept next to an `Rc` of it, which is
+    // dropped after them.
+    let (library, language) = unsafe {
+      let library = libloading::Library::new(&library_path)?;
+      let language = library
+        .get::<unsafe extern "C" fn() -> tree_sitter::Language>(
+          symbol.as_bytes(),
+        )?();
+      (library, language)
+    };
+    if language.version() < tree_sitter::MIN_COMPATIBLE_LANGUAGE_VERSION
+      || language.version() > tree_sitter::LANGUAGE_VERSION
+    {
+      anyhow::bail!(
+        "grammar version {} isn't supported, it has to be between {} and {}",
+        language.version(),
+        tree_sitter::MIN_COMPATIBLE_LANGUAGE_VERSION,
+        tree_sitter::LANGUAGE_VERSION
+      );
+    }
+
+    let query =
+      |file: &str| std::fs::read_to_string(dir.join(file)).unwrap_or_default();
+    let highlights = std::fs::read_to_string(dir.join("highlights.scm"))?;
+    let list = |key: &str| {
+      manifest
+        .get(key)
+        .and_then(|v| v.as_array())
+        .map(|values| values.iter().filter_map(|v| v.as_str()).collect())
+        .unwrap_or_else(Vec::new)
+    };
+
+    let mut lang = Self::new(
+      &name,
+      &list("extensions"),
+      language,
+      &highlights,
+      &query("injections.scm"),
+      &query("locals.scm"),
+    )?;
+    if let Ok(tags) = std::fs::read_to_string(dir.join("tags.scm")) {
+      lang = lang.with_tags(&tags)?;
+    }
+    if let Ok(folds) = std::fs::read_to_string(dir.join("folds.scm")) {
+      lang = lang.with_folds(&folds)?;
+    }
+    lang.aliases = strings(&list("aliases"));
+    lang.filenames = strings(&list("filenames"));
+    lang.globs = strings(&list("globs"));
+    lang.interpreters = strings(&list("interpreters"));
+    if let Some(values) = manifest.get("pairs").and_then(|v| v.as_array()) {
+      lang.auto_pairs = parse_pairs(values)?;
+    }
+    let block = manifest
+      .get("block_comment")
+      .and_then(|v| v.as_array())
+      .and_then(|tokens| match tokens.as_slice() {
+        [open, close] => Some((open.as_str()?, close.as_str()?)),
+        _ => None,
+      });
+    lang.comment_tokens = CommentTokens::new(string("line_comment"), block);
+    lang.library = Some(library);
+    Ok(lang)
+  }
+}
+
+fn parse_pairs(
+  values: &[toml::Value],
+) -> Result<Vec<(char, char)>, anyhow::Error> {
+  values
+    .iter()
+    .map(|value| {
+      let chars = value.as_str().map(|s| s.chars().collect::<Vec<_>>());
+      match chars.as_deref() {
+        Some(&[open, close]) => Ok((open, close)),
+        _ => Err(anyhow::anyhow!(
+          "invalid pair {}, expected two chars",
+          value
+        )),
+      }
+    })
+    .collect()
+}
+
+/// User rules from the languages file, each mapping a file name, glob pattern,
+/// interpreter or extension to the name of a language.
+#[derive(Default)]
+struct Overrides {
+  filenames: Vec<(String, String)>,
+  globs: Vec<(String, String)>,
+  interpreters: Vec<(String, String)>,
+  extensions: Vec<(String, String)>,
+  pairs: Vec<(String, Vec<(char, char)>)>,
+}
+
+impl Overrides {
+  fn parse(source: &str) -> Result<Self, anyhow::Error> {
+    let value: toml::Value = source.parse()?;
+    let table = |key: &str| {
+      value
+        .get(key)
+        .and_then(|v| v.as_table())
+        .map(|table| {
+          table
+            .iter()
+            .filter_map(|(k, v)| Some((k.clone(), v.as_str()?.to_string())))
+            .collect()
+        })
+        .unwrap_or_default()
+    };
+
+    let mut pairs = vec![];
+    if let Some(table) = value.get("pairs").and_then(|v| v.as_table()) {
+      for (name, values) in table {
+        let values = values
+          .as_array()
+          .ok_or_else(|| anyhow::anyhow!("pairs of {} aren't a list", name))?;
+        pairs.push((name.clone(), parse_pairs(values)?));
+      }
+    }
+
+    Ok(Self {
+      filenames: table("filenames"),
+      globs: table("globs"),
+      interpreters: table("interpreters"),
+      extensions: table("extensions"),
+      pairs,
+    })
+  }
+}
+
+/// Languages known to the editor, those found in the grammars directory
+/// followed by those compiled in.
+pub struct Registry {
+  languages: Vec<Rc<Language>>,
+  overrides: Overrides,
+}
+
+impl Registry {
+  pub fn builtin() -> Self {
+    Self {
+      languages: builtin_languages(),
+      overrides: Overrides::default(),
+    }
+  }
+
+  pub fn new() -> Self {
+    let mut registry = Self::builtin();
+    let dir = shellexpand::tilde(GRAMMARS_DIR);
+    for err in registry.load_dir(Path::new(dir.as_ref())) {
+      log::error!("failed to load grammar: {:#}", err);
+    }
+
+    let path = shellexpand::tilde(LANGUAGES_FILE);
+    if let Ok(source) = std::fs::read_to_string(path.as_ref()) {
+      match Overrides::parse(&source) {
+        Ok(overrides) => registry.overrides = overrides,
+        Err(err) => log::error!("failed to read {}: {}", LANGUAGES_FILE, err),
+      }
+    }
+    registry.set_pairs();
+    registry
+  }
+
+  fn set_pairs(&mut self) {
+    for (name, pairs) in &self.overrides.pairs {
+      let language = self
+        .languages
+        .iter_mut()
+        .find(|l| l.is_named(name))
+        .and_then(Rc::get_mut);
+      match language {
+        Some(language) => language.auto_pairs = pairs.clone(),
+        None => log::warn!("no language {} to set the pairs of", name),
+      }
+    }
+  }
+
+  pub fn load_dir(&mut self, dir: &Path) -> Vec<anyhow::Error> {
+    let entries = match std::fs::read_dir(dir) {
+      Ok(entries) => entries,
+      Err(_) => return vec![],
+    };
+
+    let mut errors = vec![];
+    for path in entries.filter_map(|entry| entry.ok().map(|e| e.path())) {
+      if !path.is_dir() {
+        continue;
+      }
+      match Language::load(&path) {
+        Ok(language) => {
+          self.languages.retain(|l| l.name != language.name);
+          // runtime grammars take precedence for their extensions
+          self.languages.insert(0, Rc::new(language));
+        }
+        Err(err) => errors.push(err.context(format!("{:?}", path))),
+      }
+    }
+    errors
+  }
+
+  pub fn get(&self, name: &str) -> Option<&Rc<Language>> {
+    self.languages.iter().find(|l| l.is_named(name))
+  }
+
+  pub fn by_name(&self, name: &str) -> Option<Rc<Language>> {
+    self.get(name).cloned()
+  }
+
+  pub fn by_extension(&self, ext: &str) -> Option<Rc<Language>> {
+    self.find(|l| l.extensions.iter().any(|e| e == ext))
+  }
+
+  pub fn languages(&self) -> &[Rc<Language>] {
+    &self.languages
+  }
+
+  fn find(&self, f: impl Fn(&Language) -> bool) -> Option<Rc<Language>> {
+    self.languages.iter().find(|l| f(l)).cloned()
+  }
+
+  pub fn detect(
+    &self,
+    path: &Path,
+    text: &ropey::Rope,
+  ) -> Option<Rc<Language>> {
+    let overridden = |rules: &[(String, String)], f: &dyn Fn(&str) -> bool| {
+      rules
+        .iter()
+        .find(|(rule, _)| f(rule))
+        .and_then(|(_, name)| self.by_name(name))
+    };
+    let file_name = path
+      .file_name()
+      .map(|name| name.to_string_lossy().to_string())
+      .unwrap_or_default();
+
+    let is_name = |name: &str| name == file_name;
+    if let Some(language) = overridden(&self.overrides.filenames, &is_name)
+      .or_else(|| self.find(|l| l.filenames.iter().any(|n| is_name(n))))
+    {
+      return Some(language);
+    }
+
+    let matches = |glob: &str| detect::glob_matches(glob, &file_name);
+    if let Some(language) = overridden(&self.overrides.globs, &matches)
+      .or_else(|| self.find(|l| l.globs.iter().any(|g| matches(g))))
+    {
+      return Some(language);
+    }
+
+    let first_line = text.lines().next().map(|line| line.to_string());
+    if let Some(interpreter) =
+      first_line.and_then(|line| detect::shebang_interpreter(&line))
+    {
+      for interpreter in [&interpreter, detect::strip_version(&interpreter)] {
+        let is_interpreter = |name: &str| name == interpreter;
+        if let Some(language) =
+          overridden(&self.overrides.interpreters, &is_interpreter).or_else(
+            || self.find(|l| l.interpreters.iter().any(|i| i == interpreter)),
+          )
+        {
+          return Some(language);
+        }
+      }
+    }
+
+    // modelines are looked for in the first and last few lines
+    const MODELINE_LINES: usize = 5;
+    let len_lines = text.len_lines();
+    let last = len_lines.saturating_sub(MODELINE_LINES).max(MODELINE_LINES);
+    let modeline_lines = (0..len_lines.min(MODELINE_LINES))
+      .chain((last..len_lines).rev())
+      .map(|row| text.line(row).to_string());
+    if let Some(language) = detect::modeline_language(modeline_lines)
+      .and_then(|name| self.by_name(&name))
+    {
+      return Some(language);
+    }
+
+    let ext = path.extension()?.to_string_lossy();
+    overridden(&self.overrides.extensions, &|e| e == ext)
+      .or_else(|| self.by_extension(&ext))
+  }
+}
+
+fn builtin_languages() -> Vec<Rc<Language>> {
+  const CPP_TAGS: &str = include_str!("./tags/cpp.scm");
+  const JAVA_TAGS: &str = include_str!("./tags/java.scm");
+  const JAVASCRIPT_TAGS: &str = include_str!("./tags/javascript.scm");
+  const OCAML_TAGS: &str = include_str!("./tags/ocaml.scm");
+  const PYTHON_TAGS: &str = include_str!("./tags/python.scm");
+  const RUST_TAGS: &str = include_str!("./tags/rust.scm");
+  // typescript extends the javascript grammar and its tags
+  const TYPESCRIPT_TAGS: &str = concat!(
+    include_str!("./tags/javascript.scm"),
+    include_str!("./tags/typescript.scm")
+  );
+  const CPP_FOLDS: &str = include_str!("./folds/cpp.scm");
+  const JAVA_FOLDS: &str = include_str!("./folds/java.scm");
+  const JAVASCRIPT_FOLDS: &str = include_str!("./folds/javascript.scm");
+  const OCAML_FOLDS: &str = include_str!("./folds/ocaml.scm");
+  const PYTHON_FOLDS: &str = include_str!("./folds/python.scm");
+  const RUST_FOLDS: &str = include_str!("./folds/rust.scm");
+  const TYPESCRIPT_FOLDS: &str = concat!(
+    include_str!("./folds/javascript.scm"),
+    include_str!("./folds/typescript.scm")
+  );
+  const PYTHON_LOCALS: &str = include_str!("./locals/python.scm");
+  const RUST_LOCALS: &str = include_str!("./locals/rust.scm");
+  // the typescript locals only add the parameters to those of javascript
+  let typescript_locals = [
+    tree_sitter_javascript::LOCALS_QUERY,
+    tree_sitter_typescript::LOCALS_QUERY,
+  ]
+  .concat();
+
+  // single quotes are used for lifetimes and type variables
+  const NO_SINGLE_QUOTES: &[(char, char)] =
+    &[('(', ')'), ('[', ']'), ('{', '}'), ('"', '"')];
+  const JS_PAIRS: &[(char, char)] = &[
+    ('(', ')'),
+    ('[', ']'),
+    ('{', '}'),
+    ('"', '"'),
+    ('\'', '\''),
+    ('`', '`'),
+  ];
+
+  let languages = vec![
+    Language::new(
+      "cpp",
+      &["cpp", "cxx", "cc", "hpp", "hh"],
+      tree_sitter_cpp::language(),
+      tree_sitter_cpp::HIGHLIGHT_QUERY,
+      "",
+      "",
+    )
+    .and_then(|l| l.with_tags(CPP_TAGS))
+    .and_then(|l| l.with_folds(CPP_FOLDS))
+    .map(|l| Language {
+      aliases: strings(&["c++"]),
+      ..l
+    }),
+    Language::new(
+      "java",
+      &["java"],
+      tree_sitter_java::language(),
+      tree_sitter_java::HIGHLIGHT_QUERY,
+      "",
+      "",
+    )
+    .and_then(|l| l.with_tags(JAVA_TAGS))
+    .and_then(|l| l.with_folds(JAVA_FOLDS)),
+    Language::new(
+      "javascript",
+      &["js", "cjs", "mjs"],
+      tree_sitter_javascript::language(),
+      tree_sitter_javascript::HIGHLIGHT_QUERY,
+      tree_sitter_javascript::INJECTION_QUERY,
+      tree_sitter_javascript::LOCALS_QUERY,
+    )
+    .and_then(|l| l.with_tags(JAVASCRIPT_TAGS))
+    .and_then(|l| l.with_folds(JAVASCRIPT_FOLDS))
+    .map(|l| Language {
+      aliases: strings(&["js"]),
+      interpreters: strings(&["node", "nodejs"]),
+      auto_pairs: JS_PAIRS.to_vec(),
+      ..l
+    }),
+    Language::new(
+      "jsx",
+      &["jsx"],
+      tree_sitter_javascript::language(),
+      tree_sitter_javascript::JSX_HIGHLIGHT_QUERY,
+      tree_sitter_javascript::INJECTION_QUERY,
+      tree_sitter_javascript::LOCALS_QUERY,
+    )
+    .and_then(|l| l.with_tags(JAVASCRIPT_TAGS))
+    .and_then(|l| l.with_folds(JAVASCRIPT_FOLDS))
+    .map(|l| Language {
+      auto_pairs: JS_PAIRS.to_vec(),
+      ..l
+    }),
+    Language::new(
+      "ocaml",
+      &["ml"],
+      tree_sitter_ocaml::language_ocaml(),
+      tree_sitter_ocaml::HIGHLIGHT_QUERY,
+      "",
+      tree_sitter_ocaml::LOCALS_QUERY,
+    )
+    .and_then(|l| l.with_tags(OCAML_TAGS))
+    .and_then(|l| l.with_folds(OCAML_FOLDS))
+    .map(|l| Language {
+      aliases: strings(&["tuareg"]),
+      interpreters: strings(&["ocaml"]),
+      auto_pairs: NO_SINGLE_QUOTES.to_vec(),
+      comment_tokens: CommentTokens::ocaml(),
+      ..l
+    }),
+    Language::new(
+      "ocaml_interface",
+      &["mli"],
+      tree_sitter_ocaml::language_ocaml_interface(),
+      tree_sitter_ocaml::HIGHLIGHT_QUERY,
+      "",
+      tree_sitter_ocaml::LOCALS_QUERY,
+    )
+    .and_then(|l| l.with_tags(OCAML_TAGS))
+    .and_then(|l| l.with_folds(OCAML_FOLDS))
+    .map(|l| Language {
+      auto_pairs: NO_SINGLE_QUOTES.to_vec(),
+      comment_tokens: CommentTokens::ocaml(),
+      ..l
+    }),
+    Language::new(
+      "python",
+      &["py"],
+      tree_sitter_python::language(),
+      tree_sitter_python::HIGHLIGHT_QUERY,
+      "",
+      "",
+    )
+    .and_then(|l| l.with_tags(PYTHON_TAGS))
+    .and_then(|l| l.with_folds(PYTHON_FOLDS))
+    .and_then(|l| l.with_locals(PYTHON_LOCALS))
+    .map(|l| Language {
+      aliases: strings(&["py"]),
+      interpreters: strings(&["python"]),
+      comment_tokens: CommentTokens::hash(),
+      ..l
+    }),
+    Language::new(
+      "rust",
+      &["rs"],
+      tree_sitter_rust::language(),
+      tree_sitter_rust::HIGHLIGHT_QUERY,
+      "",
+      "",
+    )
+    .and_then(|l| l.with_tags(RUST_TAGS))
+    .and_then(|l| l.with_folds(RUST_FOLDS))
+    .and_then(|l| l.with_locals(RUST_LOCALS))
+    .map(|l| Language {
+      aliases: strings(&["rs"]),
+      auto_pairs: NO_SINGLE_QUOTES.to_vec(),
+      ..l
+    }),
+    Language::new(
+      "typescript",
+      &["ts"],
+      tree_sitter_typescript::language_typescript(),
+      tree_sitter_typescript::HIGHLIGHT_QUERY,
+      "",
+      tree_sitter_typescript::LOCALS_QUERY,
+    )
+    .and_then(|l| l.with_tags(TYPESCRIPT_TAGS))
+    .and_then(|l| l.with_folds(TYPESCRIPT_FOLDS))
+    .and_then(|l| l.with_locals(&typescript_locals))
+    .map(|l| Language {
+      aliases: strings(&["ts"]),
+      interpreters: strings(&["ts-node", "deno"]),
+      auto_pairs: JS_PAIRS.to_vec(),
+      ..l
+    }),
+    Language::new(
+      "tsx",
+      &["tsx"],
+      tree_sitter_typescript::language_tsx(),
+      tree_sitter_typescript::HIGHLIGHT_QUERY,
+      "",
+      tree_sitter_typescript::LOCALS_QUERY,
+    )
+    .and_then(|l| l.with_tags(TYPESCRIPT_TAGS))
+    .and_then(|l| l.with_folds(TYPESCRIPT_FOLDS))
+    .and_then(|l| l.with_locals(&typescript_locals))
+    .map(|l| Language {
+      auto_pairs: JS_PAIRS.to_vec(),
+      ..l
+    }),
+  ];
+
+  languages
+    .into_iter()
+    .map(|language| Rc::new(language.unwrap()))
+    .collect()
+}
+
+#[cfg(test)]
+pub fn parse_builtin(
+  name: &str,
+  source: &str,
+) -> (Rc<Language>, tree_sitter::Tree) {
+  let language = Registry::builtin().by_name(name).unwrap();
+  let mut parser = tree_sitter::Parser::new();
+  parser.set_language(language.highlight.language).unwrap();
+  let tree = parser.parse(source, None).unwrap();
+  (language, tree)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn names_contains_all_language_names() {
+    let names = HighlightNames::VARIANTS
+      .iter()
+      .map(|v| v.to_string())
+      .collect::<Vec<String>>();
+
+    for language in builtin_languages() {
+      let missing = language
+        .highlight
+        .names()
+        .iter()
+        .filter(|name| !names.contains(name))
+        .collect::<Vec<_>>();
+      assert!(
+        missing.is_empty(),
+        "Language '{}' doesnt have all names in Names struct. Missing are: {:?}",
+        language.name,
+        missing
+      );
+    }
+  }
+
+  #[test]
+  fn every_language_has_tags_and_folds() {
+    for language in builtin_languages() {
+      assert!(language.tags.is_some(), "no tags for {}", language.name);
+      assert!(language.folds.is_some(), "no folds for {}", language.name);
+    }
+  }
+
+  #[test]
+  fn finds_languages() {
+    let mut registry = Registry {
+      overrides: Overrides::parse("[filenames]\nBUILD = \"python\"").unwrap(),
+      ..Registry::builtin()
+    };
+    assert_eq!(
+      registry.by_extension("mli").unwrap().name,
+      "ocaml_interface"
+    );
+    assert_eq!(registry.by_extension("hpp").unwrap().name, "cpp");
+    assert!(registry.by_extension("go").is_none());
+    assert!(registry.load_dir(Path::new("/nonexistent")).is_empty());
+
+    let detect = |path: &str, text: &str| {
+      registry
+        .detect(Path::new(path), &ropey::Rope::from_str(text))
+        .map(|l| l.name.clone())
+    };
+    assert_eq!(detect("BUILD", "").as_deref(), Some("python"));
+    assert_eq!(
+      detect("run", "#!/usr/bin/env python3\n").as_deref(),
+      Some("python")
+    );
+    assert_eq!(
+      detect("a.h", "// -*- mode: C++ -*-\n").as_deref(),
+      Some("cpp")
+    );
+    assert_eq!(detect("main.rs", "").as_deref(), Some("rust"));
+    assert_eq!(detect("notes", "text\n"), None);
+  }
+
+  #[test]
+  fn sets_pairs() {
+    let mut registry = Registry {
+      overrides: Overrides::parse("[pairs]\nRS = [\"()\", \"<>\"]").unwrap(),
+      ..Registry::builtin()
+    };
+    registry.set_pairs();
+    let rust = registry.get("rust").unwrap();
+    assert_eq!(rust.auto_pairs, vec![('(', ')'), ('<', '>')]);
+    assert!(Overrides::parse("[pairs]\nrust = [\"(\"]").is_err());
+  }
+}
@@ -0,0 +1,231 @@
+use super::language::Language;
+use super::tags;
+use std::ops::Range;
+
+fn is_name(node: tree_sitter::Node, source: &[u8]) -> bool {
+  let text = &source[node.byte_range()];
+  node.is_named()
+    && node.child_count() == 0
+    && !node.kind().contains("literal")
+    && text
+      .first()
+      .map_or(false, |c| c.is_ascii_alphabetic() || *c == b'_')
+    && text.iter().all(|c| c.is_ascii_alphanumeric() || *c == b'_')
+}
+
+pub fn name_at(
+  tree: &tree_sitter::Tree,
+  text: &ropey::Rope,
+  char_idx: usize,
+) -> Option<(Range<usize>, String)> {
+  let source = text.bytes().collect::<Vec<u8>>();
+  let byte = text.char_to_byte(char_idx);
+  let mut candidates = vec![byte];
+  if byte > 0 {
+    candidates.push(byte - 1);
+  }
+  candidates.into_iter().find_map(|byte| {
+    let node = tree.root_node().descendant_for_byte_range(byte, byte)?;
+    is_name(node, &source).then(|| {
+      (
+        text.byte_to_char(node.start_byte())
+          ..text.byte_to_char(node.end_byte()),
+        String::from_utf8_lossy(&source[node.byte_range()]).into_owned(),
+      )
+    })
+  })
+}
+
+pub fn names(
+  tree: &tree_sitter::Tree,
+  text: &ropey::Rope,
+  name: &str,
+) -> Vec<Range<usize>> {
+  let source = text.bytes().collect::<Vec<u8>>();
+  let mut ranges = vec![];
+  let mut cursor = tree.walk();
+  loop {
+    let node = cursor.node();
+    if is_name(node, &source) && &source[node.byte_range()] == name.as_bytes() {
+      ranges.push(
+        text.byte_to_char(node.start_byte())
+          ..text.byte_to_char(node.end_byte()),
+      );
+    }
+    if cursor.goto_first_child() {
+      continue;
+    }
+    while !cursor.goto_next_sibling() {
+      if !cursor.goto_parent() {
+        return ranges;
+      }
+    }
+  }
+}
+
+/// Scopes and the names defined in them, as found by a locals query, in
+/// char ranges.
+#[derive(Debug, Default)]
+pub struct Locals {
+  scopes: Vec<Range<usize>>,
+  definitions: Vec<(String, Range<usize>, Option<usize>)>,
+}
+
+impl Locals {
+  pub fn new(
+    query: &tree_sitter::Query,
+    tree: &tree_sitter::Tree,
+    text: &ropey::Rope,
+  ) -> Self {
+    let source = text.bytes().collect::<Vec<u8>>();
+    let capture_names = query.capture_names();
+    let mut cursor = tree_sitter::QueryCursor::new();
+    let mut scopes = vec![];
+    let mut definitions = vec![];
+    for m in
+      cursor.matches(query, tree.root_node(), |node| &source[node.byte_range()])
+    {
+      for capture in m.captures {
+        let node = capture.node;
+        let range = text.byte_to_char(node.start_byte())
+          ..text.byte_to_char(node.end_byte());
+        match capture_names[capture.index as usize].as_str() {
+          "local.scope" => scopes.push(range),
+          "local.definition" => definitions.push((
+            String::from_utf8_lossy(&source[node.byte_range()]).into_owned(),
+            range,
+          )),
+          _ => {}
+        }
+      }
+    }
+    scopes.sort_by_key(|scope| (scope.start, scope.end));
+    scopes.dedup();
+
+    let mut locals = Self {
+      scopes,
+      definitions: vec![],
+    };
+    locals.definitions = definitions
+      .into_iter()
+      .map(|(name, range)| {
+        let scope = locals.innermost_scope(range.start);
+        (name, range, scope)
+      })
+      .collect();
+    locals
+  }
+
+  fn innermost_scope(&self, char_idx: usize) -> Option<usize> {
+    self
+      .scopes
+      .iter()
+      .enumerate()
+      .filter(|(_, scope)| scope.contains(&char_idx))
+      .min_by_key(|(_, scope)| scope.len())
+      .map(|(i, _)| i)
+  }
+
+  pub fn resolve(&self, name: &str, char_idx: usize) -> Option<Range<usize>> {
+    let mut enclosing = self
+      .scopes
+      .iter()
+      .enumerate()
+      .filter(|(_, scope)| scope.contains(&char_idx))
+      .collect::<Vec<_>>();
+    enclosing.sort_by_key(|(_, scope)| scope.len());
+    let scopes = enclosing.into_iter().map(|(i, _)| Some(i));
+
+    scopes.chain(std::iter::once(None)).find_map(|scope| {
+      let defined = self
+        .definitions
+        .iter()
+        .filter(|(n, _, s)| n == name && *s == scope)
+        .map(|(_, range, _)| range);
+      defined
+        .clone()
+        .filter(|range| range.start <= char_idx)
+        .last()
+        .or_else(|| defined.clone().next().filter(|_| scope.is_none()))
+        .cloned()
+    })
+  }
+}
+
+pub fn definition(
+  language: &Language,
+  tree: &tree_sitter::Tree,
+  text: &ropey::Rope,
+  name: &str,
+  range: Range<usize>,
+) -> Option<usize> {
+  let local = language.locals.as_ref().and_then(|query| {
+    Locals::new(query, tree, text).resolve(name, range.start)
+  });
+  local.map(|range| range.start).or_else(|| {
+    let query = language.tags.as_ref()?;
+    tags::symbols(query, tree, text)
+      .into_iter()
+      .find(|symbol| symbol.name == name)
+      .map(|symbol| symbol.name_start)
+  })
+}
+
+pub fn references(
+  language: &Language,
+  tree: &tree_sitter::Tree,
+  text: &ropey::Rope,
+  name: &str,
+  definition: Option<Range<usize>>,
+) -> Vec<Range<usize>> {
+  let locals = language
+    .locals
+    .as_ref()
+    .map(|query| Locals::new(query, tree, text))
+    .unwrap_or_default();
+  names(tree, text, name)
+    .into_iter()
+    .filter(|range| locals.resolve(name, range.start) == definition)
+    .collect()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::renderer::code_view::language::parse_builtin;
+
+  #[test]
+  fn resolves_local_definitions() {
+    let source = "fn a(x: u8) {\n  let y = x;\n  let x = y;\n  b(x);\n}\n";
+    let (language, tree) = parse_builtin("rust", source);
+    let text = ropey::Rope::from_str(source);
+
+    let at = |needle: &str, nth: usize| {
+      source.match_indices(needle).nth(nth).unwrap().0
+    };
+    // the parameter, then the shadowing let
+    assert_eq!(name_at(&tree, &text, at("x;", 0) + 1).unwrap().1, "x");
+    assert_eq!(
+      definition(&language, &tree, &text, "x", at("x;", 0)..at("x;", 0) + 1),
+      Some(5)
+    );
+    let b_x = at("x)", 0);
+    assert_eq!(
+      definition(&language, &tree, &text, "x", b_x..b_x + 1),
+      Some(at("x =", 0))
+    );
+    // a symbol of the tags query
+    assert_eq!(definition(&language, &tree, &text, "a", 0..0), Some(3));
+
+    let shadowing = at("x =", 0);
+    assert_eq!(
+      references(&language, &tree, &text, "x", Some(shadowing..shadowing + 1)),
+      vec![shadowing..shadowing + 1, b_x..b_x + 1]
+    );
+    let b = at("b(", 0);
+    assert_eq!(
+      references(&language, &tree, &text, "b", None),
+      vec![b..b + 1]
+    );
+  }
+}
@@ -0,0 +1,42 @@
+; Scopes
+;-------
+
+[
+  (function_definition)
+  (lambda)
+  (list_comprehension)
+  (dictionary_comprehension)
+  (set_comprehension)
+] @local.scope
+
+; Definitions
+;------------
+
+(parameters
+  (identifier) @local.definition)
+
+(default_parameter
+  name: (identifier) @local.definition)
+
+(typed_parameter
+  (identifier) @local.definition)
+
+(typed_default_parameter
+  name: (identifier) @local.definition)
+
+(lambda_parameters
+  (identifier) @local.definition)
+
+(assignment
+  left: (identifier) @local.definition)
+
+(for_statement
+  left: (identifier) @local.definition)
+
+(for_in_clause
+  left: (identifier) @local.definition)
+
+; References
+;-----------
+
+(identifier) @local.reference
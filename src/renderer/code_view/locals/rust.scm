@@ -0,0 +1,35 @@
+; Scopes
+;-------
+
+[
+  (block)
+  (function_item)
+  (closure_expression)
+  (for_expression)
+  (match_arm)
+  (if_let_expression)
+  (while_let_expression)
+] @local.scope
+
+; Definitions
+;------------
+
+(parameter
+  pattern: (identifier) @local.definition)
+
+(closure_parameters
+  (identifier) @local.definition)
+
+(let_declaration
+  pattern: (identifier) @local.definition)
+
+(for_expression
+  pattern: (identifier) @local.definition)
+
+(tuple_pattern
+  (identifier) @local.definition)
+
+; References
+;-----------
+
+(identifier) @local.reference
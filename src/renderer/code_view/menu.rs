@@ -0,0 +1,146 @@
+use crate::renderer::input::max_line_length;
+use crate::renderer::rectangle::Rectangle;
+use crate::renderer::theme;
+use crate::renderer::Dimensions;
+use std::rc::Rc;
+use wgpu::util::StagingBelt;
+use wgpu::{CommandEncoder, Device, TextureView};
+use wgpu_glyph::ab_glyph::FontArc;
+use wgpu_glyph::{GlyphBrush, Section, Text};
+use winit::dpi::PhysicalSize;
+
+const MENU_PADDING: f32 = 5.0;
+/// Entries the menu shows at once, the others are scrolled to.
+pub const MENU_ROWS: usize = 10;
+
+/// Popup under the cursor listing what can be done there, such as the code
+/// actions of a language server, to pick one of.
+pub struct Menu {
+  font_height: f32,
+  entries: Vec<String>,
+  selected: usize,
+  first: usize,
+  rect: Rectangle,
+  selection_rect: Rectangle,
+}
+
+impl Menu {
+  pub fn new(
+    device: &Rc<wgpu::Device>,
+    screen_size: PhysicalSize<f32>,
+    font: FontArc,
+    font_height: f32,
+    entries: Vec<String>,
+    anchor: Dimensions,
+  ) -> Self {
+    let ui = theme::current().ui;
+    let width = max_line_length(entries.iter().cloned(), font, font_height)
+      + 2.0 * MENU_PADDING;
+    let width = width.min(screen_size.width / 2.0);
+    let height = entries.len().min(MENU_ROWS) as f32 * font_height;
+    let below = anchor.y + anchor.height;
+    let y = if below + height > screen_size.height {
+      (anchor.y - height).max(0.0)
+    } else {
+      below
+    };
+    let dimensions = Dimensions {
+      x: anchor.x.min(screen_size.width - width).max(0.0),
+      y,
+      width,
+      height,
+    };
+    let rect = |color| {
+      Rectangle::new(device, screen_size, dimensions, theme::rgb(color), None)
+    };
+    let mut menu = Self {
+      font_height,
+      entries,
+      selected: 0,
+      first: 0,
+      rect: rect(ui.completion_background),
+      selection_rect: rect(ui.completion_selection),
+    };
+    menu.select(screen_size, 0);
+    menu
+  }
+
+  pub fn select(&mut self, screen_size: PhysicalSize<f32>, delta: isize) {
+    let len = self.entries.len() as isize;
+    if len == 0 {
+      return;
+    }
+    let selected = self.selected as isize + delta;
+    self.selected = if selected < 0 && self.selected == 0 {
+      len - 1
+    } else if selected >= len && self.selected as isize == len - 1 {
+      0
+    } else {
+      selected.max(0).min(len - 1)
+    } as usize;
+    if self.selected < self.first {
+      self.first = self.selected;
+    } else if self.selected >= self.first + MENU_ROWS {
+      self.first = self.selected + 1 - MENU_ROWS;
+    }
+    let list = self.rect.dimensions;
+    let row = (self.selected - self.first) as f32;
+    self.selection_rect.resize(
+      screen_size,
+      Dimensions {
+        y: list.y + row * self.font_height,
+        height: self.font_height,
+        ..list
+      },
+    );
+  }
+
+  pub fn selected(&self) -> usize {
+    self.selected
+  }
+
+  pub fn redraw_overlay(
+    &mut self,
+    glyph_brush: &mut GlyphBrush<()>,
+    device: &Device,
+    staging_belt: &mut StagingBelt,
+    encoder: &mut CommandEncoder,
+    target: &TextureView,
+    size: PhysicalSize<u32>,
+  ) {
+    let color = theme::current().ui.foreground;
+    let list = self.rect.dimensions;
+    for (row, entry) in self
+      .entries
+      .iter()
+      .skip(self.first)
+      .take(MENU_ROWS)
+      .enumerate()
+    {
+      glyph_brush.queue(Section {
+        screen_position: (
+          list.x + MENU_PADDING,
+          list.y + row as f32 * self.font_height,
+        ),
+        text: vec![Text::new(entry)
+          .with_color(color)
+          .with_scale(self.font_height)],
+        ..Section::default()
+      });
+    }
+    glyph_brush
+      .draw_queued_with_transform_and_scissoring(
+        device,
+        staging_belt,
+        encoder,
+        target,
+        wgpu_glyph::orthographic_projection(size.width, size.height),
+        list.into(),
+      )
+      .unwrap();
+  }
+
+  pub fn get_overlay_rects(&self) -> Vec<&Rectangle> {
+    vec![&self.rect, &self.selection_rect]
+  }
+}
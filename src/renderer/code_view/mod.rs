@@ -1,35 +1,88 @@
+use crate::lsp::completion::{CompletionItem, Snippet};
+use crate::lsp::hover::SignatureHelp;
+use crate::lsp::{Diagnostic, TextEdit};
+use crate::renderer::input::word_range;
 use crate::renderer::rectangle::Rectangle;
 use crate::renderer::Dimensions;
+use crate::renderer::RenderElement;
 use std::cell::RefCell;
-use std::path::Path;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
 use std::rc::Rc;
+use wgpu::util::StagingBelt;
+use wgpu::{CommandEncoder, TextureView};
 use wgpu_glyph::ab_glyph::FontArc;
-use winit::dpi::PhysicalSize;
-use winit::event::VirtualKeyCode;
+use wgpu_glyph::GlyphBrush;
+use winit::dpi::{PhysicalPosition, PhysicalSize};
+use winit::event::{ModifiersState, VirtualKeyCode};
 
 mod code;
+mod completion;
+mod detect;
+pub mod fold;
 mod gutter;
-mod highlight;
+pub mod highlight;
+mod inspector;
+pub mod language;
+pub mod locals;
+mod menu;
+mod outline;
+mod sticky;
+mod syntax_selection;
+pub mod tags;
+mod tooltip;
+
+/// Part of the view's width taken by the outline.
+const OUTLINE_WIDTH: f32 = 0.2;
 
 pub struct CodeView {
-  #[allow(dead_code)]
+  device: Rc<wgpu::Device>,
+  font: FontArc,
+  font_height: f32,
+  path: PathBuf,
+  server_path: PathBuf,
   text: Rc<RefCell<ropey::Rope>>,
+  diagnostics: Rc<RefCell<Vec<Diagnostic>>>,
   gutter: gutter::Gutter,
   code: code::Code,
+  outline: outline::Outline,
+  outline_shown: bool,
+  symbols_of: Option<usize>,
+  inspector: Option<inspector::Inspector>,
+  completion: Option<completion::Completion>,
+  mouse: PhysicalPosition<f64>,
+  hovered: Option<Range<usize>>,
+  hover: Option<tooltip::Tooltip>,
+  signature: Option<tooltip::Tooltip>,
+  menu: Option<menu::Menu>,
+  chosen: Option<usize>,
+  tabbed: bool,
+  alt_shortcut: bool,
+  registry: Rc<language::Registry>,
   pub dimensions: Dimensions,
 }
 
 impl CodeView {
+  #[allow(clippy::too_many_arguments)]
   pub fn new(
-    device: &wgpu::Device,
+    device: &Rc<wgpu::Device>,
     screen_size: PhysicalSize<f32>,
     font: FontArc,
     font_height: f32,
     dimensions: Dimensions,
     text: ropey::Rope,
     path: &Path,
+    registry: &Rc<language::Registry>,
+    sticky_scroll_depth: usize,
   ) -> Self {
+    let language = registry.detect(path, &text);
     let text = Rc::new(RefCell::new(text));
+    let folds = Rc::new(RefCell::new(fold::Folds::default()));
+    let sticky = Rc::new(RefCell::new(sticky::Sticky {
+      depth: sticky_scroll_depth,
+      ..Default::default()
+    }));
+    let diagnostics = Rc::new(RefCell::new(vec![]));
 
     let gutter = gutter::Gutter::new(
       device,
@@ -38,28 +91,530 @@ impl CodeView {
       screen_size,
       dimensions,
       Rc::clone(&text),
+      Rc::clone(&folds),
+      Rc::clone(&sticky),
+      Rc::clone(&diagnostics),
+    );
+
+    let outline_width = dimensions.width * OUTLINE_WIDTH;
+    let outline = outline::Outline::new(
+      device,
+      screen_size,
+      font_height,
+      Dimensions {
+        x: dimensions.x + dimensions.width - outline_width,
+        width: outline_width,
+        ..dimensions
+      },
     );
 
     let code = code::Code::new(
       device,
       screen_size,
-      font,
+      font.clone(),
       font_height,
       Dimensions {
         x: dimensions.x + gutter.dimensions.width,
-        width: dimensions.width - gutter.dimensions.width,
+        width: dimensions.width - gutter.dimensions.width - outline_width,
         ..dimensions
       },
       Rc::clone(&text),
-      highlight::config_from_extension(path.extension()),
+      language
+        .map(|language| highlight::Config::new(language, Rc::clone(registry))),
+      folds,
+      sticky,
+      Rc::clone(&diagnostics),
     );
 
-    Self {
+    let mut code_view = Self {
+      device: Rc::clone(device),
+      font,
+      font_height,
+      path: path.to_path_buf(),
+      server_path: path.canonicalize().unwrap_or_else(|_| path.to_path_buf()),
       text,
+      diagnostics,
       gutter,
       code,
+      outline,
+      outline_shown: true,
+      symbols_of: None,
+      inspector: None,
+      completion: None,
+      mouse: PhysicalPosition { x: 0.0, y: 0.0 },
+      hovered: None,
+      hover: None,
+      signature: None,
+      menu: None,
+      chosen: None,
+      tabbed: false,
+      alt_shortcut: false,
+      registry: Rc::clone(registry),
       dimensions,
+    };
+    code_view.update_panels(screen_size);
+    code_view
+  }
+}
+
+impl CodeView {
+  pub fn path(&self) -> &Path {
+    &self.path
+  }
+
+  pub fn server_path(&self) -> &Path {
+    &self.server_path
+  }
+
+  pub fn take_changes(&mut self) -> Vec<TextEdit> {
+    self.code.take_changes()
+  }
+
+  pub fn text(&self) -> ropey::Rope {
+    self.text.borrow().clone()
+  }
+
+  pub fn tree(&self) -> Option<&tree_sitter::Tree> {
+    self.code.tree()
+  }
+
+  pub fn symbols(&self) -> &[tags::Symbol] {
+    self.outline.symbols()
+  }
+
+  pub fn cursor_index(&self) -> usize {
+    self.code.cursor_index()
+  }
+
+  pub fn selection_range(&self) -> Option<Range<usize>> {
+    self.code.selection_range()
+  }
+
+  pub fn save(&self) -> std::io::Result<()> {
+    crate::lsp::workspace_edit::write_file(&self.path, &self.text.borrow())
+  }
+
+  pub fn apply_edits(
+    &mut self,
+    screen_size: PhysicalSize<f32>,
+    edits: &[TextEdit],
+  ) {
+    self.code.apply_edits(screen_size, edits, None);
+    self.update_panels(screen_size);
+    self.update_completion(screen_size);
+  }
+
+  pub fn language(&self) -> Option<&Rc<language::Language>> {
+    self.code.language()
+  }
+
+  pub fn set_language(
+    &mut self,
+    screen_size: PhysicalSize<f32>,
+    language: Option<Rc<language::Language>>,
+  ) {
+    let config = language.map(|language| {
+      highlight::Config::new(language, Rc::clone(&self.registry))
+    });
+    self.code.set_highlight_config(screen_size, config);
+    self.update_panels(screen_size);
+  }
+
+  pub fn set_diagnostics(
+    &mut self,
+    screen_size: PhysicalSize<f32>,
+    diagnostics: Vec<Diagnostic>,
+  ) {
+    *self.diagnostics.borrow_mut() = diagnostics;
+    self.code.update_diagnostics(screen_size);
+  }
+
+  pub fn show_completions(
+    &mut self,
+    screen_size: PhysicalSize<f32>,
+    char_idx: usize,
+    items: Vec<CompletionItem>,
+  ) {
+    let text = self.text.borrow();
+    let char_idx = char_idx.min(text.len_chars());
+    let start = word_range(&text, char_idx)
+      .map_or(char_idx, |range| range.start.min(char_idx));
+    let listed = text.clone();
+    drop(text);
+    self.completion = Some(completion::Completion::new(
+      &self.device,
+      screen_size,
+      self.font.clone(),
+      self.font_height,
+      items,
+      start,
+      listed,
+      self.code.cursor_dimensions(),
+    ));
+    self.update_completion(screen_size);
+  }
+
+  pub fn completing(&self) -> bool {
+    self.completion.is_some()
+  }
+
+  fn update_completion(&mut self, screen_size: PhysicalSize<f32>) {
+    let completion = match &mut self.completion {
+      Some(completion) => completion,
+      None => return,
+    };
+    let cursor = self.code.cursor_index();
+    if cursor < completion.start {
+      self.completion = None;
+      return;
     }
+    let typed = self
+      .text
+      .borrow()
+      .slice(completion.start..cursor)
+      .to_string();
+    let in_word = typed.chars().all(|c| c.is_alphanumeric() || c == '_');
+    let anchor = self.code.cursor_dimensions();
+    if !in_word || !completion.filter(screen_size, &typed, anchor) {
+      self.completion = None;
+    }
+  }
+
+  fn accept_completion(&mut self, screen_size: PhysicalSize<f32>) {
+    let completion = match self.completion.take() {
+      Some(completion) => completion,
+      None => return,
+    };
+    let item = match completion.selected() {
+      Some(item) => item,
+      None => return,
+    };
+    let cursor = self.code.cursor_index();
+    let text = self.text.borrow();
+    let listed = &completion.text;
+    let after = text.len_chars() - cursor;
+    let unchanged = completion.start <= cursor
+      && completion.start + after <= listed.len_chars()
+      && text.slice(..completion.start) == listed.slice(..completion.start)
+      && text.slice(cursor..) == listed.slice(listed.len_chars() - after..);
+    drop(text);
+    let (range, additional_edits) = if unchanged {
+      (item.range.clone(), item.additional_edits.clone())
+    } else {
+      (None, vec![])
+    };
+    let start = range
+      .map_or(completion.start, |range| range.start)
+      .min(cursor);
+    let (new_text, stops) = if item.snippet {
+      let snippet = Snippet::parse(&item.insert_text);
+      let stops = snippet.tab_order();
+      (snippet.text, stops)
+    } else {
+      let len = item.insert_text.chars().count();
+      (item.insert_text.clone(), vec![len..len])
+    };
+    let selection = stops[0].clone();
+    let mut edits = additional_edits;
+    edits.push(TextEdit {
+      range: start..cursor,
+      new_text,
+    });
+    let i = edits.len() - 1;
+    self
+      .code
+      .apply_edits(screen_size, &edits, Some((i, selection.clone())));
+    // the snippet starts where the first placeholder is selected from
+    let start = self.code.cursor_index().saturating_sub(selection.end);
+    let stops = stops
+      .into_iter()
+      .map(|stop| start + stop.start..start + stop.end)
+      .collect();
+    self.code.start_snippet(stops);
+    self.update_panels(screen_size);
+  }
+
+  pub fn hovered(&self) -> Option<Range<usize>> {
+    self.hovered.clone()
+  }
+
+  pub fn word_at_cursor(&self) -> Option<Range<usize>> {
+    word_range(&self.text.borrow(), self.code.cursor_index())
+  }
+
+  pub fn show_hover(
+    &mut self,
+    screen_size: PhysicalSize<f32>,
+    range: Range<usize>,
+    contents: Option<String>,
+  ) {
+    self.hover = contents.map(|contents| {
+      let lines = tooltip::markdown(&contents, self.language(), &self.registry);
+      tooltip::Tooltip::new(
+        &self.device,
+        screen_size,
+        &self.font,
+        self.font_height,
+        lines,
+        range.clone(),
+        self.code.char_dimensions(range.start),
+      )
+    });
+  }
+
+  pub fn show_message(
+    &mut self,
+    screen_size: PhysicalSize<f32>,
+    message: &str,
+  ) {
+    let mut line = vec![];
+    tooltip::push(&mut line, message, tooltip::Format::Plain);
+    let cursor = self.code.cursor_index();
+    self.hover = Some(tooltip::Tooltip::new(
+      &self.device,
+      screen_size,
+      &self.font,
+      self.font_height,
+      vec![line],
+      cursor..cursor,
+      self.code.cursor_dimensions(),
+    ));
+  }
+
+  pub fn has_signature(&self) -> bool {
+    self.signature.is_some()
+  }
+
+  pub fn show_signature(
+    &mut self,
+    screen_size: PhysicalSize<f32>,
+    help: Option<SignatureHelp>,
+  ) {
+    self.signature = help.map(|help| {
+      let chars = help.label.chars().count();
+      let parameter = help.parameter.clone().unwrap_or(chars..chars);
+      let byte = |char_idx: usize| {
+        help
+          .label
+          .char_indices()
+          .nth(char_idx)
+          .map_or(help.label.len(), |(at, _)| at)
+      };
+      let (start, end) = (
+        byte(parameter.start),
+        byte(parameter.end.max(parameter.start)),
+      );
+      let mut label = vec![];
+      tooltip::push(&mut label, &help.label[..start], tooltip::Format::Plain);
+      let format = tooltip::Format::Syntax(Some(
+        highlight::HighlightNames::VariableParameter,
+      ));
+      tooltip::push(&mut label, &help.label[start..end], format);
+      tooltip::push(&mut label, &help.label[end..], tooltip::Format::Plain);
+      let mut lines = vec![label];
+      if let Some(documentation) = &help.documentation {
+        lines.push(vec![]);
+        lines.extend(tooltip::markdown(
+          documentation,
+          self.language(),
+          &self.registry,
+        ));
+      }
+      let cursor = self.code.cursor_index();
+      tooltip::Tooltip::new(
+        &self.device,
+        screen_size,
+        &self.font,
+        self.font_height,
+        lines,
+        cursor..cursor,
+        self.code.cursor_dimensions(),
+      )
+    });
+  }
+
+  pub fn show_menu(
+    &mut self,
+    screen_size: PhysicalSize<f32>,
+    entries: Vec<String>,
+  ) {
+    self.menu = Some(entries).filter(|e| !e.is_empty()).map(|entries| {
+      menu::Menu::new(
+        &self.device,
+        screen_size,
+        self.font.clone(),
+        self.font_height,
+        entries,
+        self.code.cursor_dimensions(),
+      )
+    });
+  }
+
+  pub fn take_chosen(&mut self) -> Option<usize> {
+    self.chosen.take()
+  }
+
+  fn close_popups(&mut self) {
+    self.completion = None;
+    self.hover = None;
+    self.signature = None;
+    self.menu = None;
+  }
+
+  pub fn get_overlay_rects(&self) -> Vec<&Rectangle> {
+    let mut vec = vec![];
+    for tooltip in self.hover.iter().chain(&self.signature) {
+      vec.extend(tooltip.get_overlay_rects());
+    }
+    if let Some(completion) = &self.completion {
+      vec.extend(completion.get_overlay_rects());
+    }
+    if let Some(menu) = &self.menu {
+      vec.extend(menu.get_overlay_rects());
+    }
+    vec
+  }
+
+  pub fn redraw_overlay(
+    &mut self,
+    glyph_brush: &mut GlyphBrush<()>,
+    device: &wgpu::Device,
+    staging_belt: &mut StagingBelt,
+    encoder: &mut CommandEncoder,
+    target: &TextureView,
+    size: PhysicalSize<u32>,
+  ) {
+    for tooltip in self.hover.iter_mut().chain(&mut self.signature) {
+      tooltip.redraw_overlay(
+        glyph_brush,
+        device,
+        staging_belt,
+        encoder,
+        target,
+        size,
+      );
+    }
+    if let Some(completion) = &mut self.completion {
+      completion.redraw_overlay(
+        glyph_brush,
+        device,
+        staging_belt,
+        encoder,
+        target,
+        size,
+      );
+    }
+    if let Some(menu) = &mut self.menu {
+      menu.redraw_overlay(
+        glyph_brush,
+        device,
+        staging_belt,
+        encoder,
+        target,
+        size,
+      );
+    }
+  }
+
+  fn toggle_inspector(&mut self, screen_size: PhysicalSize<f32>) {
+    self.inspector = match self.inspector {
+      Some(_) => None,
+      None => Some(inspector::Inspector::new(
+        &self.device,
+        screen_size,
+        self.font_height,
+        self.outline.dimensions,
+      )),
+    };
+    self.update_panels(screen_size);
+  }
+
+  fn toggle_outline(&mut self, screen_size: PhysicalSize<f32>) {
+    self.outline_shown = !self.outline_shown;
+    self.update_panels(screen_size);
+  }
+
+  fn panel_width(&self) -> f32 {
+    let outline = self.outline_shown && !self.outline.symbols().is_empty();
+    if self.inspector.is_some() || outline {
+      self.dimensions.width * OUTLINE_WIDTH
+    } else {
+      0.0
+    }
+  }
+
+  fn layout(&mut self, screen_size: PhysicalSize<f32>) {
+    let panel_width = self.panel_width();
+    self.outline.dimensions = Dimensions {
+      x: self.dimensions.x + self.dimensions.width - panel_width,
+      width: panel_width,
+      ..self.dimensions
+    };
+    if let Some(inspector) = &mut self.inspector {
+      inspector.dimensions = self.outline.dimensions;
+    }
+    self.code.dimensions.width =
+      self.dimensions.width - self.gutter.dimensions.width - panel_width;
+
+    for element in self.get_elements() {
+      element.resize(screen_size);
+    }
+  }
+
+  fn update_panels(&mut self, screen_size: PhysicalSize<f32>) {
+    let tree_id = self.code.tree_id();
+    if self.symbols_of != Some(tree_id) {
+      self.symbols_of = Some(tree_id);
+      let query = self.language().and_then(|language| language.tags.as_ref());
+      let symbols = match (query, self.code.tree()) {
+        (Some(query), Some(tree)) => {
+          tags::symbols(query, tree, &self.text.borrow())
+        }
+        _ => vec![],
+      };
+      self.outline.set_symbols(symbols);
+    }
+    if (self.panel_width() - self.outline.dimensions.width).abs() > 0.5 {
+      self.layout(screen_size);
+    }
+    let char_idx = self.code.cursor_index();
+    self.outline.mark(screen_size, char_idx);
+
+    let matches = match &mut self.inspector {
+      Some(inspector) => {
+        inspector.update(
+          screen_size,
+          self.code.tree_id(),
+          self.code.tree(),
+          &self.text.borrow(),
+          self.code.highlights(),
+          char_idx,
+        );
+        inspector.matches().to_vec()
+      }
+      None => vec![],
+    };
+    self.code.set_query_matches(screen_size, matches);
+  }
+
+  pub fn go_to(&mut self, screen_size: PhysicalSize<f32>, char_idx: usize) {
+    self.code.go_to(screen_size, char_idx);
+    let offset = self.code.cursor_scroll_offset();
+    self.scroll_by(screen_size, offset);
+  }
+
+  fn scroll_to_row(&mut self, screen_size: PhysicalSize<f32>, row: usize) {
+    let char_idx = self.text.borrow().line_to_char(row);
+    self.code.go_to(screen_size, char_idx);
+    let offset = self.code.row_scroll_offset(row);
+    self.scroll_by(screen_size, offset);
+  }
+
+  fn scroll_by(&mut self, screen_size: PhysicalSize<f32>, y: f64) {
+    let offset = PhysicalPosition { x: 0.0, y };
+    self.gutter.scroll(offset, screen_size);
+    self.code.scroll(offset, screen_size);
+    self.update_panels(screen_size);
   }
 }
 
@@ -68,25 +623,263 @@ impl super::input::TextInput for CodeView {
     &mut self,
     screen_size: PhysicalSize<f32>,
     key: VirtualKeyCode,
+    modifiers: ModifiersState,
   ) {
-    self.code.input_special(screen_size, key);
+    self.tabbed = false;
+    self.alt_shortcut = modifiers.alt()
+      && modifiers.shift()
+      && !modifiers.ctrl()
+      && ((VirtualKeyCode::A as u32..=VirtualKeyCode::Z as u32)
+        .contains(&(key as u32))
+        || (VirtualKeyCode::Key1 as u32..=VirtualKeyCode::Key9 as u32)
+          .contains(&(key as u32))
+        || matches!(key, VirtualKeyCode::LBracket | VirtualKeyCode::RBracket));
+    if modifiers.ctrl() && modifiers.shift() && key == VirtualKeyCode::E {
+      self.toggle_inspector(screen_size);
+      return;
+    }
+    if modifiers.ctrl() && modifiers.shift() && key == VirtualKeyCode::O {
+      self.toggle_outline(screen_size);
+      return;
+    }
+    if let Some(menu) = &mut self.menu {
+      let rows = menu::MENU_ROWS as isize;
+      match key {
+        VirtualKeyCode::Up => return menu.select(screen_size, -1),
+        VirtualKeyCode::Down => return menu.select(screen_size, 1),
+        VirtualKeyCode::PageUp => return menu.select(screen_size, -rows),
+        VirtualKeyCode::PageDown => return menu.select(screen_size, rows),
+        VirtualKeyCode::Escape => {
+          self.menu = None;
+          return;
+        }
+        _ => self.menu = None,
+      }
+    }
+    if let Some(completion) = &mut self.completion {
+      let rows = completion::COMPLETION_ROWS as isize;
+      match key {
+        VirtualKeyCode::Up => return completion.select(screen_size, -1),
+        VirtualKeyCode::Down => return completion.select(screen_size, 1),
+        VirtualKeyCode::PageUp => return completion.select(screen_size, -rows),
+        VirtualKeyCode::PageDown => {
+          return completion.select(screen_size, rows)
+        }
+        VirtualKeyCode::Escape => {
+          self.completion = None;
+          return;
+        }
+        _ => {}
+      }
+    }
+    if key == VirtualKeyCode::Escape
+      && (self.hover.is_some() || self.signature.is_some())
+    {
+      self.hover = None;
+      self.signature = None;
+      return;
+    }
+    match &mut self.inspector {
+      Some(inspector) if inspector.focused => inspector.input_special(key),
+      _ if key == VirtualKeyCode::Tab
+        && self.completion.is_none()
+        && self.code.step_snippet(screen_size, !modifiers.shift()) =>
+      {
+        self.tabbed = true;
+        self.update_panels(screen_size);
+      }
+      _ => {
+        self.code.input_special(screen_size, key, modifiers);
+        self.update_panels(screen_size);
+        self.update_completion(screen_size);
+      }
+    }
   }
 
   fn input_char(&mut self, screen_size: PhysicalSize<f32>, ch: char) {
-    self.code.input_char(screen_size, ch);
+    if ch == '\t' && std::mem::take(&mut self.tabbed) {
+      return;
+    }
+    if !ch.is_control() && std::mem::take(&mut self.alt_shortcut) {
+      return;
+    }
+    if let Some(menu) = self.menu.take() {
+      if ch == '\r' {
+        self.chosen = Some(menu.selected());
+        return;
+      }
+    }
+    match &mut self.inspector {
+      Some(inspector) if inspector.focused => {
+        if inspector.input_char(ch) {
+          self.update_panels(screen_size);
+        }
+      }
+      _ if self.completion.is_some() && matches!(ch, '\r' | '\t') => {
+        self.accept_completion(screen_size);
+      }
+      _ => {
+        self.hover = None;
+        self.code.input_char(screen_size, ch);
+        self.update_panels(screen_size);
+        self.update_completion(screen_size);
+      }
+    }
   }
 }
 
 impl super::RenderElement for CodeView {
+  fn resize(&mut self, screen_size: PhysicalSize<f32>) {
+    self.close_popups();
+    self.dimensions.width = screen_size.width - self.dimensions.x;
+    self.layout(screen_size);
+  }
+
+  fn click(
+    &mut self,
+    position: PhysicalPosition<f64>,
+    screen_size: PhysicalSize<f32>,
+    modifiers: ModifiersState,
+  ) {
+    self.close_popups();
+    self.mouse = position;
+    let gutter_width = self.gutter.dimensions.width as f64;
+    let outline_x = (self.outline.dimensions.x - self.dimensions.x) as f64;
+    let on_query = position.x >= outline_x
+      && self
+        .inspector
+        .as_ref()
+        .map_or(false, |inspector| inspector.is_query_at(position.y));
+    if let Some(inspector) = &mut self.inspector {
+      inspector.focused = on_query;
+    }
+    if on_query {
+      return;
+    }
+
+    if position.x < gutter_width {
+      let row = self.code.row_at(position.y);
+      self.code.toggle_fold(screen_size, row);
+    } else if let Some(row) = self
+      .code
+      .sticky_row_at(position.y)
+      .filter(|_| position.x < outline_x)
+    {
+      self.scroll_to_row(screen_size, row);
+    } else if position.x >= outline_x {
+      let inspected = self.inspector.as_ref().map(|inspector| {
+        inspector.row_at(position.y).map(|row| row.range.start)
+      });
+      if let Some(char_idx) = inspected {
+        if let Some(char_idx) = char_idx {
+          self.go_to(screen_size, char_idx);
+        }
+      } else if let Some(symbol) = self.outline.symbol_at(position.y) {
+        let char_idx = symbol.name_start;
+        self.go_to(screen_size, char_idx);
+      }
+    } else {
+      let position = PhysicalPosition {
+        x: position.x - gutter_width,
+        ..position
+      };
+      self.code.click(position, screen_size, modifiers);
+    }
+    self.update_panels(screen_size);
+  }
+
+  fn drag(
+    &mut self,
+    position: PhysicalPosition<f64>,
+    screen_size: PhysicalSize<f32>,
+    modifiers: ModifiersState,
+  ) {
+    self.mouse = position;
+    let gutter_width = self.gutter.dimensions.width as f64;
+    let outline_x = (self.outline.dimensions.x - self.dimensions.x) as f64;
+    if position.x >= gutter_width && position.x < outline_x {
+      let position = PhysicalPosition {
+        x: position.x - gutter_width,
+        ..position
+      };
+      self.code.drag(position, screen_size, modifiers);
+      self.update_panels(screen_size);
+    }
+  }
+
+  fn scroll(
+    &mut self,
+    offset: PhysicalPosition<f64>,
+    screen_size: PhysicalSize<f32>,
+  ) {
+    self.close_popups();
+    let outline_x = (self.outline.dimensions.x - self.dimensions.x) as f64;
+    if self.mouse.x < outline_x {
+      self.gutter.scroll(offset, screen_size);
+      self.code.scroll(offset, screen_size);
+    } else if let Some(inspector) = &mut self.inspector {
+      inspector.scroll(offset, screen_size);
+    } else {
+      self.outline.scroll(offset, screen_size);
+    }
+  }
+
+  fn hover(
+    &mut self,
+    position: PhysicalPosition<f64>,
+    _screen_size: PhysicalSize<f32>,
+  ) -> bool {
+    self.mouse = position;
+    let gutter_width = self.gutter.dimensions.width as f64;
+    let outline_x = (self.outline.dimensions.x - self.dimensions.x) as f64;
+    let char_idx = Some(position)
+      .filter(|position| position.x >= gutter_width && position.x < outline_x)
+      .and_then(|position| {
+        self.code.char_at(PhysicalPosition {
+          x: position.x - gutter_width,
+          ..position
+        })
+      });
+    let text = self.text.borrow();
+    let hovered = char_idx
+      .filter(|&char_idx| {
+        let c = text.char(char_idx);
+        c.is_alphanumeric() || c == '_'
+      })
+      .and_then(|char_idx| word_range(&text, char_idx));
+    drop(text);
+    if hovered == self.hovered {
+      return false;
+    }
+    self.hovered = hovered;
+    self.hover.take().is_some()
+  }
+
   fn get_rects(&self) -> Vec<&Rectangle> {
     let mut vec = vec![];
     vec.extend(self.gutter.get_rects());
     vec.extend(self.code.get_rects());
+    match &self.inspector {
+      Some(inspector) => vec.extend(inspector.get_rects()),
+      None if self.outline.dimensions.width > 0.0 => {
+        vec.extend(self.outline.get_rects())
+      }
+      None => {}
+    }
     vec
   }
 
   fn get_elements(&mut self) -> Vec<&mut dyn super::RenderElement> {
-    vec![&mut self.gutter, &mut self.code]
+    let mut vec: Vec<&mut dyn super::RenderElement> =
+      vec![&mut self.gutter, &mut self.code];
+    match &mut self.inspector {
+      Some(inspector) => vec.push(inspector),
+      None if self.outline.dimensions.width > 0.0 => {
+        vec.push(&mut self.outline)
+      }
+      None => {}
+    }
+    vec
   }
 
   fn get_dimensions(&self) -> Dimensions {
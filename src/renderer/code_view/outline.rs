@@ -0,0 +1,192 @@
+use super::tags::{self, Symbol};
+use crate::renderer::rectangle::Rectangle;
+use crate::renderer::theme;
+use crate::renderer::Dimensions;
+use wgpu::util::StagingBelt;
+use wgpu::{CommandEncoder, Device, TextureView};
+use wgpu_glyph::{GlyphBrush, Section, Text};
+use winit::dpi::{PhysicalPosition, PhysicalSize};
+
+const OUTLINE_PADDING: f32 = 10.0;
+
+/// Panel listing the symbols of the text, with the one the primary cursor
+/// is in marked.
+pub struct Outline {
+  rect: Rectangle,
+  current_rect: Rectangle,
+  font_height: f32,
+  pub dimensions: Dimensions,
+  scroll_offset_y: f32,
+  symbols: Vec<Symbol>,
+  current: Option<usize>,
+}
+
+impl Outline {
+  pub fn new(
+    device: &wgpu::Device,
+    screen_size: PhysicalSize<f32>,
+    font_height: f32,
+    dimensions: Dimensions,
+  ) -> Self {
+    let ui = theme::current().ui;
+    let rect = Rectangle::new(
+      device,
+      screen_size,
+      dimensions,
+      theme::rgb(ui.tree_background),
+      None,
+    );
+    let current_rect = Rectangle::new(
+      device,
+      screen_size,
+      Dimensions {
+        height: font_height,
+        ..dimensions
+      },
+      theme::rgb(ui.selection),
+      Some(dimensions.into()),
+    );
+
+    Self {
+      rect,
+      current_rect,
+      font_height,
+      dimensions,
+      scroll_offset_y: 0.0,
+      symbols: vec![],
+      current: None,
+    }
+  }
+
+  pub fn set_symbols(&mut self, symbols: Vec<Symbol>) {
+    self.symbols = symbols;
+  }
+
+  pub fn mark(&mut self, screen_size: PhysicalSize<f32>, char_idx: usize) {
+    self.current = tags::enclosing(&self.symbols, char_idx);
+    if let Some(current) = self.current {
+      let y = current as f32 * self.font_height;
+      if y + self.scroll_offset_y < 0.0 {
+        self.scroll_offset_y = -y;
+      } else if y + self.scroll_offset_y + self.font_height
+        > self.dimensions.height
+      {
+        self.scroll_offset_y = self.dimensions.height - self.font_height - y;
+      }
+    }
+    self.clamp_scroll(screen_size);
+  }
+
+  fn clamp_scroll(&mut self, screen_size: PhysicalSize<f32>) {
+    let max_offset =
+      self.symbols.len().saturating_sub(1) as f32 * self.font_height;
+    self.scroll_offset_y = self.scroll_offset_y.min(0.0).max(-max_offset);
+    self.place_current_rect(screen_size);
+  }
+
+  fn place_current_rect(&mut self, screen_size: PhysicalSize<f32>) {
+    let row = self.current.unwrap_or_default() as f32;
+    self.current_rect.resize(
+      screen_size,
+      Dimensions {
+        y: self.dimensions.y + self.scroll_offset_y + row * self.font_height,
+        height: self.font_height,
+        ..self.dimensions
+      },
+    );
+    self.current_rect.region = Some(self.dimensions.into());
+  }
+
+  pub fn symbols(&self) -> &[Symbol] {
+    &self.symbols
+  }
+
+  pub fn symbol_at(&self, y: f64) -> Option<&Symbol> {
+    let row = (y as f32 - self.scroll_offset_y) / self.font_height;
+    self.symbols.get(row.floor() as usize)
+  }
+}
+
+impl super::super::RenderElement for Outline {
+  fn resize(&mut self, screen_size: PhysicalSize<f32>) {
+    self.rect.resize(screen_size, self.dimensions);
+    self.place_current_rect(screen_size);
+  }
+
+  fn scroll(
+    &mut self,
+    offset: PhysicalPosition<f64>,
+    screen_size: PhysicalSize<f32>,
+  ) {
+    self.scroll_offset_y += offset.y as f32;
+    self.clamp_scroll(screen_size);
+  }
+
+  fn theme_changed(&mut self, _screen_size: PhysicalSize<f32>) {
+    let ui = theme::current().ui;
+    self.rect.set_color(theme::rgb(ui.tree_background));
+    self.current_rect.set_color(theme::rgb(ui.selection));
+  }
+
+  fn redraw(
+    &mut self,
+    glyph_brush: &mut GlyphBrush<()>,
+    device: &Device,
+    staging_belt: &mut StagingBelt,
+    encoder: &mut CommandEncoder,
+    target: &TextureView,
+    size: PhysicalSize<u32>,
+  ) {
+    let ui = theme::current().ui;
+    for (i, symbol) in self.symbols.iter().enumerate() {
+      let kind = format!(" {}", symbol.kind);
+      glyph_brush.queue(Section {
+        screen_position: (
+          self.dimensions.x
+            + OUTLINE_PADDING
+            + symbol.depth as f32 * self.font_height,
+          self.dimensions.y
+            + self.scroll_offset_y
+            + i as f32 * self.font_height,
+        ),
+        bounds: (f32::INFINITY, f32::INFINITY),
+        layout: Default::default(),
+        text: vec![
+          Text::new(&symbol.name)
+            .with_scale(self.font_height)
+            .with_color(ui.tree_foreground),
+          Text::new(&kind)
+            .with_scale(self.font_height)
+            .with_color(ui.gutter_foreground),
+        ],
+      });
+    }
+
+    glyph_brush
+      .draw_queued_with_transform_and_scissoring(
+        device,
+        staging_belt,
+        encoder,
+        target,
+        wgpu_glyph::orthographic_projection(size.width, size.height),
+        self.dimensions.into(),
+      )
+      .unwrap();
+  }
+
+  fn get_rects(&self) -> Vec<&Rectangle> {
+    let mut vec = vec![&self.rect];
+    if self.current.is_some() {
+      vec.push(&self.current_rect);
+    }
+    vec
+  }
+
+  fn get_elements(&mut self) -> Vec<&mut dyn super::super::RenderElement> {
+    vec![]
+  }
+
+  fn get_dimensions(&self) -> Dimensions {
+    self.dimensions
+  }
+}
@@ -0,0 +1,49 @@
+use std::ops::Range;
+
+/// Rows of the scope headers pinned at the top of the view, shared by the
+/// code and the gutter.
+#[derive(Clone, Debug, Default)]
+pub struct Sticky {
+  pub depth: usize,
+  // rows of the scopes of the text, from the first to the last
+  pub scopes: Vec<Range<usize>>,
+  pub rows: Vec<usize>,
+}
+
+pub fn scope_headers(
+  scopes: &[Range<usize>],
+  row: usize,
+  depth: usize,
+) -> Vec<usize> {
+  let mut headers = scopes
+    .iter()
+    .filter(|scope| scope.start < row && row <= scope.end)
+    .map(|scope| scope.start)
+    .collect::<Vec<_>>();
+  headers.sort_unstable();
+  headers.dedup();
+  headers.truncate(depth);
+  headers
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::renderer::code_view::fold::captured_rows;
+  use crate::renderer::code_view::language::parse_builtin;
+
+  #[test]
+  fn finds_enclosing_scopes() {
+    let source = "impl A {\n  fn a() {\n    1;\n\n    2\n  }\n}\n";
+    let (language, tree) = parse_builtin("rust", source);
+    let text = ropey::Rope::from_str(source);
+    let query = language.folds.as_ref().unwrap();
+    let scopes = captured_rows(query, &tree, &text, &["scope"]);
+
+    assert_eq!(scope_headers(&scopes, 2, 5), vec![0, 1]);
+    assert_eq!(scope_headers(&scopes, 3, 5), vec![0, 1]);
+    assert_eq!(scope_headers(&scopes, 4, 1), vec![0]);
+    assert_eq!(scope_headers(&scopes, 1, 5), vec![0]);
+    assert!(scope_headers(&scopes, 0, 5).is_empty());
+  }
+}
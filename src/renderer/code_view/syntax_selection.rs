@@ -0,0 +1,110 @@
+use std::ops::Range;
+
+pub fn enclosing_node(
+  tree: &tree_sitter::Tree,
+  text: &ropey::Rope,
+  range: Range<usize>,
+) -> Option<Range<usize>> {
+  let bytes = text.char_to_byte(range.start)..text.char_to_byte(range.end);
+  let mut node = tree
+    .root_node()
+    .descendant_for_byte_range(bytes.start, bytes.end);
+  while let Some(n) = node {
+    let node_bytes = n.byte_range();
+    if node_bytes.start <= bytes.start
+      && bytes.end <= node_bytes.end
+      && node_bytes != bytes
+    {
+      return Some(
+        text.byte_to_char(node_bytes.start)..text.byte_to_char(node_bytes.end),
+      );
+    }
+    node = n.parent();
+  }
+  None
+}
+
+/// Selections grown by syntax node, kept to walk back down by shrinking.
+#[derive(Default)]
+pub struct Expansions {
+  stack: Vec<Vec<Range<usize>>>,
+  current: Vec<Range<usize>>,
+}
+
+impl Expansions {
+  pub fn expand(
+    &mut self,
+    tree: &tree_sitter::Tree,
+    text: &ropey::Rope,
+    ranges: Vec<Range<usize>>,
+  ) -> Option<Vec<Range<usize>>> {
+    if ranges != self.current {
+      self.stack.clear();
+    }
+    let expanded = ranges
+      .iter()
+      .map(|range| {
+        enclosing_node(tree, text, range.clone())
+          .unwrap_or_else(|| range.clone())
+      })
+      .collect::<Vec<_>>();
+    if expanded == ranges {
+      return None;
+    }
+    self.stack.push(ranges);
+    self.current = expanded.clone();
+    Some(expanded)
+  }
+
+  pub fn shrink(
+    &mut self,
+    ranges: Vec<Range<usize>>,
+  ) -> Option<Vec<Range<usize>>> {
+    if ranges != self.current {
+      self.stack.clear();
+      return None;
+    }
+    let previous = self.stack.pop()?;
+    self.current = previous.clone();
+    Some(previous)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::renderer::code_view::language::parse_builtin;
+
+  #[test]
+  fn grows_and_shrinks() {
+    let source = "fn a() {\n  b(c + 1);\n}\n";
+    let (_, tree) = parse_builtin("rust", source);
+    let text = ropey::Rope::from_str(source);
+
+    let mut expansions = Expansions::default();
+    let mut ranges = vec![13..13];
+    let mut selected = vec![];
+    while let Some(expanded) = expansions.expand(&tree, &text, ranges.clone()) {
+      ranges = expanded;
+      selected.push(text.slice(ranges[0].clone()).to_string());
+    }
+    assert_eq!(
+      selected,
+      vec![
+        "c",
+        "c + 1",
+        "(c + 1)",
+        "b(c + 1)",
+        "{\n  b(c + 1);\n}",
+        "fn a() {\n  b(c + 1);\n}",
+        source,
+      ]
+    );
+
+    assert_eq!(expansions.shrink(ranges), Some(vec![0..22]));
+    assert_eq!(expansions.shrink(vec![0..22]), Some(vec![7..22]));
+    // moving the cursor forgets the expansions
+    assert_eq!(expansions.shrink(vec![12..12]), None);
+    assert_eq!(expansions.shrink(vec![7..22]), None);
+  }
+}
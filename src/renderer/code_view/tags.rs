@@ -0,0 +1,166 @@
+use std::cmp::Reverse;
+use std::ops::Range;
+
+/// Symbol defined in the text, as found by the tags query of its language.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Symbol {
+  pub name: String,
+  pub kind: String,
+  pub keyword: Option<String>,
+  pub range: Range<usize>,
+  pub name_start: usize,
+  pub depth: usize,
+}
+
+pub fn symbols(
+  query: &tree_sitter::Query,
+  tree: &tree_sitter::Tree,
+  text: &ropey::Rope,
+) -> Vec<Symbol> {
+  let source = text.bytes().collect::<Vec<u8>>();
+  let capture_names = query.capture_names();
+  let mut cursor = tree_sitter::QueryCursor::new();
+
+  let mut symbols = vec![];
+  for m in
+    cursor.matches(query, tree.root_node(), |node| &source[node.byte_range()])
+  {
+    let mut name = None;
+    let mut definition = None;
+    for capture in m.captures {
+      let capture_name = &capture_names[capture.index as usize];
+      if capture_name == "name" {
+        name = Some(capture.node);
+      } else if let Some(kind) = capture_name.strip_prefix("definition.") {
+        definition = Some((kind, capture.node));
+      }
+    }
+
+    if let (Some(name), Some((kind, node))) = (name, definition) {
+      let name_text = String::from_utf8_lossy(&source[name.byte_range()]);
+      let mut tree_cursor = node.walk();
+      let keyword = node
+        .children(&mut tree_cursor)
+        .take_while(|child| child.start_byte() < name.start_byte())
+        .filter(|child| !child.is_named())
+        .map(|child| String::from_utf8_lossy(&source[child.byte_range()]))
+        .find(|token| token.chars().all(char::is_alphabetic))
+        .map(|token| token.into_owned());
+      symbols.push((
+        m.pattern_index,
+        Symbol {
+          name: name_text.split_whitespace().collect::<Vec<_>>().join(" "),
+          kind: kind.to_string(),
+          keyword,
+          range: text.byte_to_char(node.start_byte())
+            ..text.byte_to_char(node.end_byte()),
+          name_start: text.byte_to_char(name.start_byte()),
+          depth: 0,
+        },
+      ));
+    }
+  }
+
+  // of the patterns matching the same definition, the first in the query
+  // decides its kind
+  symbols.sort_by_key(|(pattern, symbol)| {
+    (symbol.range.start, Reverse(symbol.range.end), *pattern)
+  });
+  symbols.dedup_by(|(_, a), (_, b)| a.range == b.range);
+
+  let mut outer: Vec<Range<usize>> = vec![];
+  symbols
+    .into_iter()
+    .map(|(_, mut symbol)| {
+      while outer
+        .last()
+        .map_or(false, |last| last.end < symbol.range.end)
+      {
+        outer.pop();
+      }
+      symbol.depth = outer.len();
+      outer.push(symbol.range.clone());
+      symbol
+    })
+    .collect()
+}
+
+impl Symbol {
+  pub fn label(&self) -> String {
+    match &self.keyword {
+      Some(keyword) => format!("{} {}", keyword, self.name),
+      None => self.name.clone(),
+    }
+  }
+}
+
+pub fn enclosing(symbols: &[Symbol], char_idx: usize) -> Option<usize> {
+  symbols.iter().rposition(|symbol| {
+    symbol.range.start <= char_idx && char_idx <= symbol.range.end
+  })
+}
+
+pub fn enclosing_chain(symbols: &[Symbol], char_idx: usize) -> Vec<usize> {
+  let mut chain = vec![];
+  let mut i = enclosing(symbols, char_idx);
+  while let Some(index) = i {
+    chain.push(index);
+    i = parent(symbols, index);
+  }
+  chain.reverse();
+  chain
+}
+
+pub fn parent(symbols: &[Symbol], i: usize) -> Option<usize> {
+  let depth = symbols[i].depth.checked_sub(1)?;
+  symbols[..i]
+    .iter()
+    .rposition(|symbol| symbol.depth == depth)
+}
+
+pub fn siblings(symbols: &[Symbol], i: usize) -> Vec<usize> {
+  let parent = parent(symbols, i);
+  (0..symbols.len())
+    .filter(|j| {
+      symbols[*j].depth == symbols[i].depth
+        && self::parent(symbols, *j) == parent
+    })
+    .collect()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::renderer::code_view::language::parse_builtin;
+
+  #[test]
+  fn nests_symbols() {
+    let source = "struct A;\nimpl A {\n  fn a() {}\n}\nfn b() {\n  c();\n}\n";
+    let (language, tree) = parse_builtin("rust", source);
+    let text = ropey::Rope::from_str(source);
+
+    let symbols = symbols(language.tags.as_ref().unwrap(), &tree, &text);
+    let outline = symbols
+      .iter()
+      .map(|s| (s.name.as_str(), s.kind.as_str(), s.depth))
+      .collect::<Vec<_>>();
+    assert_eq!(
+      outline,
+      vec![
+        ("A", "class", 0),
+        ("A", "implementation", 0),
+        ("a", "method", 1),
+        ("b", "function", 0),
+      ]
+    );
+    assert_eq!(symbols[2].name_start, 24);
+    assert_eq!(enclosing(&symbols, 25), Some(2));
+    assert_eq!(enclosing(&symbols, 45), Some(3));
+    assert_eq!(enclosing(&symbols, 51), None);
+    assert_eq!(enclosing_chain(&symbols, 25), vec![1, 2]);
+    assert_eq!(symbols[1].label(), "impl A");
+    assert_eq!(symbols[2].label(), "fn a");
+    assert_eq!(siblings(&symbols, 3), vec![0, 1, 3]);
+    assert_eq!(siblings(&symbols, 2), vec![2]);
+  }
+}
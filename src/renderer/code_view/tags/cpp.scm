@@ -0,0 +1,49 @@
+(namespace_definition
+  name: (_) @name) @definition.module
+
+(class_specifier
+  name: (_) @name
+  body: (_)) @definition.class
+
+(struct_specifier
+  name: (_) @name
+  body: (_)) @definition.class
+
+(union_specifier
+  name: (_) @name
+  body: (_)) @definition.class
+
+(enum_specifier
+  name: (_) @name
+  body: (_)) @definition.class
+
+(field_declaration_list
+  (function_definition
+    declarator: (function_declarator
+      declarator: (_) @name)) @definition.method)
+
+(function_definition
+  declarator: (function_declarator
+    declarator: (_) @name)) @definition.function
+
+(function_definition
+  declarator: (reference_declarator
+    (function_declarator
+      declarator: (_) @name))) @definition.function
+
+(function_definition
+  declarator: (pointer_declarator
+    declarator: (function_declarator
+      declarator: (_) @name))) @definition.function
+
+(call_expression
+  function: [
+    (identifier) @name
+    (scoped_identifier
+      name: (identifier) @name)
+    (field_expression
+      field: (field_identifier) @name)
+  ]) @reference.call
+
+(new_expression
+  type: (type_identifier) @name) @reference.class
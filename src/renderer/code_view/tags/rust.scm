@@ -0,0 +1,49 @@
+(struct_item
+  name: (type_identifier) @name) @definition.class
+
+(enum_item
+  name: (type_identifier) @name) @definition.class
+
+(union_item
+  name: (type_identifier) @name) @definition.class
+
+(type_item
+  name: (type_identifier) @name) @definition.class
+
+(trait_item
+  name: (type_identifier) @name) @definition.interface
+
+(impl_item
+  type: (_) @name) @definition.implementation
+
+(declaration_list
+  [
+    (function_item
+      name: (identifier) @name)
+    (function_signature_item
+      name: (identifier) @name)
+  ] @definition.method)
+
+(function_item
+  name: (identifier) @name) @definition.function
+
+(mod_item
+  name: (identifier) @name) @definition.module
+
+(macro_definition
+  name: (identifier) @name) @definition.macro
+
+(call_expression
+  function: [
+    (identifier) @name
+    (scoped_identifier
+      name: (identifier) @name)
+    (field_expression
+      field: (field_identifier) @name)
+  ]) @reference.call
+
+(macro_invocation
+  macro: (identifier) @name) @reference.call
+
+(impl_item
+  trait: (type_identifier) @name) @reference.implementation
@@ -0,0 +1,416 @@
+use super::highlight::{self, HighlightNames};
+use super::language::{Language, Registry};
+use crate::renderer::font::font_id;
+use crate::renderer::rectangle::Rectangle;
+use crate::renderer::theme;
+use crate::renderer::Dimensions;
+use std::ops::Range;
+use std::rc::Rc;
+use wgpu::util::StagingBelt;
+use wgpu::{CommandEncoder, Device, TextureView};
+use wgpu_glyph::ab_glyph::{Font, FontArc, ScaleFont};
+use wgpu_glyph::{GlyphBrush, Section, Text};
+use winit::dpi::PhysicalSize;
+
+const TOOLTIP_PADDING: f32 = 5.0;
+/// Longest lines of a tooltip in chars, longer ones are wrapped.
+const TOOLTIP_COLUMNS: usize = 80;
+/// Lines a tooltip shows at most, the others are left out.
+const TOOLTIP_ROWS: usize = 20;
+
+/// How a run of the text of a tooltip is drawn.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Format {
+  Plain,
+  Bold,
+  Italic,
+  Code,
+  Syntax(Option<HighlightNames>),
+}
+
+/// Runs of text making up a line of a tooltip.
+pub type Line = Vec<(String, Format)>;
+
+pub fn push(line: &mut Line, text: &str, format: Format) {
+  match line.last_mut() {
+    Some((last, last_format)) if *last_format == format => last.push_str(text),
+    _ if text.is_empty() => {}
+    _ => line.push((text.to_string(), format)),
+  }
+}
+
+fn highlighted(
+  code: &str,
+  language: Option<&Rc<Language>>,
+  registry: &Rc<Registry>,
+) -> Vec<Line> {
+  let code = code.replace('\t', "    ");
+  let language = match language {
+    Some(language) => language,
+    None => {
+      return code
+        .split('\n')
+        .map(|line| vec![(line.to_string(), Format::Syntax(None))])
+        .collect()
+    }
+  };
+  let rope = ropey::Rope::from_str(&code);
+  let mut config =
+    highlight::Config::new(Rc::clone(language), Rc::clone(registry));
+  config.generate(&rope);
+  let mut lines = vec![vec![]];
+  for span in &config.highlights {
+    let format = Format::Syntax(span.highlight());
+    let text = rope.slice(span.start..span.end).to_string();
+    for (i, piece) in text.split('\n').enumerate() {
+      if i > 0 {
+        lines.push(vec![]);
+      }
+      push(lines.last_mut().unwrap(), piece, format);
+    }
+  }
+  lines
+}
+
+fn inline(text: &str, format: Format) -> Line {
+  let chars = text.chars().collect::<Vec<_>>();
+  let mut line = vec![];
+  let (mut bold, mut italic, mut code) = (false, false, false);
+  let current = |bold: bool, italic: bool, code: bool| {
+    if code {
+      Format::Code
+    } else if bold {
+      Format::Bold
+    } else if italic {
+      Format::Italic
+    } else {
+      format
+    }
+  };
+  let mut i = 0;
+  while i < chars.len() {
+    let c = chars[i];
+    let next = chars.get(i + 1).copied();
+    match c {
+      '`' => code = !code,
+      _ if code => push(&mut line, &c.to_string(), Format::Code),
+      '\\' if next.map_or(false, |n| n.is_ascii_punctuation()) => {
+        let next = next.unwrap_or_default().to_string();
+        push(&mut line, &next, current(bold, italic, code));
+        i += 1;
+      }
+      '*' if next == Some('*') => {
+        bold = !bold;
+        i += 1;
+      }
+      // a star before a space is no emphasis, as in `a * b`
+      '*' if italic || next.map_or(false, |n| !n.is_whitespace()) => {
+        italic = !italic
+      }
+      // a link shows its text, leaving out where it leads
+      '[' => {
+        let rest = chars[i..].iter().collect::<String>();
+        let link = rest.find("](").and_then(|middle| {
+          Some((middle, middle + rest[middle..].find(')')?))
+        });
+        match link {
+          Some((middle, end)) => {
+            let format = current(bold, italic, code);
+            push(&mut line, &rest[1..middle], format);
+            i += rest[..=end].chars().count() - 1;
+          }
+          None => push(&mut line, "[", current(bold, italic, code)),
+        }
+      }
+      c => push(&mut line, &c.to_string(), current(bold, italic, code)),
+    }
+    i += 1;
+  }
+  line
+}
+
+pub fn markdown(
+  source: &str,
+  language: Option<&Rc<Language>>,
+  registry: &Rc<Registry>,
+) -> Vec<Line> {
+  let mut lines: Vec<Line> = vec![];
+  // language and code of the block being read
+  let mut block: Option<(Option<Rc<Language>>, String)> = None;
+  for text in source.lines() {
+    let fence = text.trim_start().starts_with("```");
+    match &mut block {
+      Some((block_language, code)) if fence => {
+        let code = code.strip_suffix('\n').unwrap_or(code);
+        lines.extend(highlighted(code, block_language.as_ref(), registry));
+        block = None;
+      }
+      Some((_, code)) => {
+        code.push_str(text);
+        code.push('\n');
+      }
+      None if fence => {
+        let info = text.trim_start().trim_start_matches('`').trim();
+        let block_language = match info {
+          "" => language.cloned(),
+          info => registry.get(info).cloned(),
+        };
+        block = Some((block_language, String::new()));
+      }
+      // paragraphs are kept apart by a single empty line
+      None if text.trim().is_empty() => {
+        if lines.last().map_or(false, |line| !line.is_empty()) {
+          lines.push(vec![]);
+        }
+      }
+      None if text.trim_start().starts_with('#') => {
+        let heading = text.trim_start().trim_start_matches('#').trim();
+        lines.push(inline(heading, Format::Bold));
+      }
+      None if matches!(text.trim(), "---" | "***" | "___") => {
+        lines.push(vec![]);
+      }
+      None => lines.push(inline(text.trim_end(), Format::Plain)),
+    }
+  }
+  if let Some((block_language, code)) = block {
+    let code = code.strip_suffix('\n').unwrap_or(&code);
+    lines.extend(highlighted(code, block_language.as_ref(), registry));
+  }
+  while lines.last().map_or(false, |line| line.is_empty()) {
+    lines.pop();
+  }
+  lines
+}
+
+fn wrap(lines: Vec<Line>, columns: usize) -> Vec<Line> {
+  let mut wrapped = vec![];
+  for line in lines {
+    let mut current = vec![];
+    let mut width = 0;
+    for (text, format) in line {
+      // words along with the spaces after them
+      let mut words = vec![];
+      let mut word = String::new();
+      for c in text.chars() {
+        if c != ' ' && word.ends_with(' ') {
+          words.push(std::mem::take(&mut word));
+        }
+        word.push(c);
+      }
+      words.push(word);
+
+      for word in words {
+        let mut word = word.as_str();
+        while !word.is_empty() {
+          let len = word.chars().count();
+          if width + len <= columns {
+            push(&mut current, word, format);
+            width += len;
+            break;
+          }
+          if width > 0 {
+            wrapped.push(std::mem::take(&mut current));
+            width = 0;
+            continue;
+          }
+          let split = word
+            .char_indices()
+            .nth(columns)
+            .map_or(word.len(), |(at, _)| at);
+          push(&mut current, &word[..split], format);
+          wrapped.push(std::mem::take(&mut current));
+          word = &word[split..];
+        }
+      }
+    }
+    wrapped.push(current);
+  }
+  wrapped
+}
+
+/// Box of styled lines shown above a spot of the code, or below it if there
+/// is no room above.
+pub struct Tooltip {
+  font_height: f32,
+  char_width: f32,
+  lines: Vec<Line>,
+  rect: Rectangle,
+  pub range: Range<usize>,
+}
+
+impl Tooltip {
+  pub fn new(
+    device: &wgpu::Device,
+    screen_size: PhysicalSize<f32>,
+    font: &FontArc,
+    font_height: f32,
+    lines: Vec<Line>,
+    range: Range<usize>,
+    anchor: Dimensions,
+  ) -> Self {
+    let scaled = font.as_scaled(font_height);
+    let rect = Rectangle::new(
+      device,
+      screen_size,
+      Dimensions::default(),
+      theme::rgb(theme::current().ui.tooltip_background),
+      None,
+    );
+    let mut tooltip = Self {
+      font_height,
+      char_width: scaled.h_advance(scaled.glyph_id(' ')),
+      lines: wrap(lines, TOOLTIP_COLUMNS),
+      rect,
+      range,
+    };
+    tooltip.lines.truncate(TOOLTIP_ROWS);
+    tooltip.place(screen_size, anchor);
+    tooltip
+  }
+
+  pub fn place(&mut self, screen_size: PhysicalSize<f32>, anchor: Dimensions) {
+    let columns = self
+      .lines
+      .iter()
+      .map(|line| line.iter().map(|(text, _)| text.chars().count()).sum())
+      .max()
+      .unwrap_or(0usize);
+    let width = columns as f32 * self.char_width + 2.0 * TOOLTIP_PADDING;
+    let height =
+      self.lines.len() as f32 * self.font_height + 2.0 * TOOLTIP_PADDING;
+    let y = if anchor.y - height >= 0.0 {
+      anchor.y - height
+    } else {
+      anchor.y + anchor.height
+    };
+    let x = anchor.x.min(screen_size.width - width).max(0.0);
+    self.rect.resize(
+      screen_size,
+      Dimensions {
+        x,
+        y,
+        width,
+        height,
+      },
+    );
+  }
+
+  pub fn redraw_overlay(
+    &mut self,
+    glyph_brush: &mut GlyphBrush<()>,
+    device: &Device,
+    staging_belt: &mut StagingBelt,
+    encoder: &mut CommandEncoder,
+    target: &TextureView,
+    size: PhysicalSize<u32>,
+  ) {
+    let theme = theme::current();
+    let dimensions = self.rect.dimensions;
+    for (i, line) in self.lines.iter().enumerate() {
+      let text = line
+        .iter()
+        .map(|(text, format)| {
+          let (color, bold, italic) = match format {
+            Format::Plain => (theme.ui.foreground, false, false),
+            Format::Bold => (theme.ui.foreground, true, false),
+            Format::Italic => (theme.ui.foreground, false, true),
+            Format::Code => {
+              let (color, _) = theme.highlight(HighlightNames::String);
+              (color, false, false)
+            }
+            Format::Syntax(None) => (theme.ui.foreground, false, false),
+            Format::Syntax(Some(highlight)) => {
+              let (color, style) = theme.highlight(*highlight);
+              (color, style.bold, style.italic)
+            }
+          };
+          Text::new(text)
+            .with_color(color)
+            .with_font_id(font_id(bold, italic))
+            .with_scale(self.font_height)
+        })
+        .collect();
+      glyph_brush.queue(Section {
+        screen_position: (
+          dimensions.x + TOOLTIP_PADDING,
+          dimensions.y + TOOLTIP_PADDING + i as f32 * self.font_height,
+        ),
+        text,
+        ..Section::default()
+      });
+    }
+    glyph_brush
+      .draw_queued_with_transform_and_scissoring(
+        device,
+        staging_belt,
+        encoder,
+        target,
+        wgpu_glyph::orthographic_projection(size.width, size.height),
+        dimensions.into(),
+      )
+      .unwrap();
+  }
+
+  pub fn get_overlay_rects(&self) -> Vec<&Rectangle> {
+    vec![&self.rect]
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn run(text: &str, format: Format) -> (String, Format) {
+    (text.to_string(), format)
+  }
+
+  #[test]
+  fn renders_markdown() {
+    let registry = Rc::new(Registry::builtin());
+    let lines = markdown(
+      "# Title\n\n```rust\nfn a() {}\n```\nSome **bold**, `a*b` and \
+       [a link](http://a.b).\n\n\n",
+      None,
+      &registry,
+    );
+    assert_eq!(lines.len(), 4);
+    assert_eq!(lines[0], [run("Title", Format::Bold)]);
+    assert!(lines[1].is_empty());
+    let keyword = Format::Syntax(Some(HighlightNames::Keyword));
+    assert_eq!(lines[2][0], run("fn", keyword));
+    assert_eq!(
+      lines[3],
+      [
+        run("Some ", Format::Plain),
+        run("bold", Format::Bold),
+        run(", ", Format::Plain),
+        run("a*b", Format::Code),
+        run(" and a link.", Format::Plain),
+      ]
+    );
+
+    // blocks naming no language are highlighted as the one given
+    let rust = registry.get("rust");
+    let lines = markdown("```\nfn a() {}\n```", rust, &registry);
+    assert_eq!(lines[0][0], run("fn", keyword));
+  }
+
+  #[test]
+  fn wraps_long_lines_after_spaces() {
+    let line = vec![
+      run("aaa ", Format::Plain),
+      run("bbb ccc", Format::Bold),
+      run("dddddddddd", Format::Plain),
+    ];
+    assert_eq!(
+      wrap(vec![line], 8),
+      [
+        vec![run("aaa ", Format::Plain), run("bbb ", Format::Bold)],
+        vec![run("ccc", Format::Bold)],
+        vec![run("dddddddd", Format::Plain)],
+        vec![run("dd", Format::Plain)],
+      ]
+    );
+  }
+}
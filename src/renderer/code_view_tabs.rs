@@ -1,21 +1,99 @@
+use crate::lsp::workspace_edit::{self, CodeAction, WorkspaceEdit};
+use crate::lsp::{self, LanguageServers};
+use crate::renderer::breadcrumbs::{Breadcrumbs, Target};
+use crate::renderer::code_view::language::Registry;
+use crate::renderer::code_view::locals::{self, Locals};
 use crate::renderer::code_view::CodeView;
+use crate::renderer::input::indent::indent_unit;
 use crate::renderer::input::line_length;
 use crate::renderer::rectangle::Rectangle;
+use crate::renderer::search::{self, Hit, Query, Scope, Search, Searcher};
+use crate::renderer::symbol_index::SymbolIndexer;
+use crate::renderer::theme;
 use crate::renderer::Dimensions;
-use std::path::PathBuf;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::time::{Duration, Instant};
 use wgpu::util::StagingBelt;
 use wgpu::{CommandEncoder, TextureView};
 use wgpu_glyph::ab_glyph::FontArc;
 use wgpu_glyph::{GlyphBrush, HorizontalAlign, Layout, Section, Text};
 use winit::dpi::{PhysicalPosition, PhysicalSize};
-use winit::event::VirtualKeyCode;
+use winit::event::{ModifiersState, VirtualKeyCode};
 
 const TAB_HEIGHT: f32 = 50.0;
 const TAB_PADDING: f32 = 15.0;
+/// How long the mouse rests on a word before its documentation is asked for.
+const HOVER_DELAY: Duration = Duration::from_millis(300);
+/// How long saving waits for the server to format the file.
+const FORMAT_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Request to a language server whose answer is yet to come, with what it
+/// is about.
+struct Request<T> {
+  path: PathBuf,
+  id: usize,
+  about: T,
+}
+
+fn spawn_timer(waker: lsp::Waker) -> mpsc::Sender<Instant> {
+  let (sender, deadlines) = mpsc::channel::<Instant>();
+  std::thread::spawn(move || {
+    while let Ok(mut deadline) = deadlines.recv() {
+      loop {
+        let left = deadline.saturating_duration_since(Instant::now());
+        match deadlines.recv_timeout(left) {
+          Ok(later) => deadline = later,
+          Err(RecvTimeoutError::Timeout) => break waker(),
+          Err(RecvTimeoutError::Disconnected) => return,
+        }
+      }
+    }
+  });
+  sender
+}
+
+fn answered<T>(
+  request: &mut Option<Request<T>>,
+  path: &Path,
+  id: usize,
+) -> Option<T> {
+  match request.take() {
+    Some(taken) if taken.path == path && taken.id == id => Some(taken.about),
+    taken => {
+      *request = taken;
+      None
+    }
+  }
+}
 
 pub struct CodeViewTabs {
+  device: Rc<wgpu::Device>,
   font: FontArc,
   font_height: f32,
+  registry: Rc<Registry>,
+  breadcrumbs: Breadcrumbs,
+  root: PathBuf,
+  search: Option<Search>,
+  searcher: Searcher,
+  symbol_indexer: SymbolIndexer,
+  language_servers: LanguageServers,
+  completion_request: Option<Request<usize>>,
+  hover_request: Option<Request<Range<usize>>>,
+  resting: Option<(Range<usize>, Instant)>,
+  hover_timer: mpsc::Sender<Instant>,
+  signature_request: Option<Request<()>>,
+  rename: Option<(PathBuf, usize, String)>,
+  rename_request: Option<Request<String>>,
+  preview: Option<WorkspaceEdit>,
+  code_action_request: Option<Request<()>>,
+  code_actions: Option<(PathBuf, Vec<CodeAction>)>,
+  // the deadline to save by when formatting on save
+  format_request: Option<Request<Option<Instant>>>,
+  format_timer: mpsc::Sender<Instant>,
+  sticky_scroll_depth: usize,
   pub code_views: Vec<(String, Rectangle, CodeView)>,
   active: Option<usize>,
   tabs_container: Rectangle,
@@ -23,12 +101,16 @@ pub struct CodeViewTabs {
 }
 
 impl CodeViewTabs {
+  #[allow(clippy::too_many_arguments)]
   pub fn new(
-    device: &wgpu::Device,
+    device: &Rc<wgpu::Device>,
     screen_size: PhysicalSize<f32>,
     font: FontArc,
     font_height: f32,
     dimensions: Dimensions,
+    root: PathBuf,
+    sticky_scroll_depth: usize,
+    waker: lsp::Waker,
   ) -> Self {
     let rect = Rectangle::new(
       device,
@@ -37,13 +119,52 @@ impl CodeViewTabs {
         height: TAB_HEIGHT,
         ..dimensions
       },
-      [0.4, 0.48, 0.92],
+      theme::rgb(theme::current().ui.tab_bar_background),
       None,
     );
+    let breadcrumbs = Breadcrumbs::new(
+      device,
+      screen_size,
+      font.clone(),
+      font_height,
+      Dimensions {
+        y: dimensions.y + TAB_HEIGHT,
+        height: Breadcrumbs::height(font_height),
+        ..dimensions
+      },
+      root.clone(),
+    );
+    let symbol_indexer = SymbolIndexer::spawn(root.clone());
+    let searcher =
+      Searcher::spawn(root.clone(), symbol_indexer.shared(), waker.clone());
+    let hover_timer = spawn_timer(waker.clone());
+    let format_timer = spawn_timer(waker.clone());
+    let language_servers = LanguageServers::new(root.clone(), waker);
 
     Self {
+      device: Rc::clone(device),
       font,
       font_height,
+      registry: Rc::new(Registry::new()),
+      breadcrumbs,
+      root,
+      search: None,
+      searcher,
+      symbol_indexer,
+      language_servers,
+      completion_request: None,
+      hover_request: None,
+      resting: None,
+      hover_timer,
+      signature_request: None,
+      rename: None,
+      rename_request: None,
+      preview: None,
+      code_action_request: None,
+      code_actions: None,
+      format_request: None,
+      format_timer,
+      sticky_scroll_depth,
       active: None,
       code_views: vec![],
       tabs_container: rect,
@@ -53,10 +174,15 @@ impl CodeViewTabs {
 
   pub fn add(
     &mut self,
-    device: &wgpu::Device,
+    device: &Rc<wgpu::Device>,
     screen_size: PhysicalSize<f32>,
     filepath: PathBuf,
   ) -> Result<(), anyhow::Error> {
+    if let Some(i) = self.open_view(&filepath) {
+      self.active = Some(i);
+      self.update_breadcrumbs();
+      return Ok(());
+    }
     if !filepath.exists() {
       anyhow::bail!("path doesn't exist");
     }
@@ -75,31 +201,817 @@ impl CodeViewTabs {
         width: TAB_PADDING + name_width + TAB_PADDING,
         ..self.tabs_container.dimensions
       },
-      [0.25, 0.37, 0.87],
+      theme::rgb(theme::current().ui.tab_background),
       None,
     );
 
+    let header_height = TAB_HEIGHT + self.breadcrumbs.dimensions.height;
     let code_view = CodeView::new(
       device,
       screen_size,
       self.font.clone(),
       self.font_height,
       Dimensions {
-        y: self.dimensions.y + TAB_HEIGHT,
-        height: self.dimensions.height - TAB_HEIGHT,
+        y: self.dimensions.y + header_height,
+        height: self.dimensions.height - header_height,
         ..self.dimensions
       },
       text,
       &filepath,
+      &self.registry,
+      self.sticky_scroll_depth,
     );
 
     self
       .code_views
       .push((filename.to_string(), rect, code_view));
     self.active = Some(self.code_views.len() - 1);
+    self.update_breadcrumbs();
+    self.open_in_language_server();
     Ok(())
   }
 
+  fn open_view(&self, path: &Path) -> Option<usize> {
+    let path = path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+    self
+      .code_views
+      .iter()
+      .position(|(_, _, code_view)| code_view.server_path() == path)
+  }
+
+  fn open_in_language_server(&mut self) {
+    if let Some(i) = self.active {
+      let code_view = &mut self.code_views[i].2;
+      // the server is handed the whole text
+      code_view.take_changes();
+      let path = code_view.server_path();
+      match code_view.language() {
+        Some(language) => {
+          self
+            .language_servers
+            .open(path, &language.name, &code_view.text())
+        }
+        None => self.language_servers.close(path),
+      }
+    }
+  }
+
+  fn sync_language_server(&mut self) {
+    if let Some(i) = self.active {
+      let code_view = &mut self.code_views[i].2;
+      let changes = code_view.take_changes();
+      if !changes.is_empty() {
+        let path = code_view.server_path();
+        self.language_servers.change(path, &changes);
+      }
+    }
+  }
+
+  fn request_completion(&mut self) {
+    if let Some(i) = self.active {
+      let code_view = &self.code_views[i].2;
+      let path = code_view.server_path().to_path_buf();
+      let char_idx = code_view.cursor_index();
+      self.completion_request = self
+        .language_servers
+        .completion(&path, char_idx)
+        .map(|id| Request {
+          path,
+          id,
+          about: char_idx,
+        });
+    }
+  }
+
+  fn cancel_hover(&mut self) {
+    if let Some(request) = self.hover_request.take() {
+      self.language_servers.cancel(&request.path, request.id);
+    }
+  }
+
+  pub fn poll_hover(&mut self) {
+    match &self.resting {
+      Some((word, deadline)) if *deadline <= Instant::now() => {
+        let word = word.clone();
+        self.resting = None;
+        self.request_hover(word);
+      }
+      _ => {}
+    }
+  }
+
+  fn request_hover(&mut self, word: Range<usize>) {
+    self.cancel_hover();
+    if let Some(i) = self.active {
+      let path = self.code_views[i].2.server_path().to_path_buf();
+      self.hover_request =
+        self
+          .language_servers
+          .hover(&path, word.start)
+          .map(|id| Request {
+            path,
+            id,
+            about: word,
+          });
+    }
+  }
+
+  fn request_signature(&mut self) {
+    if let Some(i) = self.active {
+      let code_view = &self.code_views[i].2;
+      let path = code_view.server_path().to_path_buf();
+      let char_idx = code_view.cursor_index();
+      self.signature_request = self
+        .language_servers
+        .signature_help(&path, char_idx)
+        .map(|id| Request {
+          path,
+          id,
+          about: (),
+        });
+    }
+  }
+
+  fn request_after(&mut self, ch: char) {
+    if let Some(i) = self.active {
+      let code_view = &self.code_views[i].2;
+      let path = code_view.server_path().to_path_buf();
+      let word = (ch.is_alphanumeric() || ch == '_') && !code_view.completing();
+      let has_signature = code_view.has_signature();
+      let triggers = |characters: Vec<String>| {
+        characters.iter().any(|trigger| trigger.ends_with(ch))
+      };
+      if word || triggers(self.language_servers.trigger_characters(&path)) {
+        self.request_completion();
+      }
+      let signature_triggers =
+        self.language_servers.signature_trigger_characters(&path);
+      if has_signature || triggers(signature_triggers) {
+        self.request_signature();
+      }
+    }
+  }
+
+  fn request_code_actions(&mut self) {
+    if let Some(i) = self.active {
+      let code_view = &self.code_views[i].2;
+      let path = code_view.server_path().to_path_buf();
+      let cursor = code_view.cursor_index();
+      let range = code_view.selection_range().unwrap_or(cursor..cursor);
+      self.code_action_request = self
+        .language_servers
+        .code_actions(&path, range)
+        .map(|id| Request {
+          path,
+          id,
+          about: (),
+        });
+    }
+  }
+
+  fn run_code_action(&mut self, screen_size: PhysicalSize<f32>, i: usize) {
+    let (path, mut actions) = match self.code_actions.take() {
+      Some((path, actions)) if i < actions.len() => (path, actions),
+      _ => return,
+    };
+    let action = actions.swap_remove(i);
+    if let Some(edit) = &action.edit {
+      if let Err(err) = self.apply_edit(screen_size, edit) {
+        self.report(screen_size, err);
+        return;
+      }
+    }
+    if let Some(command) = &action.command {
+      self.language_servers.execute_command(&path, command);
+    }
+  }
+
+  fn request_format(&mut self, save: bool) -> bool {
+    let code_view = match self.active {
+      Some(i) => &self.code_views[i].2,
+      None => return false,
+    };
+    // a pending save waits for its own answer
+    if !save
+      && matches!(self.format_request, Some(Request { about: Some(_), .. }))
+    {
+      return false;
+    }
+    let path = code_view.server_path().to_path_buf();
+    let range = code_view.selection_range().filter(|_| !save);
+    let unit = indent_unit(&code_view.text());
+    let deadline = Some(Instant::now() + FORMAT_TIMEOUT).filter(|_| save);
+    self.format_request = self
+      .language_servers
+      .formatting(&path, range, &unit)
+      .map(|id| Request {
+        path,
+        id,
+        about: deadline,
+      });
+    if let (Some(_), Some(deadline)) = (&self.format_request, deadline) {
+      let _ = self.format_timer.send(deadline);
+    }
+    self.format_request.is_some()
+  }
+
+  fn save(&mut self, screen_size: PhysicalSize<f32>) {
+    let code_view = match self.active {
+      Some(i) => &self.code_views[i].2,
+      None => return,
+    };
+    let path = code_view.path().to_path_buf();
+    let format = code_view.language().map_or(false, |language| {
+      self.language_servers.format_on_save(&language.name)
+    });
+    // the file is saved once the server answers, or now if it can't format
+    if !(format && self.request_format(true)) {
+      self.save_file(screen_size, &path);
+    }
+  }
+
+  fn save_file(&mut self, screen_size: PhysicalSize<f32>, path: &Path) -> bool {
+    let saved = match self.open_view(path) {
+      Some(i) => self.code_views[i].2.save(),
+      None => return false,
+    };
+    if let Err(err) = &saved {
+      let message = format!("failed to save {}: {}", path.display(), err);
+      self.report(screen_size, message);
+    }
+    self.symbol_indexer.refresh();
+    saved.is_ok()
+  }
+
+  fn apply_edit(
+    &mut self,
+    screen_size: PhysicalSize<f32>,
+    edit: &WorkspaceEdit,
+  ) -> Result<(), String> {
+    if edit.resource_operations {
+      return Err(
+        "creating, renaming and deleting files isn't supported".into(),
+      );
+    }
+    for file in &edit.files {
+      let open = self.open_view(&file.path);
+      let version = open.and_then(|i| {
+        let path = self.code_views[i].2.server_path().to_path_buf();
+        self.language_servers.version(&path)
+      });
+      if file.version.is_some() && version.is_some() && file.version != version
+      {
+        return Err(format!(
+          "{} changed since the edits were made",
+          file.path.display()
+        ));
+      }
+    }
+    let (open, closed): (Vec<_>, Vec<_>) = edit
+      .files
+      .iter()
+      .partition(|file| self.open_view(&file.path).is_some());
+    for file in closed {
+      workspace_edit::apply_on_disk(&file.path, &file.edits).map_err(
+        |err| format!("failed to edit {}: {}", file.path.display(), err),
+      )?;
+    }
+    for file in open {
+      if let Some(i) = self.open_view(&file.path) {
+        let code_view = &mut self.code_views[i].2;
+        let edits = workspace_edit::text_edits(&code_view.text(), &file.edits);
+        code_view.apply_edits(screen_size, &edits);
+        let changes = code_view.take_changes();
+        let path = code_view.server_path();
+        self.language_servers.change(path, &changes);
+      }
+    }
+    self.update_breadcrumbs();
+    Ok(())
+  }
+
+  fn report(&mut self, screen_size: PhysicalSize<f32>, message: String) {
+    log::warn!("{}", message);
+    if let Some(active) = self.get_active() {
+      active.show_message(screen_size, &message);
+    }
+  }
+
+  fn start_rename(&mut self, screen_size: PhysicalSize<f32>) {
+    let code_view = match self.active {
+      Some(i) => &self.code_views[i].2,
+      None => return,
+    };
+    let word = match code_view.word_at_cursor() {
+      Some(word) => word,
+      None => return,
+    };
+    let name = code_view.text().slice(word.clone()).to_string();
+    self.rename = Some((
+      code_view.server_path().to_path_buf(),
+      word.start,
+      name.clone(),
+    ));
+    self.preview = None;
+    if self.search.is_none() {
+      self.toggle_search(screen_size);
+    }
+    if let Some(search) = &mut self.search {
+      search.ask(screen_size, Scope::Rename, name);
+    }
+  }
+
+  fn request_rename(&mut self, new_name: String) {
+    if let Some((path, char_idx, _)) = &self.rename {
+      self.rename_request = self
+        .language_servers
+        .rename(path, *char_idx, &new_name)
+        .map(|id| Request {
+          path: path.clone(),
+          id,
+          about: new_name,
+        });
+    }
+  }
+
+  fn preview_rename(
+    &mut self,
+    screen_size: PhysicalSize<f32>,
+    new_name: String,
+    edit: WorkspaceEdit,
+  ) {
+    let mut hits = vec![];
+    for file in &edit.files {
+      let text = match self.open_view(&file.path) {
+        Some(i) => self.code_views[i].2.text(),
+        None => match search::read_file(&file.path) {
+          Some(text) => text,
+          None => continue,
+        },
+      };
+      hits.extend(
+        workspace_edit::text_edits(&text, &file.edits)
+          .into_iter()
+          .filter_map(|edit| Hit::new(&file.path, &text, vec![edit.range])),
+      );
+    }
+    let name = self.rename.as_ref().map_or("", |(_, _, name)| name);
+    let title = if edit.is_empty() {
+      format!("nothing to rename {} to {}", name, new_name)
+    } else {
+      format!("rename {} to {}, Return applies", name, new_name)
+    };
+    if let Some(search) = &mut self.search {
+      search.show(screen_size, title, hits);
+      search.focused = true;
+    }
+    self.preview = Some(edit).filter(|edit| !edit.is_empty());
+  }
+
+  pub fn poll_language_servers(&mut self, screen_size: PhysicalSize<f32>) {
+    for event in self.language_servers.poll() {
+      match event {
+        lsp::Event::Edit { path, id, edit } => {
+          if let Some(new_name) = answered(&mut self.rename_request, &path, id)
+          {
+            self.preview_rename(screen_size, new_name, edit);
+          } else if let Some(save) =
+            answered(&mut self.format_request, &path, id)
+          {
+            if let Err(err) = self.apply_edit(screen_size, &edit) {
+              self.report(screen_size, err);
+            }
+            if save.is_some() {
+              self.save_file(screen_size, &path);
+            }
+          }
+        }
+        lsp::Event::ApplyEdit { server, id, edit } => {
+          let result = self.apply_edit(screen_size, &edit);
+          if let Err(err) = &result {
+            self.report(screen_size, err.clone());
+          }
+          self.language_servers.applied(&server, id, result);
+        }
+        event => self.show_answer(screen_size, event),
+      }
+    }
+
+    // the server took too long to format, so the file is saved as it is
+    let timed_out = match &self.format_request {
+      Some(Request {
+        about: Some(deadline),
+        ..
+      }) => *deadline <= Instant::now(),
+      _ => false,
+    };
+    if timed_out {
+      let path = self.format_request.take().unwrap().path;
+      log::warn!("formatting {} timed out", path.display());
+      self.save_file(screen_size, &path);
+    }
+  }
+
+  pub fn poll_search(&mut self, screen_size: PhysicalSize<f32>) {
+    for (id, found) in self.searcher.poll() {
+      match &mut self.search {
+        Some(search) if search.is_searching(id) => {
+          search.found(screen_size, found)
+        }
+        _ => self.searcher.cancel(),
+      }
+    }
+  }
+
+  fn show_answer(&mut self, screen_size: PhysicalSize<f32>, event: lsp::Event) {
+    let path = match &event {
+      lsp::Event::Diagnostics { path, .. } => path,
+      lsp::Event::Completion { path, .. } => path,
+      lsp::Event::Hover { path, .. } => path,
+      lsp::Event::SignatureHelp { path, .. } => path,
+      lsp::Event::CodeActions { path, .. } => path,
+      lsp::Event::Edit { .. } | lsp::Event::ApplyEdit { .. } => return,
+    };
+    let code_view = match self
+      .code_views
+      .iter_mut()
+      .find(|(_, _, code_view)| code_view.server_path() == path.as_path())
+    {
+      Some((_, _, code_view)) => code_view,
+      None => return,
+    };
+    match event {
+      lsp::Event::Diagnostics { diagnostics, .. } => {
+        code_view.set_diagnostics(screen_size, diagnostics);
+      }
+      lsp::Event::Completion { path, id, items } => {
+        let request = &mut self.completion_request;
+        if let Some(char_idx) = answered(request, &path, id) {
+          code_view.show_completions(screen_size, char_idx, items);
+        }
+      }
+      lsp::Event::Hover { path, id, contents } => {
+        let request = &mut self.hover_request;
+        if let Some(word) = answered(request, &path, id) {
+          code_view.show_hover(screen_size, word, contents);
+        }
+      }
+      lsp::Event::SignatureHelp { path, id, help } => {
+        let request = &mut self.signature_request;
+        if answered(request, &path, id).is_some() {
+          code_view.show_signature(screen_size, help);
+        }
+      }
+      lsp::Event::CodeActions { path, id, actions } => {
+        let request = &mut self.code_action_request;
+        if answered(request, &path, id).is_some() {
+          let titles = actions.iter().map(|action| action.title.clone());
+          code_view.show_menu(screen_size, titles.collect());
+          self.code_actions = Some((path, actions));
+        }
+      }
+      lsp::Event::Edit { .. } | lsp::Event::ApplyEdit { .. } => {}
+    }
+  }
+
+  fn update_breadcrumbs(&mut self) {
+    if let Some(i) = self.active {
+      let code_view = &self.code_views[i].2;
+      self.breadcrumbs.update(
+        code_view.server_path(),
+        code_view.symbols(),
+        code_view.cursor_index(),
+      );
+    }
+  }
+
+  pub fn open_picked(
+    &mut self,
+    device: &Rc<wgpu::Device>,
+    screen_size: PhysicalSize<f32>,
+  ) {
+    if let Some(hit) = self.search.as_mut().and_then(Search::take_picked) {
+      self.go_to_hit(device, screen_size, hit);
+    }
+    match self.breadcrumbs.take_picked() {
+      Some(Target::File(path)) => {
+        if let Err(err) = self.add(device, screen_size, path.clone()) {
+          self
+            .report(screen_size, format!("failed to open {:?}: {}", path, err));
+        }
+      }
+      Some(Target::Symbol(char_idx)) => {
+        if let Some(active) = self.get_active() {
+          active.go_to(screen_size, char_idx);
+        }
+      }
+      Some(Target::Directory(_)) | None => {}
+    }
+    self.update_breadcrumbs();
+  }
+
+  fn go_to_hit(
+    &mut self,
+    device: &Rc<wgpu::Device>,
+    screen_size: PhysicalSize<f32>,
+    hit: Hit,
+  ) {
+    if let Err(err) = self.add(device, screen_size, hit.path.clone()) {
+      let err = format!("failed to open {:?}: {}", hit.path, err);
+      self.report(screen_size, err);
+      return;
+    }
+    if let Some(active) = self.get_active() {
+      active.go_to(screen_size, hit.char_idx);
+    }
+    self.update_breadcrumbs();
+  }
+
+  fn toggle_symbol_search(&mut self, screen_size: PhysicalSize<f32>) {
+    match &mut self.search {
+      Some(search) if search.scope == Scope::Symbols => self.search = None,
+      Some(search) => {
+        search.scope = Scope::Symbols;
+        search.focused = true;
+      }
+      None => {
+        self.toggle_search(screen_size);
+        if let Some(search) = &mut self.search {
+          search.scope = Scope::Symbols;
+        }
+      }
+    }
+    self.symbol_indexer.refresh();
+  }
+
+  fn toggle_search(&mut self, screen_size: PhysicalSize<f32>) {
+    self.searcher.cancel();
+    self.search = match self.search {
+      Some(_) => None,
+      None => Some(Search::new(
+        &self.device,
+        screen_size,
+        self.font.clone(),
+        self.font_height,
+        self.dimensions.x,
+        self.root.clone(),
+      )),
+    };
+  }
+
+  fn show_hits(
+    &mut self,
+    screen_size: PhysicalSize<f32>,
+    title: String,
+    hits: Vec<Hit>,
+  ) {
+    if self.search.is_none() {
+      self.toggle_search(screen_size);
+    }
+    if let Some(search) = &mut self.search {
+      search.show(screen_size, title, hits);
+    }
+  }
+
+  fn open_texts(&self) -> Vec<(PathBuf, ropey::Rope)> {
+    self
+      .code_views
+      .iter()
+      .map(|(_, _, code_view)| {
+        (code_view.path().to_path_buf(), code_view.text())
+      })
+      .collect()
+  }
+
+  fn go_to_definition(
+    &mut self,
+    device: &Rc<wgpu::Device>,
+    screen_size: PhysicalSize<f32>,
+  ) {
+    let i = match self.active {
+      Some(i) => i,
+      None => return,
+    };
+    let code_view = &self.code_views[i].2;
+    let (language, tree) = match (code_view.language(), code_view.tree()) {
+      (Some(language), Some(tree)) => (language, tree),
+      _ => return,
+    };
+    let text = code_view.text();
+    let (range, name) =
+      match locals::name_at(tree, &text, code_view.cursor_index()) {
+        Some(name) => name,
+        None => return,
+      };
+    if let Some(char_idx) =
+      locals::definition(language, tree, &text, &name, range)
+    {
+      self.code_views[i].2.go_to(screen_size, char_idx);
+      self.update_breadcrumbs();
+      return;
+    }
+
+    let name_len = name.chars().count();
+    let mut hits = vec![];
+    for (_, _, code_view) in &self.code_views {
+      let text = code_view.text();
+      for symbol in code_view.symbols() {
+        if symbol.name == name {
+          let range = symbol.name_start..symbol.name_start + name_len;
+          hits.extend(Hit::new(code_view.path(), &text, vec![range]));
+        }
+      }
+    }
+    let open = self
+      .code_views
+      .iter()
+      .map(|(_, _, code_view)| code_view.path().canonicalize().ok())
+      .collect::<Vec<_>>();
+    self.symbol_indexer.refresh();
+    hits.extend(
+      self
+        .symbol_indexer
+        .index()
+        .lookup(&name)
+        .into_iter()
+        .filter(|symbol| !open.contains(&symbol.hit.path.canonicalize().ok()))
+        .map(|symbol| symbol.hit.clone()),
+    );
+    match hits.len() {
+      0 => {}
+      1 => self.go_to_hit(device, screen_size, hits.remove(0)),
+      _ => {
+        self.show_hits(screen_size, format!("definitions of {}", name), hits)
+      }
+    }
+  }
+
+  fn show_references(&mut self, screen_size: PhysicalSize<f32>) {
+    let code_view = match self.active {
+      Some(i) => &self.code_views[i].2,
+      None => return,
+    };
+    let (language, tree) = match (code_view.language(), code_view.tree()) {
+      (Some(language), Some(tree)) => (language, tree),
+      _ => return,
+    };
+    let text = code_view.text();
+    let (range, name) =
+      match locals::name_at(tree, &text, code_view.cursor_index()) {
+        Some(name) => name,
+        None => return,
+      };
+    let definition = language.locals.as_ref().and_then(|query| {
+      Locals::new(query, tree, &text).resolve(&name, range.start)
+    });
+
+    let title = format!("references to {}", name);
+    if definition.is_none() {
+      let open = self.open_texts();
+      if self.search.is_none() {
+        self.toggle_search(screen_size);
+      }
+      let id = self.searcher.start(Query::References(name), open);
+      if let Some(search) = &mut self.search {
+        search.start(screen_size, id, Some(title));
+      }
+      return;
+    }
+    let hits = locals::references(language, tree, &text, &name, definition)
+      .into_iter()
+      .filter_map(|range| Hit::new(code_view.path(), &text, vec![range]))
+      .collect();
+    self.show_hits(screen_size, title, hits);
+  }
+
+  fn run_search(&mut self, screen_size: PhysicalSize<f32>) {
+    let search = match &self.search {
+      Some(search) => search,
+      None => return,
+    };
+    self.searcher.cancel();
+    if search.scope == Scope::Rename {
+      let new_name = search.pattern.clone();
+      self.request_rename(new_name);
+      return;
+    }
+    let hits = match search.scope {
+      Scope::File => self.active.map_or(Ok(vec![]), |i| {
+        let code_view = &self.code_views[i].2;
+        match code_view.tree() {
+          Some(tree) => search::compile_query(tree.language(), &search.pattern)
+            .map(|query| {
+              search::search_tree(
+                &query,
+                tree,
+                &code_view.text(),
+                code_view.path(),
+              )
+            }),
+          None => Err("plain text has no syntax tree".to_string()),
+        }
+      }),
+      Scope::Workspace => {
+        let query = Query::Pattern(search.pattern.clone());
+        let id = self.searcher.start(query, self.open_texts());
+        if let Some(search) = &mut self.search {
+          search.start(screen_size, id, None);
+        }
+        return;
+      }
+      Scope::Symbols => {
+        if search.pattern.is_empty() {
+          Ok(vec![])
+        } else {
+          let query = Query::Symbols(search.pattern.clone());
+          let id = self.searcher.start(query, vec![]);
+          if let Some(search) = &mut self.search {
+            search.start(screen_size, id, None);
+          }
+          return;
+        }
+      }
+      Scope::Rename => return,
+    };
+    if let Some(search) = &mut self.search {
+      search.set_hits(screen_size, hits);
+    }
+  }
+
+  pub fn blur(&mut self) {
+    if let Some(search) = &mut self.search {
+      search.focused = false;
+    }
+  }
+
+  pub fn get_overlay_rects(&self) -> Vec<&Rectangle> {
+    let mut vec = vec![];
+    if let Some(search) = &self.search {
+      vec.extend(search.get_overlay_rects());
+    }
+    vec.extend(self.breadcrumbs.get_overlay_rects());
+    if let Some(i) = self.active {
+      vec.extend(self.code_views[i].2.get_overlay_rects());
+    }
+    vec
+  }
+
+  pub fn redraw_overlay(
+    &mut self,
+    glyph_brush: &mut GlyphBrush<()>,
+    device: &wgpu::Device,
+    staging_belt: &mut StagingBelt,
+    encoder: &mut CommandEncoder,
+    target: &TextureView,
+    size: PhysicalSize<u32>,
+  ) {
+    if let Some(search) = &mut self.search {
+      search.redraw_overlay(
+        glyph_brush,
+        device,
+        staging_belt,
+        encoder,
+        target,
+        size,
+      );
+    }
+    self.breadcrumbs.redraw_overlay(
+      glyph_brush,
+      device,
+      staging_belt,
+      encoder,
+      target,
+      size,
+    );
+    if let Some(active) = self.get_active() {
+      active.redraw_overlay(
+        glyph_brush,
+        device,
+        staging_belt,
+        encoder,
+        target,
+        size,
+      );
+    }
+  }
+
+  fn cycle_language(&mut self, screen_size: PhysicalSize<f32>) {
+    let registry = Rc::clone(&self.registry);
+    if let Some(active) = self.get_active() {
+      let languages = registry.languages();
+      let next = match active.language() {
+        Some(current) => languages
+          .iter()
+          .position(|language| Rc::ptr_eq(language, current))
+          .and_then(|i| languages.get(i + 1)),
+        None => languages.first(),
+      };
+      active.set_language(screen_size, next.cloned());
+    }
+    self.update_breadcrumbs();
+    self.open_in_language_server();
+  }
+
   fn get_active(&mut self) -> Option<&mut CodeView> {
     if let Some(i) = self.active {
       Some(&mut self.code_views[i].2)
@@ -120,6 +1032,21 @@ impl super::RenderElement for CodeViewTabs {
     );
   }
 
+  fn theme_changed(&mut self, screen_size: PhysicalSize<f32>) {
+    let ui = theme::current().ui;
+    self
+      .tabs_container
+      .set_color(theme::rgb(ui.tab_bar_background));
+    self.breadcrumbs.theme_changed(screen_size);
+    if let Some(search) = &mut self.search {
+      search.theme_changed(screen_size);
+    }
+    for (_, rect, code_view) in &mut self.code_views {
+      rect.set_color(theme::rgb(ui.tab_background));
+      code_view.theme_changed(screen_size);
+    }
+  }
+
   fn scroll(
     &mut self,
     offset: PhysicalPosition<f64>,
@@ -134,6 +1061,7 @@ impl super::RenderElement for CodeViewTabs {
     &mut self,
     position: PhysicalPosition<f64>,
     screen_size: PhysicalSize<f32>,
+    modifiers: ModifiersState,
   ) {
     if let Some(pos) = self.tabs_container.dimensions.contains(position.cast())
     {
@@ -144,7 +1072,52 @@ impl super::RenderElement for CodeViewTabs {
         }
       }
     } else if let Some(active) = self.get_active() {
-      active.click(position, screen_size);
+      active.click(position, screen_size, modifiers);
+    }
+  }
+
+  fn hover(
+    &mut self,
+    position: PhysicalPosition<f64>,
+    screen_size: PhysicalSize<f32>,
+  ) -> bool {
+    let on_tabs = self.tabs_container.dimensions.contains(position.cast());
+    let (changed, hovered) = match self.get_active() {
+      Some(active) if on_tabs.is_none() => {
+        let before = active.hovered();
+        let changed = active.hover(position, screen_size);
+        (
+          changed,
+          Some(active.hovered()).filter(|after| *after != before),
+        )
+      }
+      _ => (false, None),
+    };
+    if let Some(word) = hovered {
+      self.cancel_hover();
+      self.resting = word.map(|word| (word, Instant::now() + HOVER_DELAY));
+      if let Some((_, deadline)) = &self.resting {
+        let _ = self.hover_timer.send(*deadline);
+      }
+    }
+    changed
+  }
+
+  fn drag(
+    &mut self,
+    position: PhysicalPosition<f64>,
+    screen_size: PhysicalSize<f32>,
+    modifiers: ModifiersState,
+  ) {
+    if self
+      .tabs_container
+      .dimensions
+      .contains(position.cast())
+      .is_none()
+    {
+      if let Some(active) = self.get_active() {
+        active.drag(position, screen_size, modifiers);
+      }
     }
   }
 
@@ -157,6 +1130,7 @@ impl super::RenderElement for CodeViewTabs {
     target: &TextureView,
     size: PhysicalSize<u32>,
   ) {
+    let color = theme::current().ui.tab_foreground;
     for (name, rect, _) in &self.code_views {
       glyph_brush.queue(Section {
         screen_position: (
@@ -164,13 +1138,31 @@ impl super::RenderElement for CodeViewTabs {
           (TAB_HEIGHT - self.font_height) / 2.0,
         ),
         text: vec![Text::new(name)
-          .with_color([0.9, 0.9, 0.9, 1.0])
+          .with_color(color)
           .with_scale(self.font_height)],
         layout: Layout::default_wrap().h_align(HorizontalAlign::Left),
         ..Section::default()
       });
     }
 
+    if let Some(i) = self.active {
+      let language = self.code_views[i]
+        .2
+        .language()
+        .map_or("plain text", |language| &language.name);
+      glyph_brush.queue(Section {
+        screen_position: (
+          self.dimensions.x + self.dimensions.width - TAB_PADDING,
+          (TAB_HEIGHT - self.font_height) / 2.0,
+        ),
+        text: vec![Text::new(language)
+          .with_color(color)
+          .with_scale(self.font_height)],
+        layout: Layout::default_wrap().h_align(HorizontalAlign::Right),
+        ..Section::default()
+      });
+    }
+
     glyph_brush
       .draw_queued(
         device,
@@ -182,6 +1174,14 @@ impl super::RenderElement for CodeViewTabs {
       )
       .unwrap();
 
+    self.breadcrumbs.redraw(
+      glyph_brush,
+      device,
+      staging_belt,
+      encoder,
+      target,
+      size,
+    );
     if let Some(active) = self.get_active() {
       active.redraw(glyph_brush, device, staging_belt, encoder, target, size);
     }
@@ -190,6 +1190,7 @@ impl super::RenderElement for CodeViewTabs {
   fn get_rects(&self) -> Vec<&Rectangle> {
     let mut vec = vec![&self.tabs_container];
     vec.extend(self.code_views.iter().map(|(_, rect, _)| rect));
+    vec.extend(self.breadcrumbs.get_rects());
     if let Some(i) = self.active {
       vec.extend(self.code_views[i].2.get_rects());
     }
@@ -197,11 +1198,15 @@ impl super::RenderElement for CodeViewTabs {
   }
 
   fn get_elements(&mut self) -> Vec<&mut dyn super::RenderElement> {
-    if let Some(active) = self.get_active() {
-      vec![active]
-    } else {
-      vec![]
+    let mut vec: Vec<&mut dyn super::RenderElement> = vec![];
+    if let Some(search) = &mut self.search {
+      vec.push(search);
     }
+    vec.push(&mut self.breadcrumbs);
+    if let Some(i) = self.active {
+      vec.push(&mut self.code_views[i].2);
+    }
+    vec
   }
 
   fn get_dimensions(&self) -> Dimensions {
@@ -214,15 +1219,113 @@ impl super::input::TextInput for CodeViewTabs {
     &mut self,
     screen_size: PhysicalSize<f32>,
     key: VirtualKeyCode,
+    modifiers: ModifiersState,
   ) {
-    if let Some(active) = self.get_active() {
-      active.input_special(screen_size, key);
+    let search_focused = self.search.as_ref().map_or(false, |s| s.focused);
+    if modifiers.ctrl()
+      && !modifiers.shift()
+      && !modifiers.alt()
+      && key == VirtualKeyCode::S
+    {
+      self.save(screen_size);
+    } else if modifiers.ctrl() && modifiers.shift() && key == VirtualKeyCode::M
+    {
+      self.cycle_language(screen_size);
+    } else if modifiers.ctrl() && modifiers.shift() && key == VirtualKeyCode::F
+    {
+      self.toggle_search(screen_size);
+    } else if modifiers.ctrl()
+      && !modifiers.shift()
+      && !modifiers.alt()
+      && key == VirtualKeyCode::T
+    {
+      self.toggle_symbol_search(screen_size);
+    } else if key == VirtualKeyCode::F12 {
+      if modifiers.shift() {
+        self.show_references(screen_size);
+      } else {
+        let device = Rc::clone(&self.device);
+        self.go_to_definition(&device, screen_size);
+      }
+    } else if key == VirtualKeyCode::F4 {
+      let device = Rc::clone(&self.device);
+      if let Some(search) = &mut self.search {
+        search.step(screen_size, !modifiers.shift());
+      }
+      self.open_picked(&device, screen_size);
+    } else if !search_focused
+      && modifiers.ctrl()
+      && key == VirtualKeyCode::Space
+    {
+      if modifiers.shift() {
+        self.request_signature();
+      } else {
+        self.request_completion();
+      }
+    } else if !search_focused && key == VirtualKeyCode::F2 {
+      self.start_rename(screen_size);
+    } else if !search_focused
+      && modifiers.ctrl()
+      && key == VirtualKeyCode::Period
+    {
+      self.request_code_actions();
+    } else if !search_focused
+      && modifiers.alt()
+      && modifiers.shift()
+      && key == VirtualKeyCode::F
+    {
+      self.request_format(false);
+    } else if !search_focused && modifiers.ctrl() && key == VirtualKeyCode::I {
+      let word = self.get_active().and_then(|active| active.word_at_cursor());
+      if let Some(word) = word {
+        self.request_hover(word);
+      }
+    } else if search_focused {
+      if key == VirtualKeyCode::Escape {
+        self.searcher.cancel();
+        self.search = None;
+        self.rename = None;
+        self.preview = None;
+      }
+    } else if let Some(active) = self.get_active() {
+      active.input_special(screen_size, key, modifiers);
+      let has_signature = active.has_signature();
+      self.update_breadcrumbs();
+      self.sync_language_server();
+      if has_signature {
+        self.request_signature();
+      }
     }
   }
 
   fn input_char(&mut self, screen_size: PhysicalSize<f32>, ch: char) {
-    if let Some(active) = self.get_active() {
+    let renaming = self.search.as_ref().map_or(false, |search| {
+      search.focused && search.scope == Scope::Rename
+    });
+    if let Some(search) = self.search.as_mut().filter(|s| s.focused) {
+      match self.preview.take() {
+        Some(edit) if renaming && ch == '\r' => {
+          self.search = None;
+          self.rename = None;
+          if let Err(err) = self.apply_edit(screen_size, &edit) {
+            self.report(screen_size, err);
+          }
+        }
+        _ => {
+          if search.input_char(ch) {
+            self.run_search(screen_size);
+          }
+        }
+      }
+    } else if let Some(active) = self.get_active() {
       active.input_char(screen_size, ch);
+      let chosen = active.take_chosen();
+      self.update_breadcrumbs();
+      self.sync_language_server();
+      self.request_after(ch);
+      if let Some(i) = chosen {
+        self.run_code_action(screen_size, i);
+      }
     }
   }
 }
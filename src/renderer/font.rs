@@ -0,0 +1,177 @@
+use wgpu_glyph::ab_glyph::{
+  self, point, Font, FontArc, GlyphId, Outline, OutlineCurve, Point, Rect,
+};
+use wgpu_glyph::FontId;
+
+/// How far synthetic italics lean to the right, in widths per height.
+const SLANT: f32 = 0.2;
+/// How much wider synthetic bold strokes are, in ems.
+const EMBOLDEN: f32 = 0.04;
+
+#[derive(Clone)]
+pub struct FontFamily {
+  pub regular: FontArc,
+  pub bold: FontArc,
+  pub italic: FontArc,
+  pub bold_italic: FontArc,
+}
+
+impl FontFamily {
+  pub fn faces(&self) -> Vec<FontArc> {
+    vec![
+      self.regular.clone(),
+      self.bold.clone(),
+      self.italic.clone(),
+      self.bold_italic.clone(),
+    ]
+  }
+}
+
+pub fn font_id(bold: bool, italic: bool) -> FontId {
+  FontId(bold as usize + 2 * italic as usize)
+}
+
+/// Face drawn from the outlines of another one, for fonts which don't have
+/// bold or italic faces.
+struct Synthetic {
+  font: FontArc,
+  bold: bool,
+  italic: bool,
+}
+
+fn map_points(
+  curve: &OutlineCurve,
+  f: impl Fn(Point) -> Point,
+) -> OutlineCurve {
+  match *curve {
+    OutlineCurve::Line(a, b) => OutlineCurve::Line(f(a), f(b)),
+    OutlineCurve::Quad(a, b, c) => OutlineCurve::Quad(f(a), f(b), f(c)),
+    OutlineCurve::Cubic(a, b, c, d) => {
+      OutlineCurve::Cubic(f(a), f(b), f(c), f(d))
+    }
+  }
+}
+
+pub fn synthesize(font: &FontArc, bold: bool, italic: bool) -> FontArc {
+  FontArc::new(Synthetic {
+    font: font.clone(),
+    bold,
+    italic,
+  })
+}
+
+impl Font for Synthetic {
+  fn units_per_em(&self) -> Option<f32> {
+    self.font.units_per_em()
+  }
+
+  fn ascent_unscaled(&self) -> f32 {
+    self.font.ascent_unscaled()
+  }
+
+  fn descent_unscaled(&self) -> f32 {
+    self.font.descent_unscaled()
+  }
+
+  fn line_gap_unscaled(&self) -> f32 {
+    self.font.line_gap_unscaled()
+  }
+
+  fn glyph_id(&self, c: char) -> GlyphId {
+    self.font.glyph_id(c)
+  }
+
+  fn h_advance_unscaled(&self, id: GlyphId) -> f32 {
+    self.font.h_advance_unscaled(id)
+  }
+
+  fn h_side_bearing_unscaled(&self, id: GlyphId) -> f32 {
+    self.font.h_side_bearing_unscaled(id)
+  }
+
+  fn v_advance_unscaled(&self, id: GlyphId) -> f32 {
+    self.font.v_advance_unscaled(id)
+  }
+
+  fn v_side_bearing_unscaled(&self, id: GlyphId) -> f32 {
+    self.font.v_side_bearing_unscaled(id)
+  }
+
+  fn kern_unscaled(&self, first: GlyphId, second: GlyphId) -> f32 {
+    self.font.kern_unscaled(first, second)
+  }
+
+  // the outline is slanted, and emboldened by drawing it a second time a
+  // little to the right, the rasterizer filling the overlap only once
+  fn outline(&self, id: GlyphId) -> Option<Outline> {
+    let outline = self.font.outline(id)?;
+    // bounds run from the top left to the bottom right, with y going up
+    let Rect { min, max } = outline.bounds;
+    let (mut left, mut right) = (min.x, max.x);
+
+    let mut curves = outline.curves;
+    if self.italic {
+      let slant = |p: Point| point(p.x + p.y * SLANT, p.y);
+      curves = curves
+        .iter()
+        .map(|curve| map_points(curve, slant))
+        .collect();
+      left += max.y * SLANT;
+      right += min.y * SLANT;
+    }
+    if self.bold {
+      let offset = point(self.units_per_em().unwrap_or(1000.0) * EMBOLDEN, 0.0);
+      let shifted = curves
+        .iter()
+        .map(|curve| map_points(curve, |p| p + offset))
+        .collect::<Vec<_>>();
+      curves.extend(shifted);
+      right += offset.x;
+    }
+
+    Some(Outline {
+      bounds: Rect {
+        min: point(left, min.y),
+        max: point(right, max.y),
+      },
+      curves,
+    })
+  }
+
+  fn glyph_count(&self) -> usize {
+    self.font.glyph_count()
+  }
+
+  fn codepoint_ids(&self) -> ab_glyph::CodepointIdIter<'_> {
+    self.font.codepoint_ids()
+  }
+
+  fn glyph_raster_image(
+    &self,
+    id: GlyphId,
+    pixel_size: u16,
+  ) -> Option<ab_glyph::GlyphImage> {
+    self.font.glyph_raster_image(id, pixel_size)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn slants_and_emboldens_outlines() {
+    let regular =
+      FontArc::try_from_slice(include_bytes!("../JetBrainsMono-Regular.ttf"))
+        .unwrap();
+    let id = regular.glyph_id('l');
+    let bounds = |font: FontArc| font.outline(id).unwrap().bounds;
+    let plain = bounds(regular.clone());
+    let bold = bounds(synthesize(&regular, true, false));
+    let italic = bounds(synthesize(&regular, false, true));
+
+    assert!(bold.max.x > plain.max.x && bold.min == plain.min);
+    assert!(italic.max.x > plain.max.x && italic.min.x == plain.min.x);
+    assert_eq!(synthesize(&regular, true, true).glyph_id('l'), id);
+  }
+}
@@ -1,4 +1,5 @@
 use crate::renderer::rectangle::Rectangle;
+use crate::renderer::theme;
 use crate::renderer::Dimensions;
 use std::collections::HashSet;
 use std::ffi::OsString;
@@ -8,6 +9,7 @@ use wgpu::util::StagingBelt;
 use wgpu::{CommandEncoder, Device, TextureView};
 use wgpu_glyph::{GlyphBrush, Section, Text};
 use winit::dpi::{PhysicalPosition, PhysicalSize};
+use winit::event::ModifiersState;
 
 struct TreeEntry {
   name: String,
@@ -128,8 +130,13 @@ impl FsTree {
     dimensions: Dimensions,
     path: PathBuf,
   ) -> Self {
-    let rect =
-      Rectangle::new(device, screen_size, dimensions, [0.2, 0.2, 0.2], None);
+    let rect = Rectangle::new(
+      device,
+      screen_size,
+      dimensions,
+      theme::rgb(theme::current().ui.tree_background),
+      None,
+    );
 
     let mut ignore_set = HashSet::new();
     ignore_set.insert(OsString::from_str(".DS_Store").unwrap());
@@ -161,10 +168,16 @@ impl super::RenderElement for FsTree {
       .max(-((self.counter - 3) as f32 * self.font_height) as f64);
   }
 
+  fn theme_changed(&mut self, _screen_size: PhysicalSize<f32>) {
+    let color = theme::current().ui.tree_background;
+    self.rect.set_color(theme::rgb(color));
+  }
+
   fn click(
     &mut self,
     position: PhysicalPosition<f64>,
     _screen_size: PhysicalSize<f32>,
+    _modifiers: ModifiersState,
   ) {
     let index = ((position.y - self.scroll_offset.y) / self.font_height as f64)
       .floor() as usize;
@@ -189,6 +202,7 @@ impl super::RenderElement for FsTree {
   ) {
     let font_height = self.font_height;
     let y_offset = self.scroll_offset.y;
+    let color = theme::current().ui.tree_foreground;
     let mut index = 0;
     self.counter = self.tree.walk(&mut |entry| {
       glyph_brush.queue(Section {
@@ -200,7 +214,7 @@ impl super::RenderElement for FsTree {
         layout: Default::default(),
         text: vec![Text::new(&entry.name)
           .with_scale(font_height)
-          .with_color([0.0, 0.9, 0.0, 1.0])],
+          .with_color(color)],
       });
       index += 1;
 
@@ -1,9 +1,22 @@
+pub mod brackets;
+pub mod clipboard;
+pub mod comment;
+pub mod edit;
+pub mod history;
+pub mod indent;
+pub mod lines;
+pub mod pairs;
+
+use crate::renderer::code_view::fold::Folds;
+use crate::renderer::code_view::highlight;
 use crate::renderer::rectangle::{Rectangle, Region};
 use crate::renderer::Dimensions;
+use edit::Recording;
+use std::ops::{Range, RangeInclusive};
 use wgpu_glyph::ab_glyph::{Font, FontArc};
 use wgpu_glyph::{GlyphPositioner, Layout, SectionGeometry, Text};
 use winit::dpi::{PhysicalPosition, PhysicalSize};
-use winit::event::VirtualKeyCode;
+use winit::event::{ModifiersState, VirtualKeyCode};
 
 #[derive(Debug)]
 pub struct Cursor {
@@ -11,6 +24,8 @@ pub struct Cursor {
   pub row: usize,
   pub column: usize,
   pub x_offset: f32,
+  pub selection: Option<(usize, usize)>,
+  pub closers: Vec<usize>,
 }
 
 impl Cursor {
@@ -26,8 +41,198 @@ impl Cursor {
       row: 0,
       column: 0,
       x_offset: 0.0,
+      selection: None,
+      closers: vec![],
+    }
+  }
+
+  pub fn index(&self, rope: &ropey::Rope) -> usize {
+    rope.line_to_char(self.row) + self.column
+  }
+
+  pub fn selection_range(&self, rope: &ropey::Rope) -> Option<Range<usize>> {
+    let (row, column) = self.selection?;
+    let anchor = rope.line_to_char(row) + column;
+    let index = self.index(rope);
+    match anchor.cmp(&index) {
+      std::cmp::Ordering::Less => Some(anchor..index),
+      std::cmp::Ordering::Greater => Some(index..anchor),
+      std::cmp::Ordering::Equal => None,
+    }
+  }
+}
+
+pub fn position(rope: &ropey::Rope, char_idx: usize) -> (usize, usize) {
+  let row = rope.char_to_line(char_idx);
+  (row, char_idx - rope.line_to_char(row))
+}
+
+pub fn word_range(rope: &ropey::Rope, char_idx: usize) -> Option<Range<usize>> {
+  let is_word = |c: char| c.is_alphanumeric() || c == '_';
+  let mut start = char_idx;
+  let mut chars = rope.chars_at(char_idx);
+  while let Some(c) = chars.prev() {
+    if !is_word(c) {
+      break;
+    }
+    start -= 1;
+  }
+  let end =
+    char_idx + rope.chars_at(char_idx).take_while(|c| is_word(*c)).count();
+
+  if start == end {
+    None
+  } else {
+    Some(start..end)
+  }
+}
+
+fn clamp_cursor(rope: &ropey::Rope, cursor: &mut Cursor) {
+  cursor.row = cursor.row.min(rope.len_lines() - 1);
+  cursor.column = cursor.column.min(lines::line_len(rope, cursor.row));
+}
+
+pub fn selected_rows(
+  rope: &ropey::Rope,
+  cursor: &Cursor,
+) -> RangeInclusive<usize> {
+  match cursor.selection_range(rope) {
+    Some(range) => {
+      let start = rope.char_to_line(range.start);
+      let (end, column) = position(rope, range.end);
+      if column == 0 && end > start {
+        start..=(end - 1)
+      } else {
+        start..=end
+      }
+    }
+    None => cursor.row..=cursor.row,
+  }
+}
+
+pub fn apply_edits(
+  rope: &mut Recording,
+  cursor: &mut Cursor,
+  edits: &[edit::Edit],
+) {
+  let index = edit::map_index(edits, cursor.index(rope));
+  let anchor = cursor.selection.map(|(row, column)| {
+    edit::map_index(edits, rope.line_to_char(row) + column)
+  });
+  edit::apply(rope, edits);
+
+  let (row, column) = position(rope, index);
+  cursor.row = row;
+  cursor.column = column;
+  cursor.selection = anchor.map(|anchor| position(rope, anchor));
+}
+
+pub fn is_line_command(key: VirtualKeyCode, modifiers: ModifiersState) -> bool {
+  match key {
+    VirtualKeyCode::Up | VirtualKeyCode::Down => {
+      modifiers.alt() && !modifiers.ctrl()
+    }
+    VirtualKeyCode::D | VirtualKeyCode::K => {
+      modifiers.ctrl() && modifiers.shift()
+    }
+    VirtualKeyCode::J | VirtualKeyCode::Slash => modifiers.ctrl(),
+    VirtualKeyCode::F9 => true,
+    _ => false,
+  }
+}
+
+pub fn is_box_selection_key(
+  key: VirtualKeyCode,
+  modifiers: ModifiersState,
+) -> bool {
+  modifiers.alt()
+    && modifiers.shift()
+    && matches!(
+      key,
+      VirtualKeyCode::Up
+        | VirtualKeyCode::Down
+        | VirtualKeyCode::Left
+        | VirtualKeyCode::Right
+    )
+}
+
+pub fn expand_selection_key(
+  key: VirtualKeyCode,
+  modifiers: ModifiersState,
+) -> Option<bool> {
+  if key == VirtualKeyCode::W && modifiers.ctrl() && !modifiers.alt() {
+    Some(!modifiers.shift())
+  } else {
+    None
+  }
+}
+
+pub fn dedup_lines(rope: &ropey::Rope, cursors: &mut Vec<Cursor>) {
+  let mut touched: Vec<RangeInclusive<usize>> = vec![];
+  cursors.retain(|cursor| {
+    let rows = selected_rows(rope, cursor);
+    let repeated = touched
+      .iter()
+      .any(|other| other.start() <= rows.end() && rows.start() <= other.end());
+    touched.push(rows);
+    !repeated
+  });
+}
+
+pub fn for_each_cursor(
+  rope: &mut Recording,
+  cursors: &mut Vec<Cursor>,
+  mut f: impl FnMut(&mut Recording, &mut Cursor),
+) {
+  let mut order = (0..cursors.len()).collect::<Vec<_>>();
+  order.sort_by_key(|i| std::cmp::Reverse(cursors[*i].index(rope)));
+
+  // char indices of the cursors and their anchors that were already handled
+  let mut done: Vec<(usize, usize, Option<usize>)> = vec![];
+  for i in order {
+    let len = rope.len_chars();
+    f(rope, &mut cursors[i]);
+    let shift = |index: &mut usize| {
+      *index = (*index + rope.len_chars()).saturating_sub(len);
+    };
+    for (_, index, anchor) in &mut done {
+      shift(index);
+      anchor.iter_mut().for_each(shift);
     }
+
+    let cursor = &cursors[i];
+    let anchor = cursor
+      .selection
+      .map(|(row, column)| rope.line_to_char(row) + column);
+    done.push((i, cursor.index(rope), anchor));
+  }
+
+  for (i, index, anchor) in done {
+    let (row, column) = position(rope, index);
+    cursors[i].row = row;
+    cursors[i].column = column;
+    cursors[i].selection = anchor.map(|anchor| position(rope, anchor));
   }
+  let mut seen = std::collections::HashSet::new();
+  cursors.retain(|cursor| seen.insert(cursor.index(rope)));
+}
+
+pub fn next_occurrence(
+  rope: &ropey::Rope,
+  needle: &str,
+  from: usize,
+) -> Option<Range<usize>> {
+  if needle.is_empty() {
+    return None;
+  }
+  let text = rope.to_string();
+  let from = rope.char_to_byte(from);
+  let byte = text[from..]
+    .find(needle)
+    .map(|i| i + from)
+    .or_else(|| text.find(needle))?;
+  let start = rope.byte_to_char(byte);
+  Some(start..start + needle.chars().count())
 }
 
 pub trait TextInput {
@@ -35,6 +240,7 @@ pub trait TextInput {
     &mut self,
     screen_size: PhysicalSize<f32>,
     key: VirtualKeyCode,
+    modifiers: ModifiersState,
   );
   fn input_char(&mut self, screen_size: PhysicalSize<f32>, ch: char);
 }
@@ -110,16 +316,51 @@ pub fn cursor_x_position(
   }
 }
 
+pub fn column_at_x(
+  rope: &ropey::Rope,
+  row: usize,
+  x: f32,
+  font: FontArc,
+  font_height: f32,
+) -> usize {
+  let line = rope.line(row).to_string();
+  let glyphs = Layout::default_wrap().calculate_glyphs(
+    &[font.clone()],
+    &SectionGeometry::default(),
+    &[Text::new(&line).with_scale(font_height)],
+  );
+  // the line is laid out once, each column starting where its glyph does
+  // and the one after the last glyph where that glyph ends
+  (0..=lines::line_len(rope, row))
+    .map(|column| {
+      let column_x = match glyphs.get(column) {
+        Some(glyph) => glyph.glyph.position.x,
+        None => column.checked_sub(1).and_then(|i| glyphs.get(i)).map_or(
+          0.0,
+          |glyph| {
+            glyph.glyph.position.x + font.glyph_bounds(&glyph.glyph).width()
+          },
+        ),
+      };
+      (column, (column_x - x).abs())
+    })
+    .min_by(|(_, a), (_, b)| a.partial_cmp(b).unwrap())
+    .map_or(0, |(column, _)| column)
+}
+
 #[allow(clippy::too_many_arguments)]
 pub fn input_special(
   screen_size: PhysicalSize<f32>,
   key: VirtualKeyCode,
-  rope: &mut ropey::Rope,
+  modifiers: ModifiersState,
+  rope: &mut Recording,
   cursor: &mut Cursor,
   font: FontArc,
   font_height: f32,
   offset: PhysicalPosition<f32>,
   scroll_offset: PhysicalPosition<f32>,
+  syntax: Option<&highlight::Config>,
+  folds: &Folds,
 ) {
   let cursor_x_pos = |row: usize, column: usize| {
     cursor_x_position(
@@ -132,10 +373,51 @@ pub fn input_special(
     )
   };
 
+  let update_x_offset = |rope: &ropey::Rope, cursor: &mut Cursor| {
+    cursor.x_offset = cursor_x_position(
+      cursor.row,
+      cursor.column,
+      rope,
+      font.clone(),
+      font_height,
+      scroll_offset,
+    )
+    .unwrap_or_default();
+  };
+
+  if matches!(
+    key,
+    VirtualKeyCode::Up
+      | VirtualKeyCode::Left
+      | VirtualKeyCode::Down
+      | VirtualKeyCode::Right
+  ) && !modifiers.alt()
+  {
+    if !modifiers.shift() {
+      cursor.selection = None;
+    } else if cursor.selection.is_none() {
+      cursor.selection = Some((cursor.row, cursor.column));
+    }
+  }
+
   match key {
+    VirtualKeyCode::Up | VirtualKeyCode::Down
+      if modifiers.alt() && !modifiers.ctrl() =>
+    {
+      let up = key == VirtualKeyCode::Up;
+      let rows = selected_rows(rope, cursor);
+      if let Some(edit) = lines::move_lines(rope, rows, up) {
+        edit::apply(rope, &[edit]);
+        let shift = |row: usize| if up { row - 1 } else { row + 1 };
+        cursor.row = shift(cursor.row);
+        cursor.selection =
+          cursor.selection.map(|(row, column)| (shift(row), column));
+        update_x_offset(rope, cursor);
+      }
+    }
     VirtualKeyCode::Up => {
       if cursor.row != 0 {
-        cursor.row -= 1;
+        cursor.row = folds.shown_row(cursor.row - 1);
         if let Some(offset) = cursor_x_pos(cursor.row, cursor.column) {
           cursor.x_offset = offset;
         } else {
@@ -153,7 +435,7 @@ pub fn input_special(
         (0, 0) => {}
         (_, 0) => {
           // TODO: https://github.com/cessen/ropey/issues/44
-          cursor.row -= 1;
+          cursor.row = folds.shown_row(cursor.row - 1);
           cursor.column = rope.line(cursor.row).len_chars() - 1;
           cursor.x_offset =
             cursor_x_pos(cursor.row, cursor.column).unwrap_or_default();
@@ -165,8 +447,9 @@ pub fn input_special(
       }
     }
     VirtualKeyCode::Down => {
-      if cursor.row != (rope.len_lines() - 1) {
-        cursor.row += 1;
+      let below = folds.next_shown_row(cursor.row + 1);
+      if below < rope.len_lines() {
+        cursor.row = below;
         if let Some(offset) = cursor_x_pos(cursor.row, cursor.column) {
           cursor.x_offset = offset;
         } else {
@@ -181,86 +464,362 @@ pub fn input_special(
       }
     }
     VirtualKeyCode::Right => {
-      if cursor.row != (rope.len_lines() - 1) {
+      let below = folds.next_shown_row(cursor.row + 1);
+      if below < rope.len_lines() {
         if let Some(offset) = cursor_x_pos(cursor.row, cursor.column + 1) {
           cursor.column += 1;
           cursor.x_offset = offset;
         } else {
           cursor.x_offset = 0.0;
           cursor.column = 0;
-          cursor.row += 1;
+          cursor.row = below;
         }
       } else if let Some(offset) = cursor_x_pos(cursor.row, cursor.column + 1) {
         cursor.column += 1;
         cursor.x_offset = offset;
       }
     }
+    VirtualKeyCode::Backslash if modifiers.ctrl() && modifiers.shift() => {
+      let tree = syntax.and_then(|syntax| syntax.tree.as_ref());
+      let index = cursor.index(rope);
+      if let Some((_, matching)) = brackets::matching_bracket(rope, index, tree)
+      {
+        let (row, column) = position(rope, matching);
+        cursor.selection = None;
+        cursor.row = row;
+        cursor.column = column;
+        cursor.x_offset = cursor_x_pos(row, column).unwrap_or_default();
+      }
+    }
+    VirtualKeyCode::Slash if modifiers.ctrl() => {
+      let tokens = match syntax {
+        Some(syntax) => &syntax.language.comment_tokens,
+        None => return,
+      };
+      let rows = selected_rows(rope, cursor);
+      let edits = comment::toggle_line_comment(rope, rows, tokens);
+      apply_edits(rope, cursor, &edits);
+      update_x_offset(rope, cursor);
+    }
+    VirtualKeyCode::A if modifiers.ctrl() && modifiers.shift() => {
+      let tokens = match syntax {
+        Some(syntax) => &syntax.language.comment_tokens,
+        None => return,
+      };
+      let range = cursor.selection_range(rope).unwrap_or_else(|| {
+        let start = rope.line_to_char(cursor.row);
+        let line = rope.line(cursor.row);
+        let len = line.len_chars()
+          - line.chars().last().map_or(0, |c| (c == '\n') as usize);
+        start..start + len
+      });
+      let edits = comment::toggle_block_comment(rope, range, tokens);
+      apply_edits(rope, cursor, &edits);
+      update_x_offset(rope, cursor);
+    }
+    VirtualKeyCode::D if modifiers.ctrl() && modifiers.shift() => {
+      let edit = lines::duplicate_lines(rope, selected_rows(rope, cursor));
+      apply_edits(rope, cursor, &[edit]);
+      update_x_offset(rope, cursor);
+    }
+    VirtualKeyCode::K if modifiers.ctrl() && modifiers.shift() => {
+      let rows = selected_rows(rope, cursor);
+      let edit = lines::delete_lines(rope, rows.clone());
+      edit::apply(rope, &[edit]);
+      cursor.selection = None;
+      cursor.row = *rows.start();
+      clamp_cursor(rope, cursor);
+      update_x_offset(rope, cursor);
+    }
+    VirtualKeyCode::J if modifiers.ctrl() => {
+      let edit = lines::join_lines(rope, selected_rows(rope, cursor));
+      apply_edits(rope, cursor, &[edit]);
+      update_x_offset(rope, cursor);
+    }
+    VirtualKeyCode::F9 => {
+      let rows = match cursor.selection_range(rope) {
+        Some(_) => selected_rows(rope, cursor),
+        None => match lines::paragraph(rope, cursor.row) {
+          Some(rows) => rows,
+          None => return,
+        },
+      };
+      let edit = lines::sort_lines(rope, rows, modifiers.shift());
+      edit::apply(rope, &[edit]);
+      cursor.selection = None;
+      clamp_cursor(rope, cursor);
+      update_x_offset(rope, cursor);
+    }
+    VirtualKeyCode::U
+    | VirtualKeyCode::L
+    | VirtualKeyCode::T
+    | VirtualKeyCode::S
+    | VirtualKeyCode::C
+      if modifiers.alt() && modifiers.shift() && !modifiers.ctrl() =>
+    {
+      let case = match key {
+        VirtualKeyCode::U => lines::Case::Upper,
+        VirtualKeyCode::L => lines::Case::Lower,
+        VirtualKeyCode::T => lines::Case::Title,
+        VirtualKeyCode::S => lines::Case::Snake,
+        _ => lines::Case::Camel,
+      };
+      let range = match cursor
+        .selection_range(rope)
+        .or_else(|| word_range(rope, cursor.index(rope)))
+      {
+        Some(range) => range,
+        None => return,
+      };
+      let text = rope.slice(range.clone()).to_string();
+      let edit = edit::Edit {
+        range,
+        text: lines::transform_case(&text, case),
+      };
+      apply_edits(rope, cursor, &[edit]);
+      update_x_offset(rope, cursor);
+    }
     _ => return,
   }
 
+  resize_cursor_rect(screen_size, cursor, font_height, offset, scroll_offset);
+}
+
+fn resize_cursor_rect(
+  screen_size: PhysicalSize<f32>,
+  cursor: &mut Cursor,
+  font_height: f32,
+  offset: PhysicalPosition<f32>,
+  scroll_offset: PhysicalPosition<f32>,
+) {
   cursor.rect.resize(
     screen_size,
     Dimensions {
       x: offset.x + scroll_offset.x + cursor.x_offset,
-      y: scroll_offset.y + font_height + (cursor.row as f32 * font_height),
+      y: offset.y + scroll_offset.y + (cursor.row as f32 * font_height),
       ..cursor.rect.dimensions
     },
   );
 }
 
+#[allow(clippy::too_many_arguments)]
+pub fn move_cursor(
+  screen_size: PhysicalSize<f32>,
+  rope: &ropey::Rope,
+  cursor: &mut Cursor,
+  row: usize,
+  column: usize,
+  font: FontArc,
+  font_height: f32,
+  offset: PhysicalPosition<f32>,
+  scroll_offset: PhysicalPosition<f32>,
+) {
+  cursor.row = row;
+  cursor.column = column;
+  cursor.x_offset =
+    cursor_x_position(row, column, rope, font, font_height, scroll_offset)
+      .unwrap_or_default();
+  resize_cursor_rect(screen_size, cursor, font_height, offset, scroll_offset);
+}
+
+fn delete_selection(rope: &mut Recording, cursor: &mut Cursor) -> bool {
+  let range = cursor.selection_range(rope);
+  cursor.selection = None;
+  if let Some(range) = range {
+    let (row, column) = position(rope, range.start);
+    rope.remove(range);
+    cursor.row = row;
+    cursor.column = column;
+    true
+  } else {
+    false
+  }
+}
+
+pub fn insert_text(rope: &mut Recording, cursor: &mut Cursor, text: &str) {
+  delete_selection(rope, cursor);
+  let index = cursor.index(rope);
+  rope.insert(index, text);
+  let (row, column) = position(rope, index + text.chars().count());
+  cursor.row = row;
+  cursor.column = column;
+}
+
 #[allow(clippy::too_many_arguments)]
 pub fn input_char(
   screen_size: PhysicalSize<f32>,
   ch: char,
-  rope: &mut ropey::Rope,
+  rope: &mut Recording,
   cursor: &mut Cursor,
   font: FontArc,
   font_height: f32,
   offset: PhysicalPosition<f32>,
   scroll_offset: PhysicalPosition<f32>,
+  syntax: Option<&highlight::Config>,
+  folds: &Folds,
 ) -> f32 {
+  let tree = syntax.and_then(|syntax| syntax.tree.as_ref());
+  let pairs =
+    syntax.map_or(pairs::DEFAULT_PAIRS, |syntax| &syntax.language.auto_pairs);
+
+  let move_to =
+    |text: &ropey::Rope, cursor: &mut Cursor, row: usize, column: usize| {
+      move_cursor(
+        screen_size,
+        text,
+        cursor,
+        row,
+        column,
+        font.clone(),
+        font_height,
+        offset,
+        scroll_offset,
+      );
+    };
   let input_spc =
-    |key: VirtualKeyCode, text: &mut ropey::Rope, cursor: &mut Cursor| {
+    |key: VirtualKeyCode, text: &mut Recording, cursor: &mut Cursor| {
       input_special(
         screen_size,
         key,
+        ModifiersState::empty(),
         text,
         cursor,
         font.clone(),
         font_height,
         offset,
         scroll_offset,
+        syntax,
+        folds,
       );
     };
 
   match ch {
     // backspace
     '\u{7f}' => match (cursor.row, cursor.column) {
+      _ if delete_selection(rope, cursor) => {
+        cursor.closers.clear();
+        move_to(rope, cursor, cursor.row, cursor.column);
+      }
       (0, 0) => {}
       (row, 0) => {
         // TODO: https://github.com/cessen/ropey/issues/44
         let ln = rope.line_to_char(row);
+        cursor.closers.clear();
         input_spc(VirtualKeyCode::Left, rope, cursor);
         rope.remove((ln - 1)..ln);
       }
       (row, column) => {
         let index = rope.line_to_char(row) + column;
-        rope.remove((index - 1)..index);
+        if pairs::in_empty_pair(rope, index, pairs, &cursor.closers) {
+          rope.remove((index - 1)..(index + 1));
+          cursor.closers.pop();
+          cursor.closers.iter_mut().for_each(|d| *d -= 1);
+        } else {
+          rope.remove((index - 1)..index);
+        }
         input_spc(VirtualKeyCode::Left, rope, cursor);
       }
     },
     // enter
     '\r' => {
-      rope.insert_char(rope.line_to_char(cursor.row) + cursor.column, '\n');
-      input_spc(VirtualKeyCode::Right, rope, cursor);
+      cursor.closers.clear();
+      delete_selection(rope, cursor);
+      let index = cursor.index(rope);
+      let newline = indent::newline_indent(rope, index, tree);
+      rope.insert(index, &format!("\n{}", newline.indent));
+      if let Some(closing) = newline.closing {
+        let index = index + 1 + newline.indent.chars().count();
+        rope.insert(index, &format!("\n{}", closing));
+      }
+      move_to(rope, cursor, cursor.row + 1, newline.indent.chars().count());
     }
     // esc
-    '\u{1b}' => {}
+    '\u{1b}' => cursor.selection = None,
+    // remaining control characters, such as those sent alongside shortcuts
+    c if c.is_control() && c != '\t' => {}
     _ => {
-      rope.insert_char(rope.line_to_char(cursor.row) + cursor.column, ch);
-      input_spc(VirtualKeyCode::Right, rope, cursor);
+      let wrap = cursor.selection_range(rope).zip(pairs::closing(pairs, ch));
+      if let Some((range, close)) = wrap {
+        // wrap the selection, keeping the wrapped text selected
+        cursor.closers.clear();
+        let cursor_at_start = cursor.index(rope) == range.start;
+        rope.insert_char(range.end, close);
+        rope.insert_char(range.start, ch);
+        let (start, end) = (range.start + 1, range.end + 1);
+        let (anchor_index, cursor_index) = if cursor_at_start {
+          (end, start)
+        } else {
+          (start, end)
+        };
+        cursor.selection = Some(position(rope, anchor_index));
+        let (row, column) = position(rope, cursor_index);
+        move_to(rope, cursor, row, column);
+      } else {
+        if delete_selection(rope, cursor) {
+          cursor.closers.clear();
+        }
+        let index = cursor.index(rope);
+        let in_literal =
+          syntax.map_or(false, |syntax| syntax.in_string_or_comment(index));
+
+        if pairs::skips_closing(rope, index, ch, &cursor.closers) {
+          cursor.closers.pop();
+          cursor.closers.iter_mut().for_each(|d| *d -= 1);
+          move_to(rope, cursor, cursor.row, cursor.column + 1);
+        } else if let Some(indent) =
+          indent::closing_indent(rope, index, ch, tree)
+        {
+          cursor.closers.clear();
+          let line_start = rope.line_to_char(cursor.row);
+          rope.remove(line_start..index);
+          rope.insert(line_start, &indent);
+          rope.insert_char(line_start + indent.chars().count(), ch);
+          move_to(rope, cursor, cursor.row, indent.chars().count() + 1);
+        } else if let Some(close) =
+          pairs::auto_close(rope, index, ch, pairs).filter(|_| !in_literal)
+        {
+          rope.insert(index, &format!("{}{}", ch, close));
+          cursor.closers.iter_mut().for_each(|d| *d += 1);
+          cursor.closers.push(0);
+          move_to(rope, cursor, cursor.row, cursor.column + 1);
+        } else {
+          rope.insert_char(index, ch);
+          input_spc(VirtualKeyCode::Right, rope, cursor);
+        }
+      }
     }
   }
 
   max_line_length(rope.lines().map(|l| l.to_string()), font, font_height)
 }
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn finds_next_occurrence() {
+    let rope = ropey::Rope::from_str("fö a fö b fö");
+    assert_eq!(next_occurrence(&rope, "fö", 1), Some(5..7));
+    assert_eq!(next_occurrence(&rope, "fö", 11), Some(0..2));
+    assert_eq!(next_occurrence(&rope, "x", 0), None);
+  }
+
+  #[test]
+  fn widens_box_selection_with_alt_shift() {
+    let alt_shift = ModifiersState::ALT | ModifiersState::SHIFT;
+    for key in [VirtualKeyCode::Left, VirtualKeyCode::Right] {
+      assert!(is_box_selection_key(key, alt_shift));
+      assert!(is_box_selection_key(key, alt_shift | ModifiersState::CTRL));
+      assert_eq!(expand_selection_key(key, alt_shift), None);
+    }
+    let ctrl = ModifiersState::CTRL;
+    assert_eq!(expand_selection_key(VirtualKeyCode::W, ctrl), Some(true));
+    let ctrl_shift = ctrl | ModifiersState::SHIFT;
+    assert_eq!(
+      expand_selection_key(VirtualKeyCode::W, ctrl_shift),
+      Some(false)
+    );
+    assert!(!is_box_selection_key(VirtualKeyCode::W, ctrl_shift));
+  }
+}
@@ -0,0 +1,186 @@
+use tree_sitter::Tree;
+
+pub const BRACKETS: [(char, char); 3] = [('(', ')'), ('[', ']'), ('{', '}')];
+
+pub fn opening_bracket(closing: char) -> Option<char> {
+  BRACKETS
+    .iter()
+    .find(|(_, close)| *close == closing)
+    .map(|(open, _)| *open)
+}
+
+pub fn closing_bracket(opening: char) -> Option<char> {
+  BRACKETS
+    .iter()
+    .find(|(open, _)| *open == opening)
+    .map(|(_, close)| *close)
+}
+
+// brackets in strings and comments aren't tokens of their own in the tree
+fn is_token(rope: &ropey::Rope, char_idx: usize, c: char, tree: &Tree) -> bool {
+  let byte = rope.char_to_byte(char_idx);
+  tree
+    .root_node()
+    .descendant_for_byte_range(byte, byte + c.len_utf8())
+    .map_or(false, |node| {
+      node.start_byte() == byte && node.kind() == c.to_string()
+    })
+}
+
+pub fn scan_backward(
+  rope: &ropey::Rope,
+  char_idx: usize,
+  close: char,
+  tree: Option<&Tree>,
+) -> Option<usize> {
+  let open = opening_bracket(close)?;
+  let mut depth = 0usize;
+  let mut index = char_idx;
+  let mut chars = rope.chars_at(char_idx);
+  while let Some(c) = chars.prev() {
+    index -= 1;
+    if (c == close || c == open)
+      && tree.map_or(false, |tree| !is_token(rope, index, c, tree))
+    {
+      continue;
+    }
+    if c == close {
+      depth += 1;
+    } else if c == open {
+      if depth == 0 {
+        return Some(index);
+      }
+      depth -= 1;
+    }
+  }
+  None
+}
+
+pub fn scan_forward(
+  rope: &ropey::Rope,
+  char_idx: usize,
+  open: char,
+) -> Option<usize> {
+  let close = closing_bracket(open)?;
+  let mut depth = 0usize;
+  for (i, c) in rope.chars_at(char_idx).enumerate() {
+    if c == open {
+      depth += 1;
+    } else if c == close {
+      if depth == 0 {
+        return Some(char_idx + i);
+      }
+      depth -= 1;
+    }
+  }
+  None
+}
+
+fn match_in_tree(
+  rope: &ropey::Rope,
+  char_idx: usize,
+  tree: &Tree,
+) -> Option<usize> {
+  let ch = rope.char(char_idx);
+  let byte = rope.char_to_byte(char_idx);
+  let node = tree
+    .root_node()
+    .descendant_for_byte_range(byte, byte + ch.len_utf8())?;
+  if node.start_byte() != byte || node.kind() != ch.to_string() {
+    return None;
+  }
+  let parent = node.parent()?;
+
+  let (target, forward) = if let Some(close) = closing_bracket(ch) {
+    (close.to_string(), true)
+  } else {
+    (opening_bracket(ch)?.to_string(), false)
+  };
+
+  let mut cursor = parent.walk();
+  let mut siblings = parent.children(&mut cursor).collect::<Vec<_>>();
+  let position = siblings.iter().position(|sibling| *sibling == node)?;
+  if !forward {
+    siblings.reverse();
+  }
+  let start = if forward {
+    position + 1
+  } else {
+    siblings.len() - position
+  };
+
+  let mut depth = 0usize;
+  for sibling in &siblings[start..] {
+    if sibling.kind() == node.kind() {
+      depth += 1;
+    } else if sibling.kind() == target {
+      if depth == 0 {
+        if sibling.is_missing() {
+          return None;
+        }
+        return Some(rope.byte_to_char(sibling.start_byte()));
+      }
+      depth -= 1;
+    }
+  }
+  None
+}
+
+pub fn matching_bracket(
+  rope: &ropey::Rope,
+  char_idx: usize,
+  tree: Option<&Tree>,
+) -> Option<(usize, usize)> {
+  let is_bracket = |c: char| {
+    BRACKETS
+      .iter()
+      .any(|(open, close)| *open == c || *close == c)
+  };
+
+  let bracket = match rope.get_char(char_idx) {
+    Some(c) if is_bracket(c) => char_idx,
+    _ if char_idx != 0 && is_bracket(rope.char(char_idx - 1)) => char_idx - 1,
+    _ => return None,
+  };
+
+  let matching = if let Some(tree) = tree {
+    match_in_tree(rope, bracket, tree)
+  } else {
+    let ch = rope.char(bracket);
+    if closing_bracket(ch).is_some() {
+      scan_forward(rope, bracket + 1, ch)
+    } else {
+      scan_backward(rope, bracket, ch, None)
+    }
+  };
+
+  matching.map(|matching| (bracket, matching))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn matches_by_nesting() {
+    let rope = ropey::Rope::from_str("a(b[c](d))e\n");
+    assert_eq!(matching_bracket(&rope, 1, None), Some((1, 9)));
+    assert_eq!(matching_bracket(&rope, 10, None), Some((9, 1)));
+    assert_eq!(matching_bracket(&rope, 6, None), Some((6, 8)));
+    assert_eq!(matching_bracket(&rope, 0, None), None);
+  }
+
+  #[test]
+  fn matches_by_syntax_tree() {
+    let text = "fn a() { b(\")\"); }\n";
+    let rope = ropey::Rope::from_str(text);
+    let mut parser = tree_sitter::Parser::new();
+    parser.set_language(tree_sitter_rust::language()).unwrap();
+    let tree = parser.parse(text, None).unwrap();
+
+    assert_eq!(matching_bracket(&rope, 10, Some(&tree)), Some((10, 14)));
+    assert_eq!(matching_bracket(&rope, 15, Some(&tree)), Some((14, 10)));
+    assert_eq!(matching_bracket(&rope, 7, Some(&tree)), Some((7, 17)));
+    assert_eq!(matching_bracket(&rope, 12, Some(&tree)), None);
+  }
+}
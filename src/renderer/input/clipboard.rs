@@ -0,0 +1,166 @@
+use std::cell::RefCell;
+use std::io::Write;
+use std::process::{Command, Stdio};
+use std::sync::{Arc, Mutex};
+
+thread_local! {
+  static CLIPBOARD: RefCell<Clipboard> = RefCell::new(Clipboard::new(tool()));
+}
+
+/// Commands writing their input to the system clipboard and printing it.
+type Tool = (&'static [&'static str], &'static [&'static str]);
+
+/// Clipboard tools of the platform, the first one found being used.
+#[cfg(target_os = "macos")]
+const TOOLS: &[Tool] = &[(&["pbcopy"], &["pbpaste"])];
+#[cfg(windows)]
+const TOOLS: &[Tool] = &[(
+  &["clip"],
+  &["powershell", "-NoProfile", "-Command", "Get-Clipboard -Raw"],
+)];
+#[cfg(not(any(target_os = "macos", windows)))]
+const TOOLS: &[Tool] = &[
+  (&["wl-copy"], &["wl-paste", "--no-newline"]),
+  (
+    &["xclip", "-selection", "clipboard"],
+    &["xclip", "-selection", "clipboard", "-o"],
+  ),
+  (
+    &["xsel", "--clipboard", "--input"],
+    &["xsel", "--clipboard", "--output"],
+  ),
+];
+
+fn tool() -> Option<&'static Tool> {
+  let wayland = std::env::var_os("WAYLAND_DISPLAY").is_some();
+  TOOLS
+    .iter()
+    .find(|(copy, _)| (wayland || copy[0] != "wl-copy") && which(copy[0]))
+}
+
+fn which(program: &str) -> bool {
+  let paths = std::env::var_os("PATH").unwrap_or_default();
+  std::env::split_paths(&paths).any(|dir| {
+    let path = dir.join(program);
+    path.is_file() || path.with_extension("exe").is_file()
+  })
+}
+
+fn command(command: &[&str]) -> Command {
+  let mut cmd = Command::new(command[0]);
+  cmd.args(&command[1..]).stderr(Stdio::null());
+  cmd
+}
+
+/// Text copied by the last copy or cut, one piece per cursor that had a
+/// selection, and the text of the system clipboard as last read.
+struct Clipboard {
+  tool: Option<&'static Tool>,
+  pieces: Vec<String>,
+  // the system text and the number of copies made before it was read, so
+  // that a read finishing after a copy doesn't replace the copied text
+  system: Arc<Mutex<(usize, Option<String>)>>,
+}
+
+impl Clipboard {
+  fn new(tool: Option<&'static Tool>) -> Self {
+    let clipboard = Self {
+      tool,
+      pieces: vec![],
+      system: Arc::new(Mutex::new((0, None))),
+    };
+    clipboard.refresh();
+    clipboard
+  }
+
+  fn set(&mut self, pieces: Vec<String>) {
+    let mut system = self.system.lock().unwrap();
+    *system = (system.0 + 1, None);
+    if let Some((copy, _)) = self.tool {
+      let text = pieces.join("\n");
+      std::thread::spawn(move || {
+        let written = command(copy)
+          .stdin(Stdio::piped())
+          .stdout(Stdio::null())
+          .spawn()
+          .and_then(|mut child| {
+            child.stdin.take().unwrap().write_all(text.as_bytes())?;
+            child.wait()
+          });
+        if let Err(err) = written {
+          log::warn!("failed to copy with {}: {}", copy[0], err);
+        }
+      });
+    }
+    self.pieces = pieces;
+  }
+
+  // reads the system clipboard in the background, for the pastes after it
+  fn refresh(&self) {
+    let paste = match self.tool {
+      Some((_, paste)) => *paste,
+      None => return,
+    };
+    let system = Arc::clone(&self.system);
+    let copies = system.lock().unwrap().0;
+    std::thread::spawn(move || {
+      let output = command(paste).stdin(Stdio::null()).output();
+      let text = match output {
+        Ok(output) if output.status.success() => {
+          String::from_utf8(output.stdout).ok()
+        }
+        _ => None,
+      };
+      let text = text.map(|text| match text.strip_suffix("\r\n") {
+        Some(text) if cfg!(windows) => text.to_string(),
+        _ => text,
+      });
+      let mut system = system.lock().unwrap();
+      if system.0 == copies {
+        system.1 = text;
+      }
+    });
+  }
+
+  fn pieces_for(&self, cursors: usize) -> Vec<String> {
+    let pieces = match &self.system.lock().unwrap().1 {
+      Some(text) if *text != self.pieces.join("\n") => vec![text.clone()],
+      _ => self.pieces.clone(),
+    };
+    if pieces.len() == cursors {
+      pieces
+    } else {
+      vec![pieces.join("\n"); cursors]
+    }
+  }
+}
+
+pub fn set(pieces: Vec<String>) {
+  CLIPBOARD.with(|clipboard| clipboard.borrow_mut().set(pieces));
+}
+
+/// Picks up text copied in other programs, when the window gets the focus.
+pub fn refresh() {
+  CLIPBOARD.with(|clipboard| clipboard.borrow().refresh());
+}
+
+pub fn pieces_for(cursors: usize) -> Vec<String> {
+  CLIPBOARD.with(|clipboard| clipboard.borrow().pieces_for(cursors))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn distributes_pieces() {
+    let mut clipboard = Clipboard::new(None);
+    clipboard.set(vec![String::from("a"), String::from("b")]);
+    assert_eq!(clipboard.pieces_for(2), vec!["a", "b"]);
+    assert_eq!(clipboard.pieces_for(1), vec!["a\nb"]);
+    assert_eq!(clipboard.pieces_for(3), vec!["a\nb"; 3]);
+
+    clipboard.system.lock().unwrap().1 = Some(String::from("c"));
+    assert_eq!(clipboard.pieces_for(2), vec!["c", "c"]);
+  }
+}
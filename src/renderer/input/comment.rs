@@ -0,0 +1,210 @@
+use super::edit::Edit;
+use super::indent::line_indent;
+use std::ops::{Range, RangeInclusive};
+
+#[derive(Clone, Debug, Default)]
+pub struct CommentTokens {
+  pub line: Option<String>,
+  pub block: Option<(String, String)>,
+}
+
+impl CommentTokens {
+  pub fn new(line: Option<&str>, block: Option<(&str, &str)>) -> Self {
+    Self {
+      line: line.map(String::from),
+      block: block.map(|(open, close)| (open.to_string(), close.to_string())),
+    }
+  }
+
+  pub fn c_like() -> Self {
+    Self::new(Some("//"), Some(("/*", "*/")))
+  }
+
+  pub fn hash() -> Self {
+    Self::new(Some("#"), None)
+  }
+
+  pub fn ocaml() -> Self {
+    Self::new(None, Some(("(*", "*)")))
+  }
+
+  fn block(&self) -> Option<(&str, &str)> {
+    self
+      .block
+      .as_ref()
+      .map(|(open, close)| (open.as_str(), close.as_str()))
+  }
+}
+
+fn line_content(rope: &ropey::Rope, row: usize) -> String {
+  let line = rope.line(row).to_string();
+  line.trim_end_matches(&['\n', '\r'][..]).to_string()
+}
+
+pub fn toggle_line_comment(
+  rope: &ropey::Rope,
+  rows: RangeInclusive<usize>,
+  tokens: &CommentTokens,
+) -> Vec<Edit> {
+  let lines = rows
+    .map(|row| (row, line_content(rope, row)))
+    .filter(|(_, line)| !line.trim().is_empty())
+    .collect::<Vec<_>>();
+
+  if let Some(token) = tokens.line.as_deref() {
+    let commented = lines
+      .iter()
+      .all(|(_, line)| line.trim_start().starts_with(token));
+
+    if commented {
+      lines
+        .iter()
+        .map(|(row, line)| {
+          let start = rope.line_to_char(*row) + line_indent(rope, *row).len();
+          let mut len = token.chars().count();
+          if line.trim_start()[token.len()..].starts_with(' ') {
+            len += 1;
+          }
+          Edit::remove(start..start + len)
+        })
+        .collect()
+    } else {
+      let column = lines
+        .iter()
+        .map(|(row, _)| line_indent(rope, *row).chars().count())
+        .min()
+        .unwrap_or_default();
+      lines
+        .iter()
+        .map(|(row, _)| {
+          Edit::insert(rope.line_to_char(*row) + column, format!("{} ", token))
+        })
+        .collect()
+    }
+  } else if let Some((open, close)) = tokens.block() {
+    let commented = lines.iter().all(|(_, line)| {
+      let trimmed = line.trim();
+      trimmed.starts_with(open) && trimmed.ends_with(close)
+    });
+
+    lines
+      .iter()
+      .flat_map(|(row, line)| {
+        let start = rope.line_to_char(*row) + line_indent(rope, *row).len();
+        let end = rope.line_to_char(*row) + line.trim_end().chars().count();
+        if commented {
+          block_uncomment_edits(rope, start..end, open, close)
+        } else {
+          vec![
+            Edit::insert(start, format!("{} ", open)),
+            Edit::insert(end, format!(" {}", close)),
+          ]
+        }
+      })
+      .collect()
+  } else {
+    vec![]
+  }
+}
+
+fn block_uncomment_edits(
+  rope: &ropey::Rope,
+  range: Range<usize>,
+  open: &str,
+  close: &str,
+) -> Vec<Edit> {
+  let open_len = open.chars().count();
+  let close_len = close.chars().count();
+  let mut open_end = range.start + open_len;
+  if rope.get_char(open_end) == Some(' ') && open_end < range.end - close_len {
+    open_end += 1;
+  }
+  let mut close_start = range.end - close_len;
+  if close_start > open_end && rope.get_char(close_start - 1) == Some(' ') {
+    close_start -= 1;
+  }
+
+  vec![
+    Edit::remove(range.start..open_end),
+    Edit::remove(close_start..range.end),
+  ]
+}
+
+pub fn toggle_block_comment(
+  rope: &ropey::Rope,
+  range: Range<usize>,
+  tokens: &CommentTokens,
+) -> Vec<Edit> {
+  let (open, close) = match tokens.block() {
+    Some(block) => block,
+    None => {
+      let start_row = rope.char_to_line(range.start);
+      let end_row = rope.char_to_line(range.end);
+      return toggle_line_comment(rope, start_row..=end_row, tokens);
+    }
+  };
+
+  let text = rope.slice(range.clone()).to_string();
+  let start =
+    range.start + (text.chars().count() - text.trim_start().chars().count());
+  let end = range.start + text.trim_end().chars().count();
+  if start >= end {
+    return vec![];
+  }
+
+  let trimmed = text.trim();
+  if trimmed.starts_with(open)
+    && trimmed.ends_with(close)
+    && trimmed.len() >= open.len() + close.len()
+  {
+    block_uncomment_edits(rope, start..end, open, close)
+  } else {
+    vec![
+      Edit::insert(start, format!("{} ", open)),
+      Edit::insert(end, format!(" {}", close)),
+    ]
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::super::edit::{apply, Recording};
+  use super::*;
+
+  #[test]
+  fn toggles_line_comments() {
+    let mut rope = ropey::Rope::from_str("  a\n\n    b\n");
+    let edits = toggle_line_comment(&rope, 0..=2, &CommentTokens::c_like());
+    apply(&mut Recording::new(&mut rope), &edits);
+    assert_eq!(rope.to_string(), "  // a\n\n  //   b\n");
+
+    let edits = toggle_line_comment(&rope, 0..=2, &CommentTokens::c_like());
+    apply(&mut Recording::new(&mut rope), &edits);
+    assert_eq!(rope.to_string(), "  a\n\n    b\n");
+
+    let mut rope = ropey::Rope::from_str("let x = 1\n");
+    let edits = toggle_line_comment(&rope, 0..=0, &CommentTokens::ocaml());
+    apply(&mut Recording::new(&mut rope), &edits);
+    assert_eq!(rope.to_string(), "(* let x = 1 *)\n");
+    let edits = toggle_line_comment(&rope, 0..=0, &CommentTokens::ocaml());
+    apply(&mut Recording::new(&mut rope), &edits);
+    assert_eq!(rope.to_string(), "let x = 1\n");
+  }
+
+  #[test]
+  fn toggles_block_comments() {
+    let mut rope = ropey::Rope::from_str("a(b, c)\n");
+    let edits = toggle_block_comment(&rope, 2..6, &CommentTokens::c_like());
+    apply(&mut Recording::new(&mut rope), &edits);
+    assert_eq!(rope.to_string(), "a(/* b, c */)\n");
+
+    let edits = toggle_block_comment(&rope, 2..12, &CommentTokens::c_like());
+    apply(&mut Recording::new(&mut rope), &edits);
+    assert_eq!(rope.to_string(), "a(b, c)\n");
+
+    let mut rope = ropey::Rope::from_str("x = 1\n");
+    let edits = toggle_block_comment(&rope, 0..5, &CommentTokens::hash());
+    apply(&mut Recording::new(&mut rope), &edits);
+    assert_eq!(rope.to_string(), "# x = 1\n");
+  }
+}
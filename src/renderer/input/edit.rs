@@ -0,0 +1,131 @@
+use std::ops::{Deref, Range};
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Edit {
+  pub range: Range<usize>,
+  pub text: String,
+}
+
+impl Edit {
+  pub fn insert(index: usize, text: impl Into<String>) -> Self {
+    Self {
+      range: index..index,
+      text: text.into(),
+    }
+  }
+
+  pub fn remove(range: Range<usize>) -> Self {
+    Self {
+      range,
+      text: String::new(),
+    }
+  }
+}
+
+/// Rope being edited, which keeps the edits made to it in the order they
+/// are made, each in char indices of the text as it was then, so that they
+/// can be passed on to language servers.
+pub struct Recording<'a> {
+  rope: &'a mut ropey::Rope,
+  pub edits: Vec<Edit>,
+}
+
+impl<'a> Recording<'a> {
+  pub fn new(rope: &'a mut ropey::Rope) -> Self {
+    Self {
+      rope,
+      edits: vec![],
+    }
+  }
+
+  pub fn insert(&mut self, char_idx: usize, text: &str) {
+    if !text.is_empty() {
+      self.rope.insert(char_idx, text);
+      self.edits.push(Edit::insert(char_idx, text));
+    }
+  }
+
+  pub fn insert_char(&mut self, char_idx: usize, ch: char) {
+    self.insert(char_idx, ch.encode_utf8(&mut [0; 4]));
+  }
+
+  pub fn remove(&mut self, range: Range<usize>) {
+    if !range.is_empty() {
+      self.rope.remove(range.clone());
+      self.edits.push(Edit::remove(range));
+    }
+  }
+}
+
+impl Deref for Recording<'_> {
+  type Target = ropey::Rope;
+
+  fn deref(&self) -> &ropey::Rope {
+    self.rope
+  }
+}
+
+pub fn apply(rope: &mut Recording, edits: &[Edit]) {
+  let mut edits = edits.iter().collect::<Vec<_>>();
+  edits.sort_by_key(|edit| std::cmp::Reverse(edit.range.start));
+  for edit in edits {
+    rope.remove(edit.range.clone());
+    rope.insert(edit.range.start, &edit.text);
+  }
+}
+
+pub fn map_index(edits: &[Edit], index: usize) -> usize {
+  let mut mapped = index;
+  for edit in edits {
+    let inserted = edit.text.chars().count();
+    let removed = edit.range.len();
+    let before = if removed == 0 {
+      edit.range.start <= index
+    } else {
+      edit.range.end <= index
+    };
+    if before {
+      mapped = mapped + inserted - removed;
+    } else if edit.range.start < index && index < edit.range.end {
+      let offset = index - edit.range.start;
+      mapped = mapped - offset + inserted.min(offset);
+    }
+  }
+  mapped
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn applies_and_maps() {
+    let mut rope = ropey::Rope::from_str("ab cd ef");
+    let edits = vec![
+      Edit::insert(0, "// "),
+      Edit::remove(2..3),
+      Edit {
+        range: 6..8,
+        text: String::from("xyz"),
+      },
+    ];
+    let mut recording = Recording::new(&mut rope);
+    apply(&mut recording, &edits);
+    // from the last edit to the first, leaving out the empty parts
+    assert_eq!(
+      recording.edits,
+      [
+        Edit::remove(6..8),
+        Edit::insert(6, "xyz"),
+        Edit::remove(2..3),
+        Edit::insert(0, "// "),
+      ]
+    );
+    assert_eq!(rope.to_string(), "// abcd xyz");
+    assert_eq!(map_index(&edits, 0), 3);
+    assert_eq!(map_index(&edits, 3), 5);
+    assert_eq!(map_index(&edits, 2), 5);
+    assert_eq!(map_index(&edits, 8), 11);
+    assert_eq!(map_index(&edits, 7), 9);
+  }
+}
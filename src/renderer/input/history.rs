@@ -0,0 +1,87 @@
+#[derive(Clone)]
+pub struct Snapshot {
+  pub text: ropey::Rope,
+  pub cursors: Vec<(usize, usize)>,
+}
+
+#[derive(Default)]
+pub struct History {
+  undo: Vec<Snapshot>,
+  redo: Vec<Snapshot>,
+  mergeable: Option<Vec<(usize, usize)>>,
+}
+
+impl History {
+  pub fn record(
+    &mut self,
+    before: Snapshot,
+    cursors: Vec<(usize, usize)>,
+    mergeable: bool,
+  ) {
+    if !(mergeable && self.mergeable.as_ref() == Some(&before.cursors)) {
+      self.undo.push(before);
+    }
+    self.mergeable = Some(cursors).filter(|_| mergeable);
+    self.redo.clear();
+  }
+
+  pub fn undo(&mut self, current: Snapshot) -> Option<Snapshot> {
+    let snapshot = self.undo.pop()?;
+    self.redo.push(current);
+    self.mergeable = None;
+    Some(snapshot)
+  }
+
+  pub fn redo(&mut self, current: Snapshot) -> Option<Snapshot> {
+    let snapshot = self.redo.pop()?;
+    self.undo.push(current);
+    self.mergeable = None;
+    Some(snapshot)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn snapshot(text: &str) -> Snapshot {
+    Snapshot {
+      text: ropey::Rope::from_str(text),
+      cursors: vec![(0, text.len())],
+    }
+  }
+
+  #[test]
+  fn merges_typing() {
+    let mut history = History::default();
+    history.record(snapshot(""), vec![(0, 1)], true);
+    history.record(snapshot("a"), vec![(0, 2)], true);
+    history.record(snapshot("ab"), vec![(1, 0)], false);
+
+    let undone = history.undo(snapshot("ab\n")).unwrap();
+    assert_eq!(undone.text.to_string(), "ab");
+    let undone = history.undo(undone).unwrap();
+    assert_eq!(undone.text.to_string(), "");
+    assert!(history.undo(undone.clone()).is_none());
+
+    let redone = history.redo(undone).unwrap();
+    assert_eq!(redone.text.to_string(), "ab");
+  }
+
+  #[test]
+  fn stops_merging_once_the_cursor_moved() {
+    let mut history = History::default();
+    history.record(snapshot(""), vec![(0, 1)], true);
+    // typed at the start after moving there
+    let at = |text, column| Snapshot {
+      cursors: vec![(0, column)],
+      ..snapshot(text)
+    };
+    history.record(at("a", 0), vec![(0, 1)], true);
+    history.record(at("ba", 1), vec![(0, 2)], true);
+
+    let undone = history.undo(snapshot("bca")).unwrap();
+    assert_eq!(undone.text.to_string(), "a");
+    assert_eq!(history.undo(undone).unwrap().text.to_string(), "");
+  }
+}
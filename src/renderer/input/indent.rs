@@ -0,0 +1,277 @@
+use super::brackets::{opening_bracket, scan_backward, BRACKETS};
+use tree_sitter::{Node, Tree};
+
+/// Node kinds whose content is indented one level further than the line
+/// they start on.
+const INDENT_KINDS: [&str; 33] = [
+  "arguments",
+  "argument_list",
+  "array",
+  "block",
+  "class_body",
+  "class_definition",
+  "compound_statement",
+  "declaration_list",
+  "dictionary",
+  "enum_body",
+  "enum_variant_list",
+  "enumerator_list",
+  "field_declaration_list",
+  "for_statement",
+  "formal_parameters",
+  "function_definition",
+  "if_statement",
+  "initializer_list",
+  "interface_body",
+  "list",
+  "match_block",
+  "object",
+  "object_type",
+  "parameter_list",
+  "parameters",
+  "statement_block",
+  "switch_block",
+  "switch_body",
+  "token_tree",
+  "try_statement",
+  "tuple",
+  "use_list",
+  "while_statement",
+];
+
+/// Indentation to apply when a newline is inserted.
+pub struct NewlineIndent {
+  pub indent: String,
+  pub closing: Option<String>,
+}
+
+/// The indent most lines are indented by more than the line before them.
+pub fn indent_unit(rope: &ropey::Rope) -> String {
+  let mut steps = [0usize; 9];
+  let mut previous = 0;
+  for line in rope.lines().take(1000) {
+    let mut chars = line.chars().peekable();
+    if chars.peek() == Some(&'\t') {
+      return String::from("\t");
+    }
+    let spaces = chars.take_while(|c| *c == ' ').count();
+    let first = line.chars().nth(spaces);
+    // blank lines and the continuation lines of block comments
+    if matches!(first, None | Some('\n' | '\r' | '*')) {
+      continue;
+    }
+    if let Some(count) = spaces
+      .checked_sub(previous)
+      .and_then(|step| steps.get_mut(step))
+    {
+      *count += 1;
+    }
+    previous = spaces;
+  }
+
+  // the smallest of the most common steps
+  let unit = (1..steps.len())
+    .rev()
+    .max_by_key(|step| steps[*step])
+    .filter(|step| steps[*step] != 0);
+  " ".repeat(unit.unwrap_or(4))
+}
+
+pub fn line_indent(rope: &ropey::Rope, row: usize) -> String {
+  rope
+    .line(row)
+    .chars()
+    .take_while(|c| *c == ' ' || *c == '\t')
+    .collect()
+}
+
+fn starts_indented_node(node: Node, row: usize, cursor_byte: usize) -> bool {
+  let mut node = Some(node);
+  while let Some(n) = node {
+    if n.start_position().row != row {
+      break;
+    }
+    if INDENT_KINDS.contains(&n.kind())
+      && (n.end_byte() > cursor_byte || n.has_error())
+    {
+      return true;
+    }
+    node = n.parent();
+  }
+  false
+}
+
+pub fn newline_indent(
+  rope: &ropey::Rope,
+  char_idx: usize,
+  tree: Option<&Tree>,
+) -> NewlineIndent {
+  let row = rope.char_to_line(char_idx);
+  let line_start = rope.line_to_char(row);
+  let base = line_indent(rope, row);
+  let unit = indent_unit(rope);
+
+  let before = rope
+    .slice(line_start..char_idx)
+    .chars()
+    .enumerate()
+    .filter(|(_, c)| !c.is_whitespace())
+    .last();
+  let after = rope
+    .slice(char_idx..rope.line_to_char(row + 1).max(char_idx))
+    .chars()
+    .find(|c| *c != ' ' && *c != '\t');
+
+  let (last_index, last_char) = match before {
+    Some(last) => last,
+    None => {
+      return NewlineIndent {
+        indent: base,
+        closing: None,
+      }
+    }
+  };
+
+  let opens_bracket = BRACKETS.iter().any(|(open, _)| *open == last_char);
+  let increase = opens_bracket
+    || tree
+      .and_then(|tree| {
+        let byte = rope.char_to_byte(line_start + last_index);
+        tree.root_node().descendant_for_byte_range(byte, byte)
+      })
+      .map_or(false, |node| {
+        starts_indented_node(node, row, rope.char_to_byte(char_idx))
+      });
+
+  if !increase {
+    return NewlineIndent {
+      indent: base,
+      closing: None,
+    };
+  }
+
+  let closing =
+    if opens_bracket && after.and_then(opening_bracket) == Some(last_char) {
+      Some(base.clone())
+    } else {
+      None
+    };
+
+  NewlineIndent {
+    indent: base + &unit,
+    closing,
+  }
+}
+
+pub fn closing_indent(
+  rope: &ropey::Rope,
+  char_idx: usize,
+  ch: char,
+  tree: Option<&Tree>,
+) -> Option<String> {
+  opening_bracket(ch)?;
+  let row = rope.char_to_line(char_idx);
+  let line_start = rope.line_to_char(row);
+  if !rope
+    .slice(line_start..char_idx)
+    .chars()
+    .all(|c| c == ' ' || c == '\t')
+  {
+    return None;
+  }
+
+  if let Some(open) = scan_backward(rope, line_start, ch, tree) {
+    return Some(line_indent(rope, rope.char_to_line(open)));
+  }
+
+  let indent = line_indent(rope, row);
+  let unit = indent_unit(rope);
+  Some(
+    indent
+      .strip_suffix(unit.as_str())
+      .map(String::from)
+      .unwrap_or(indent),
+  )
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn rust_tree(text: &str) -> Tree {
+    let mut parser = tree_sitter::Parser::new();
+    parser.set_language(tree_sitter_rust::language()).unwrap();
+    parser.parse(text, None).unwrap()
+  }
+
+  fn python_tree(text: &str) -> Tree {
+    let mut parser = tree_sitter::Parser::new();
+    parser.set_language(tree_sitter_python::language()).unwrap();
+    parser.parse(text, None).unwrap()
+  }
+
+  #[test]
+  fn inherits_indent() {
+    let rope = ropey::Rope::from_str("fn main() {\n  let a = 1;\n}\n");
+    let index = rope.line_to_char(1) + 12;
+    let newline = newline_indent(&rope, index, None);
+    assert_eq!(newline.indent, "  ");
+    assert!(newline.closing.is_none());
+  }
+
+  #[test]
+  fn indents_between_brackets() {
+    let rope = ropey::Rope::from_str("  foo {}\n  bar;\n");
+    let newline = newline_indent(&rope, 7, None);
+    assert_eq!(newline.indent, "    ");
+    assert_eq!(newline.closing.as_deref(), Some("  "));
+  }
+
+  #[test]
+  fn indents_after_block_node() {
+    let text = "fn main() {\n  foo(a, b)\n}\n";
+    let rope = ropey::Rope::from_str(text);
+    let tree = rust_tree(text);
+    let index = rope.line_to_char(2) - 1;
+    let newline = newline_indent(&rope, index, Some(&tree));
+    assert_eq!(newline.indent, "  ");
+
+    let text = "def main():\n  if a:\n    pass\n";
+    let rope = ropey::Rope::from_str(text);
+    let tree = python_tree(text);
+    let index = rope.line_to_char(1) - 1;
+    let newline = newline_indent(&rope, index, Some(&tree));
+    assert_eq!(newline.indent, "  ");
+    let index = rope.line_to_char(2) - 1;
+    let newline = newline_indent(&rope, index, Some(&tree));
+    assert_eq!(newline.indent, "    ");
+  }
+
+  #[test]
+  fn dedents_closing_bracket() {
+    let rope = ropey::Rope::from_str("  foo(\n    a,\n    \n");
+    let index = rope.line_to_char(2) + 4;
+    let closing = |ch| closing_indent(&rope, index, ch, None);
+    assert_eq!(closing(')').as_deref(), Some("  "));
+    assert_eq!(closing(']').as_deref(), Some("  "));
+    assert_eq!(closing('a'), None);
+
+    let text = "fn main() {\n  foo(\n    \"(\",\n    \n";
+    let rope = ropey::Rope::from_str(text);
+    let tree = rust_tree(text);
+    let index = rope.line_to_char(3) + 4;
+    let closing = closing_indent(&rope, index, ')', Some(&tree));
+    assert_eq!(closing.as_deref(), Some("  "));
+  }
+
+  #[test]
+  fn finds_indent_unit() {
+    let text =
+      "/**\n * Docs.\n */\nfunction a() {\n  if (b) {\n    c();\n  }\n}\n";
+    assert_eq!(indent_unit(&ropey::Rope::from_str(text)), "  ");
+    let text = "class A:\n    def b(self):\n        pass\n";
+    assert_eq!(indent_unit(&ropey::Rope::from_str(text)), "    ");
+    assert_eq!(indent_unit(&ropey::Rope::from_str("\tfoo\n")), "\t");
+    assert_eq!(indent_unit(&ropey::Rope::from_str("foo\n")), "    ");
+  }
+}
@@ -0,0 +1,326 @@
+use super::edit::Edit;
+use std::ops::{Range, RangeInclusive};
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Case {
+  Upper,
+  Lower,
+  Title,
+  Snake,
+  Camel,
+}
+
+fn break_len(line: ropey::RopeSlice) -> usize {
+  let mut chars = line.chars_at(line.len_chars());
+  match (chars.prev(), chars.prev()) {
+    (Some('\n'), Some('\r')) => 2,
+    (Some('\n'), _) => 1,
+    _ => 0,
+  }
+}
+
+pub fn line_len(rope: &ropey::Rope, row: usize) -> usize {
+  let line = rope.line(row);
+  line.len_chars() - break_len(line)
+}
+
+// lines put together are separated like the first of them
+fn line_break(rope: &ropey::Rope, row: usize) -> &'static str {
+  if break_len(rope.line(row)) == 2 {
+    "\r\n"
+  } else {
+    "\n"
+  }
+}
+
+pub fn last_row(rope: &ropey::Rope) -> usize {
+  let len = rope.len_chars();
+  if len != 0 && rope.char(len - 1) == '\n' {
+    rope.len_lines() - 2
+  } else {
+    rope.len_lines() - 1
+  }
+}
+
+fn line_contents(
+  rope: &ropey::Rope,
+  rows: RangeInclusive<usize>,
+) -> Vec<String> {
+  rows
+    .map(|row| {
+      let start = rope.line_to_char(row);
+      rope.slice(start..start + line_len(rope, row)).to_string()
+    })
+    .collect()
+}
+
+fn rows_range(
+  rope: &ropey::Rope,
+  rows: &RangeInclusive<usize>,
+) -> Range<usize> {
+  rope.line_to_char(*rows.start())
+    ..rope.line_to_char(*rows.end()) + line_len(rope, *rows.end())
+}
+
+pub fn move_lines(
+  rope: &ropey::Rope,
+  rows: RangeInclusive<usize>,
+  up: bool,
+) -> Option<Edit> {
+  let (start, end) = (*rows.start(), *rows.end());
+  let (range, lines) = if up {
+    if start == 0 {
+      return None;
+    }
+    let lines = [
+      line_contents(rope, rows),
+      line_contents(rope, (start - 1)..=(start - 1)),
+    ];
+    ((start - 1)..=end, lines.concat())
+  } else {
+    if end >= last_row(rope) {
+      return None;
+    }
+    let lines = [
+      line_contents(rope, (end + 1)..=(end + 1)),
+      line_contents(rope, rows),
+    ];
+    (start..=(end + 1), lines.concat())
+  };
+
+  Some(Edit {
+    range: rows_range(rope, &range),
+    text: lines.join(line_break(rope, *range.start())),
+  })
+}
+
+pub fn duplicate_lines(
+  rope: &ropey::Rope,
+  rows: RangeInclusive<usize>,
+) -> Edit {
+  let line_break = line_break(rope, *rows.start());
+  let start = rope.line_to_char(*rows.start());
+  let lines = line_contents(rope, rows);
+  Edit::insert(start, lines.join(line_break) + line_break)
+}
+
+pub fn delete_lines(rope: &ropey::Rope, rows: RangeInclusive<usize>) -> Edit {
+  let (start, end) = (*rows.start(), *rows.end());
+  if end + 1 < rope.len_lines() {
+    Edit::remove(rope.line_to_char(start)..rope.line_to_char(end + 1))
+  } else if start != 0 {
+    // the last line has no line break, so remove the one before the rows
+    let start = rope.line_to_char(start) - break_len(rope.line(start - 1));
+    Edit::remove(start..rope.len_chars())
+  } else {
+    Edit::remove(0..rope.len_chars())
+  }
+}
+
+pub fn join_lines(rope: &ropey::Rope, rows: RangeInclusive<usize>) -> Edit {
+  let (start, mut end) = (*rows.start(), *rows.end());
+  if start == end {
+    end += 1;
+  }
+  let rows = start..=end.min(last_row(rope)).max(start);
+
+  let lines = line_contents(rope, rows.clone());
+  let mut text = lines[0].clone();
+  for line in &lines[1..] {
+    let line = line.trim_start();
+    if line.is_empty() {
+      continue;
+    }
+    if !text.is_empty() && !text.ends_with(char::is_whitespace) {
+      text.push(' ');
+    }
+    text.push_str(line);
+  }
+
+  Edit {
+    range: rows_range(rope, &rows),
+    text,
+  }
+}
+
+pub fn sort_lines(
+  rope: &ropey::Rope,
+  rows: RangeInclusive<usize>,
+  unique: bool,
+) -> Edit {
+  let range = rows_range(rope, &rows);
+  let line_break = line_break(rope, *rows.start());
+  let mut lines = line_contents(rope, rows);
+  if unique {
+    let mut seen = std::collections::HashSet::new();
+    lines.retain(|line| seen.insert(line.clone()));
+  } else {
+    lines.sort();
+  }
+
+  Edit {
+    range,
+    text: lines.join(line_break),
+  }
+}
+
+pub fn paragraph(
+  rope: &ropey::Rope,
+  row: usize,
+) -> Option<RangeInclusive<usize>> {
+  let blank = |row: usize| rope.line(row).chars().all(char::is_whitespace);
+  if row > last_row(rope) || blank(row) {
+    return None;
+  }
+  let start = (0..row).rev().take_while(|&r| !blank(r)).last();
+  let end = (row + 1..=last_row(rope)).take_while(|&r| !blank(r)).last();
+  Some(start.unwrap_or(row)..=end.unwrap_or(row))
+}
+
+fn words(text: &str) -> Vec<String> {
+  let mut words = vec![];
+  let mut current = String::new();
+  let mut prev_lower = false;
+  for c in text.chars() {
+    if !c.is_alphanumeric() {
+      if !current.is_empty() {
+        words.push(std::mem::take(&mut current));
+      }
+      prev_lower = false;
+      continue;
+    }
+    if c.is_uppercase() && prev_lower && !current.is_empty() {
+      words.push(std::mem::take(&mut current));
+    }
+    prev_lower = c.is_lowercase() || c.is_numeric();
+    current.push(c);
+  }
+  if !current.is_empty() {
+    words.push(current);
+  }
+  words
+}
+
+fn capitalize(word: &str) -> String {
+  let mut chars = word.chars();
+  match chars.next() {
+    Some(first) => first
+      .to_uppercase()
+      .chain(chars.flat_map(char::to_lowercase))
+      .collect(),
+    None => String::new(),
+  }
+}
+
+pub fn transform_case(text: &str, case: Case) -> String {
+  match case {
+    Case::Upper => text.to_uppercase(),
+    Case::Lower => text.to_lowercase(),
+    Case::Title => {
+      let mut title = String::new();
+      let mut start = true;
+      for c in text.chars() {
+        if start {
+          title.extend(c.to_uppercase());
+        } else {
+          title.extend(c.to_lowercase());
+        }
+        start = !c.is_alphanumeric();
+      }
+      title
+    }
+    Case::Snake => words(text)
+      .iter()
+      .map(|word| word.to_lowercase())
+      .collect::<Vec<_>>()
+      .join("_"),
+    Case::Camel => words(text)
+      .iter()
+      .enumerate()
+      .map(|(i, word)| {
+        if i == 0 {
+          word.to_lowercase()
+        } else {
+          capitalize(word)
+        }
+      })
+      .collect(),
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::super::edit::{apply, Recording};
+  use super::*;
+
+  fn apply_one(text: &str, edit: Edit) -> String {
+    let mut rope = ropey::Rope::from_str(text);
+    apply(&mut Recording::new(&mut rope), &[edit]);
+    rope.to_string()
+  }
+
+  #[test]
+  fn moves_lines() {
+    let rope = ropey::Rope::from_str("a\nb\nc\n");
+    let edit = move_lines(&rope, 1..=2, true).unwrap();
+    assert_eq!(apply_one("a\nb\nc\n", edit), "b\nc\na\n");
+    let edit = move_lines(&rope, 0..=0, false).unwrap();
+    assert_eq!(apply_one("a\nb\nc\n", edit), "b\na\nc\n");
+    assert!(move_lines(&rope, 0..=0, true).is_none());
+    assert!(move_lines(&rope, 2..=2, false).is_none());
+  }
+
+  #[test]
+  fn duplicates_and_deletes_lines() {
+    let rope = ropey::Rope::from_str("a\nb");
+    assert_eq!(apply_one("a\nb", duplicate_lines(&rope, 1..=1)), "a\nb\nb");
+    assert_eq!(apply_one("a\nb", delete_lines(&rope, 1..=1)), "a");
+    assert_eq!(apply_one("a\nb", delete_lines(&rope, 0..=0)), "b");
+  }
+
+  #[test]
+  fn joins_and_sorts_lines() {
+    let text = "a\n    b\n\nc\n";
+    let rope = ropey::Rope::from_str(text);
+    assert_eq!(apply_one(text, join_lines(&rope, 0..=0)), "a b\n\nc\n");
+    assert_eq!(apply_one(text, join_lines(&rope, 0..=3)), "a b c\n");
+
+    let rope = ropey::Rope::from_str("b\na\nb\n");
+    assert_eq!(
+      apply_one("b\na\nb\n", sort_lines(&rope, 0..=2, false)),
+      "a\nb\nb\n"
+    );
+    assert_eq!(
+      apply_one("b\na\nb\n", sort_lines(&rope, 0..=2, true)),
+      "b\na\n"
+    );
+
+    let rope = ropey::Rope::from_str("a\n\nc\nb\n\nd\n");
+    assert_eq!(paragraph(&rope, 3), Some(2..=3));
+    assert_eq!(paragraph(&rope, 0), Some(0..=0));
+    assert_eq!(paragraph(&rope, 1), None);
+  }
+
+  #[test]
+  fn keeps_crlf_line_breaks() {
+    let text = "a\r\n  b\r\nc";
+    let rope = ropey::Rope::from_str(text);
+    assert_eq!(line_len(&rope, 0), 1);
+    assert_eq!(apply_one(text, join_lines(&rope, 0..=0)), "a b\r\nc");
+    let sorted = apply_one(text, sort_lines(&rope, 0..=2, false));
+    assert_eq!(sorted, "  b\r\na\r\nc");
+    let moved = apply_one(text, move_lines(&rope, 2..=2, true).unwrap());
+    assert_eq!(moved, "a\r\nc\r\n  b");
+    assert_eq!(apply_one(text, delete_lines(&rope, 2..=2)), "a\r\n  b");
+    let duplicated = apply_one(text, duplicate_lines(&rope, 0..=0));
+    assert_eq!(duplicated, "a\r\na\r\n  b\r\nc");
+  }
+
+  #[test]
+  fn transforms_case() {
+    assert_eq!(transform_case("fooBar baz", Case::Upper), "FOOBAR BAZ");
+    assert_eq!(transform_case("hello wORLD", Case::Title), "Hello World");
+    assert_eq!(transform_case("fooBar-baz", Case::Snake), "foo_bar_baz");
+    assert_eq!(transform_case("foo_bar_baz", Case::Camel), "fooBarBaz");
+  }
+}
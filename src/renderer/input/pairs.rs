@@ -0,0 +1,96 @@
+/// Pairs used for buffers without a language specific definition.
+pub const DEFAULT_PAIRS: &[(char, char)] =
+  &[('(', ')'), ('[', ']'), ('{', '}'), ('"', '"'), ('\'', '\'')];
+
+pub fn closing(pairs: &[(char, char)], ch: char) -> Option<char> {
+  pairs
+    .iter()
+    .find(|(open, _)| *open == ch)
+    .map(|(_, close)| *close)
+}
+
+fn is_closing(pairs: &[(char, char)], ch: char) -> bool {
+  pairs.iter().any(|(_, close)| *close == ch)
+}
+
+pub fn skips_closing(
+  rope: &ropey::Rope,
+  char_idx: usize,
+  ch: char,
+  closers: &[usize],
+) -> bool {
+  closers.last() == Some(&0) && rope.get_char(char_idx) == Some(ch)
+}
+
+pub fn auto_close(
+  rope: &ropey::Rope,
+  char_idx: usize,
+  ch: char,
+  pairs: &[(char, char)],
+) -> Option<char> {
+  let close = closing(pairs, ch)?;
+
+  let next_allows = match rope.get_char(char_idx) {
+    None => true,
+    Some(next) => {
+      next.is_whitespace()
+        || is_closing(pairs, next)
+        || next == ','
+        || next == ';'
+    }
+  };
+  if !next_allows {
+    return None;
+  }
+
+  if close == ch && char_idx != 0 {
+    let prev = rope.char(char_idx - 1);
+    if prev.is_alphanumeric() || prev == ch {
+      return None;
+    }
+  }
+
+  Some(close)
+}
+
+pub fn in_empty_pair(
+  rope: &ropey::Rope,
+  char_idx: usize,
+  pairs: &[(char, char)],
+  closers: &[usize],
+) -> bool {
+  if char_idx == 0 || closers.last() != Some(&0) {
+    return false;
+  }
+
+  match (rope.get_char(char_idx - 1), rope.get_char(char_idx)) {
+    (Some(prev), Some(next)) => closing(pairs, prev) == Some(next),
+    _ => false,
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn closes_pairs() {
+    let rope = ropey::Rope::from_str("foo bar\n");
+    assert_eq!(auto_close(&rope, 3, '(', DEFAULT_PAIRS), Some(')'));
+    assert_eq!(auto_close(&rope, 4, '(', DEFAULT_PAIRS), None);
+    assert_eq!(auto_close(&rope, 3, '\'', DEFAULT_PAIRS), None);
+    assert_eq!(auto_close(&rope, 0, '"', DEFAULT_PAIRS), None);
+    assert_eq!(auto_close(&rope, 7, 'a', DEFAULT_PAIRS), None);
+  }
+
+  #[test]
+  fn skips_and_deletes_pairs() {
+    let rope = ropey::Rope::from_str("foo()\n");
+    assert!(skips_closing(&rope, 4, ')', &[0]));
+    assert!(!skips_closing(&rope, 4, ')', &[]));
+    assert!(!skips_closing(&rope, 3, ')', &[1]));
+    assert!(in_empty_pair(&rope, 4, DEFAULT_PAIRS, &[0]));
+    assert!(!in_empty_pair(&rope, 4, DEFAULT_PAIRS, &[]));
+    assert!(!in_empty_pair(&rope, 5, DEFAULT_PAIRS, &[0]));
+  }
+}
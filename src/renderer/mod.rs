@@ -1,17 +1,24 @@
+mod breadcrumbs;
 mod code_view;
 mod code_view_tabs;
+pub mod font;
 mod fs_tree;
 pub mod input;
 mod rectangle;
+mod search;
+mod symbol_index;
+mod theme;
 
 use futures::task::SpawnExt;
 use std::path::PathBuf;
+use std::rc::Rc;
+use std::sync::{Arc, Mutex};
 use wgpu::util::StagingBelt;
 use wgpu::{CommandEncoder, Device, TextureView};
 use wgpu_glyph::ab_glyph::Font;
 use wgpu_glyph::GlyphBrush;
 use winit::dpi::{PhysicalPosition, PhysicalSize};
-use winit::event::ElementState;
+use winit::event::{ElementState, ModifiersState};
 
 const RENDER_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Bgra8Unorm;
 
@@ -20,7 +27,7 @@ pub struct Renderer {
   pub size: PhysicalSize<u32>,
   surface: wgpu::Surface,
   surface_config: wgpu::SurfaceConfiguration,
-  device: wgpu::Device,
+  device: Rc<wgpu::Device>,
   queue: wgpu::Queue,
   staging_belt: wgpu::util::StagingBelt,
   local_spawner: futures::executor::LocalSpawner,
@@ -28,6 +35,7 @@ pub struct Renderer {
   glyph_brush: wgpu_glyph::GlyphBrush<()>,
   rectangle_render_pipeline: wgpu::RenderPipeline,
   fs_tree: fs_tree::FsTree,
+  themes: theme::Themes,
   pub font_height: f32,
   pub code_views: code_view_tabs::CodeViewTabs,
 }
@@ -35,7 +43,9 @@ pub struct Renderer {
 impl Renderer {
   pub async fn new(
     event_loop: &winit::event_loop::EventLoop<()>,
-    font: wgpu_glyph::ab_glyph::FontArc,
+    font_family: font::FontFamily,
+    theme_name: Option<&str>,
+    sticky_scroll_depth: usize,
     filepath: PathBuf,
   ) -> Result<Self, anyhow::Error> {
     let window = winit::window::WindowBuilder::new()
@@ -56,6 +66,7 @@ impl Renderer {
     let (device, queue) = adapter
       .request_device(&wgpu::DeviceDescriptor::default(), None)
       .await?;
+    let device = Rc::new(device);
 
     let staging_belt = wgpu::util::StagingBelt::new(1024);
     let local_pool = futures::executor::LocalPool::new();
@@ -71,6 +82,7 @@ impl Renderer {
     };
     surface.configure(&device, &surface_config);
 
+    let font = font_family.regular.clone();
     let px_per_em = (10.0 / 72.0) * (96.0 * window.scale_factor() as f32);
     let units_per_em = font.units_per_em().unwrap();
     let height = font.height_unscaled();
@@ -80,11 +92,20 @@ impl Renderer {
       .glyph_bounds(&font.glyph_id('0').with_scale(scale))
       .height();
 
-    let glyph_brush = wgpu_glyph::GlyphBrushBuilder::using_font(font.clone())
-      .build(&device, RENDER_FORMAT);
+    let glyph_brush =
+      wgpu_glyph::GlyphBrushBuilder::using_fonts(font_family.faces())
+        .build(&device, RENDER_FORMAT);
 
     // 20% for window for file tree
     let tree_width = (size.width as f32 / 100.0) * 20.0;
+    let path = std::path::Path::new("./").canonicalize()?;
+    // language servers, the workspace search and the theme watcher wake the
+    // event loop when they have something
+    let proxy = Mutex::new(event_loop.create_proxy());
+    let waker: crate::lsp::Waker = Arc::new(move || {
+      let _ = proxy.lock().unwrap().send_event(());
+    });
+    let themes = theme::Themes::new(theme_name, Arc::clone(&waker));
 
     let mut code_views = code_view_tabs::CodeViewTabs::new(
       &device,
@@ -97,10 +118,12 @@ impl Renderer {
         width: size.width as f32 - tree_width,
         height: size.height as f32,
       },
+      path.clone(),
+      sticky_scroll_depth,
+      waker,
     );
     code_views.add(&device, size.cast(), filepath)?;
 
-    let path = std::path::Path::new("./").canonicalize()?;
     let fs_tree = fs_tree::FsTree::new(
       &device,
       size.cast(),
@@ -128,6 +151,7 @@ impl Renderer {
       glyph_brush,
       rectangle_render_pipeline,
       fs_tree,
+      themes,
       font_height,
       code_views,
     })
@@ -168,19 +192,68 @@ impl Renderer {
     &mut self,
     position: PhysicalPosition<f64>,
     state: ElementState,
+    modifiers: ModifiersState,
   ) {
     if state == ElementState::Pressed {
       let size = self.size.cast();
+      self.code_views.blur();
       for element in self.get_elements() {
         if let Some(pos) = element.get_dimensions().contains(position.cast()) {
-          element.click(pos.cast(), size);
+          element.click(pos.cast(), size, modifiers);
           self.window.request_redraw();
           break;
         }
       }
+      self.code_views.open_picked(&self.device, size);
     }
   }
 
+  pub fn drag(
+    &mut self,
+    position: PhysicalPosition<f64>,
+    modifiers: ModifiersState,
+  ) {
+    let size = self.size.cast();
+    for element in self.get_elements() {
+      if let Some(pos) = element.get_dimensions().contains(position.cast()) {
+        element.drag(pos.cast(), size, modifiers);
+        self.window.request_redraw();
+        break;
+      }
+    }
+    self.code_views.open_picked(&self.device, size);
+  }
+
+  pub fn hover(&mut self, position: PhysicalPosition<f64>) {
+    let size = self.size.cast();
+    for element in self.get_elements() {
+      if let Some(pos) = element.get_dimensions().contains(position.cast()) {
+        if element.hover(pos.cast(), size) {
+          self.window.request_redraw();
+        }
+        break;
+      }
+    }
+  }
+
+  pub fn cycle_theme(&mut self) {
+    self.themes.cycle();
+    self.theme_changed();
+  }
+
+  pub fn reload_theme(&mut self) {
+    if self.themes.reload() {
+      self.theme_changed();
+    }
+  }
+
+  fn theme_changed(&mut self) {
+    let size = self.size.cast();
+    self.fs_tree.theme_changed(size);
+    self.code_views.theme_changed(size);
+    self.window.request_redraw();
+  }
+
   pub fn redraw(&mut self) -> Result<(), anyhow::Error> {
     let mut encoder =
       self
@@ -193,42 +266,14 @@ impl Renderer {
     let view = frame
       .texture
       .create_view(&wgpu::TextureViewDescriptor::default());
-    {
-      let mut rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
-        label: None,
-        color_attachments: &[wgpu::RenderPassColorAttachment {
-          view: &view,
-          resolve_target: None,
-          ops: wgpu::Operations {
-            load: wgpu::LoadOp::Clear(wgpu::Color {
-              r: 0.1,
-              g: 0.1,
-              b: 0.1,
-              a: 1.0,
-            }),
-            store: true,
-          },
-        }],
-        depth_stencil_attachment: None,
-      });
-
-      rpass.set_pipeline(&self.rectangle_render_pipeline);
-      for rect in self.get_rects() {
-        rect.write_buffer(&self.queue);
-        rpass.set_vertex_buffer(0, rect.vertex_buffer.slice(..));
-        if let Some(ref region) = rect.region {
-          rpass.set_scissor_rect(
-            region.x,
-            region.y,
-            region.width,
-            region.height,
-          );
-        } else {
-          rpass.set_scissor_rect(0, 0, self.size.width, self.size.height);
-        }
-        rpass.draw(0..4, 0..1);
-      }
-    }
+    let [r, g, b, a] = theme::current().ui.background;
+    let clear = wgpu::LoadOp::Clear(wgpu::Color {
+      r: r as f64,
+      g: g as f64,
+      b: b as f64,
+      a: a as f64,
+    });
+    self.draw_rects(&mut encoder, &view, self.get_rects(), clear);
 
     self.code_views.redraw(
       &mut self.glyph_brush,
@@ -248,6 +293,20 @@ impl Renderer {
       self.size,
     );
 
+    // popups go over the text of everything else
+    let overlay_rects = self.code_views.get_overlay_rects();
+    if !overlay_rects.is_empty() {
+      self.draw_rects(&mut encoder, &view, overlay_rects, wgpu::LoadOp::Load);
+      self.code_views.redraw_overlay(
+        &mut self.glyph_brush,
+        &self.device,
+        &mut self.staging_belt,
+        &mut encoder,
+        &view,
+        self.size,
+      );
+    }
+
     self.staging_belt.finish();
     self.queue.submit(Some(encoder.finish()));
     self.local_spawner.spawn(self.staging_belt.recall())?;
@@ -256,6 +315,36 @@ impl Renderer {
     Ok(())
   }
 
+  fn draw_rects(
+    &self,
+    encoder: &mut CommandEncoder,
+    view: &TextureView,
+    rects: Vec<&rectangle::Rectangle>,
+    load: wgpu::LoadOp<wgpu::Color>,
+  ) {
+    let mut rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+      label: None,
+      color_attachments: &[wgpu::RenderPassColorAttachment {
+        view,
+        resolve_target: None,
+        ops: wgpu::Operations { load, store: true },
+      }],
+      depth_stencil_attachment: None,
+    });
+
+    rpass.set_pipeline(&self.rectangle_render_pipeline);
+    for rect in rects {
+      rect.write_buffer(&self.queue);
+      rpass.set_vertex_buffer(0, rect.vertex_buffer.slice(..));
+      if let Some(ref region) = rect.region {
+        rpass.set_scissor_rect(region.x, region.y, region.width, region.height);
+      } else {
+        rpass.set_scissor_rect(0, 0, self.size.width, self.size.height);
+      }
+      rpass.draw(0..4, 0..1);
+    }
+  }
+
   fn get_rects(&self) -> Vec<&rectangle::Rectangle> {
     let mut vec = vec![];
     vec.extend(self.code_views.get_rects());
@@ -287,19 +376,53 @@ trait RenderElement {
     }
   }
 
+  fn theme_changed(&mut self, screen_size: PhysicalSize<f32>) {
+    for element in self.get_elements() {
+      element.theme_changed(screen_size);
+    }
+  }
+
   fn click(
     &mut self,
     position: PhysicalPosition<f64>,
     screen_size: PhysicalSize<f32>,
+    modifiers: ModifiersState,
+  ) {
+    for element in self.get_elements() {
+      if let Some(pos) = element.get_dimensions().contains(position.cast()) {
+        element.click(pos.cast(), screen_size, modifiers);
+        break;
+      }
+    }
+  }
+
+  fn drag(
+    &mut self,
+    position: PhysicalPosition<f64>,
+    screen_size: PhysicalSize<f32>,
+    modifiers: ModifiersState,
   ) {
     for element in self.get_elements() {
       if let Some(pos) = element.get_dimensions().contains(position.cast()) {
-        element.click(pos.cast(), screen_size);
+        element.drag(pos.cast(), screen_size, modifiers);
         break;
       }
     }
   }
 
+  fn hover(
+    &mut self,
+    position: PhysicalPosition<f64>,
+    screen_size: PhysicalSize<f32>,
+  ) -> bool {
+    for element in self.get_elements() {
+      if let Some(pos) = element.get_dimensions().contains(position.cast()) {
+        return element.hover(pos.cast(), screen_size);
+      }
+    }
+    false
+  }
+
   fn redraw(
     &mut self,
     glyph_brush: &mut GlyphBrush<()>,
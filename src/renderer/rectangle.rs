@@ -149,6 +149,13 @@ impl Rectangle {
     self.vertices = Self::create_vertices(pos, end_pos, self.color);
   }
 
+  pub fn set_color(&mut self, color: [f32; 3]) {
+    self.color = color;
+    for vertex in &mut self.vertices {
+      vertex.color = color;
+    }
+  }
+
   pub fn write_buffer(&self, queue: &wgpu::Queue) {
     queue.write_buffer(
       &self.vertex_buffer,
@@ -0,0 +1,1131 @@
+use crate::lsp;
+use crate::renderer::code_view::language::{Language, Registry};
+use crate::renderer::code_view::locals;
+use crate::renderer::input::lines;
+use crate::renderer::rectangle::Rectangle;
+use crate::renderer::symbol_index::SymbolIndex;
+use crate::renderer::theme;
+use crate::renderer::Dimensions;
+use crate::renderer::RenderElement;
+use std::collections::{HashMap, HashSet};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{mpsc, Arc, Mutex};
+use std::time::{Duration, Instant};
+use wgpu::util::StagingBelt;
+use wgpu::{CommandEncoder, Device, TextureView};
+use wgpu_glyph::ab_glyph::{Font, FontArc, ScaleFont};
+use wgpu_glyph::{GlyphBrush, Section, Text};
+use winit::dpi::{PhysicalPosition, PhysicalSize};
+use winit::event::ModifiersState;
+
+const SEARCH_PADDING: f32 = 5.0;
+/// Rows above the hits, holding the pattern and what the search found.
+const HEADER_ROWS: usize = 2;
+/// Hits the panel shows at once, the others are scrolled to.
+const HIT_ROWS: usize = 10;
+/// The search stops after finding this many hits.
+pub const MAX_HITS: usize = 1000;
+/// Larger files are left out of workspace searches.
+const MAX_FILE_SIZE: u64 = 1 << 20;
+/// Directories left out of workspace searches, besides hidden ones.
+const SKIPPED_DIRECTORIES: &[&str] = &["target", "node_modules"];
+/// Hits of a workspace search are sent to the panel at most this often.
+const STREAM_INTERVAL: Duration = Duration::from_millis(100);
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Scope {
+  File,
+  Workspace,
+  Symbols,
+  Rename,
+}
+
+/// Match of a search, with the line it starts on.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Hit {
+  pub path: PathBuf,
+  pub char_idx: usize,
+  pub row: usize,
+  pub column: usize,
+  pub line: String,
+  pub captures: Vec<Range<usize>>,
+}
+
+impl Hit {
+  pub fn new(
+    path: &Path,
+    text: &ropey::Rope,
+    ranges: Vec<Range<usize>>,
+  ) -> Option<Self> {
+    let char_idx = ranges.iter().map(|range| range.start).min()?;
+    let row = text.char_to_line(char_idx);
+    let line_start = text.line_to_char(row);
+    let line_end = line_start + lines::line_len(text, row);
+    Some(Self {
+      path: path.to_path_buf(),
+      char_idx,
+      row,
+      column: char_idx - line_start,
+      line: text.slice(line_start..line_end).to_string(),
+      captures: ranges
+        .into_iter()
+        .map(|range| {
+          range.start.clamp(line_start, line_end) - line_start
+            ..range.end.clamp(line_start, line_end) - line_start
+        })
+        .filter(|range| !range.is_empty())
+        .collect(),
+    })
+  }
+}
+
+pub fn compile_query(
+  language: tree_sitter::Language,
+  pattern: &str,
+) -> Result<tree_sitter::Query, String> {
+  // tree-sitter panics reporting a misspelled name at the very end of the
+  // pattern, which a line break after it avoids
+  tree_sitter::Query::new(language, &format!("{}\n", pattern)).map_err(
+    |error| {
+      format!(
+        "{:?} error at {}:{}",
+        error.kind,
+        error.row + 1,
+        error.column + 1
+      )
+    },
+  )
+}
+
+pub fn search_tree(
+  query: &tree_sitter::Query,
+  tree: &tree_sitter::Tree,
+  text: &ropey::Rope,
+  path: &Path,
+) -> Vec<Hit> {
+  let source = text.bytes().collect::<Vec<u8>>();
+  let mut cursor = tree_sitter::QueryCursor::new();
+  let mut hits = vec![];
+  for m in
+    cursor.matches(query, tree.root_node(), |node| &source[node.byte_range()])
+  {
+    let captures = m
+      .captures
+      .iter()
+      .map(|capture| {
+        let bytes = capture.node.byte_range();
+        text.byte_to_char(bytes.start)..text.byte_to_char(bytes.end)
+      })
+      .collect::<Vec<_>>();
+    hits.extend(Hit::new(path, text, captures));
+  }
+  hits.sort_by_key(|hit| hit.char_idx);
+  hits
+}
+
+fn glob(pattern: &[char], text: &[char]) -> bool {
+  match pattern {
+    [] => text.is_empty(),
+    ['*', '*', '/', rest @ ..] if glob(rest, text) => true,
+    ['*', '*', rest @ ..] => (0..=text.len()).any(|i| glob(rest, &text[i..])),
+    ['*', rest @ ..] => (0..=text.len())
+      .take_while(|&i| i == 0 || text[i - 1] != '/')
+      .any(|i| glob(rest, &text[i..])),
+    ['?', rest @ ..] => {
+      matches!(text, [c, text @ ..] if *c != '/' && glob(rest, text))
+    }
+    ['[', class @ ..] => {
+      let (negated, class) = match class {
+        ['!', class @ ..] | ['^', class @ ..] => (true, class),
+        class => (false, class),
+      };
+      // a `]` right at the start is one of the chars
+      let end = match class.iter().skip(1).position(|c| *c == ']') {
+        Some(end) => end + 1,
+        None => return text.first() == Some(&'[') && glob(class, &text[1..]),
+      };
+      let (c, text) = match text {
+        [c, text @ ..] if *c != '/' => (c, text),
+        _ => return false,
+      };
+      let mut set = &class[..end];
+      let mut found = false;
+      loop {
+        match set {
+          [from, '-', to, rest @ ..] => {
+            found |= (*from..=*to).contains(c);
+            set = rest;
+          }
+          [first, rest @ ..] => {
+            found |= first == c;
+            set = rest;
+          }
+          [] => break,
+        }
+      }
+      found != negated && glob(&class[end + 1..], text)
+    }
+    ['\\', c, rest @ ..] | [c, rest @ ..] => {
+      text.first() == Some(c) && glob(rest, &text[1..])
+    }
+  }
+}
+
+struct IgnoreRule {
+  glob: Vec<char>,
+  negated: bool,
+  directory: bool,
+  anchored: bool,
+}
+
+struct Gitignore {
+  dir: PathBuf,
+  rules: Vec<IgnoreRule>,
+}
+
+impl Gitignore {
+  fn read(dir: &Path) -> Option<Self> {
+    let source = std::fs::read_to_string(dir.join(".gitignore")).ok()?;
+    Some(Self::parse(dir, &source))
+  }
+
+  fn parse(dir: &Path, source: &str) -> Self {
+    let rules = source
+      .lines()
+      .map(|line| line.trim_end())
+      .filter(|line| !line.is_empty() && !line.starts_with('#'))
+      .map(|line| {
+        let (negated, line) = match line.strip_prefix('!') {
+          Some(line) => (true, line),
+          None => (false, line.strip_prefix('\\').unwrap_or(line)),
+        };
+        let (directory, line) = match line.strip_suffix('/') {
+          Some(line) => (true, line),
+          None => (false, line),
+        };
+        IgnoreRule {
+          glob: line.trim_start_matches('/').chars().collect(),
+          negated,
+          directory,
+          anchored: line.contains('/'),
+        }
+      })
+      .collect();
+    Self {
+      dir: dir.to_path_buf(),
+      rules,
+    }
+  }
+
+  fn ignores(&self, path: &Path, is_dir: bool) -> Option<bool> {
+    let relative = path.strip_prefix(&self.dir).ok()?;
+    let relative = relative
+      .components()
+      .map(|component| component.as_os_str().to_string_lossy())
+      .collect::<Vec<_>>()
+      .join("/")
+      .chars()
+      .collect::<Vec<_>>();
+    let name = match relative.iter().rposition(|c| *c == '/') {
+      Some(slash) => &relative[slash + 1..],
+      None => &relative[..],
+    };
+    self
+      .rules
+      .iter()
+      .rev()
+      .find(|rule| {
+        (is_dir || !rule.directory)
+          && glob(&rule.glob, if rule.anchored { &relative } else { name })
+      })
+      .map(|rule| !rule.negated)
+  }
+}
+
+pub fn workspace_files(dir: &Path, files: &mut Vec<PathBuf>) {
+  // those of the directories above it count up to the root of the repository
+  let repository = dir.ancestors().find(|dir| dir.join(".git").exists());
+  let mut ignores = match repository {
+    Some(repository) => dir
+      .ancestors()
+      .skip(1)
+      .take_while(|parent| parent.starts_with(repository))
+      .filter_map(Gitignore::read)
+      .collect(),
+    None => vec![],
+  };
+  ignores.reverse();
+  walk(dir, &mut ignores, files);
+}
+
+fn walk(dir: &Path, ignores: &mut Vec<Gitignore>, files: &mut Vec<PathBuf>) {
+  let mut entries = match dir.read_dir() {
+    Ok(entries) => entries
+      .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+      .collect::<Vec<_>>(),
+    Err(_) => return,
+  };
+  entries.sort();
+  let ignore = Gitignore::read(dir);
+  let has_ignore = ignore.is_some();
+  ignores.extend(ignore);
+  for path in entries {
+    let name = path
+      .file_name()
+      .map(|name| name.to_string_lossy().into_owned())
+      .unwrap_or_default();
+    let is_dir = path.is_dir();
+    let ignored = ignores
+      .iter()
+      .rev()
+      .find_map(|ignore| ignore.ignores(&path, is_dir))
+      .unwrap_or(false);
+    if name.starts_with('.') || ignored {
+      continue;
+    }
+    if is_dir {
+      if !SKIPPED_DIRECTORIES.contains(&name.as_str()) {
+        walk(&path, ignores, files);
+      }
+    } else {
+      files.push(path);
+    }
+  }
+  if has_ignore {
+    ignores.pop();
+  }
+}
+
+pub fn read_file(path: &Path) -> Option<ropey::Rope> {
+  let small = path
+    .metadata()
+    .map_or(false, |metadata| metadata.len() <= MAX_FILE_SIZE);
+  if !small {
+    return None;
+  }
+  std::fs::File::open(path)
+    .and_then(ropey::Rope::from_reader)
+    .ok()
+}
+
+pub fn workspace_texts<'a>(
+  registry: &'a Registry,
+  root: &Path,
+  open: &'a [(PathBuf, ropey::Rope)],
+) -> impl Iterator<Item = (PathBuf, Rc<Language>, ropey::Rope)> + 'a {
+  let mut files = vec![];
+  workspace_files(root, &mut files);
+  let open = open
+    .iter()
+    .map(|(path, text)| (path.canonicalize().ok(), text))
+    .collect::<Vec<_>>();
+  files.into_iter().filter_map(move |path| {
+    let canonical = path.canonicalize().ok();
+    let text = match open.iter().find(|(open, _)| *open == canonical) {
+      Some((_, text)) => (*text).clone(),
+      None => read_file(&path)?,
+    };
+    let language = registry.detect(&path, &text)?;
+    Some((path, language, text))
+  })
+}
+
+pub fn search_workspace(
+  registry: &Registry,
+  root: &Path,
+  pattern: &str,
+  open: &[(PathBuf, ropey::Rope)],
+  mut found: impl FnMut(Vec<Hit>) -> bool,
+) -> Result<(), String> {
+  let mut parser = tree_sitter::Parser::new();
+  let mut queries = HashMap::new();
+  let mut error = None;
+  let mut count = 0;
+  for (path, language, text) in workspace_texts(registry, root, open) {
+    let query = queries.entry(language.name.clone()).or_insert_with(|| {
+      compile_query(language.highlight.language, pattern)
+        .map_err(|err| error.get_or_insert(err).clone())
+        .ok()
+    });
+    let query = match query {
+      Some(query) => query,
+      None => continue,
+    };
+    parser.set_language(language.highlight.language).unwrap();
+    let source = text.bytes().collect::<Vec<u8>>();
+    let mut hits = match parser.parse(&source, None) {
+      Some(tree) => search_tree(query, &tree, &text, &path),
+      None => vec![],
+    };
+    hits.truncate(MAX_HITS - count);
+    count += hits.len();
+    if !found(hits) || count == MAX_HITS {
+      break;
+    }
+  }
+
+  match error {
+    Some(error) if queries.values().all(Option::is_none) => Err(error),
+    _ => Ok(()),
+  }
+}
+
+pub fn find_references(
+  registry: &Registry,
+  root: &Path,
+  name: &str,
+  open: &[(PathBuf, ropey::Rope)],
+  mut found: impl FnMut(Vec<Hit>) -> bool,
+) {
+  let mut parser = tree_sitter::Parser::new();
+  let mut count = 0;
+  for (path, language, text) in workspace_texts(registry, root, open) {
+    let source = text.bytes().collect::<Vec<u8>>();
+    // files without the name needn't be parsed
+    let has_name = source.windows(name.len()).any(|w| w == name.as_bytes());
+    let tree =
+      if has_name && parser.set_language(language.highlight.language).is_ok() {
+        parser.parse(&source, None)
+      } else {
+        None
+      };
+    let mut hits = match tree {
+      Some(tree) => locals::references(&language, &tree, &text, name, None)
+        .into_iter()
+        .filter_map(|range| Hit::new(&path, &text, vec![range]))
+        .collect(),
+      None => vec![],
+    };
+    hits.truncate(MAX_HITS - count);
+    count += hits.len();
+    if !found(hits) || count == MAX_HITS {
+      break;
+    }
+  }
+}
+
+pub enum Query {
+  Pattern(String),
+  References(String),
+  Symbols(String),
+}
+
+/// What a workspace search sent back: hits it found, or that it is over
+/// and whether the pattern suited any language.
+pub enum Found {
+  Hits(Vec<Hit>),
+  Done(Result<(), String>),
+}
+
+struct Job {
+  id: usize,
+  query: Query,
+  open: Vec<(PathBuf, ropey::Rope)>,
+}
+
+/// Searches the workspace on a thread of its own, sending the hits back as they
+/// are found.
+pub struct Searcher {
+  jobs: mpsc::Sender<Job>,
+  found: mpsc::Receiver<(usize, Found)>,
+  latest: Arc<AtomicUsize>,
+}
+
+impl Searcher {
+  pub fn spawn(
+    root: PathBuf,
+    symbols: Arc<Mutex<SymbolIndex>>,
+    waker: lsp::Waker,
+  ) -> Self {
+    let (jobs, queued) = mpsc::channel::<Job>();
+    let (send, found) = mpsc::channel();
+    let latest = Arc::new(AtomicUsize::new(0));
+    let current = Arc::clone(&latest);
+    std::thread::spawn(move || {
+      let registry = Registry::new();
+      while let Ok(job) = queued.recv() {
+        let stale = || current.load(Ordering::SeqCst) != job.id;
+        if stale() {
+          continue;
+        }
+        let mut pending = vec![];
+        let mut sent = Instant::now();
+        let mut found = |hits: Vec<Hit>| {
+          pending.extend(hits);
+          if !pending.is_empty() && sent.elapsed() >= STREAM_INTERVAL {
+            let hits = std::mem::take(&mut pending);
+            if send.send((job.id, Found::Hits(hits))).is_err() {
+              return false;
+            }
+            sent = Instant::now();
+            waker();
+          }
+          !stale()
+        };
+        let result = match &job.query {
+          Query::Pattern(pattern) => {
+            search_workspace(&registry, &root, pattern, &job.open, found)
+          }
+          Query::References(name) => {
+            find_references(&registry, &root, name, &job.open, found);
+            Ok(())
+          }
+          // searched in a copy, which leaves the index to the indexer
+          Query::Symbols(pattern) => {
+            let index = symbols.lock().unwrap().clone();
+            found(index.fuzzy_search(pattern, MAX_HITS));
+            Ok(())
+          }
+        };
+        if !pending.is_empty() {
+          let _ = send.send((job.id, Found::Hits(pending)));
+        }
+        if send.send((job.id, Found::Done(result))).is_err() {
+          return;
+        }
+        waker();
+      }
+    });
+    Self {
+      jobs,
+      found,
+      latest,
+    }
+  }
+
+  pub fn start(
+    &self,
+    query: Query,
+    open: Vec<(PathBuf, ropey::Rope)>,
+  ) -> usize {
+    let id = self.latest.fetch_add(1, Ordering::SeqCst) + 1;
+    let _ = self.jobs.send(Job { id, query, open });
+    id
+  }
+
+  pub fn cancel(&self) {
+    self.latest.fetch_add(1, Ordering::SeqCst);
+  }
+
+  pub fn poll(&self) -> Vec<(usize, Found)> {
+    let latest = self.latest.load(Ordering::SeqCst);
+    self
+      .found
+      .try_iter()
+      .filter(|(id, _)| *id == latest)
+      .collect()
+  }
+}
+
+/// Panel at the bottom of the code views for searching with a tree-sitter
+/// query, listing the hits with their captures marked.
+pub struct Search {
+  device: Rc<wgpu::Device>,
+  font: FontArc,
+  font_height: f32,
+  rect: Rectangle,
+  header_rect: Rectangle,
+  current_rect: Rectangle,
+  capture_rects: Vec<Rectangle>,
+  pub dimensions: Dimensions,
+  root: PathBuf,
+  pub pattern: String,
+  pub scope: Scope,
+  pub focused: bool,
+  title: Option<String>,
+  status: String,
+  hits: Vec<Hit>,
+  searching: Option<usize>,
+  current: Option<usize>,
+  scroll_offset_y: f32,
+  picked: Option<Hit>,
+}
+
+impl Search {
+  pub fn new(
+    device: &Rc<wgpu::Device>,
+    screen_size: PhysicalSize<f32>,
+    font: FontArc,
+    font_height: f32,
+    x: f32,
+    root: PathBuf,
+  ) -> Self {
+    let ui = theme::current().ui;
+    let rect = |color| {
+      Rectangle::new(
+        device,
+        screen_size,
+        Dimensions::default(),
+        theme::rgb(color),
+        None,
+      )
+    };
+    let mut search = Self {
+      device: Rc::clone(device),
+      font,
+      font_height,
+      rect: rect(ui.tree_background),
+      header_rect: rect(ui.breadcrumbs_background),
+      current_rect: rect(ui.selection),
+      capture_rects: vec![],
+      dimensions: Dimensions {
+        x,
+        ..Dimensions::default()
+      },
+      root,
+      pattern: String::new(),
+      scope: Scope::File,
+      focused: true,
+      title: None,
+      status: String::new(),
+      hits: vec![],
+      searching: None,
+      current: None,
+      scroll_offset_y: 0.0,
+      picked: None,
+    };
+    search.resize(screen_size);
+    search
+  }
+
+  fn hits_dimensions(&self) -> Dimensions {
+    let header_height = HEADER_ROWS as f32 * self.font_height;
+    Dimensions {
+      y: self.dimensions.y + header_height,
+      height: self.dimensions.height - header_height,
+      ..self.dimensions
+    }
+  }
+
+  pub fn input_char(&mut self, ch: char) -> bool {
+    self.title = None;
+    match ch {
+      '\r' => return true,
+      '\t' => {
+        self.scope = match self.scope {
+          Scope::File => Scope::Workspace,
+          Scope::Workspace => Scope::File,
+          scope => scope,
+        };
+      }
+      '\u{7f}' | '\u{8}' => {
+        self.pattern.pop();
+      }
+      c if c.is_control() => return false,
+      c => self.pattern.push(c),
+    }
+    self.scope == Scope::Symbols
+  }
+
+  fn count(&self) -> String {
+    let files = self
+      .hits
+      .iter()
+      .map(|hit| &hit.path)
+      .collect::<HashSet<_>>()
+      .len();
+    format!(
+      "{} {} in {} {}",
+      self.hits.len(),
+      if self.hits.len() == 1 { "hit" } else { "hits" },
+      files,
+      if files == 1 { "file" } else { "files" },
+    )
+  }
+
+  pub fn set_hits(
+    &mut self,
+    screen_size: PhysicalSize<f32>,
+    hits: Result<Vec<Hit>, String>,
+  ) {
+    match hits {
+      Ok(hits) => {
+        self.hits = hits;
+        self.status = self.count();
+      }
+      Err(error) => {
+        self.status = error;
+        self.hits = vec![];
+      }
+    }
+    self.searching = None;
+    self.current = None;
+    self.scroll_offset_y = 0.0;
+    self.update_rects(screen_size);
+  }
+
+  pub fn start(
+    &mut self,
+    screen_size: PhysicalSize<f32>,
+    id: usize,
+    title: Option<String>,
+  ) {
+    if title.is_some() {
+      self.title = title;
+      self.focused = false;
+    }
+    self.set_hits(screen_size, Ok(vec![]));
+    self.status = String::from("searching…");
+    self.searching = Some(id);
+  }
+
+  pub fn is_searching(&self, id: usize) -> bool {
+    self.searching == Some(id)
+  }
+
+  pub fn found(&mut self, screen_size: PhysicalSize<f32>, found: Found) {
+    match found {
+      Found::Hits(hits) => {
+        self.hits.extend(hits);
+        self.status = format!("{}, searching…", self.count());
+      }
+      Found::Done(Ok(())) => {
+        self.status = self.count();
+        self.searching = None;
+      }
+      Found::Done(Err(error)) => {
+        self.set_hits(screen_size, Err(error));
+        return;
+      }
+    }
+    self.update_rects(screen_size);
+  }
+
+  pub fn show(
+    &mut self,
+    screen_size: PhysicalSize<f32>,
+    title: String,
+    hits: Vec<Hit>,
+  ) {
+    self.title = Some(title);
+    self.focused = false;
+    self.set_hits(screen_size, Ok(hits));
+  }
+
+  pub fn ask(
+    &mut self,
+    screen_size: PhysicalSize<f32>,
+    scope: Scope,
+    pattern: String,
+  ) {
+    self.scope = scope;
+    self.pattern = pattern;
+    self.title = None;
+    self.focused = true;
+    self.status = String::new();
+    self.hits = vec![];
+    self.searching = None;
+    self.current = None;
+    self.scroll_offset_y = 0.0;
+    self.update_rects(screen_size);
+  }
+
+  pub fn step(&mut self, screen_size: PhysicalSize<f32>, forward: bool) {
+    if self.hits.is_empty() {
+      return;
+    }
+    let last = self.hits.len() - 1;
+    let current = match (self.current, forward) {
+      (None, true) => 0,
+      (None, false) => last,
+      (Some(i), true) => {
+        if i == last {
+          0
+        } else {
+          i + 1
+        }
+      }
+      (Some(i), false) => i.checked_sub(1).unwrap_or(last),
+    };
+    self.pick(screen_size, current);
+
+    let y = current as f32 * self.font_height;
+    let height = self.hits_dimensions().height;
+    if y + self.scroll_offset_y < 0.0 {
+      self.scroll_offset_y = -y;
+    } else if y + self.scroll_offset_y + self.font_height > height {
+      self.scroll_offset_y = height - self.font_height - y;
+    }
+    self.update_rects(screen_size);
+  }
+
+  fn pick(&mut self, screen_size: PhysicalSize<f32>, i: usize) {
+    self.current = Some(i);
+    self.picked = Some(self.hits[i].clone());
+    self.update_rects(screen_size);
+  }
+
+  pub fn take_picked(&mut self) -> Option<Hit> {
+    self.picked.take()
+  }
+
+  fn label(&self, hit: &Hit) -> String {
+    let path = hit.path.strip_prefix(&self.root).unwrap_or(&hit.path);
+    format!("{}:{}:{}  ", path.display(), hit.row + 1, hit.column + 1)
+  }
+
+  fn char_width(&self) -> f32 {
+    let font = self.font.as_scaled(self.font_height);
+    font.h_advance(font.glyph_id(' '))
+  }
+
+  fn indent(hit: &Hit) -> usize {
+    hit.line.chars().take_while(|c| c.is_whitespace()).count()
+  }
+
+  fn update_rects(&mut self, screen_size: PhysicalSize<f32>) {
+    let hits_dimensions = self.hits_dimensions();
+    let (scroll_offset_y, font_height) =
+      (self.scroll_offset_y, self.font_height);
+    let row_y =
+      |i: usize| hits_dimensions.y + scroll_offset_y + i as f32 * font_height;
+    self.current_rect.resize(
+      screen_size,
+      Dimensions {
+        y: row_y(self.current.unwrap_or_default()),
+        height: self.font_height,
+        ..hits_dimensions
+      },
+    );
+    self.current_rect.region = Some(hits_dimensions.into());
+
+    let first = (-self.scroll_offset_y / self.font_height).floor() as usize;
+    let char_width = self.char_width();
+    let color = theme::rgb(theme::current().ui.query_match);
+    let mut rects = vec![];
+    for (i, hit) in self.hits.iter().enumerate().skip(first).take(HIT_ROWS + 1)
+    {
+      let start = self.label(hit).chars().count();
+      let indent = Self::indent(hit);
+      for capture in &hit.captures {
+        let from = (start + capture.start.max(indent) - indent) as f32;
+        let to = (start + capture.end.max(indent) - indent) as f32;
+        rects.push(Rectangle::new(
+          &self.device,
+          screen_size,
+          Dimensions {
+            x: hits_dimensions.x + SEARCH_PADDING + from * char_width,
+            y: row_y(i),
+            width: (to - from) * char_width,
+            height: self.font_height,
+          },
+          color,
+          Some(hits_dimensions.into()),
+        ));
+      }
+    }
+    self.capture_rects = rects;
+  }
+
+  pub fn redraw_overlay(
+    &mut self,
+    glyph_brush: &mut GlyphBrush<()>,
+    device: &Device,
+    staging_belt: &mut StagingBelt,
+    encoder: &mut CommandEncoder,
+    target: &TextureView,
+    size: PhysicalSize<u32>,
+  ) {
+    let ui = theme::current().ui;
+    let x = self.dimensions.x + SEARCH_PADDING;
+    let prompt = match (&self.title, self.scope) {
+      (Some(title), _) => format!("{} › ", title),
+      (None, Scope::File) => "search file › ".to_string(),
+      (None, Scope::Workspace) => "search workspace › ".to_string(),
+      (None, Scope::Symbols) => "go to symbol › ".to_string(),
+      (None, Scope::Rename) => "rename to › ".to_string(),
+    };
+    let pattern = match self.title {
+      Some(_) => "",
+      None => &self.pattern,
+    };
+    let caret = if self.focused { "|" } else { "" };
+    glyph_brush.queue(Section {
+      screen_position: (x, self.dimensions.y),
+      text: vec![
+        Text::new(&prompt)
+          .with_scale(self.font_height)
+          .with_color(ui.breadcrumbs_foreground),
+        Text::new(pattern)
+          .with_scale(self.font_height)
+          .with_color(ui.foreground),
+        Text::new(caret)
+          .with_scale(self.font_height)
+          .with_color(ui.cursor),
+      ],
+      ..Section::default()
+    });
+    glyph_brush.queue(Section {
+      screen_position: (x, self.dimensions.y + self.font_height),
+      text: vec![Text::new(&self.status)
+        .with_scale(self.font_height)
+        .with_color(ui.breadcrumbs_foreground)],
+      ..Section::default()
+    });
+    glyph_brush
+      .draw_queued_with_transform_and_scissoring(
+        device,
+        staging_belt,
+        encoder,
+        target,
+        wgpu_glyph::orthographic_projection(size.width, size.height),
+        self.dimensions.into(),
+      )
+      .unwrap();
+
+    let hits_dimensions = self.hits_dimensions();
+    let first = (-self.scroll_offset_y / self.font_height).floor() as usize;
+    let rows = self
+      .hits
+      .iter()
+      .skip(first)
+      .take(HIT_ROWS + 1)
+      .map(|hit| {
+        let line = hit
+          .line
+          .chars()
+          .skip(Self::indent(hit))
+          .map(|c| if c == '\t' { ' ' } else { c })
+          .collect::<String>();
+        (self.label(hit), line)
+      })
+      .collect::<Vec<_>>();
+    for (i, (label, line)) in rows.iter().enumerate() {
+      glyph_brush.queue(Section {
+        screen_position: (
+          x,
+          hits_dimensions.y
+            + self.scroll_offset_y
+            + (first + i) as f32 * self.font_height,
+        ),
+        text: vec![
+          Text::new(label)
+            .with_scale(self.font_height)
+            .with_color(ui.breadcrumbs_foreground),
+          Text::new(line)
+            .with_scale(self.font_height)
+            .with_color(ui.foreground),
+        ],
+        ..Section::default()
+      });
+    }
+    glyph_brush
+      .draw_queued_with_transform_and_scissoring(
+        device,
+        staging_belt,
+        encoder,
+        target,
+        wgpu_glyph::orthographic_projection(size.width, size.height),
+        hits_dimensions.into(),
+      )
+      .unwrap();
+  }
+
+  pub fn get_overlay_rects(&self) -> Vec<&Rectangle> {
+    let mut vec = vec![&self.rect, &self.header_rect];
+    if self.current.is_some() {
+      vec.push(&self.current_rect);
+    }
+    vec.extend(self.capture_rects.iter());
+    vec
+  }
+}
+
+impl super::RenderElement for Search {
+  fn resize(&mut self, screen_size: PhysicalSize<f32>) {
+    let height = (HEADER_ROWS + HIT_ROWS) as f32 * self.font_height;
+    self.dimensions = Dimensions {
+      x: self.dimensions.x,
+      y: screen_size.height - height,
+      width: screen_size.width - self.dimensions.x,
+      height,
+    };
+    self.rect.resize(screen_size, self.dimensions);
+    self.header_rect.resize(
+      screen_size,
+      Dimensions {
+        height: HEADER_ROWS as f32 * self.font_height,
+        ..self.dimensions
+      },
+    );
+    self.update_rects(screen_size);
+  }
+
+  fn theme_changed(&mut self, screen_size: PhysicalSize<f32>) {
+    let ui = theme::current().ui;
+    self.rect.set_color(theme::rgb(ui.tree_background));
+    self
+      .header_rect
+      .set_color(theme::rgb(ui.breadcrumbs_background));
+    self.current_rect.set_color(theme::rgb(ui.selection));
+    self.update_rects(screen_size);
+  }
+
+  fn scroll(
+    &mut self,
+    offset: PhysicalPosition<f64>,
+    screen_size: PhysicalSize<f32>,
+  ) {
+    let hidden = self.hits.len().saturating_sub(HIT_ROWS);
+    self.scroll_offset_y = (self.scroll_offset_y + offset.y as f32)
+      .min(0.0)
+      .max(-(hidden as f32 * self.font_height));
+    self.update_rects(screen_size);
+  }
+
+  fn click(
+    &mut self,
+    position: PhysicalPosition<f64>,
+    screen_size: PhysicalSize<f32>,
+    _modifiers: ModifiersState,
+  ) {
+    self.focused = true;
+    let y = position.y as f32 - HEADER_ROWS as f32 * self.font_height;
+    if y >= 0.0 {
+      let row = ((y - self.scroll_offset_y) / self.font_height).floor();
+      if (row as usize) < self.hits.len() {
+        self.pick(screen_size, row as usize);
+      }
+    }
+  }
+
+  fn redraw(
+    &mut self,
+    _glyph_brush: &mut GlyphBrush<()>,
+    _device: &Device,
+    _staging_belt: &mut StagingBelt,
+    _encoder: &mut CommandEncoder,
+    _target: &TextureView,
+    _size: PhysicalSize<u32>,
+  ) {
+  }
+
+  fn get_rects(&self) -> Vec<&Rectangle> {
+    vec![]
+  }
+
+  fn get_elements(&mut self) -> Vec<&mut dyn super::RenderElement> {
+    vec![]
+  }
+
+  fn get_dimensions(&self) -> Dimensions {
+    self.dimensions
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::renderer::code_view::language::parse_builtin;
+
+  #[test]
+  fn finds_matches_with_predicates() {
+    let source = "fn a() {\n    b.unwrap();\n    c.expect(\"\");\n}\n";
+    let (language, tree) = parse_builtin("rust", source);
+    let text = ropey::Rope::from_str(source);
+
+    let query = tree_sitter::Query::new(
+      language.highlight.language,
+      "(call_expression function: (field_expression field: \
+       (field_identifier) @f (#eq? @f \"unwrap\")))",
+    )
+    .unwrap();
+    let hits = search_tree(&query, &tree, &text, Path::new("a.rs"));
+    assert_eq!(
+      hits,
+      vec![Hit {
+        path: PathBuf::from("a.rs"),
+        char_idx: 15,
+        row: 1,
+        column: 6,
+        line: "    b.unwrap();".to_string(),
+        captures: vec![6..12],
+      }]
+    );
+  }
+
+  #[test]
+  fn reports_patterns_no_language_accepts() {
+    let registry = Registry::builtin();
+    let root = Path::new(env!("CARGO_MANIFEST_DIR")).join("src");
+    assert_eq!(
+      search_workspace(&registry, &root, "(nonsense", &[], |_| true),
+      Err("NodeType error at 1:2".to_string())
+    );
+
+    let mut hits = vec![];
+    search_workspace(&registry, &root, "(mod_item name: (_) @m)", &[], |h| {
+      hits.extend(h);
+      true
+    })
+    .unwrap();
+    assert!(hits
+      .iter()
+      .any(|hit| hit.path.ends_with("renderer/mod.rs")
+        && hit.line == "mod search;"));
+  }
+
+  #[test]
+  fn finds_references_across_files() {
+    let registry = Registry::builtin();
+    let root = Path::new(env!("CARGO_MANIFEST_DIR")).join("src");
+    let mut hits = vec![];
+    find_references(&registry, &root, "Searcher", &[], |h| {
+      hits.extend(h);
+      true
+    });
+    let lines = hits.iter().map(|hit| hit.line.trim()).collect::<Vec<_>>();
+    assert!(lines.contains(&"pub struct Searcher {"));
+    assert!(lines.contains(&"searcher: Searcher,"));
+  }
+
+  #[test]
+  fn leaves_out_ignored_files() {
+    let root = std::env::temp_dir()
+      .join(format!("devcode-gitignore-test-{}", std::process::id()));
+    std::fs::create_dir_all(root.join(".git")).unwrap();
+    std::fs::create_dir_all(root.join("build/keep")).unwrap();
+    std::fs::create_dir_all(root.join("src/gen")).unwrap();
+    let files = [
+      "a.rs",
+      "a.pyc",
+      "a.pyo",
+      "b.log",
+      "important.log",
+      "build/out.rs",
+      "src/a.rs",
+      "src/gen/a.rs",
+      "src/gen/b.txt",
+    ];
+    for file in files {
+      std::fs::write(root.join(file), "").unwrap();
+    }
+    std::fs::write(
+      root.join(".gitignore"),
+      "# build output\n/build/\n*.py[co]\n*.log\n!important.log\n",
+    )
+    .unwrap();
+    std::fs::write(root.join("src/.gitignore"), "gen/**\n!*.rs\n").unwrap();
+
+    let mut found = vec![];
+    workspace_files(&root.join("src"), &mut found);
+    assert_eq!(
+      found,
+      vec![root.join("src/a.rs"), root.join("src/gen/a.rs")]
+    );
+
+    found.clear();
+    workspace_files(&root, &mut found);
+    assert_eq!(
+      found,
+      ["a.rs", "important.log", "src/a.rs", "src/gen/a.rs"]
+        .iter()
+        .map(|file| root.join(file))
+        .collect::<Vec<_>>()
+    );
+    std::fs::remove_dir_all(&root).unwrap();
+  }
+}
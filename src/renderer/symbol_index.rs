@@ -0,0 +1,488 @@
+use crate::renderer::code_view::language::Registry;
+use crate::renderer::code_view::tags;
+use crate::renderer::search::{self, Hit};
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Directory holding the index of each workspace, to start from next time.
+const CACHE_DIR: &str = "~/.cache/devcode/symbols";
+/// First line of the index files, changed along with their format.
+const CACHE_HEADER: &str = "devcode symbols 1";
+/// The workspace is looked through for changed files this often.
+const RESCAN_INTERVAL: Duration = Duration::from_secs(30);
+/// Files indexed before the symbols found in them are made available.
+const BATCH_SIZE: usize = 200;
+
+/// Definition found in a file of the workspace by the tags query of its
+/// language.
+#[derive(Clone, Debug, PartialEq)]
+pub struct IndexedSymbol {
+  pub name: String,
+  pub kind: String,
+  pub hit: Hit,
+}
+
+/// When a file was modified and its size, which tell whether it changed
+/// since it was indexed.
+#[derive(Copy, Clone, Debug, PartialEq)]
+struct Stamp {
+  modified: SystemTime,
+  len: u64,
+}
+
+impl Stamp {
+  fn of(path: &Path) -> Option<Self> {
+    let metadata = path.metadata().ok()?;
+    Some(Self {
+      modified: metadata.modified().ok()?,
+      len: metadata.len(),
+    })
+  }
+}
+
+/// Symbols defined in the files of the workspace.
+#[derive(Clone, Default)]
+pub struct SymbolIndex {
+  files: HashMap<PathBuf, (Stamp, Arc<Vec<IndexedSymbol>>)>,
+}
+
+impl SymbolIndex {
+  fn changes(
+    stamps: &HashMap<PathBuf, Stamp>,
+    root: &Path,
+  ) -> (Vec<(PathBuf, Stamp)>, Vec<PathBuf>) {
+    let mut paths = vec![];
+    search::workspace_files(root, &mut paths);
+
+    let mut changed = vec![];
+    let mut gone = stamps.clone();
+    for path in paths {
+      let stamp = match Stamp::of(&path) {
+        Some(stamp) => stamp,
+        None => continue,
+      };
+      if gone.remove(&path) != Some(stamp) {
+        changed.push((path, stamp));
+      }
+    }
+    (changed, gone.into_iter().map(|(path, _)| path).collect())
+  }
+
+  fn stamps(&self) -> HashMap<PathBuf, Stamp> {
+    self
+      .files
+      .iter()
+      .map(|(path, (stamp, _))| (path.clone(), *stamp))
+      .collect()
+  }
+
+  pub fn lookup(&self, name: &str) -> Vec<&IndexedSymbol> {
+    let mut symbols = self
+      .files
+      .values()
+      .flat_map(|(_, symbols)| symbols.iter())
+      .filter(|symbol| symbol.name == name)
+      .collect::<Vec<_>>();
+    symbols.sort_by(|a, b| {
+      (&a.hit.path, a.hit.char_idx).cmp(&(&b.hit.path, b.hit.char_idx))
+    });
+    symbols
+  }
+
+  pub fn fuzzy_search(&self, pattern: &str, limit: usize) -> Vec<Hit> {
+    if pattern.is_empty() {
+      return vec![];
+    }
+    let mut matches = self
+      .files
+      .values()
+      .flat_map(|(_, symbols)| symbols.iter())
+      .filter_map(|symbol| {
+        let (score, chars) = fuzzy_match(pattern, &symbol.name)?;
+        Some((score, symbol, chars))
+      })
+      .collect::<Vec<_>>();
+    matches.sort_by(|(a_score, a, _), (b_score, b, _)| {
+      b_score
+        .cmp(a_score)
+        .then_with(|| a.name.len().cmp(&b.name.len()))
+        .then_with(|| (&a.hit.path, a.hit.row).cmp(&(&b.hit.path, b.hit.row)))
+    });
+    matches.truncate(limit);
+
+    matches
+      .into_iter()
+      .map(|(_, symbol, chars)| {
+        let start = symbol.hit.captures.first().map_or(0, |name| name.start);
+        let mut captures: Vec<Range<usize>> = vec![];
+        for i in chars {
+          match captures.last_mut() {
+            Some(last) if last.end == start + i => last.end += 1,
+            _ => captures.push(start + i..start + i + 1),
+          }
+        }
+        Hit {
+          captures,
+          ..symbol.hit.clone()
+        }
+      })
+      .collect()
+  }
+
+  fn apply(
+    &mut self,
+    indexed: Vec<(PathBuf, Stamp, Vec<IndexedSymbol>)>,
+    gone: &[PathBuf],
+  ) {
+    for path in gone {
+      self.files.remove(path);
+    }
+    for (path, stamp, symbols) in indexed {
+      self.files.insert(path, (stamp, Arc::new(symbols)));
+    }
+  }
+
+  fn serialize(&self) -> String {
+    let mut out = format!("{}\n", CACHE_HEADER);
+    for (path, (stamp, symbols)) in &self.files {
+      let path = match path.to_str() {
+        Some(path) if !path.contains('\n') => path,
+        _ => continue,
+      };
+      let since_epoch = stamp
+        .modified
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default();
+      out += &format!(
+        "F\t{}\t{}\t{}\t{}\n",
+        since_epoch.as_secs(),
+        since_epoch.subsec_nanos(),
+        stamp.len,
+        path
+      );
+      for symbol in symbols.iter() {
+        let hit = &symbol.hit;
+        let name = hit.captures.first().cloned().unwrap_or_default();
+        out += &format!(
+          "S\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\n",
+          symbol.kind,
+          symbol.name,
+          hit.char_idx,
+          hit.row,
+          hit.column,
+          name.start,
+          name.end,
+          hit.line
+        );
+      }
+    }
+    out
+  }
+
+  fn deserialize(source: &str) -> Option<Self> {
+    let mut lines = source.split('\n');
+    if lines.next() != Some(CACHE_HEADER) {
+      return None;
+    }
+    let mut files = HashMap::new();
+    let mut current: Option<(PathBuf, Stamp, Vec<IndexedSymbol>)> = None;
+    for line in lines.filter(|line| !line.is_empty()) {
+      if let Some(file) = line.strip_prefix("F\t") {
+        let fields = file.splitn(4, '\t').collect::<Vec<_>>();
+        if fields.len() != 4 {
+          return None;
+        }
+        let modified = UNIX_EPOCH
+          + Duration::new(fields[0].parse().ok()?, fields[1].parse().ok()?);
+        let stamp = Stamp {
+          modified,
+          len: fields[2].parse().ok()?,
+        };
+        if let Some((path, stamp, symbols)) = current.take() {
+          files.insert(path, (stamp, Arc::new(symbols)));
+        }
+        current = Some((PathBuf::from(fields[3]), stamp, vec![]));
+      } else if let Some(symbol) = line.strip_prefix("S\t") {
+        let fields = symbol.splitn(8, '\t').collect::<Vec<_>>();
+        let (path, _, symbols) = current.as_mut()?;
+        if fields.len() != 8 {
+          return None;
+        }
+        let number = |i: usize| fields[i].parse::<usize>().ok();
+        symbols.push(IndexedSymbol {
+          kind: fields[0].to_string(),
+          name: fields[1].to_string(),
+          hit: Hit {
+            path: path.clone(),
+            char_idx: number(2)?,
+            row: number(3)?,
+            column: number(4)?,
+            captures: vec![number(5)?..number(6)?],
+            line: fields[7].to_string(),
+          },
+        });
+      } else {
+        return None;
+      }
+    }
+    if let Some((path, stamp, symbols)) = current {
+      files.insert(path, (stamp, Arc::new(symbols)));
+    }
+    Some(Self { files })
+  }
+}
+
+pub fn fuzzy_match(pattern: &str, name: &str) -> Option<(i64, Vec<usize>)> {
+  let pattern = pattern.to_lowercase().chars().collect::<Vec<_>>();
+  let chars = name.chars().collect::<Vec<_>>();
+  let lower = chars
+    .iter()
+    .map(|c| c.to_lowercase().next().unwrap_or(*c))
+    .collect::<Vec<_>>();
+  let word_start = |i: usize| {
+    i == 0
+      || !chars[i - 1].is_alphanumeric()
+      || (chars[i - 1].is_lowercase() && chars[i].is_uppercase())
+  };
+  // the last index each rest of the pattern can start being matched at
+  let mut limits = vec![lower.len(); pattern.len() + 1];
+  let mut limit = lower.len();
+  for (p, c) in pattern.iter().enumerate().rev() {
+    limit = (0..limit).rev().find(|&i| lower[i] == *c)?;
+    limits[p] = limit;
+  }
+  let fits = |rest: usize, from: usize| from <= limits[rest];
+
+  let mut matched: Vec<usize> = vec![];
+  let mut score = 0;
+  let mut from = 0;
+  for (p, c) in pattern.iter().enumerate() {
+    let rest = p + 1;
+    let candidates = (from..lower.len()).filter(|&i| lower[i] == *c);
+    let consecutive = matched.last().map(|last| last + 1) == Some(from)
+      && lower.get(from) == Some(c)
+      && fits(rest, from + 1);
+    let i = if consecutive {
+      from
+    } else {
+      candidates
+        .clone()
+        .find(|&i| word_start(i) && fits(rest, i + 1))
+        .or_else(|| candidates.clone().next())?
+    };
+
+    score += 1;
+    if matched.last().map(|last| last + 1) == Some(i) {
+      score += 8;
+    }
+    if word_start(i) {
+      score += if i == 0 { 10 } else { 6 };
+    }
+    matched.push(i);
+    from = i + 1;
+  }
+  Some((score - chars.len() as i64 / 4, matched))
+}
+
+fn index_file(
+  registry: &Registry,
+  parser: &mut tree_sitter::Parser,
+  path: &Path,
+) -> Option<Vec<IndexedSymbol>> {
+  let text = search::read_file(path)?;
+  let language = registry.detect(path, &text)?;
+  let query = language.tags.as_ref()?;
+  parser.set_language(language.highlight.language).ok()?;
+  let tree = parser.parse(text.bytes().collect::<Vec<u8>>(), None)?;
+  let symbols = tags::symbols(query, &tree, &text)
+    .into_iter()
+    .filter_map(|symbol| {
+      let name_end = symbol.name_start + symbol.name.chars().count();
+      let hit = Hit::new(path, &text, vec![symbol.name_start..name_end])?;
+      Some(IndexedSymbol {
+        name: symbol.name,
+        kind: symbol.kind,
+        hit,
+      })
+    })
+    .collect();
+  Some(symbols)
+}
+
+fn cache_path(root: &Path) -> PathBuf {
+  let mut hasher = DefaultHasher::new();
+  root.hash(&mut hasher);
+  let dir = shellexpand::tilde(CACHE_DIR);
+  Path::new(dir.as_ref()).join(format!("{:016x}", hasher.finish()))
+}
+
+fn save(path: &Path, contents: &str) -> std::io::Result<()> {
+  if let Some(dir) = path.parent() {
+    std::fs::create_dir_all(dir)?;
+  }
+  let temporary = path.with_extension("tmp");
+  std::fs::write(&temporary, contents)?;
+  std::fs::rename(&temporary, path)
+}
+
+/// Keeps the symbol index of a workspace up to date on a thread of its own,
+/// starting from the one saved by the last run and saving it after each
+/// change.
+pub struct SymbolIndexer {
+  index: Arc<Mutex<SymbolIndex>>,
+  wake: mpsc::Sender<()>,
+}
+
+impl SymbolIndexer {
+  pub fn spawn(root: PathBuf) -> Self {
+    let index = Arc::new(Mutex::new(SymbolIndex::default()));
+    let (wake, woken) = mpsc::channel();
+    let shared = Arc::clone(&index);
+    std::thread::spawn(move || {
+      let cache = cache_path(&root);
+      if let Some(saved) = std::fs::read_to_string(&cache)
+        .ok()
+        .and_then(|source| SymbolIndex::deserialize(&source))
+      {
+        *shared.lock().unwrap() = saved;
+      }
+      let registry = Registry::new();
+      let mut parser = tree_sitter::Parser::new();
+      loop {
+        let stamps = shared.lock().unwrap().stamps();
+        let (changed, gone) = SymbolIndex::changes(&stamps, &root);
+        if !gone.is_empty() {
+          shared.lock().unwrap().apply(vec![], &gone);
+        }
+        for batch in changed.chunks(BATCH_SIZE) {
+          let indexed = batch
+            .iter()
+            .map(|(path, stamp)| {
+              let symbols =
+                index_file(&registry, &mut parser, path).unwrap_or_default();
+              (path.clone(), *stamp, symbols)
+            })
+            .collect();
+          shared.lock().unwrap().apply(indexed, &[]);
+        }
+        if !changed.is_empty() || !gone.is_empty() {
+          // written from a copy so that lookups aren't held up meanwhile
+          let index = shared.lock().unwrap().clone();
+          if let Err(err) = save(&cache, &index.serialize()) {
+            log::warn!("failed to save the symbol index: {}", err);
+          }
+        }
+
+        match woken.recv_timeout(RESCAN_INTERVAL) {
+          Ok(()) | Err(RecvTimeoutError::Timeout) => {
+            while woken.try_recv().is_ok() {}
+          }
+          Err(RecvTimeoutError::Disconnected) => return,
+        }
+      }
+    });
+    Self { index, wake }
+  }
+
+  pub fn refresh(&self) {
+    let _ = self.wake.send(());
+  }
+
+  pub fn index(&self) -> MutexGuard<SymbolIndex> {
+    self.index.lock().unwrap()
+  }
+
+  pub fn shared(&self) -> Arc<Mutex<SymbolIndex>> {
+    Arc::clone(&self.index)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn update(index: &mut SymbolIndex, registry: &Registry, root: &Path) {
+    let (changed, gone) = SymbolIndex::changes(&index.stamps(), root);
+    let mut parser = tree_sitter::Parser::new();
+    let indexed = changed
+      .into_iter()
+      .map(|(path, stamp)| {
+        let symbols =
+          index_file(registry, &mut parser, &path).unwrap_or_default();
+        (path, stamp, symbols)
+      })
+      .collect();
+    index.apply(indexed, &gone);
+  }
+
+  #[test]
+  fn looks_up_symbols_of_the_workspace() {
+    let root = std::env::temp_dir()
+      .join(format!("devcode-symbols-test-{}", std::process::id()));
+    std::fs::create_dir_all(root.join("src")).unwrap();
+    std::fs::write(
+      root.join("src/index.rs"),
+      "pub struct SymbolIndex {\n  files: usize,\n}\n\nimpl SymbolIndex {\n}\n",
+    )
+    .unwrap();
+    std::fs::write(root.join("other.py"), "def index():\n  pass\n").unwrap();
+
+    let registry = Registry::builtin();
+    let mut index = SymbolIndex::default();
+    update(&mut index, &registry, &root);
+
+    let symbols = index.lookup("SymbolIndex");
+    assert_eq!(
+      symbols
+        .iter()
+        .map(|symbol| (symbol.kind.as_str(), symbol.hit.line.as_str()))
+        .collect::<Vec<_>>(),
+      vec![
+        ("class", "pub struct SymbolIndex {"),
+        ("implementation", "impl SymbolIndex {")
+      ]
+    );
+    assert_eq!(symbols[0].hit.path, root.join("src/index.rs"));
+    assert_eq!(symbols[0].hit.captures, vec![11..22]);
+
+    // unchanged files are not indexed again
+    assert_eq!(
+      SymbolIndex::changes(&index.stamps(), &root),
+      (vec![], vec![])
+    );
+
+    let saved = SymbolIndex::deserialize(&index.serialize()).unwrap();
+    assert_eq!(saved.files, index.files);
+
+    let hits = index.fuzzy_search("symidx", 1);
+    assert_eq!(hits.len(), 1);
+    assert_eq!(hits[0].line, "pub struct SymbolIndex {");
+    assert_eq!(hits[0].captures, vec![11..14, 17..18, 19..20, 21..22]);
+    assert_eq!(index.lookup("index")[0].kind, "function");
+
+    // removed files are forgotten
+    std::fs::remove_file(root.join("other.py")).unwrap();
+    update(&mut index, &registry, &root);
+    assert!(index.lookup("index").is_empty());
+    std::fs::remove_dir_all(&root).unwrap();
+  }
+
+  #[test]
+  fn prefers_word_starts_and_runs() {
+    assert_eq!(fuzzy_match("gtd", "go_to_definition").unwrap().1, [0, 3, 6]);
+    assert_eq!(fuzzy_match("def", "go_to_definition").unwrap().1, [6, 7, 8]);
+    assert_eq!(fuzzy_match("cvt", "CodeViewTabs").unwrap().1, [0, 4, 8]);
+    assert_eq!(fuzzy_match("xyz", "CodeViewTabs"), None);
+
+    let score = |pattern, name| fuzzy_match(pattern, name).unwrap().0;
+    assert!(score("view", "View") > score("view", "CodeView"));
+    assert!(score("view", "CodeView") > score("view", "vertex_in_edge_walk"));
+    assert!(score("cvt", "CodeViewTabs") > score("cvt", "concave_tile"));
+  }
+}
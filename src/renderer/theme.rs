@@ -0,0 +1,453 @@
+pub mod vscode;
+
+use super::code_view::highlight::{HighlightNames, Style};
+use crate::lsp::Severity;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
+
+pub const THEMES_DIR: &str = "~/.config/devcode/themes";
+const DEFAULT_THEME: &str = include_str!("./theme/default.toml");
+/// How often the file of the active theme is checked for changes.
+const WATCH_INTERVAL: Duration = Duration::from_secs(1);
+
+pub type Color = [f32; 4];
+
+pub fn rgb(color: Color) -> [f32; 3] {
+  [color[0], color[1], color[2]]
+}
+
+pub fn parse_color(hex: &str) -> Result<Color, anyhow::Error> {
+  let digits = hex
+    .strip_prefix('#')
+    .filter(|digits| {
+      (digits.len() == 6 || digits.len() == 8)
+        && digits.chars().all(|c| c.is_ascii_hexdigit())
+    })
+    .ok_or_else(|| anyhow::anyhow!("invalid color `{}`", hex))?;
+  let channel = |i: usize| {
+    u8::from_str_radix(&digits[i * 2..i * 2 + 2], 16).unwrap() as f32 / 255.0
+  };
+  let alpha = if digits.len() == 8 { channel(3) } else { 1.0 };
+  Ok([channel(0), channel(1), channel(2), alpha])
+}
+
+/// Colors of the parts of the editor around the text.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Ui {
+  pub background: Color,
+  pub foreground: Color,
+  pub tree_background: Color,
+  pub tree_foreground: Color,
+  pub tab_bar_background: Color,
+  pub tab_background: Color,
+  pub tab_foreground: Color,
+  pub breadcrumbs_background: Color,
+  pub breadcrumbs_foreground: Color,
+  pub sticky_scroll_background: Color,
+  pub gutter_background: Color,
+  pub gutter_foreground: Color,
+  pub selection: Color,
+  pub matching_bracket: Color,
+  pub query_match: Color,
+  pub cursor: Color,
+  pub diagnostic_error: Color,
+  pub diagnostic_warning: Color,
+  pub diagnostic_info: Color,
+  pub diagnostic_hint: Color,
+  pub completion_background: Color,
+  pub completion_selection: Color,
+  pub tooltip_background: Color,
+}
+
+impl Ui {
+  pub fn diagnostic(&self, severity: Severity) -> Color {
+    match severity {
+      Severity::Error => self.diagnostic_error,
+      Severity::Warning => self.diagnostic_warning,
+      Severity::Information => self.diagnostic_info,
+      Severity::Hint => self.diagnostic_hint,
+    }
+  }
+
+  fn parse(
+    table: Option<&toml::Value>,
+    base: Option<&Ui>,
+  ) -> Result<Self, anyhow::Error> {
+    let color = |key: &str, base: Option<Color>| match table
+      .and_then(|table| table.get(key))
+    {
+      Some(value) => parse_color(
+        value
+          .as_str()
+          .ok_or_else(|| anyhow::anyhow!("`ui.{}` isn't a string", key))?,
+      ),
+      None => base.ok_or_else(|| anyhow::anyhow!("missing `ui.{}`", key)),
+    };
+
+    Ok(Self {
+      background: color("background", base.map(|b| b.background))?,
+      foreground: color("foreground", base.map(|b| b.foreground))?,
+      tree_background: color(
+        "tree_background",
+        base.map(|b| b.tree_background),
+      )?,
+      tree_foreground: color(
+        "tree_foreground",
+        base.map(|b| b.tree_foreground),
+      )?,
+      tab_bar_background: color(
+        "tab_bar_background",
+        base.map(|b| b.tab_bar_background),
+      )?,
+      tab_background: color("tab_background", base.map(|b| b.tab_background))?,
+      tab_foreground: color("tab_foreground", base.map(|b| b.tab_foreground))?,
+      breadcrumbs_background: color(
+        "breadcrumbs_background",
+        base.map(|b| b.breadcrumbs_background),
+      )?,
+      breadcrumbs_foreground: color(
+        "breadcrumbs_foreground",
+        base.map(|b| b.breadcrumbs_foreground),
+      )?,
+      sticky_scroll_background: color(
+        "sticky_scroll_background",
+        base.map(|b| b.sticky_scroll_background),
+      )?,
+      gutter_background: color(
+        "gutter_background",
+        base.map(|b| b.gutter_background),
+      )?,
+      gutter_foreground: color(
+        "gutter_foreground",
+        base.map(|b| b.gutter_foreground),
+      )?,
+      selection: color("selection", base.map(|b| b.selection))?,
+      matching_bracket: color(
+        "matching_bracket",
+        base.map(|b| b.matching_bracket),
+      )?,
+      query_match: color("query_match", base.map(|b| b.query_match))?,
+      cursor: color("cursor", base.map(|b| b.cursor))?,
+      diagnostic_error: color(
+        "diagnostic_error",
+        base.map(|b| b.diagnostic_error),
+      )?,
+      diagnostic_warning: color(
+        "diagnostic_warning",
+        base.map(|b| b.diagnostic_warning),
+      )?,
+      diagnostic_info: color(
+        "diagnostic_info",
+        base.map(|b| b.diagnostic_info),
+      )?,
+      diagnostic_hint: color(
+        "diagnostic_hint",
+        base.map(|b| b.diagnostic_hint),
+      )?,
+      completion_background: color(
+        "completion_background",
+        base.map(|b| b.completion_background),
+      )?,
+      completion_selection: color(
+        "completion_selection",
+        base.map(|b| b.completion_selection),
+      )?,
+      tooltip_background: color(
+        "tooltip_background",
+        base.map(|b| b.tooltip_background),
+      )?,
+    })
+  }
+}
+
+pub struct Theme {
+  pub name: String,
+  pub ui: Ui,
+  highlights: Vec<(Color, Style)>,
+}
+
+impl Theme {
+  pub fn new(
+    name: String,
+    ui: Ui,
+    syntax: &HashMap<String, (Option<Color>, Style)>,
+  ) -> Self {
+    let highlights = HighlightNames::VARIANTS
+      .iter()
+      .map(|highlight| {
+        let mut name = highlight.to_string();
+        loop {
+          if let Some((color, style)) = syntax.get(&name) {
+            break (color.unwrap_or(ui.foreground), *style);
+          }
+          match name.rfind('.') {
+            Some(i) => name.truncate(i),
+            None => break (ui.foreground, Style::default()),
+          }
+        }
+      })
+      .collect();
+
+    Self {
+      name,
+      ui,
+      highlights,
+    }
+  }
+
+  pub fn builtin() -> Self {
+    Self::parse(DEFAULT_THEME, "devcode", None).unwrap()
+  }
+
+  pub fn parse(
+    source: &str,
+    default_name: &str,
+    base: Option<&Ui>,
+  ) -> Result<Self, anyhow::Error> {
+    let value = source.parse::<toml::Value>()?;
+    let name = value
+      .get("name")
+      .and_then(|name| name.as_str())
+      .unwrap_or(default_name)
+      .to_string();
+    let ui = Ui::parse(value.get("ui"), base)?;
+
+    let mut syntax = HashMap::new();
+    if let Some(table) = value.get("syntax").and_then(|s| s.as_table()) {
+      for (key, value) in table {
+        let entry = if let Some(hex) = value.as_str() {
+          (Some(parse_color(hex)?), Style::default())
+        } else {
+          let flag = |name: &str| {
+            value.get(name).and_then(|v| v.as_bool()).unwrap_or(false)
+          };
+          let color = value
+            .get("color")
+            .and_then(|color| color.as_str())
+            .map(parse_color)
+            .transpose()?;
+          let style = Style {
+            bold: flag("bold"),
+            italic: flag("italic"),
+            underline: flag("underline"),
+            strikethrough: flag("strikethrough"),
+          };
+          (color, style)
+        };
+        syntax.insert(key.clone(), entry);
+      }
+    }
+
+    Ok(Self::new(name, ui, &syntax))
+  }
+
+  pub fn load(path: &Path, base: &Ui) -> Result<Self, anyhow::Error> {
+    let source = std::fs::read_to_string(path)?;
+    let default_name = path
+      .file_stem()
+      .map(|stem| stem.to_string_lossy().into_owned())
+      .unwrap_or_default();
+    if path.extension().map_or(false, |ext| ext == "json") {
+      vscode::import(&source, &default_name, base)
+    } else {
+      Self::parse(&source, &default_name, Some(base))
+    }
+  }
+
+  pub fn highlight(&self, highlight: HighlightNames) -> (Color, Style) {
+    self.highlights[highlight as usize]
+  }
+}
+
+thread_local! {
+  /// The theme everything is drawn with.
+  static CURRENT: RefCell<Rc<Theme>> = RefCell::new(Rc::new(Theme::builtin()));
+}
+
+pub fn current() -> Rc<Theme> {
+  CURRENT.with(|current| Rc::clone(&current.borrow()))
+}
+
+fn set_current(theme: Rc<Theme>) {
+  CURRENT.with(|current| *current.borrow_mut() = theme);
+}
+
+struct Entry {
+  theme: Rc<Theme>,
+  path: Option<(PathBuf, Option<SystemTime>)>,
+}
+
+pub struct Themes {
+  entries: Vec<Entry>,
+  active: usize,
+  watched: Arc<Mutex<Option<PathBuf>>>,
+}
+
+impl Themes {
+  pub fn new(name: Option<&str>, waker: crate::lsp::Waker) -> Self {
+    let mut themes = Self {
+      entries: vec![Entry {
+        theme: Rc::new(Theme::builtin()),
+        path: None,
+      }],
+      active: 0,
+      watched: Arc::new(Mutex::new(None)),
+    };
+    let dir = shellexpand::tilde(THEMES_DIR);
+    for err in themes.load_dir(Path::new(dir.as_ref())) {
+      log::error!("failed to load theme: {}", err);
+    }
+
+    let active = name.and_then(|name| {
+      themes
+        .entries
+        .iter()
+        .position(|entry| entry.theme.name.eq_ignore_ascii_case(name))
+    });
+    if let (Some(name), None) = (name, active) {
+      log::warn!("unknown theme {}", name);
+    }
+    themes.activate(active.unwrap_or(0));
+    spawn_watcher(Arc::clone(&themes.watched), waker);
+    themes
+  }
+
+  pub fn load_dir(&mut self, dir: &Path) -> Vec<anyhow::Error> {
+    let mut paths = match std::fs::read_dir(dir) {
+      Ok(entries) => entries
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|path| {
+          path
+            .extension()
+            .map_or(false, |ext| ext == "toml" || ext == "json")
+        })
+        .collect::<Vec<_>>(),
+      Err(_) => return vec![],
+    };
+    paths.sort();
+
+    let base = self.entries[0].theme.ui;
+    let mut errors = vec![];
+    for path in paths {
+      match Theme::load(&path, &base) {
+        Ok(theme) => self.entries.push(Entry {
+          theme: Rc::new(theme),
+          path: Some((path.clone(), modified(&path))),
+        }),
+        Err(err) => errors.push(err.context(format!("{:?}", path))),
+      }
+    }
+    errors
+  }
+
+  fn activate(&mut self, i: usize) {
+    self.active = i;
+    *self.watched.lock().unwrap() =
+      self.entries[i].path.as_ref().map(|(path, _)| path.clone());
+    set_current(Rc::clone(&self.entries[i].theme));
+  }
+
+  pub fn cycle(&mut self) {
+    self.activate((self.active + 1) % self.entries.len());
+  }
+
+  pub fn reload(&mut self) -> bool {
+    let base = self.entries[0].theme.ui;
+    let entry = &mut self.entries[self.active];
+    let (path, last_modified) = match &mut entry.path {
+      Some(path) => path,
+      None => return false,
+    };
+    let now_modified = modified(path);
+    if now_modified == *last_modified {
+      return false;
+    }
+    *last_modified = now_modified;
+
+    match Theme::load(path, &base) {
+      Ok(theme) => {
+        entry.theme = Rc::new(theme);
+        self.activate(self.active);
+        true
+      }
+      Err(err) => {
+        log::error!("failed to reload theme {:?}: {}", path, err);
+        false
+      }
+    }
+  }
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+  std::fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+/// Wakes the event loop when the file of the active theme changes, which
+/// then calls `Themes::reload`.
+fn spawn_watcher(
+  watched: Arc<Mutex<Option<PathBuf>>>,
+  waker: crate::lsp::Waker,
+) {
+  std::thread::spawn(move || {
+    let mut last = None;
+    loop {
+      std::thread::sleep(WATCH_INTERVAL);
+      let path = watched.lock().unwrap().clone();
+      let now = path
+        .as_deref()
+        .map(|path| (path.to_owned(), modified(path)));
+      if let (Some((before, then)), Some((path, now))) = (&last, &now) {
+        if before == path && then != now {
+          waker();
+        }
+      }
+      last = now;
+    }
+  });
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn falls_back_to_parent_names() {
+    let theme = Theme::parse(
+      r##"
+        [syntax]
+        function = "#ff0000"
+        "function.macro" = { color = "#00ff00", bold = true }
+        comment = { italic = true }
+      "##,
+      "test",
+      Some(&Theme::builtin().ui),
+    )
+    .unwrap();
+
+    let red = [1.0, 0.0, 0.0, 1.0];
+    assert_eq!(theme.name, "test");
+    assert_eq!(theme.highlight(HighlightNames::Function).0, red);
+    assert_eq!(theme.highlight(HighlightNames::FunctionMethod).0, red);
+    let (color, style) = theme.highlight(HighlightNames::FunctionMacro);
+    assert_eq!(color, [0.0, 1.0, 0.0, 1.0]);
+    assert!(style.bold);
+    let (color, style) = theme.highlight(HighlightNames::Comment);
+    assert_eq!(color, theme.ui.foreground);
+    assert!(style.italic);
+    assert_eq!(
+      theme.highlight(HighlightNames::Keyword).0,
+      theme.ui.foreground
+    );
+  }
+
+  #[test]
+  fn parses_colors() {
+    assert_eq!(parse_color("#ff000080").unwrap()[3], 128.0 / 255.0);
+    assert!(parse_color("ff0000").is_err());
+    assert!(parse_color("#ff00").is_err());
+  }
+}
@@ -0,0 +1,313 @@
+use super::{parse_color, Color, Theme, Ui};
+use crate::json::{self, Json};
+use crate::renderer::code_view::highlight::Style;
+use std::collections::HashMap;
+
+/// TextMate scopes matching each highlight, the preferred ones first.
+const SCOPES: &[(&str, &[&str])] = &[
+  (
+    "attribute",
+    &["entity.other.attribute-name", "meta.attribute"],
+  ),
+  ("comment", &["comment"]),
+  ("constant", &["variable.other.constant", "constant"]),
+  (
+    "constant.builtin",
+    &["constant.language", "support.constant"],
+  ),
+  ("constructor", &["entity.name.class", "entity.name.type"]),
+  ("embedded", &["meta.embedded"]),
+  ("escape", &["constant.character.escape"]),
+  ("function", &["entity.name.function"]),
+  ("function.builtin", &["support.function"]),
+  (
+    "function.macro",
+    &["entity.name.function.macro", "support.macro"],
+  ),
+  ("function.method", &["entity.name.function.member"]),
+  ("keyword", &["keyword.control", "keyword", "storage"]),
+  ("label", &["entity.name.label"]),
+  ("number", &["constant.numeric"]),
+  ("operator", &["keyword.operator"]),
+  (
+    "property",
+    &["variable.other.property", "variable.other.object.property"],
+  ),
+  ("punctuation", &["punctuation"]),
+  (
+    "punctuation.bracket",
+    &["punctuation.bracket", "meta.brace", "punctuation.section"],
+  ),
+  ("punctuation.delimiter", &["punctuation.separator"]),
+  (
+    "punctuation.special",
+    &["punctuation.definition.template-expression"],
+  ),
+  ("string", &["string"]),
+  ("string.special", &["string.regexp"]),
+  ("tag", &["entity.name.tag"]),
+  (
+    "type",
+    &["entity.name.type", "support.type", "storage.type"],
+  ),
+  ("type.builtin", &["support.type.primitive", "storage.type"]),
+  ("variable", &["variable"]),
+  ("variable.builtin", &["variable.language"]),
+  ("variable.parameter", &["variable.parameter"]),
+];
+
+/// Workbench colors making up each UI color.
+const UI_COLORS: &[(&str, &[&str])] = &[
+  ("background", &["editor.background"]),
+  ("foreground", &["editor.foreground", "foreground"]),
+  ("tree_background", &["sideBar.background"]),
+  ("tree_foreground", &["sideBar.foreground", "foreground"]),
+  ("tab_bar_background", &["editorGroupHeader.tabsBackground"]),
+  (
+    "tab_background",
+    &["tab.activeBackground", "tab.inactiveBackground"],
+  ),
+  ("tab_foreground", &["tab.activeForeground", "foreground"]),
+  (
+    "breadcrumbs_background",
+    &["breadcrumb.background", "editor.background"],
+  ),
+  (
+    "breadcrumbs_foreground",
+    &["breadcrumb.foreground", "foreground"],
+  ),
+  (
+    "sticky_scroll_background",
+    &["editorStickyScroll.background", "editor.background"],
+  ),
+  (
+    "gutter_background",
+    &["editorGutter.background", "editor.background"],
+  ),
+  ("gutter_foreground", &["editorLineNumber.foreground"]),
+  ("selection", &["editor.selectionBackground"]),
+  (
+    "matching_bracket",
+    &["editorBracketMatch.background", "editorBracketMatch.border"],
+  ),
+  (
+    "query_match",
+    &[
+      "editor.findMatchHighlightBackground",
+      "editor.selectionHighlightBackground",
+      "editor.selectionBackground",
+    ],
+  ),
+  ("cursor", &["editorCursor.foreground"]),
+  ("diagnostic_error", &["editorError.foreground"]),
+  ("diagnostic_warning", &["editorWarning.foreground"]),
+  ("diagnostic_info", &["editorInfo.foreground"]),
+  ("diagnostic_hint", &["editorHint.foreground"]),
+  (
+    "completion_background",
+    &["editorSuggestWidget.background", "editorWidget.background"],
+  ),
+  (
+    "completion_selection",
+    &[
+      "editorSuggestWidget.selectedBackground",
+      "list.activeSelectionBackground",
+    ],
+  ),
+  (
+    "tooltip_background",
+    &["editorHoverWidget.background", "editorWidget.background"],
+  ),
+];
+
+fn vscode_color(hex: &str) -> Option<Color> {
+  let digits = hex.strip_prefix('#')?;
+  if digits.len() == 3 || digits.len() == 4 {
+    let long = digits.chars().flat_map(|c| vec![c, c]).collect::<String>();
+    parse_color(&format!("#{}", long)).ok()
+  } else {
+    parse_color(hex).ok()
+  }
+}
+
+fn selects(selector: &str, scope: &str) -> bool {
+  scope == selector
+    || (scope.starts_with(selector) && scope[selector.len()..].starts_with('.'))
+}
+
+pub fn import(
+  source: &str,
+  default_name: &str,
+  base: &Ui,
+) -> Result<Theme, anyhow::Error> {
+  let json = json::parse_with_comments(source)?;
+  let name = json
+    .get("name")
+    .and_then(|name| name.as_str())
+    .unwrap_or(default_name)
+    .to_string();
+
+  let colors = json.get("colors");
+  let ui_color = |key: &str, base: Color| {
+    UI_COLORS
+      .iter()
+      .find(|(name, _)| *name == key)
+      .into_iter()
+      .flat_map(|(_, keys)| keys.iter())
+      .find_map(|key| {
+        colors
+          .and_then(|colors| colors.get(key))
+          .and_then(|color| color.as_str())
+          .and_then(vscode_color)
+      })
+      .unwrap_or(base)
+  };
+  let ui = Ui {
+    background: ui_color("background", base.background),
+    foreground: ui_color("foreground", base.foreground),
+    tree_background: ui_color("tree_background", base.tree_background),
+    tree_foreground: ui_color("tree_foreground", base.tree_foreground),
+    tab_bar_background: ui_color("tab_bar_background", base.tab_bar_background),
+    tab_background: ui_color("tab_background", base.tab_background),
+    tab_foreground: ui_color("tab_foreground", base.tab_foreground),
+    breadcrumbs_background: ui_color(
+      "breadcrumbs_background",
+      base.breadcrumbs_background,
+    ),
+    breadcrumbs_foreground: ui_color(
+      "breadcrumbs_foreground",
+      base.breadcrumbs_foreground,
+    ),
+    sticky_scroll_background: ui_color(
+      "sticky_scroll_background",
+      base.sticky_scroll_background,
+    ),
+    gutter_background: ui_color("gutter_background", base.gutter_background),
+    gutter_foreground: ui_color("gutter_foreground", base.gutter_foreground),
+    selection: ui_color("selection", base.selection),
+    matching_bracket: ui_color("matching_bracket", base.matching_bracket),
+    query_match: ui_color("query_match", base.query_match),
+    cursor: ui_color("cursor", base.cursor),
+    diagnostic_error: ui_color("diagnostic_error", base.diagnostic_error),
+    diagnostic_warning: ui_color("diagnostic_warning", base.diagnostic_warning),
+    diagnostic_info: ui_color("diagnostic_info", base.diagnostic_info),
+    diagnostic_hint: ui_color("diagnostic_hint", base.diagnostic_hint),
+    completion_background: ui_color(
+      "completion_background",
+      base.completion_background,
+    ),
+    completion_selection: ui_color(
+      "completion_selection",
+      base.completion_selection,
+    ),
+    tooltip_background: ui_color("tooltip_background", base.tooltip_background),
+  };
+
+  // the scope selectors of the token rules with what they set
+  let mut rules = vec![];
+  let token_colors = match json.get("tokenColors") {
+    Some(Json::Array(rules)) => rules.as_slice(),
+    _ => &[],
+  };
+  for rule in token_colors {
+    let settings = match rule.get("settings") {
+      Some(settings) => settings,
+      None => continue,
+    };
+    let color = settings
+      .get("foreground")
+      .and_then(|color| color.as_str())
+      .and_then(vscode_color);
+    let style = settings
+      .get("fontStyle")
+      .and_then(|style| style.as_str())
+      .map(|font_style| {
+        let has = |word| font_style.split_whitespace().any(|w| w == word);
+        Style {
+          bold: has("bold"),
+          italic: has("italic"),
+          underline: has("underline"),
+          strikethrough: has("strikethrough"),
+        }
+      });
+
+    let selectors = match rule.get("scope") {
+      Some(Json::String(scopes)) => {
+        scopes.split(',').map(|s| s.trim().to_string()).collect()
+      }
+      Some(Json::Array(scopes)) => scopes
+        .iter()
+        .filter_map(|scope| scope.as_str())
+        .map(|s| s.trim().to_string())
+        .collect(),
+      _ => vec![],
+    };
+    for selector in selectors {
+      // selectors on nested scopes can't be told apart by the highlights
+      if !selector.contains(' ') {
+        rules.push((selector, color, style));
+      }
+    }
+  }
+
+  // every highlight takes the most specific rule matching one of its scopes,
+  // later rules winning among equally specific ones
+  let mut syntax = HashMap::new();
+  for (highlight, scopes) in SCOPES {
+    let best = scopes.iter().find_map(|scope| {
+      rules
+        .iter()
+        .filter(|(selector, _, _)| selects(selector, scope))
+        .fold(None, |best: Option<&(String, _, _)>, rule| match best {
+          Some(best) if best.0.len() > rule.0.len() => Some(best),
+          _ => Some(rule),
+        })
+    });
+    if let Some((_, color, style)) = best {
+      syntax.insert(highlight.to_string(), (*color, style.unwrap_or_default()));
+    }
+  }
+
+  Ok(Theme::new(name, ui, &syntax))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::renderer::code_view::highlight::HighlightNames;
+
+  #[test]
+  fn imports_themes() {
+    let theme = import(
+      r##"{
+        "name": "Test",
+        "colors": { "editor.background": "#fff" },
+        "tokenColors": [
+          { "scope": "keyword", "settings": { "foreground": "#ff0000" } },
+          {
+            "scope": ["keyword.control", "comment"],
+            "settings": { "foreground": "#00ff00", "fontStyle": "italic" }
+          },
+          { "scope": "source string", "settings": { "foreground": "#000" } }
+        ]
+      }"##,
+      "test",
+      &Theme::builtin().ui,
+    )
+    .unwrap();
+
+    assert_eq!(theme.name, "Test");
+    assert_eq!(theme.ui.background, [1.0; 4]);
+    let (color, style) = theme.highlight(HighlightNames::Keyword);
+    assert_eq!(color, [0.0, 1.0, 0.0, 1.0]);
+    assert!(style.italic);
+    assert_eq!(
+      theme.highlight(HighlightNames::Operator).0,
+      [1.0, 0.0, 0.0, 1.0]
+    );
+    assert_eq!(
+      theme.highlight(HighlightNames::String).0,
+      theme.ui.foreground
+    );
+  }
+}